@@ -38,6 +38,7 @@ impl Stage {
             vertex_buffers: vec![vertex_buffer],
             index_buffer: index_buffer,
             images: vec![],
+            ..Default::default()
         };
 
         let shader = Shader::new(ctx, shader::VERTEX, shader::FRAGMENT, shader::meta()).unwrap();
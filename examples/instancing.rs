@@ -48,6 +48,7 @@ impl Stage {
             vertex_buffers: vec![geometry_vertex_buffer, positions_vertex_buffer],
             index_buffer: index_buffer,
             images: vec![],
+            ..Default::default()
         };
 
         let shader = Shader::new(ctx, shader::VERTEX, shader::FRAGMENT, shader::meta()).unwrap();
@@ -87,12 +87,14 @@ impl Stage {
             vertex_buffers: vec![vertex_buffer.clone()],
             index_buffer: index_buffer.clone(),
             images: vec![],
+            ..Default::default()
         };
 
         let display_bind = Bindings {
             vertex_buffers: vec![vertex_buffer],
             index_buffer: index_buffer,
             images: vec![color_img],
+            ..Default::default()
         };
 
         let default_shader = Shader::new(
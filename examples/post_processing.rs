@@ -88,6 +88,7 @@ impl Stage {
             vertex_buffers: vec![vertex_buffer.clone()],
             index_buffer: index_buffer.clone(),
             images: vec![],
+            ..Default::default()
         };
 
         #[rustfmt::skip]
@@ -109,6 +110,7 @@ impl Stage {
             vertex_buffers: vec![vertex_buffer],
             index_buffer: index_buffer,
             images: vec![color_img],
+            ..Default::default()
         };
 
         let default_shader = Shader::new(
@@ -180,12 +180,12 @@ impl AdvancedVulkanExample {
             vertex_buffers: vec![vertex_buffer],
             index_buffer,
             images: vec![],
+            ..Default::default()
         };
         
         let compute_bindings = Bindings {
-            vertex_buffers: vec![compute_buffer],
-            index_buffer: Buffer::default(),
-            images: vec![],
+            storage_buffers: vec![compute_buffer],
+            ..Default::default()
         };
         
         Self {
@@ -106,12 +106,13 @@ impl VulkanExample {
             vertex_buffers: vec![vertex_buffer],
             index_buffer,
             images: vec![],
+            ..Default::default()
         };
         
         let compute_bindings = Bindings {
-            vertex_buffers: vec![compute_buffer, time_uniform],
-            index_buffer: Buffer::default(),
-            images: vec![],
+            storage_buffers: vec![compute_buffer],
+            uniform_buffers: vec![time_uniform],
+            ..Default::default()
         };
         
         Self {
@@ -129,7 +130,7 @@ impl VulkanExample {
         
         // Update uniform buffer
         let time_data = [self.time];
-        ctx.buffer_update(&self.compute_bindings.vertex_buffers[1], &time_data);
+        ctx.buffer_update(&self.compute_bindings.uniform_buffers[0], &time_data);
         
         // Dispatch compute shader
         ctx.compute_pass(&self.compute_pipeline, &self.compute_bindings, 16, 1, 1);
@@ -157,6 +158,9 @@ impl EventHandler for VulkanExample {
         {
             if let Ok(stats) = ctx.get_vulkan_stats() {
                 egui::Window::new("Vulkan Performance").show(ctx, |ui| {
+                    let device = &stats.device_info;
+                    ui.label(format!("GPU: {} ({:?})", device.name, device.device_type));
+                    ui.label(format!("Driver: {} / Vulkan {}", device.driver_version, device.api_version));
                     ui.label(format!("MSAA Samples: {:?}", stats.msaa_samples));
                     ui.label(format!("MSAA Enabled: {}", stats.msaa_enabled));
                     ui.label(format!("Buffer Count: {}", stats.buffer_count));
@@ -0,0 +1,121 @@
+//! Mid-gray check for the Vulkan color space modes
+//!
+//! Clears the window to 50% gray. In both `ColorSpaceMode::Srgb` and
+//! `ColorSpaceMode::Linear` a color picker has to read 128, 128, 128 from the
+//! window; the washed out 188 of a double applied gamma means the clear
+//! color and the swapchain format disagree.
+//!
+//! Before opening the window a gray ramp is rendered offscreen in each mode
+//! and read back. Every step has to come back as its sRGB encoded byte, e.g.
+//! 128 for 50% gray. Without a Vulkan device the check is skipped.
+//!
+//! Run with `cargo run --features vulkan --example vulkan_gamma -- linear`
+//! to try the linear workflow.
+
+use miniquad::conf::ColorSpaceMode;
+use miniquad::*;
+
+const MID_GRAY: f32 = 0.5;
+
+/// Steps of the offscreen gray ramp, from black to white
+#[cfg(feature = "vulkan")]
+const RAMP_STEPS: u32 = 16;
+
+struct GammaTest;
+
+impl EventHandler for GammaTest {
+    fn update(&mut self, _ctx: &mut Context) {}
+
+    fn draw(&mut self, ctx: &mut Context) {
+        #[cfg(feature = "vulkan")]
+        if let Some(vulkan) = ctx.vulkan() {
+            vulkan
+                .begin_render_pass(PassAction::clear_color(MID_GRAY, MID_GRAY, MID_GRAY, 1.0))
+                .unwrap();
+            vulkan.end_render_pass().unwrap();
+            return;
+        }
+        ctx.begin_default_pass(PassAction::clear_color(MID_GRAY, MID_GRAY, MID_GRAY, 1.0));
+        ctx.end_render_pass();
+    }
+}
+
+/// Clear one 1x1 render texture per ramp step in `mode` and read them back,
+/// as (sRGB value, stored byte) pairs
+#[cfg(feature = "vulkan")]
+fn render_ramp(mode: ColorSpaceMode) -> Result<Vec<(f32, u8)>, VulkanError> {
+    let mut context = VulkanContext::new_for_testing()?;
+    context.set_display(conf::Conf {
+        headless: true,
+        platform: conf::Platform {
+            color_space_mode: mode,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+
+    context.begin_frame()?;
+    let mut steps = vec![];
+    for step in 0..=RAMP_STEPS {
+        let value = step as f32 / RAMP_STEPS as f32;
+        let color = context.create_render_texture(1, 1, TextureFormat::RGBA8)?;
+        let target = context.create_render_target(&[color], None)?;
+        let clear = LoadOp::Clear([value, value, value, 1.0]);
+        context.begin_render_pass_to(target, &[clear], LoadOp::DontCare, 0)?;
+        context.end_render_pass()?;
+        steps.push((value, color));
+    }
+    context.end_frame()?;
+
+    let mut ramp = vec![];
+    for (value, color) in steps {
+        ramp.push((value, context.read_texture(color)?[0]));
+    }
+    context.cleanup();
+    Ok(ramp)
+}
+
+fn main() {
+    let mode = match std::env::args().nth(1).as_deref() {
+        Some("linear") => ColorSpaceMode::Linear,
+        _ => ColorSpaceMode::Srgb,
+    };
+
+    #[cfg(feature = "vulkan")]
+    for mode in [ColorSpaceMode::Srgb, ColorSpaceMode::Linear] {
+        let ramp = match render_ramp(mode) {
+            Ok(ramp) => ramp,
+            Err(e) => {
+                println!("{:?}: skipping the readback check, {}", mode, e);
+                continue;
+            }
+        };
+        for (value, stored) in ramp {
+            let expected = (value * 255.0).round() as u8;
+            println!("{:?}: {:.4} gray is stored as {}", mode, value, stored);
+            // sRGB encoding on write may round the other way
+            assert!(
+                stored.abs_diff(expected) <= 1,
+                "{} gray must read back as {} in {:?} mode, got {}",
+                value,
+                expected,
+                mode,
+                stored
+            );
+        }
+    }
+
+    let conf = conf::Conf {
+        window_title: format!("Vulkan mid gray, {:?} mode", mode),
+        window_width: 400,
+        window_height: 300,
+        platform: miniquad::conf::Platform {
+            rendering_backend: miniquad::conf::RenderingBackend::Vulkan,
+            color_space_mode: mode,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    miniquad::start(conf, |_ctx| Box::new(GammaTest));
+}
@@ -75,6 +75,7 @@ impl VulkanWindowTest {
             vertex_buffers: vec![vertex_buffer],
             index_buffer: Buffer::default(),
             images: vec![],
+            ..Default::default()
         };
         
         Self {
@@ -168,8 +169,8 @@ fn main() {
         ..Default::default()
     };
     
-    // 启动应用
-    miniquad::start(conf, |ctx| {
+    // 启动应用，没有 Vulkan 时回退到 OpenGL
+    miniquad::start_with_backend(conf, |ctx| {
         let test = VulkanWindowTest::new(ctx);
         UserData::owning(test, EventHandler::on_update, EventHandler::on_draw)
     });
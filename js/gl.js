@@ -714,6 +714,30 @@ var importObject = {
             var view = getArray(value, Int32Array, 4 * count);
             gl.uniform4iv(GL.uniforms[location], view);
         },
+        glUniform1uiv: function (location, count, value) {
+            GL.validateGLObjectID(GL.uniforms, location, 'glUniform1uiv', 'location');
+            assert((value & 3) == 0, 'Pointer to u32 data passed to glUniform1uiv must be aligned to four bytes!');
+            var view = getArray(value, Uint32Array, 1 * count);
+            gl.uniform1uiv(GL.uniforms[location], view);
+        },
+        glUniform2uiv: function (location, count, value) {
+            GL.validateGLObjectID(GL.uniforms, location, 'glUniform2uiv', 'location');
+            assert((value & 3) == 0, 'Pointer to u32 data passed to glUniform2uiv must be aligned to four bytes!');
+            var view = getArray(value, Uint32Array, 2 * count);
+            gl.uniform2uiv(GL.uniforms[location], view);
+        },
+        glUniform3uiv: function (location, count, value) {
+            GL.validateGLObjectID(GL.uniforms, location, 'glUniform3uiv', 'location');
+            assert((value & 3) == 0, 'Pointer to u32 data passed to glUniform3uiv must be aligned to four bytes!');
+            var view = getArray(value, Uint32Array, 3 * count);
+            gl.uniform3uiv(GL.uniforms[location], view);
+        },
+        glUniform4uiv: function (location, count, value) {
+            GL.validateGLObjectID(GL.uniforms, location, 'glUniform4uiv', 'location');
+            assert((value & 3) == 0, 'Pointer to u32 data passed to glUniform4uiv must be aligned to four bytes!');
+            var view = getArray(value, Uint32Array, 4 * count);
+            gl.uniform4uiv(GL.uniforms[location], view);
+        },
         glBlendFunc: function (sfactor, dfactor) {
             gl.blendFunc(sfactor, dfactor);
         },
@@ -767,6 +791,12 @@ var importObject = {
                 return -1;
             }
         },
+        glUniformMatrix3fv: function (location, count, transpose, value) {
+            GL.validateGLObjectID(GL.uniforms, location, 'glUniformMatrix3fv', 'location');
+            assert((value & 3) == 0, 'Pointer to float data passed to glUniformMatrix3fv must be aligned to four bytes!');
+            var view = getArray(value, Float32Array, 9 * count);
+            gl.uniformMatrix3fv(GL.uniforms[location], !!transpose, view);
+        },
         glUniformMatrix4fv: function (location, count, transpose, value) {
             GL.validateGLObjectID(GL.uniforms, location, 'glUniformMatrix4fv', 'location');
             assert((value & 3) == 0, 'Pointer to float data passed to glUniformMatrix4fv must be aligned to four bytes!');
@@ -799,6 +829,10 @@ var importObject = {
             GL.validateGLObjectID(GL.buffers, buffer, 'glBindBuffer', 'buffer');
             gl.bindBuffer(target, GL.buffers[buffer]);
         },
+        glBindBufferBase: function (target, index, buffer) {
+            GL.validateGLObjectID(GL.buffers, buffer, 'glBindBufferBase', 'buffer');
+            gl.bindBufferBase(target, index, GL.buffers[buffer]);
+        },
         glBufferData: function (target, size, data, usage) {
             gl.bufferData(target, data ? getArray(data, Uint8Array, size) : size, usage);
         },
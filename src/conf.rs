@@ -39,7 +39,7 @@
 //! dpi_scale   -> 1.0
 //! ```
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum LinuxX11Gl {
     /// Use libGLX.so/libGLX.so.0 and its funciton for creating OpenGL context
     /// If there is no libGLX - just panic right away
@@ -54,7 +54,7 @@ pub enum LinuxX11Gl {
     EGLWithGLXFallback,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum LinuxBackend {
     X11Only,
     WaylandOnly,
@@ -77,8 +77,138 @@ impl Default for RenderingBackend {
     }
 }
 
+/// Color space requested for the presented images.
+///
+/// Only honored by the Vulkan backend. Anything other than `Srgb` needs the
+/// `VK_EXT_swapchain_colorspace` instance extension and a display that reports
+/// a matching surface format, otherwise the swapchain falls back to sRGB.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ColorSpace {
+    /// Standard 8-bit sRGB output (default)
+    #[default]
+    Srgb,
+    /// Wide gamut Display P3
+    DisplayP3,
+    /// scRGB, linear extended range sRGB in half floats
+    ExtendedSrgbLinear,
+    /// HDR10 with the ST.2084 (PQ) transfer function
+    Hdr10,
+}
+
+/// How the Vulkan backend resets the command buffer of a frame slot before
+/// recording into it again, see `Platform::command_buffer_reset`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum CommandBufferReset {
+    /// `vkResetCommandBuffer` on a buffer from the shared pool (default).
+    /// The pool has to track every buffer on its own, which some drivers
+    /// make slower.
+    #[default]
+    Individual,
+    /// A pool per frame slot, reset as a whole with `vkResetCommandPool`.
+    /// Usually the cheapest reset, but it is all or nothing: only the frame
+    /// command buffer lives in these pools.
+    Pool,
+}
+
+/// Usage of the Vulkan swapchain images on top of rendering into them and
+/// `capture_frame`, see `Platform::swapchain_usage`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SwapchainUsage {
+    /// `TRANSFER_DST`, copies and blits into the backbuffer
+    pub transfer_dst: bool,
+    /// `SAMPLED`, reading the backbuffer in shaders
+    pub sampled: bool,
+    /// `STORAGE`, compute shaders writing straight into the backbuffer
+    pub storage: bool,
+}
+
+/// How the window system blends a window with what is behind it, see
+/// `Platform::composite_alpha`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum CompositeAlphaMode {
+    /// Alpha is ignored and the window is opaque (default)
+    #[default]
+    Opaque,
+    /// The window's colors are already multiplied by its alpha
+    PreMultiplied,
+    /// The compositor multiplies the window's colors by its alpha
+    PostMultiplied,
+    /// Left to the window system, e.g. whatever native window APIs set
+    Inherit,
+}
+
+/// Clip space depth range of the projection matrices given to the Vulkan
+/// backend, see `Platform::depth_range`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum DepthRange {
+    /// Vulkan's 0 to 1, what `graphics::projection` produces (default)
+    #[default]
+    ZeroToOne,
+    /// OpenGL's -1 to 1, for GL style matrices shared with the GL backend
+    NegOneToOne,
+}
+
+/// How color values are encoded in textures and render targets.
+///
+/// Only honored by the Vulkan backend. In both modes the colors passed to
+/// clears are sRGB values, so a 0.5 gray clear ends up as 128 on screen.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ColorSpaceMode {
+    /// Textures and the swapchain use UNORM formats and store sRGB encoded
+    /// values as they are, shaders blend and filter in sRGB space. Matches
+    /// the OpenGL backend. (default)
+    #[default]
+    Srgb,
+    /// Linear workflow: textures and the swapchain use sRGB formats, so
+    /// samples are decoded to linear light and shader outputs are encoded on
+    /// write. Vertex colors and uniforms have to be linear values.
+    Linear,
+}
+
+/// Optional device features the application wants enabled.
+///
+/// Only honored by the Vulkan backend, which enables the supported subset
+/// when creating the device. The features actually granted are reported by
+/// `VulkanContext::enabled_features`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RequestedFeatures {
+    /// `samplerAnisotropy`, lets samplers use anisotropic filtering
+    pub sampler_anisotropy: bool,
+    /// `fillModeNonSolid`, wireframe and point polygon modes
+    pub fill_mode_non_solid: bool,
+    /// `multiDrawIndirect`, indirect draws with a draw count above 1
+    pub multi_draw_indirect: bool,
+    /// `pipelineStatisticsQuery`, pipeline statistics query pools
+    pub pipeline_statistics_query: bool,
+    /// `occlusionQueryPrecise`, occlusion queries count the exact number of
+    /// passing samples instead of only telling zero from non zero
+    pub occlusion_query_precise: bool,
+    /// `sparseBinding`, needed together with `sparse_residency_image_2d`
+    /// for partially resident textures
+    pub sparse_binding: bool,
+    /// `sparseResidencyImage2D`, 2D images whose pages are bound on demand
+    pub sparse_residency_image_2d: bool,
+    /// `wideLines`, line widths other than 1.0 for `set_line_width`
+    pub wide_lines: bool,
+    /// `geometryShader`, shaders made with `create_shader_with_geometry`
+    pub geometry_shader: bool,
+    /// `tessellationShader`, shaders made with `create_shader_tessellated`
+    pub tessellation_shader: bool,
+    /// `shaderStorageImageWriteWithoutFormat`, compute shaders writing
+    /// storage images declared without a format qualifier, see
+    /// `VulkanContext::check_shader_capabilities`
+    pub shader_storage_image_write_without_format: bool,
+    /// `bufferDeviceAddress` (Vulkan 1.2 or `VK_KHR_buffer_device_address`),
+    /// GPU pointers to buffers created with `SHADER_DEVICE_ADDRESS` usage
+    /// through `buffer_device_address`
+    pub buffer_device_address: bool,
+    /// Fail device creation when a requested feature is unsupported instead
+    /// of printing a warning and continuing without it
+    pub strict: bool,
+}
+
 /// Platform specific settings.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Platform {
     /// On X11 there are two ways to get OpenGl context: libglx.so and libegl.so
     /// Default is GLXWithEGLFallback - will try to create glx context and if fails -
@@ -109,8 +239,270 @@ pub struct Platform {
     /// Multisample anti-aliasing configuration
     pub multisample_antialiasing: MultisampleConfig,
 
-    /// Rendering backend selection
+    /// Rendering backend selection, checked by `Conf::resolve_backend`
     pub rendering_backend: RenderingBackend,
+
+    /// Whether `Conf::resolve_backend` fails when `rendering_backend` is not
+    /// available instead of falling back to OpenGL.
+    ///
+    /// Default: false
+    pub strict_rendering_backend: bool,
+
+    /// Preferred color space of the swapchain, used to opt into HDR/wide gamut output.
+    /// The color space actually granted may differ, check it on the backend context.
+    pub preferred_color_space: ColorSpace,
+
+    /// Whether color math happens on sRGB encoded or on linear values, see
+    /// `ColorSpaceMode`.
+    ///
+    /// Default: ColorSpaceMode::Srgb
+    pub color_space_mode: ColorSpaceMode,
+
+    /// How many frames the CPU may record ahead of the GPU on the Vulkan backend.
+    /// 1 gives the lowest latency, 3 the smoothest pacing. Clamped to 1..=3.
+    ///
+    /// Default: 2
+    pub frames_in_flight: u32,
+
+    /// How the command buffer of a frame slot is reset in `begin_frame` on
+    /// Vulkan, before it is begun again with `ONE_TIME_SUBMIT`.
+    ///
+    /// Default: CommandBufferReset::Individual
+    pub command_buffer_reset: CommandBufferReset,
+
+    /// Optional device features to enable on Vulkan, see `RequestedFeatures`.
+    ///
+    /// Default: nothing requested, non strict
+    pub requested_features: RequestedFeatures,
+
+    /// Number of swapchain images to request on Vulkan: 2 for double buffering
+    /// (lower latency), 3 for triple buffering (smoother). Clamped to what the
+    /// surface supports, `None` asks for one more than the surface minimum.
+    ///
+    /// Default: None
+    pub swapchain_image_count: Option<u32>,
+
+    /// How the window is composited with what is behind it on Vulkan, e.g.
+    /// `PreMultiplied` for a transparent overlay. Falls back to `Opaque` with
+    /// a warning when the surface doesn't support the mode.
+    ///
+    /// Default: CompositeAlphaMode::Opaque
+    pub composite_alpha: CompositeAlphaMode,
+
+    /// Extra usage for the Vulkan swapchain images. Usage the surface or the
+    /// swapchain format doesn't support is left out with a warning, check
+    /// `VulkanContext::swapchain_usage` for what was granted. `storage`
+    /// usually needs a UNORM format, see `preferred_color_space`.
+    ///
+    /// Default: none
+    pub swapchain_usage: SwapchainUsage,
+
+    /// Depth range of clip space on Vulkan. `NegOneToOne` needs
+    /// `VK_EXT_depth_clip_control`; without it a warning is printed and
+    /// projections have to go through `projection::gl_to_vulkan`, see
+    /// `VulkanContext::depth_clip_control`. Y is up to `flip_viewport_y`.
+    ///
+    /// Default: DepthRange::ZeroToOne
+    pub depth_range: DepthRange,
+
+    /// Sampler the Vulkan backend uses for textures bound without a sampler
+    /// of their own, e.g. by `VulkanContext::write_texture_descriptor` with
+    /// no sampler id and the bindless set. Anisotropy is clamped to what the
+    /// device grants, `RequestedFeatures::sampler_anisotropy` has to be set
+    /// for any. Changing it later with `VulkanContext::set_default_sampler`
+    /// only affects textures bound from then on.
+    ///
+    /// Default: trilinear filtering, clamped, 16x anisotropy
+    pub default_sampler: SamplerDesc,
+
+    /// Size of each descriptor pool on Vulkan. Another pool of the same
+    /// size is added whenever the current one runs out.
+    pub descriptor_pool_sizes: DescriptorPoolSizes,
+
+    /// Settings of the GPU memory allocator on Vulkan
+    pub allocator: AllocatorConfig,
+
+    /// GPU the Vulkan backend runs on, see `VulkanContext::enumerate_adapters`.
+    ///
+    /// Default: AdapterSelection::Best
+    pub adapter: AdapterSelection,
+
+    /// Seconds the Vulkan backend waits for instance creation and device
+    /// enumeration before failing with a timeout, so a hung driver doesn't
+    /// freeze the application. `None` waits forever. See
+    /// `VulkanContext::init_report` for what initialization found.
+    ///
+    /// Default: Some(10)
+    pub init_timeout_secs: Option<u32>,
+
+    /// Instance extensions the Vulkan backend enables on top of its own,
+    /// e.g. "VK_EXT_debug_utils". Initialization fails listing the missing
+    /// ones when any isn't available. See
+    /// `VulkanContext::enabled_extensions`.
+    ///
+    /// Default: empty
+    pub extra_instance_extensions: Vec<String>,
+
+    /// Device extensions the Vulkan backend enables on top of the ones it
+    /// picks itself, e.g. "VK_KHR_ray_query", failing like
+    /// `extra_instance_extensions` when any is missing. Only the extension
+    /// is enabled, features it brings along stay off.
+    ///
+    /// Default: empty
+    pub extra_device_extensions: Vec<String>,
+
+    /// Whether the Vulkan backend collects texture and buffer updates of a
+    /// frame into one command buffer, submitted together with the frame
+    /// instead of once per update. Turning it off submits and waits for
+    /// every update on its own.
+    ///
+    /// Default: true
+    pub batch_submissions: bool,
+
+    /// Whether the Vulkan backend flips its viewports with a negative height
+    /// (`VK_KHR_maintenance1`, core in 1.1), so clip space Y points up as on
+    /// OpenGL and the same vertex data and shaders render the same way on
+    /// both backends. The flip also mirrors the winding, which keeps
+    /// `FrontFaceOrder` meaning what it means on OpenGL; without the flip
+    /// counter clockwise GL meshes come out clockwise. Render targets are
+    /// stored top row first either way, like uploaded textures.
+    ///
+    /// Default: true
+    pub flip_viewport_y: bool,
+}
+
+/// Kind of GPU, as reported by the Vulkan driver
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AdapterType {
+    Discrete,
+    Integrated,
+    Virtual,
+    /// Software rasterizers such as lavapipe and SwiftShader
+    Cpu,
+    Other,
+}
+
+/// A GPU Vulkan can run on, see `VulkanContext::enumerate_adapters`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdapterInfo {
+    /// Position in the driver's device list, what `AdapterSelection::Index` takes
+    pub index: usize,
+    pub name: String,
+    pub adapter_type: AdapterType,
+    /// PCI vendor id, e.g. 0x10DE for NVIDIA, 0x1002 for AMD, 0x8086 for Intel
+    pub vendor_id: u32,
+    pub device_id: u32,
+    /// Bytes in the device local memory heaps. Integrated GPUs report the
+    /// share of system memory they may use.
+    pub dedicated_memory: u64,
+    /// Whether the adapter has a graphics queue; the others can't be selected
+    pub supports_graphics: bool,
+}
+
+/// How the Vulkan backend picks its GPU
+#[derive(Debug, Default, Clone, Copy)]
+pub enum AdapterSelection {
+    /// Discrete GPUs first, then integrated, virtual and software ones
+    #[default]
+    Best,
+    /// The adapter with this `AdapterInfo::index`. Falls back to `Best` with
+    /// a warning when it is gone or can't do graphics, so a stored setting
+    /// survives hardware changes.
+    Index(usize),
+    /// The first adapter the function accepts, falling back to `Best` like
+    /// `Index`
+    Predicate(fn(&AdapterInfo) -> bool),
+}
+
+/// Sampler parameters for `VulkanContext::create_sampler`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerDesc {
+    pub min_filter: crate::graphics::FilterMode,
+    pub mag_filter: crate::graphics::FilterMode,
+    pub mipmap_filter: crate::graphics::FilterMode,
+    pub wrap: crate::graphics::TextureWrap,
+    /// 1.0 disables anisotropic filtering, higher values are clamped to
+    /// `VulkanContext::max_anisotropy`
+    pub max_anisotropy: f32,
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        SamplerDesc {
+            min_filter: crate::graphics::FilterMode::Linear,
+            mag_filter: crate::graphics::FilterMode::Linear,
+            mipmap_filter: crate::graphics::FilterMode::Linear,
+            wrap: crate::graphics::TextureWrap::Clamp,
+            max_anisotropy: 1.0,
+        }
+    }
+}
+
+/// Capacity of one Vulkan descriptor pool, in descriptor sets and in
+/// descriptors of each type
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DescriptorPoolSizes {
+    pub max_sets: u32,
+    pub uniform_buffers: u32,
+    pub combined_image_samplers: u32,
+    pub storage_buffers: u32,
+    pub storage_images: u32,
+    pub input_attachments: u32,
+}
+
+impl Default for DescriptorPoolSizes {
+    fn default() -> DescriptorPoolSizes {
+        DescriptorPoolSizes {
+            max_sets: 256,
+            uniform_buffers: 256,
+            combined_image_samplers: 1024,
+            storage_buffers: 128,
+            storage_images: 64,
+            input_attachments: 64,
+        }
+    }
+}
+
+/// Settings of the Vulkan GPU memory allocator
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct AllocatorConfig {
+    /// Allow allocations for buffers created with
+    /// `SHADER_DEVICE_ADDRESS` usage. Requests
+    /// `RequestedFeatures::buffer_device_address` as well, and has no
+    /// effect when the device doesn't grant it.
+    pub buffer_device_address: bool,
+    pub debug_settings: AllocatorDebugSettings,
+}
+
+/// What the Vulkan memory allocator logs, through the `log` crate
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AllocatorDebugSettings {
+    /// Log the memory heaps and types of the device on startup
+    pub log_memory_information: bool,
+    /// Log every allocation still alive when the allocator is destroyed
+    pub log_leaks_on_shutdown: bool,
+    /// Keep a backtrace of every allocation so leaks can be traced back.
+    /// Costs memory, keep it off in shipping builds.
+    pub store_stack_traces: bool,
+    /// Log every allocation, at debug level
+    pub log_allocations: bool,
+    /// Log every free, at debug level
+    pub log_frees: bool,
+    /// Add backtraces to `log_allocations` and `log_frees`
+    pub log_stack_traces: bool,
+}
+
+impl Default for AllocatorDebugSettings {
+    fn default() -> AllocatorDebugSettings {
+        AllocatorDebugSettings {
+            log_memory_information: false,
+            log_leaks_on_shutdown: true,
+            store_stack_traces: false,
+            log_allocations: false,
+            log_frees: false,
+            log_stack_traces: false,
+        }
+    }
 }
 
 /// Multisample anti-aliasing configuration
@@ -137,12 +529,34 @@ impl Default for Platform {
             linux_backend: LinuxBackend::X11Only,
             framebuffer_alpha: false,
             rendering_backend: RenderingBackend::OpenGL,
+            strict_rendering_backend: false,
             multisample_antialiasing: MultisampleConfig::default(),
+            preferred_color_space: ColorSpace::default(),
+            color_space_mode: ColorSpaceMode::default(),
+            frames_in_flight: 2,
+            command_buffer_reset: CommandBufferReset::default(),
+            requested_features: RequestedFeatures::default(),
+            swapchain_image_count: None,
+            composite_alpha: CompositeAlphaMode::default(),
+            swapchain_usage: SwapchainUsage::default(),
+            depth_range: DepthRange::default(),
+            default_sampler: SamplerDesc {
+                max_anisotropy: 16.0,
+                ..SamplerDesc::default()
+            },
+            descriptor_pool_sizes: DescriptorPoolSizes::default(),
+            allocator: AllocatorConfig::default(),
+            adapter: AdapterSelection::default(),
+            init_timeout_secs: Some(10),
+            extra_instance_extensions: vec![],
+            extra_device_extensions: vec![],
+            batch_submissions: true,
+            flip_viewport_y: true,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Conf {
     /// Title of the window, defaults to an empty string.
     pub window_title: String,
@@ -170,6 +584,20 @@ pub struct Conf {
     /// Determines if the application user can resize the window
     pub window_resizable: bool,
 
+    /// Cap on the frame rate, enforced by waiting after each present.
+    /// Keeps the GPU from running flat out with MAILBOX/IMMEDIATE present modes.
+    /// Currently only honored by the Vulkan backend.
+    ///
+    /// Default: None
+    pub target_fps: Option<u32>,
+
+    /// Run compute dispatches on a dedicated compute queue so they overlap with
+    /// rendering. Needs Vulkan 1.2 and a compute-only queue family, otherwise
+    /// compute stays on the graphics queue.
+    ///
+    /// Default: false
+    pub prefer_async_compute: bool,
+
     /// Miniquad allows to change the window icon programmatically.
     /// The icon will be used as
     /// - taskbar and titlebar icons on Windows.
@@ -185,6 +613,29 @@ pub struct Conf {
     pub headless: bool,
 }
 
+impl Conf {
+    /// The backend `start_with_backend` will run: `platform.rendering_backend`
+    /// when it is available, OpenGL otherwise. Vulkan is unavailable in builds
+    /// without the `vulkan` feature and on machines without a Vulkan driver.
+    /// With `platform.strict_rendering_backend` an unavailable backend is an
+    /// error instead. Runs before any window exists and logs the fallback.
+    pub fn resolve_backend(
+        &self,
+    ) -> Result<RenderingBackend, crate::graphics::backend::BackendError> {
+        use crate::graphics::backend::{BackendError, RenderingBackendContext};
+
+        let requested = self.platform.rendering_backend;
+        if RenderingBackendContext::is_available(requested) {
+            return Ok(requested);
+        }
+        if self.platform.strict_rendering_backend {
+            return Err(BackendError::Unavailable(requested));
+        }
+        log_04::warn!("Rendering backend {:?} is not available, using OpenGL instead", requested);
+        Ok(RenderingBackend::OpenGL)
+    }
+}
+
 /// Icon image in three levels of detail.
 #[derive(Clone)]
 pub struct Icon {
@@ -225,6 +676,8 @@ impl Default for Conf {
             fullscreen: false,
             sample_count: 4, // Default to 4x MSAA
             window_resizable: true,
+            target_fps: None,
+            prefer_async_compute: false,
             icon: Some(Icon::miniquad_logo()),
             platform: Default::default(),
             headless: false,
@@ -243,6 +696,8 @@ impl Default for Conf {
             fullscreen: true,
             sample_count: 1,
             window_resizable: false,
+            target_fps: None,
+            prefer_async_compute: false,
             icon: Some(Icon::miniquad_logo()),
             platform: Default::default(),
             headless: false,
@@ -256,4 +256,9 @@ pub trait EventHandler {
     /// `ctx.dropped_file_path()`, and for wasm targets the file bytes
     /// can be requested with `ctx.dropped_file_bytes()`.
     fn files_dropped_event(&mut self, _ctx: &mut Context) {}
+
+    /// The GPU device was lost (driver reset, hang, TDR) and the Vulkan backend
+    /// has been reinitialized. All GPU resources created before are gone and
+    /// have to be created again.
+    fn on_device_lost(&mut self, _ctx: &mut Context) {}
 }
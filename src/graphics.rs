@@ -5,6 +5,21 @@ mod texture;
 #[cfg(feature = "vulkan")]
 mod vulkan;
 
+#[cfg(feature = "vulkan")]
+pub use vulkan::projection;
+#[cfg(feature = "vulkan")]
+pub use vulkan::vk::{
+    ComputeTicket, DeviceInfo, EnabledExtensions, ExtensionRequest, ExternalMemoryHandle,
+    FrameGraph, FrameResource, FrameTimeHistory, GraphicsPipelineRecipe, IncludeResolver,
+    InitReport, LoadActions, LoadOp, OverlayTextureId, QueueInfo, ReadbackToken,
+    ResourceUploader, ResourceUsage, ShaderMeta as VulkanShaderMeta, ShaderOptions, Subpass,
+    UploadedResource, VulkanContext, VulkanError, VulkanRawHandles, VulkanRenderTarget,
+    VulkanStats, WindowId, FRAME_SKIPPED,
+    GLOBAL_UNIFORM_SET, OCCLUSION_QUERY_COUNT, TEXTURE_SET, check_point_size, clear_color_value,
+    lint_glsl, pack_std140, preprocess_glsl, spirv_local_size, std140_offsets,
+    validate_shader_interface,
+};
+
 pub mod backend;
 
 use crate::{native::gl::*, Context};
@@ -34,14 +49,25 @@ pub enum UniformType {
     Float3,
     /// Four 32-bit wide floats (equivalent to `[f32; 4]`)
     Float4,
-    /// One unsigned 32-bit integers (equivalent to `[u32; 1]`)
+    /// One signed 32-bit integers (equivalent to `[i32; 1]`)
     Int1,
-    /// Two unsigned 32-bit integers (equivalent to `[u32; 2]`)
+    /// Two signed 32-bit integers (equivalent to `[i32; 2]`)
     Int2,
-    /// Three unsigned 32-bit integers (equivalent to `[u32; 3]`)
+    /// Three signed 32-bit integers (equivalent to `[i32; 3]`)
     Int3,
-    /// Four unsigned 32-bit integers (equivalent to `[u32; 4]`)
+    /// Four signed 32-bit integers (equivalent to `[i32; 4]`)
     Int4,
+    /// One unsigned 32-bit integers (equivalent to `[u32; 1]`)
+    UInt1,
+    /// Two unsigned 32-bit integers (equivalent to `[u32; 2]`)
+    UInt2,
+    /// Three unsigned 32-bit integers (equivalent to `[u32; 3]`)
+    UInt3,
+    /// Four unsigned 32-bit integers (equivalent to `[u32; 4]`)
+    UInt4,
+    /// Three by three matrix of 32-bit floats, column major without padding
+    /// (equivalent to `[f32; 9]`)
+    Mat3,
     /// Four by four matrix of 32-bit floats
     Mat4,
 }
@@ -58,6 +84,11 @@ impl UniformType {
             UniformType::Int2 => 8,
             UniformType::Int3 => 12,
             UniformType::Int4 => 16,
+            UniformType::UInt1 => 4,
+            UniformType::UInt2 => 8,
+            UniformType::UInt3 => 12,
+            UniformType::UInt4 => 16,
+            UniformType::Mat3 => 36,
             UniformType::Mat4 => 64,
         }
     }
@@ -134,6 +165,15 @@ pub enum VertexFormat {
     Int4,
     /// Four by four matrix of 32-bit floats
     Mat4,
+    /// Four unsigned 8-bit integers mapped to `0.0..=1.0`, e.g. packed vertex colors
+    Byte4Norm,
+    /// Four signed 8-bit integers (equivalent to `[i8; 4]`) mapped to `-1.0..=1.0`
+    SByte4Norm,
+    /// Two signed 16-bit integers (equivalent to `[i16; 2]`) mapped to `-1.0..=1.0`
+    Short2Norm,
+    /// Four signed 16-bit integers (equivalent to `[i16; 4]`) mapped to `-1.0..=1.0`,
+    /// e.g. packed normals
+    Short4Norm,
 }
 
 impl VertexFormat {
@@ -156,6 +196,10 @@ impl VertexFormat {
             VertexFormat::Int3 => 3,
             VertexFormat::Int4 => 4,
             VertexFormat::Mat4 => 16,
+            VertexFormat::Byte4Norm => 4,
+            VertexFormat::SByte4Norm => 4,
+            VertexFormat::Short2Norm => 2,
+            VertexFormat::Short4Norm => 4,
         }
     }
 
@@ -178,6 +222,10 @@ impl VertexFormat {
             VertexFormat::Int3 => 3 * 4,
             VertexFormat::Int4 => 4 * 4,
             VertexFormat::Mat4 => 16 * 4,
+            VertexFormat::Byte4Norm => 4,
+            VertexFormat::SByte4Norm => 4,
+            VertexFormat::Short2Norm => 2 * 2,
+            VertexFormat::Short4Norm => 4 * 2,
         }
     }
 
@@ -200,8 +248,24 @@ impl VertexFormat {
             VertexFormat::Int3 => GL_UNSIGNED_INT,
             VertexFormat::Int4 => GL_UNSIGNED_INT,
             VertexFormat::Mat4 => GL_FLOAT,
+            VertexFormat::Byte4Norm => GL_UNSIGNED_BYTE,
+            VertexFormat::SByte4Norm => GL_BYTE,
+            VertexFormat::Short2Norm => GL_SHORT,
+            VertexFormat::Short4Norm => GL_SHORT,
         }
     }
+
+    /// Whether integer components are mapped to `0.0..=1.0` (unsigned) or
+    /// `-1.0..=1.0` (signed) instead of being converted as is
+    pub fn normalized(&self) -> bool {
+        matches!(
+            self,
+            VertexFormat::Byte4Norm
+                | VertexFormat::SByte4Norm
+                | VertexFormat::Short2Norm
+                | VertexFormat::Short4Norm
+        )
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -466,6 +530,12 @@ impl GlCache {
                     glBindBuffer(target, buffer);
                 }
             }
+        } else if target != GL_ELEMENT_ARRAY_BUFFER {
+            // Uniform and storage buffers are bound to indexed slots for
+            // drawing, the generic binding only matters for uploads
+            unsafe {
+                glBindBuffer(target, buffer);
+            }
         } else {
             if self.index_buffer != buffer {
                 self.index_buffer = buffer;
@@ -480,7 +550,7 @@ impl GlCache {
     fn store_buffer_binding(&mut self, target: GLenum) {
         if target == GL_ARRAY_BUFFER {
             self.stored_vertex_buffer = self.vertex_buffer;
-        } else {
+        } else if target == GL_ELEMENT_ARRAY_BUFFER {
             self.stored_index_buffer = self.index_buffer;
             self.stored_index_type = self.index_type;
         }
@@ -492,11 +562,9 @@ impl GlCache {
                 self.bind_buffer(target, self.stored_vertex_buffer, None);
                 self.stored_vertex_buffer = 0;
             }
-        } else {
-            if self.stored_index_buffer != 0 {
-                self.bind_buffer(target, self.stored_index_buffer, self.stored_index_type);
-                self.stored_index_buffer = 0;
-            }
+        } else if target == GL_ELEMENT_ARRAY_BUFFER && self.stored_index_buffer != 0 {
+            self.bind_buffer(target, self.stored_index_buffer, self.stored_index_type);
+            self.stored_index_buffer = 0;
         }
     }
 
@@ -553,6 +621,30 @@ impl PassAction {
             stencil: None,
         }
     }
+
+    /// Buffers `begin_pass` clears with `glClear`, 0 keeps all of them
+    pub(crate) fn gl_clear_bits(&self) -> GLbitfield {
+        match self {
+            PassAction::Nothing => 0,
+            PassAction::Clear {
+                color,
+                depth,
+                stencil,
+            } => {
+                let mut bits = 0;
+                if color.is_some() {
+                    bits |= GL_COLOR_BUFFER_BIT;
+                }
+                if depth.is_some() {
+                    bits |= GL_DEPTH_BUFFER_BIT;
+                }
+                if stencil.is_some() {
+                    bits |= GL_STENCIL_BUFFER_BIT;
+                }
+                bits
+            }
+        }
+    }
 }
 
 impl Default for PassAction {
@@ -571,6 +663,8 @@ pub struct RenderPass(usize);
 struct RenderPassInternal {
     gl_fb: GLuint,
     texture: Texture,
+    /// Color attachments after the first one, for multiple render targets
+    extra_textures: Vec<Texture>,
     depth_texture: Option<Texture>,
 }
 
@@ -579,6 +673,7 @@ impl RenderPass {
         let pass = RenderPassInternal {
             gl_fb,
             texture: dummy_texture,
+            extra_textures: vec![],
             depth_texture: None,
         };
         ctx.passes.push(pass);
@@ -618,6 +713,7 @@ impl RenderPass {
         let pass = RenderPassInternal {
             gl_fb,
             texture: color_img,
+            extra_textures: vec![],
             depth_texture: depth_img,
         };
 
@@ -626,6 +722,50 @@ impl RenderPass {
         RenderPass(ctx.passes.len() - 1)
     }
 
+    /// Render pass writing to several color textures at once, e.g. a G-buffer.
+    /// Fragment output `N` (`layout(location = N) out` or `gl_FragData[N]`)
+    /// goes to `color_imgs[N]`.
+    ///
+    /// Needs `features().multiple_render_targets`; 4 attachments are
+    /// guaranteed everywhere it is supported.
+    pub fn new_mrt(
+        ctx: &mut Context,
+        color_imgs: &[Texture],
+        depth_img: impl Into<Option<Texture>>,
+    ) -> RenderPass {
+        assert!(!color_imgs.is_empty(), "Render pass without color attachments");
+        assert!(
+            color_imgs.len() == 1 || ctx.features.multiple_render_targets,
+            "Multiple render targets are not supported by the GPU"
+        );
+
+        let pass = RenderPass::new(ctx, color_imgs[0], depth_img);
+        let gl_fb = ctx.passes[pass.0].gl_fb;
+        let draw_buffers: Vec<GLenum> = (0..color_imgs.len() as u32)
+            .map(|i| GL_COLOR_ATTACHMENT0 + i)
+            .collect();
+
+        unsafe {
+            glBindFramebuffer(GL_FRAMEBUFFER, gl_fb);
+            for (i, color_img) in color_imgs.iter().enumerate().skip(1) {
+                glFramebufferTexture2D(
+                    GL_FRAMEBUFFER,
+                    GL_COLOR_ATTACHMENT0 + i as u32,
+                    GL_TEXTURE_2D,
+                    color_img.texture,
+                    0,
+                );
+            }
+            if color_imgs.len() > 1 {
+                glDrawBuffers(draw_buffers.len() as _, draw_buffers.as_ptr());
+            }
+            glBindFramebuffer(GL_FRAMEBUFFER, ctx.default_framebuffer);
+        }
+        ctx.passes[pass.0].extra_textures = color_imgs[1..].to_vec();
+
+        pass
+    }
+
     pub fn gl_internal_id(&self, ctx: &mut Context) -> GLuint {
         let render_pass = &mut ctx.passes[self.0];
 
@@ -644,6 +784,9 @@ impl RenderPass {
         unsafe { glDeleteFramebuffers(1, &mut render_pass.gl_fb as *mut _) }
 
         render_pass.texture.delete();
+        for texture in &render_pass.extra_textures {
+            texture.delete();
+        }
         if let Some(depth_texture) = render_pass.depth_texture {
             depth_texture.delete();
         }
@@ -656,6 +799,10 @@ pub const MAX_SHADERSTAGE_IMAGES: usize = 12;
 pub struct Features {
     pub instancing: bool,
     pub alpha_texture: bool,
+    /// 3D (volume) textures, not available on GLES2
+    pub texture_3d: bool,
+    /// Render passes with more than one color attachment, not available on GLES2
+    pub multiple_render_targets: bool,
 }
 
 impl Features {
@@ -663,6 +810,8 @@ impl Features {
         Features {
             instancing: !is_gles2,
             alpha_texture: is_gles2,
+            texture_3d: !is_gles2,
+            multiple_render_targets: !is_gles2,
         }
     }
 }
@@ -676,6 +825,10 @@ pub struct GraphicsContext {
 
     pub(crate) features: Features,
     pub(crate) display: Option<*mut dyn crate::NativeDisplay>,
+    /// Vulkan backend presenting to the window instead of GL, see
+    /// `Context::vulkan`
+    #[cfg(feature = "vulkan")]
+    pub(crate) vulkan: Option<Box<VulkanContext>>,
 }
 
 impl GraphicsContext {
@@ -690,6 +843,14 @@ impl GraphicsContext {
 
             glGenVertexArrays(1, &mut vao as *mut _);
             glBindVertexArray(vao);
+            // Desktop GL ignores gl_PointSize unless asked to, GLES always uses it
+            #[cfg(not(any(
+                target_arch = "wasm32",
+                target_os = "android",
+                target_os = "ios",
+                target_env = "ohos"
+            )))]
+            glEnable(GL_PROGRAM_POINT_SIZE);
             GraphicsContext {
                 default_framebuffer,
                 shaders: vec![],
@@ -714,6 +875,8 @@ impl GraphicsContext {
                     attributes: [None; MAX_VERTEX_ATTRIBUTES],
                 },
                 display: None,
+                #[cfg(feature = "vulkan")]
+                vulkan: None,
             }
         }
     }
@@ -749,6 +912,16 @@ impl GraphicsContext {
                 }
             }
 
+            match pipeline.params.depth_write_offset {
+                Some((constant, slope)) => unsafe {
+                    glEnable(GL_POLYGON_OFFSET_FILL);
+                    glPolygonOffset(slope, constant);
+                },
+                None => unsafe {
+                    glDisable(GL_POLYGON_OFFSET_FILL);
+                },
+            }
+
             match pipeline.params.front_face_order {
                 FrontFaceOrder::Clockwise => unsafe {
                     glFrontFace(GL_CW);
@@ -845,6 +1018,16 @@ impl GraphicsContext {
         self.cache.alpha_blend = alpha_blend;
     }
 
+    /// Change the stencil reference value of both faces for the following
+    /// draws, keeping the rest of the current stencil state.
+    pub fn set_stencil_reference(&mut self, reference: i32) {
+        if let Some(mut stencil) = self.cache.stencil {
+            stencil.front.test_ref = reference;
+            stencil.back.test_ref = reference;
+            self.set_stencil(Some(stencil));
+        }
+    }
+
     pub fn set_stencil(&mut self, stencil_test: Option<StencilState>) {
         if self.cache.stencil == stencil_test {
             return;
@@ -908,7 +1091,73 @@ impl GraphicsContext {
         }
     }
 
+    /// Check that `bindings` fit the applied pipeline: one vertex buffer per
+    /// `BufferLayout`, all of them `BufferType::VertexBuffer`, an index
+    /// buffer, one image per `ShaderMeta::images` entry and storage and
+    /// uniform buffers of their own type.
+    pub fn validate_bindings(&self, bindings: &Bindings) -> Result<(), backend::GlError> {
+        let invalid = |msg: String| Err(backend::GlError::InvalidData(msg));
+        let pip = match self.cache.cur_pipeline {
+            Some(pipeline) => &self.pipelines[pipeline.0],
+            None => return invalid("bindings applied without a pipeline".to_string()),
+        };
+        let shader = &self.shaders[pip.shader.0];
+
+        if bindings.vertex_buffers.len() != pip.buffer_count {
+            return invalid(format!(
+                "{} vertex buffers bound to a pipeline with {} buffer layouts",
+                bindings.vertex_buffers.len(),
+                pip.buffer_count
+            ));
+        }
+        for (n, buffer) in bindings.vertex_buffers.iter().enumerate() {
+            if buffer.buffer_type != BufferType::VertexBuffer {
+                return invalid(format!("vertex buffer {} is a {:?}", n, buffer.buffer_type));
+            }
+        }
+        if bindings.index_buffer.buffer_type != BufferType::IndexBuffer {
+            return invalid(format!("index buffer is a {:?}", bindings.index_buffer.buffer_type));
+        }
+        let slots = [
+            (&bindings.storage_buffers, BufferType::StorageBuffer, "storage"),
+            (&bindings.uniform_buffers, BufferType::UniformBuffer, "uniform"),
+        ];
+        for (buffers, buffer_type, kind) in slots.iter() {
+            for (n, buffer) in buffers.iter().enumerate() {
+                if buffer.buffer_type != *buffer_type {
+                    return invalid(format!("{} buffer {} is a {:?}", kind, n, buffer.buffer_type));
+                }
+            }
+        }
+        if bindings.images.len() != shader.images.len() {
+            return invalid(format!(
+                "{} images bound to a shader with {} texture slots",
+                bindings.images.len(),
+                shader.images.len()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Bind `bindings` for the following draw calls. Bindings that don't fit
+    /// the pipeline, see `validate_bindings`, are reported but still bound,
+    /// `try_apply_bindings` rejects them instead.
     pub fn apply_bindings(&mut self, bindings: &Bindings) {
+        if let Err(error) = self.validate_bindings(bindings) {
+            log_04::warn!("Bindings don't fit the pipeline: {}", error);
+        }
+        self.bind(bindings);
+    }
+
+    /// `apply_bindings` for bindings that pass `validate_bindings`, others
+    /// are returned as an error without binding anything
+    pub fn try_apply_bindings(&mut self, bindings: &Bindings) -> Result<(), backend::GlError> {
+        self.validate_bindings(bindings)?;
+        self.bind(bindings);
+        Ok(())
+    }
+
+    fn bind(&mut self, bindings: &Bindings) {
         let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
         let shader = &self.shaders[pip.shader.0];
 
@@ -931,6 +1180,13 @@ impl GraphicsContext {
             bindings.index_buffer.index_type,
         );
 
+        for (n, buffer) in bindings.storage_buffers.iter().enumerate() {
+            unsafe { glBindBufferBase(GL_SHADER_STORAGE_BUFFER, n as GLuint, buffer.gl_buf) };
+        }
+        for (n, buffer) in bindings.uniform_buffers.iter().enumerate() {
+            unsafe { glBindBufferBase(GL_UNIFORM_BUFFER, n as GLuint, buffer.gl_buf) };
+        }
+
         let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
 
         for attr_index in 0..MAX_VERTEX_ATTRIBUTES {
@@ -952,7 +1208,7 @@ impl GraphicsContext {
                             attr_index as GLuint,
                             attribute.size,
                             attribute.type_,
-                            GL_FALSE as u8,
+                            attribute.normalized as u8,
                             attribute.stride,
                             attribute.offset as *mut _,
                         );
@@ -1001,8 +1257,9 @@ impl GraphicsContext {
             );
 
             unsafe {
-                let data = (uniform_ptr as *const f32).offset(offset as isize);
-                let data_int = (uniform_ptr as *const i32).offset(offset as isize);
+                let data = (uniform_ptr as *const f32).add(offset);
+                let data_int = (uniform_ptr as *const i32).add(offset);
+                let data_uint = (uniform_ptr as *const u32).add(offset);
 
                 if let Some(gl_loc) = uniform.gl_loc {
                     match uniform.uniform_type {
@@ -1030,6 +1287,21 @@ impl GraphicsContext {
                         Int4 => {
                             glUniform4iv(gl_loc, uniform.array_count, data_int);
                         }
+                        UInt1 => {
+                            glUniform1uiv(gl_loc, uniform.array_count, data_uint);
+                        }
+                        UInt2 => {
+                            glUniform2uiv(gl_loc, uniform.array_count, data_uint);
+                        }
+                        UInt3 => {
+                            glUniform3uiv(gl_loc, uniform.array_count, data_uint);
+                        }
+                        UInt4 => {
+                            glUniform4uiv(gl_loc, uniform.array_count, data_uint);
+                        }
+                        Mat3 => {
+                            glUniformMatrix3fv(gl_loc, uniform.array_count, 0, data);
+                        }
                         Mat4 => {
                             glUniformMatrix4fv(gl_loc, uniform.array_count, 0, data);
                         }
@@ -1046,23 +1318,25 @@ impl GraphicsContext {
         depth: Option<f32>,
         stencil: Option<i32>,
     ) {
-        let mut bits = 0;
+        let bits = PassAction::Clear {
+            color,
+            depth,
+            stencil,
+        }
+        .gl_clear_bits();
         if let Some((r, g, b, a)) = color {
-            bits |= GL_COLOR_BUFFER_BIT;
             unsafe {
                 glClearColor(r, g, b, a);
             }
         }
 
         if let Some(v) = depth {
-            bits |= GL_DEPTH_BUFFER_BIT;
             unsafe {
                 glClearDepthf(v);
             }
         }
 
         if let Some(v) = stencil {
-            bits |= GL_STENCIL_BUFFER_BIT;
             unsafe {
                 glClearStencil(v);
             }
@@ -1429,6 +1703,12 @@ impl From<CompareFunc> for GLenum {
 pub enum PrimitiveType {
     Triangles,
     Lines,
+    /// One point per vertex. The vertex shader has to write `gl_PointSize`,
+    /// otherwise the point size is undefined.
+    Points,
+    /// Patches for the tessellation stages, Vulkan only. Every
+    /// `GraphicsPipelineRecipe::patch_control_points` vertices form a patch.
+    Patches,
 }
 
 impl From<PrimitiveType> for GLenum {
@@ -1436,6 +1716,8 @@ impl From<PrimitiveType> for GLenum {
         match primitive_type {
             PrimitiveType::Triangles => GL_TRIANGLES,
             PrimitiveType::Lines => GL_LINES,
+            PrimitiveType::Points => GL_POINTS,
+            PrimitiveType::Patches => GL_PATCHES,
         }
     }
 }
@@ -1480,8 +1762,15 @@ impl IndexType {
 pub struct PipelineParams {
     pub cull_face: CullFace,
     pub front_face_order: FrontFaceOrder,
+    /// Depth compare function, only used when `depth_write` is set.
+    /// The depth clear value has to match it: with `Less`/`LessOrEqual` clear
+    /// depth to 1.0, for reversed-Z with `Greater`/`GreaterOrEqual` clear it
+    /// to 0.0, otherwise nothing ever passes the test.
     pub depth_test: Comparison,
     pub depth_write: bool,
+    /// Depth bias as (constant factor, slope factor), e.g. to fight shadow
+    /// acne when rendering shadow maps. Positive values push depth away from
+    /// the camera; flip the signs when using reversed-Z.
     pub depth_write_offset: Option<(f32, f32)>,
     /// Color (RGB) blend function. If None - blending will be disabled for this pipeline.
     /// Usual use case to get alpha-blending:
@@ -1522,6 +1811,38 @@ pub struct PipelineParams {
     pub stencil_test: Option<StencilState>,
     pub color_write: ColorMask,
     pub primitive_type: PrimitiveType,
+    /// State set while recording instead of baked into the pipeline, only
+    /// used by the Vulkan backend.
+    /// Default: stencil reference and line width both dynamic
+    pub dynamic_states: DynamicStateFlags,
+}
+
+/// Which optional pipeline state is left dynamic on Vulkan. Viewport and
+/// scissor are always dynamic. State that isn't comes from `PipelineParams`:
+/// the `test_ref` values of `stencil_test` and a line width of 1.0.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DynamicStateFlags {
+    /// Allows `set_stencil_reference`
+    pub stencil_reference: bool,
+    /// Allows `set_line_width`
+    pub line_width: bool,
+}
+
+impl DynamicStateFlags {
+    /// Only viewport and scissor dynamic
+    pub const NONE: DynamicStateFlags = DynamicStateFlags {
+        stencil_reference: false,
+        line_width: false,
+    };
+}
+
+impl Default for DynamicStateFlags {
+    fn default() -> DynamicStateFlags {
+        DynamicStateFlags {
+            stencil_reference: true,
+            line_width: true,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -1540,6 +1861,7 @@ impl Default for PipelineParams {
             stencil_test: None,
             color_write: (true, true, true, true),
             primitive_type: PrimitiveType::Triangles,
+            dynamic_states: DynamicStateFlags::default(),
         }
     }
 }
@@ -1637,6 +1959,7 @@ impl Pipeline {
                         attr_loc,
                         size: format.size(),
                         type_: format.type_(),
+                        normalized: format.normalized(),
                         offset: buffer_data.offset,
                         stride: buffer_data.stride,
                         buffer_index: *buffer_index,
@@ -1657,6 +1980,7 @@ impl Pipeline {
 
         let pipeline = PipelineInternal {
             layout: vertex_layout,
+            buffer_count: buffer_layout.len(),
             shader,
             params,
         };
@@ -1676,6 +2000,7 @@ struct VertexAttributeInternal {
     attr_loc: GLuint,
     size: i32,
     type_: GLuint,
+    normalized: bool,
     offset: i64,
     stride: i32,
     buffer_index: usize,
@@ -1684,12 +2009,14 @@ struct VertexAttributeInternal {
 
 struct PipelineInternal {
     layout: Vec<Option<VertexAttributeInternal>>,
+    /// Number of `BufferLayout`s, the vertex buffers `Bindings` has to have
+    buffer_count: usize,
     shader: Shader,
     params: PipelineParams,
 }
 
 /// Geometry bindings
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct Bindings {
     /// Vertex buffers. Data contained in the buffer must match layout
     /// specified in the `Pipeline`.
@@ -1705,12 +2032,28 @@ pub struct Bindings {
     /// Textures to be used with when drawing the geometry in the fragment
     /// shader.
     pub images: Vec<Texture>,
+    /// `BufferType::StorageBuffer`s, bound to storage buffer bindings 0, 1,
+    /// ... in order.
+    pub storage_buffers: Vec<Buffer>,
+    /// `BufferType::UniformBuffer`s, bound to uniform block bindings 0, 1,
+    /// ... in order. Plain uniforms set with `apply_uniforms` don't need one.
+    pub uniform_buffers: Vec<Buffer>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum BufferType {
     VertexBuffer,
     IndexBuffer,
+    /// Uniform block data, see `Bindings::uniform_buffers`. Needs GL 3.1,
+    /// GLES 3 or WebGL 2.
+    UniformBuffer,
+    /// Shader storage data, see `Bindings::storage_buffers`. Needs GL 4.3
+    /// or GLES 3.1.
+    StorageBuffer,
+    /// CPU visible scratch memory to copy from when orchestrating uploads
+    /// by hand, see `VulkanContext::map_buffer`. Vulkan only uses it as a
+    /// transfer source; on OpenGL it is a plain buffer.
+    Staging,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -1724,6 +2067,9 @@ fn gl_buffer_target(buffer_type: &BufferType) -> GLenum {
     match buffer_type {
         BufferType::VertexBuffer => GL_ARRAY_BUFFER,
         BufferType::IndexBuffer => GL_ELEMENT_ARRAY_BUFFER,
+        BufferType::UniformBuffer => GL_UNIFORM_BUFFER,
+        BufferType::StorageBuffer => GL_SHADER_STORAGE_BUFFER,
+        BufferType::Staging => GL_ARRAY_BUFFER,
     }
 }
 
@@ -1743,6 +2089,19 @@ pub struct Buffer {
     index_type: Option<IndexType>,
 }
 
+/// No buffer at all, e.g. for the index buffer of `Bindings` only used for
+/// compute
+impl Default for Buffer {
+    fn default() -> Buffer {
+        Buffer {
+            gl_buf: 0,
+            buffer_type: BufferType::VertexBuffer,
+            size: 0,
+            index_type: None,
+        }
+    }
+}
+
 impl Buffer {
     /// Create an immutable buffer resource object.
     /// ```ignore
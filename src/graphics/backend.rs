@@ -5,10 +5,81 @@
 
 use crate::conf::RenderingBackend;
 use crate::graphics::*;
+use crate::native::gl::*;
 use crate::native::NativeDisplay;
 
 #[cfg(feature = "vulkan")]
-use crate::graphics::vulkan::vk::VulkanContext;
+use crate::graphics::vulkan::vk::{VulkanContext, VulkanError, VulkanStats};
+
+use std::{error::Error, fmt, mem};
+
+/// Errors reported by the OpenGL side of the backend abstraction
+#[derive(Debug)]
+pub enum GlError {
+    /// The operation needs a GL feature the current context lacks (e.g. on GLES2)
+    Unsupported(&'static str),
+    /// The supplied data does not match the requested resource
+    InvalidData(String),
+}
+
+impl fmt::Display for GlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GlError::Unsupported(what) => write!(f, "Unsupported by the OpenGL context: {}", what),
+            GlError::InvalidData(msg) => write!(f, "Invalid data: {}", msg),
+        }
+    }
+}
+
+impl Error for GlError {}
+
+/// Error type shared by both rendering backends, so applications can react
+/// to e.g. out of memory or device loss without caring which backend runs.
+#[derive(Debug)]
+pub enum BackendError {
+    /// The requested backend can not be used on this machine or build
+    Unavailable(RenderingBackend),
+    OpenGL(GlError),
+    #[cfg(feature = "vulkan")]
+    Vulkan(VulkanError),
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::Unavailable(backend) => {
+                write!(f, "Rendering backend {:?} is not available", backend)
+            }
+            BackendError::OpenGL(error) => write!(f, "OpenGL backend error: {}", error),
+            #[cfg(feature = "vulkan")]
+            BackendError::Vulkan(error) => write!(f, "Vulkan backend error: {}", error),
+        }
+    }
+}
+
+impl Error for BackendError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BackendError::Unavailable(_) => None,
+            BackendError::OpenGL(error) => Some(error),
+            #[cfg(feature = "vulkan")]
+            BackendError::Vulkan(error) => Some(error),
+        }
+    }
+}
+
+impl From<GlError> for BackendError {
+    fn from(error: GlError) -> Self {
+        BackendError::OpenGL(error)
+    }
+}
+
+#[cfg(feature = "vulkan")]
+impl From<VulkanError> for BackendError {
+    fn from(error: VulkanError) -> Self {
+        BackendError::Vulkan(error)
+    }
+}
 
 /// Rendering backend abstraction
 pub enum RenderingBackendContext {
@@ -19,18 +90,30 @@ pub enum RenderingBackendContext {
 
 impl RenderingBackendContext {
     /// Create a new rendering backend context
+    ///
+    /// Panics when Vulkan is requested in a build without the `vulkan`
+    /// feature, see `try_new` for the fallible version.
     pub fn new(backend: RenderingBackend) -> Self {
+        Self::try_new(backend).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Create a new rendering backend context, or
+    /// `BackendError::Unavailable` when Vulkan is requested in a build
+    /// without the `vulkan` feature
+    pub fn try_new(backend: RenderingBackend) -> Result<Self, BackendError> {
         match backend {
-            RenderingBackend::OpenGL => RenderingBackendContext::OpenGL(GraphicsContext::new(false)),
+            RenderingBackend::OpenGL => {
+                Ok(RenderingBackendContext::OpenGL(GraphicsContext::new(false)))
+            }
             #[cfg(feature = "vulkan")]
-            RenderingBackend::Vulkan => RenderingBackendContext::Vulkan(VulkanContext::new()),
+            RenderingBackend::Vulkan => Ok(RenderingBackendContext::Vulkan(VulkanContext::try_new()?)),
             #[cfg(not(feature = "vulkan"))]
-            RenderingBackend::Vulkan => panic!("Vulkan backend is not available. Enable the 'vulkan' feature to use Vulkan."),
+            RenderingBackend::Vulkan => Err(BackendError::Unavailable(backend)),
         }
     }
 
     /// Initialize the backend
-    pub fn initialize(&mut self, display: &mut dyn NativeDisplay) -> Result<(), String> {
+    pub fn initialize(&mut self, display: &mut dyn NativeDisplay) -> Result<(), BackendError> {
         match self {
             RenderingBackendContext::OpenGL(gl_ctx) => {
                 // Update GL context after OpenGL functions are loaded
@@ -40,42 +123,50 @@ impl RenderingBackendContext {
             }
             #[cfg(feature = "vulkan")]
             RenderingBackendContext::Vulkan(vk_ctx) => {
-                vk_ctx.initialize(display).map_err(|e| e.to_string())
+                vk_ctx.initialize(display).map_err(BackendError::from)
             }
         }
     }
 
     /// Begin a render pass
-    pub fn begin_render_pass(&mut self, clear_color: Option<(f32, f32, f32, f32)>) -> Result<(), String> {
+    pub fn begin_render_pass(&mut self, action: PassAction) -> Result<(), BackendError> {
         match self {
             RenderingBackendContext::OpenGL(gl_ctx) => {
-                // OpenGL render pass handling
+                // Clears what the action asks for and keeps the rest, like the
+                // load ops Vulkan derives from the same action
+                gl_ctx.begin_default_pass(action);
                 Ok(())
             }
             #[cfg(feature = "vulkan")]
             RenderingBackendContext::Vulkan(vk_ctx) => {
-                let color = clear_color.unwrap_or((0.0, 0.0, 0.0, 1.0));
-                vk_ctx.begin_render_pass(color).map_err(|e| e.to_string())
+                // Nothing is drawn while the window is minimized
+                if vk_ctx.is_frame_skipped() {
+                    return Ok(());
+                }
+                vk_ctx.begin_render_pass(action).map_err(BackendError::from)
             }
         }
     }
 
     /// End a render pass
-    pub fn end_render_pass(&mut self) -> Result<(), String> {
+    pub fn end_render_pass(&mut self) -> Result<(), BackendError> {
         match self {
             RenderingBackendContext::OpenGL(gl_ctx) => {
-                // OpenGL render pass handling
+                gl_ctx.end_render_pass();
                 Ok(())
             }
             #[cfg(feature = "vulkan")]
             RenderingBackendContext::Vulkan(vk_ctx) => {
-                vk_ctx.end_render_pass().map_err(|e| e.to_string())
+                if vk_ctx.is_frame_skipped() {
+                    return Ok(());
+                }
+                vk_ctx.end_render_pass().map_err(BackendError::from)
             }
         }
     }
 
     /// Present the current frame
-    pub fn present(&mut self) -> Result<(), String> {
+    pub fn present(&mut self) -> Result<(), BackendError> {
         match self {
             RenderingBackendContext::OpenGL(gl_ctx) => {
                 // OpenGL present handling (usually done by swap buffers)
@@ -83,13 +174,16 @@ impl RenderingBackendContext {
             }
             #[cfg(feature = "vulkan")]
             RenderingBackendContext::Vulkan(vk_ctx) => {
-                vk_ctx.present().map_err(|e| e.to_string())
+                vk_ctx.present().map_err(BackendError::from)
             }
         }
     }
 
     /// Create a buffer
-    pub fn create_buffer(&mut self, size: usize, usage: BufferType) -> Result<usize, String> {
+    ///
+    /// On Vulkan `Usage::Immutable` buffers live in device local memory and are
+    /// filled through a staging copy, see `VulkanContext::create_buffer`.
+    pub fn create_buffer(&mut self, size: usize, usage: BufferType, usage_hint: Usage) -> Result<usize, BackendError> {
         match self {
             RenderingBackendContext::OpenGL(gl_ctx) => {
                 // OpenGL buffer creation
@@ -98,20 +192,27 @@ impl RenderingBackendContext {
             #[cfg(feature = "vulkan")]
             RenderingBackendContext::Vulkan(vk_ctx) => {
                 use ash_037::vk;
-                use gpu_allocator_022::MemoryLocation;
                 let vk_usage = match usage {
                     BufferType::VertexBuffer => vk::BufferUsageFlags::VERTEX_BUFFER,
                     BufferType::IndexBuffer => vk::BufferUsageFlags::INDEX_BUFFER,
-                    BufferType::IndexBuffer => vk::BufferUsageFlags::UNIFORM_BUFFER, // Temporary mapping
+                    BufferType::UniformBuffer => vk::BufferUsageFlags::UNIFORM_BUFFER,
+                    BufferType::StorageBuffer => vk::BufferUsageFlags::STORAGE_BUFFER,
+                    BufferType::Staging => vk::BufferUsageFlags::TRANSFER_SRC,
                 };
-                vk_ctx.create_buffer(size as vk::DeviceSize, vk_usage, MemoryLocation::CpuToGpu)
-                    .map_err(|e| e.to_string())
+                // Staging buffers are there to be mapped
+                let usage_hint = if usage == BufferType::Staging {
+                    Usage::Stream
+                } else {
+                    usage_hint
+                };
+                vk_ctx.create_buffer(size as vk::DeviceSize, vk_usage, usage_hint)
+                    .map_err(BackendError::from)
             }
         }
     }
 
     /// Update buffer data
-    pub fn update_buffer(&mut self, buffer_id: usize, data: &[u8]) -> Result<(), String> {
+    pub fn update_buffer(&mut self, buffer_id: usize, data: &[u8]) -> Result<(), BackendError> {
         match self {
             RenderingBackendContext::OpenGL(gl_ctx) => {
                 // OpenGL buffer update
@@ -119,13 +220,13 @@ impl RenderingBackendContext {
             }
             #[cfg(feature = "vulkan")]
             RenderingBackendContext::Vulkan(vk_ctx) => {
-                vk_ctx.update_buffer(buffer_id, data).map_err(|e| e.to_string())
+                vk_ctx.update_buffer(buffer_id, data).map_err(BackendError::from)
             }
         }
     }
 
     /// Create a texture
-    pub fn create_texture(&mut self, width: u32, height: u32, data: &[u8]) -> Result<usize, String> {
+    pub fn create_texture(&mut self, width: u32, height: u32, data: &[u8]) -> Result<usize, BackendError> {
         match self {
             RenderingBackendContext::OpenGL(gl_ctx) => {
                 // OpenGL texture creation
@@ -133,7 +234,43 @@ impl RenderingBackendContext {
             }
             #[cfg(feature = "vulkan")]
             RenderingBackendContext::Vulkan(vk_ctx) => {
-                vk_ctx.create_texture(width, height, data).map_err(|e| e.to_string())
+                vk_ctx.create_texture(width, height, data).map_err(BackendError::from)
+            }
+        }
+    }
+
+    /// Toggle the wireframe debug overlay. Vulkan only; GL reports it as
+    /// unsupported since GLES has no polygon mode.
+    pub fn set_debug_wireframe(&mut self, enabled: bool) -> Result<(), BackendError> {
+        match self {
+            RenderingBackendContext::OpenGL(_) => {
+                Err(GlError::Unsupported("wireframe rendering is not available on GL").into())
+            }
+            #[cfg(feature = "vulkan")]
+            RenderingBackendContext::Vulkan(vk_ctx) => {
+                vk_ctx.set_debug_wireframe(enabled);
+                Ok(())
+            }
+        }
+    }
+
+    /// Forward the loss of the native window surface, e.g. on Android
+    /// `onSurfaceDestroyed`. GL keeps its surface handling in the EGL code.
+    pub fn on_surface_lost(&mut self) {
+        match self {
+            RenderingBackendContext::OpenGL(_) => {}
+            #[cfg(feature = "vulkan")]
+            RenderingBackendContext::Vulkan(vk_ctx) => vk_ctx.on_surface_lost(),
+        }
+    }
+
+    /// Forward a new native window surface after `on_surface_lost`
+    pub fn on_surface_recreated(&mut self, display: &mut dyn NativeDisplay) -> Result<(), BackendError> {
+        match self {
+            RenderingBackendContext::OpenGL(_) => Ok(()),
+            #[cfg(feature = "vulkan")]
+            RenderingBackendContext::Vulkan(vk_ctx) => {
+                vk_ctx.on_surface_recreated(display).map_err(BackendError::from)
             }
         }
     }
@@ -172,25 +309,46 @@ impl RenderingBackendContext {
     }
 }
 
+/// Buffer created through `GraphicsContextWrapper`, usable with the
+/// wrapper that created it whichever backend it runs on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BufferHandle(usize);
+
+/// Texture created through `GraphicsContextWrapper`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureHandle(usize);
+
 /// Common graphics context that works with both OpenGL and Vulkan
+///
+/// Renderers written against its resource and draw calls run on either
+/// backend. Pipelines are still backend specific, see `as_opengl` and
+/// `as_vulkan`. Not available on GLES2, where the calls fail with
+/// `GlError::Unsupported`: instanced draws, 3D textures and render passes
+/// with several color attachments. Wireframe rendering is Vulkan only.
 pub struct GraphicsContextWrapper {
     backend: RenderingBackendContext,
+    /// GL objects behind the handles, Vulkan handles hold its resource ids
+    gl_buffers: Vec<Buffer>,
+    /// `None` once deleted
+    gl_textures: Vec<Option<GlTexture>>,
 }
 
 impl GraphicsContextWrapper {
     /// Create a new graphics context with the specified backend
-    pub fn new(backend: RenderingBackend) -> Result<Self, String> {
+    pub fn new(backend: RenderingBackend) -> Result<Self, BackendError> {
         if !RenderingBackendContext::is_available(backend) {
-            return Err(format!("Rendering backend {:?} is not available", backend));
+            return Err(BackendError::Unavailable(backend));
         }
 
         Ok(Self {
-            backend: RenderingBackendContext::new(backend),
+            backend: RenderingBackendContext::try_new(backend)?,
+            gl_buffers: Vec::new(),
+            gl_textures: Vec::new(),
         })
     }
 
     /// Initialize the graphics context
-    pub fn initialize(&mut self, display: &mut dyn NativeDisplay) -> Result<(), String> {
+    pub fn initialize(&mut self, display: &mut dyn NativeDisplay) -> Result<(), BackendError> {
         self.backend.initialize(display)
     }
 
@@ -199,6 +357,330 @@ impl GraphicsContextWrapper {
         self.backend.backend_type()
     }
 
+    /// Create a buffer of `buffer_type` holding `data`. Index buffers take
+    /// their index type from `T`. `Usage::Stream` and `Usage::Dynamic`
+    /// buffers can be overwritten later with `update_buffer`.
+    pub fn create_buffer<T>(
+        &mut self,
+        buffer_type: BufferType,
+        usage: Usage,
+        data: &[T],
+    ) -> Result<BufferHandle, BackendError> {
+        let size = mem::size_of_val(data);
+        match &mut self.backend {
+            RenderingBackendContext::OpenGL(gl_ctx) => {
+                let buffer = match (usage, buffer_type) {
+                    (Usage::Immutable, _) => Buffer::immutable(gl_ctx, buffer_type, data),
+                    (_, BufferType::IndexBuffer) => {
+                        Buffer::index_stream(gl_ctx, IndexType::for_type::<T>(), size)
+                    }
+                    (_, _) => Buffer::stream(gl_ctx, buffer_type, size),
+                };
+                if usage != Usage::Immutable {
+                    buffer.update(gl_ctx, data);
+                }
+                self.gl_buffers.push(buffer);
+                Ok(BufferHandle(self.gl_buffers.len() - 1))
+            }
+            #[cfg(feature = "vulkan")]
+            RenderingBackendContext::Vulkan(vk_ctx) => {
+                if usage == Usage::Immutable {
+                    return Ok(BufferHandle(vk_ctx.create_immutable_buffer(buffer_type, data)?));
+                }
+                use ash_037::vk;
+                let (vk_usage, index_type) = match buffer_type {
+                    BufferType::VertexBuffer => (vk::BufferUsageFlags::VERTEX_BUFFER, None),
+                    BufferType::IndexBuffer => {
+                        (vk::BufferUsageFlags::INDEX_BUFFER, Some(IndexType::for_type::<T>()))
+                    }
+                    BufferType::UniformBuffer => (vk::BufferUsageFlags::UNIFORM_BUFFER, None),
+                    BufferType::StorageBuffer => (vk::BufferUsageFlags::STORAGE_BUFFER, None),
+                    BufferType::Staging => (vk::BufferUsageFlags::TRANSFER_SRC, None),
+                };
+                let id = vk_ctx.create_buffer(size as vk::DeviceSize, vk_usage, usage)?;
+                if let Some(buffer) = vk_ctx.buffers.get_mut(&id) {
+                    buffer.index_type = index_type;
+                }
+                vk_ctx.update_buffer(id, as_bytes(data))?;
+                Ok(BufferHandle(id))
+            }
+        }
+    }
+
+    /// Overwrite the start of a buffer from `create_buffer` with `data`
+    pub fn update_buffer<T>(&mut self, buffer: BufferHandle, data: &[T]) -> Result<(), BackendError> {
+        match &mut self.backend {
+            RenderingBackendContext::OpenGL(gl_ctx) => {
+                let gl_buffer = self.gl_buffers.get(buffer.0).ok_or_else(|| {
+                    GlError::InvalidData(format!("unknown buffer {:?}", buffer))
+                })?;
+                if mem::size_of_val(data) > gl_buffer.size() {
+                    return Err(GlError::InvalidData(format!(
+                        "{} bytes do not fit into a buffer of {} bytes",
+                        mem::size_of_val(data),
+                        gl_buffer.size()
+                    ))
+                    .into());
+                }
+                gl_buffer.update(gl_ctx, data);
+                Ok(())
+            }
+            #[cfg(feature = "vulkan")]
+            RenderingBackendContext::Vulkan(vk_ctx) => {
+                vk_ctx.update_buffer(buffer.0, as_bytes(data)).map_err(BackendError::from)
+            }
+        }
+    }
+
+    /// Create a sampled RGBA8 texture from `data`
+    pub fn create_texture(
+        &mut self,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> Result<TextureHandle, BackendError> {
+        match &mut self.backend {
+            RenderingBackendContext::OpenGL(gl_ctx) => {
+                let size = TextureFormat::RGBA8.size(width, height) as usize;
+                if data.len() != size {
+                    return Err(GlError::InvalidData(format!(
+                        "expected {} bytes for {}x{}, got {}",
+                        size,
+                        width,
+                        height,
+                        data.len()
+                    ))
+                    .into());
+                }
+                let params = TextureParams {
+                    width,
+                    height,
+                    ..Default::default()
+                };
+                let texture = Texture::from_data_and_format(gl_ctx, data, params);
+                self.gl_textures.push(Some(GlTexture {
+                    texture,
+                    target: GL_TEXTURE_2D,
+                }));
+                Ok(TextureHandle(self.gl_textures.len() - 1))
+            }
+            #[cfg(feature = "vulkan")]
+            RenderingBackendContext::Vulkan(vk_ctx) => {
+                Ok(TextureHandle(vk_ctx.create_texture(width, height, data)?))
+            }
+        }
+    }
+
+    /// Create a 3D (volume) texture of `width`x`height`x`depth` texels.
+    /// `data` holds the slices tightly packed along the depth axis. It can be
+    /// deleted with `delete_texture`, but not updated or bound with
+    /// `apply_bindings` on GL, where samplers are bound as 2D textures.
+    pub fn create_texture_3d(
+        &mut self,
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: TextureFormat,
+        data: &[u8],
+    ) -> Result<TextureHandle, BackendError> {
+        match &mut self.backend {
+            RenderingBackendContext::OpenGL(gl_ctx) => {
+                if !gl_ctx.features().texture_3d {
+                    return Err(GlError::Unsupported("3D textures are not supported on GLES2").into());
+                }
+                if format.is_compressed() {
+                    return Err(GlError::Unsupported("compressed 3D textures are not supported").into());
+                }
+                if data.len() != format.size(width, height) as usize * depth as usize {
+                    return Err(GlError::InvalidData(format!(
+                        "3D texture data size mismatch: got {} bytes",
+                        data.len()
+                    ))
+                    .into());
+                }
+
+                let (internal_format, gl_format, pixel_type) =
+                    format.into_gl_params(gl_ctx.features().alpha_texture);
+                let mut texture: GLuint = 0;
+                unsafe {
+                    glGenTextures(1, &mut texture as *mut _);
+                    glBindTexture(GL_TEXTURE_3D, texture);
+                    glPixelStorei(GL_UNPACK_ALIGNMENT, 1);
+                    glTexImage3D(
+                        GL_TEXTURE_3D,
+                        0,
+                        internal_format as i32,
+                        width as i32,
+                        height as i32,
+                        depth as i32,
+                        0,
+                        gl_format,
+                        pixel_type,
+                        data.as_ptr() as *const _,
+                    );
+                    glTexParameteri(GL_TEXTURE_3D, GL_TEXTURE_WRAP_S, GL_CLAMP_TO_EDGE as i32);
+                    glTexParameteri(GL_TEXTURE_3D, GL_TEXTURE_WRAP_T, GL_CLAMP_TO_EDGE as i32);
+                    glTexParameteri(GL_TEXTURE_3D, GL_TEXTURE_WRAP_R, GL_CLAMP_TO_EDGE as i32);
+                    glTexParameteri(GL_TEXTURE_3D, GL_TEXTURE_MIN_FILTER, GL_LINEAR as i32);
+                    glTexParameteri(GL_TEXTURE_3D, GL_TEXTURE_MAG_FILTER, GL_LINEAR as i32);
+                    glBindTexture(GL_TEXTURE_3D, 0);
+                }
+                let texture = Texture {
+                    texture,
+                    width,
+                    height,
+                    format,
+                };
+                self.gl_textures.push(Some(GlTexture {
+                    texture,
+                    target: GL_TEXTURE_3D,
+                }));
+                Ok(TextureHandle(self.gl_textures.len() - 1))
+            }
+            #[cfg(feature = "vulkan")]
+            RenderingBackendContext::Vulkan(vk_ctx) => Ok(TextureHandle(
+                vk_ctx.create_texture_3d(width, height, depth, format, data)?,
+            )),
+        }
+    }
+
+    /// Begin a render pass on the window, see `RenderingBackendContext::begin_render_pass`
+    pub fn begin_render_pass(&mut self, action: PassAction) -> Result<(), BackendError> {
+        self.backend.begin_render_pass(action)
+    }
+
+    /// End the current render pass
+    pub fn end_render_pass(&mut self) -> Result<(), BackendError> {
+        self.backend.end_render_pass()
+    }
+
+    /// Replace the whole contents of a texture from `create_texture`, which
+    /// has to be `width` x `height`, with RGBA8 `data`
+    pub fn update_texture(
+        &mut self,
+        texture: TextureHandle,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> Result<(), BackendError> {
+        match &mut self.backend {
+            RenderingBackendContext::OpenGL(gl_ctx) => {
+                let gl_texture = gl_texture_2d(&self.gl_textures, texture)?;
+                let size = gl_texture.format.size(width, height) as usize;
+                if (gl_texture.width, gl_texture.height) != (width, height) || data.len() != size {
+                    return Err(GlError::InvalidData(format!(
+                        "texture {:?} is {}x{}, got {} bytes for {}x{}",
+                        texture,
+                        gl_texture.width,
+                        gl_texture.height,
+                        data.len(),
+                        width,
+                        height
+                    ))
+                    .into());
+                }
+                gl_texture.update(gl_ctx, data);
+                Ok(())
+            }
+            #[cfg(feature = "vulkan")]
+            RenderingBackendContext::Vulkan(vk_ctx) => vk_ctx
+                .update_texture(texture.0, width, height, data)
+                .map_err(BackendError::from),
+        }
+    }
+
+    /// Delete a texture from `create_texture` or `create_texture_3d`. On
+    /// Vulkan it is freed once the frames in flight are done with it.
+    pub fn delete_texture(&mut self, texture: TextureHandle) -> Result<(), BackendError> {
+        match &mut self.backend {
+            RenderingBackendContext::OpenGL(_) => {
+                gl_texture(&self.gl_textures, texture)?.texture.delete();
+                self.gl_textures[texture.0] = None;
+                Ok(())
+            }
+            #[cfg(feature = "vulkan")]
+            RenderingBackendContext::Vulkan(vk_ctx) => {
+                vk_ctx.delete_texture(texture.0).map_err(BackendError::from)
+            }
+        }
+    }
+
+    /// Bind buffers and textures for the following `draw` calls, after the
+    /// pipeline has been applied. On Vulkan texture `N` is bound to binding
+    /// `N` of `TEXTURE_SET`, see `VulkanContext::apply_textures`.
+    pub fn apply_bindings(
+        &mut self,
+        vertex_buffers: &[BufferHandle],
+        index_buffer: BufferHandle,
+        textures: &[TextureHandle],
+    ) -> Result<(), BackendError> {
+        match &mut self.backend {
+            RenderingBackendContext::OpenGL(gl_ctx) => {
+                let (gl_buffers, gl_textures) = (&self.gl_buffers, &self.gl_textures);
+                let buffer = |handle: &BufferHandle| {
+                    gl_buffers.get(handle.0).copied().ok_or_else(|| {
+                        GlError::InvalidData(format!("unknown buffer {:?}", handle))
+                    })
+                };
+                let images = textures
+                    .iter()
+                    .map(|&handle| gl_texture_2d(gl_textures, handle))
+                    .collect::<Result<_, _>>()?;
+                let bindings = Bindings {
+                    vertex_buffers: vertex_buffers.iter().map(buffer).collect::<Result<_, _>>()?,
+                    index_buffer: buffer(&index_buffer)?,
+                    images,
+                    ..Default::default()
+                };
+                gl_ctx.try_apply_bindings(&bindings)?;
+                Ok(())
+            }
+            #[cfg(feature = "vulkan")]
+            RenderingBackendContext::Vulkan(vk_ctx) => {
+                let ids: Vec<usize> = vertex_buffers.iter().map(|handle| handle.0).collect();
+                vk_ctx.apply_vertex_buffers(&ids)?;
+                vk_ctx.apply_index_buffer(index_buffer.0)?;
+                let ids: Vec<usize> = textures.iter().map(|handle| handle.0).collect();
+                vk_ctx.apply_textures(&ids).map_err(BackendError::from)
+            }
+        }
+    }
+
+    /// Draw `num_elements` indices of the bound index buffer starting at
+    /// `base_element`, `num_instances` times. More than one instance needs
+    /// instancing, which GLES2 lacks.
+    pub fn draw(
+        &mut self,
+        base_element: u32,
+        num_elements: u32,
+        num_instances: u32,
+    ) -> Result<(), BackendError> {
+        match &mut self.backend {
+            RenderingBackendContext::OpenGL(gl_ctx) => {
+                if num_instances > 1 && !gl_ctx.features().instancing {
+                    return Err(GlError::Unsupported("instanced drawing is not available on GLES2").into());
+                }
+                gl_ctx.draw(base_element as i32, num_elements as i32, num_instances as i32);
+                Ok(())
+            }
+            #[cfg(feature = "vulkan")]
+            RenderingBackendContext::Vulkan(vk_ctx) => vk_ctx
+                .draw(base_element, num_elements, num_instances)
+                .map_err(BackendError::from),
+        }
+    }
+
+    /// Resource counts, memory use and frame timing of the Vulkan backend,
+    /// see `VulkanContext::get_performance_stats`. GL keeps no such counters
+    /// and fails with `BackendError::Unavailable`.
+    #[cfg(feature = "vulkan")]
+    pub fn get_vulkan_stats(&self) -> Result<VulkanStats, BackendError> {
+        match &self.backend {
+            RenderingBackendContext::Vulkan(vk_ctx) => Ok(vk_ctx.get_performance_stats()),
+            _ => Err(BackendError::Unavailable(RenderingBackend::Vulkan)),
+        }
+    }
+
     /// Get the underlying OpenGL context (if available)
     pub fn as_opengl(&mut self) -> Option<&mut GraphicsContext> {
         match &mut self.backend {
@@ -216,4 +698,41 @@ impl GraphicsContextWrapper {
             _ => None,
         }
     }
-}
\ No newline at end of file
+}
+
+/// GL texture behind a `TextureHandle`
+#[derive(Clone, Copy)]
+struct GlTexture {
+    texture: Texture,
+    /// `GL_TEXTURE_2D` or `GL_TEXTURE_3D`
+    target: GLenum,
+}
+
+/// GL texture behind `handle`, unless it was deleted
+fn gl_texture(
+    gl_textures: &[Option<GlTexture>],
+    handle: TextureHandle,
+) -> Result<GlTexture, GlError> {
+    gl_textures
+        .get(handle.0)
+        .copied()
+        .flatten()
+        .ok_or_else(|| GlError::InvalidData(format!("unknown texture {:?}", handle)))
+}
+
+/// 2D GL texture behind `handle`, for the calls that can't take 3D textures
+fn gl_texture_2d(
+    gl_textures: &[Option<GlTexture>],
+    handle: TextureHandle,
+) -> Result<Texture, GlError> {
+    match gl_texture(gl_textures, handle)? {
+        GlTexture { texture, target } if target == GL_TEXTURE_2D => Ok(texture),
+        _ => Err(GlError::InvalidData(format!("texture {:?} is a 3D texture", handle))),
+    }
+}
+
+/// Raw bytes of `data` for the Vulkan upload calls
+#[cfg(feature = "vulkan")]
+fn as_bytes<T>(data: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, mem::size_of_val(data)) }
+}
@@ -55,6 +55,18 @@ pub enum TextureFormat {
     Depth,
     Alpha,
     LuminanceAlpha,
+    /// BC1 (DXT1) 4x4 blocks of 8 bytes, RGB with 1-bit alpha
+    Bc1,
+    /// BC3 (DXT5) 4x4 blocks of 16 bytes, RGBA
+    Bc3,
+    /// BC7 4x4 blocks of 16 bytes, high quality RGBA
+    Bc7,
+    /// ETC2 4x4 blocks of 8 bytes, RGB
+    Etc2Rgb8,
+    /// ETC2 with EAC alpha, 4x4 blocks of 16 bytes, RGBA
+    Etc2Rgba8,
+    /// ASTC 4x4 blocks of 16 bytes, RGBA
+    Astc4x4,
 }
 
 impl TextureFormat {
@@ -82,11 +94,40 @@ impl TextureFormat {
             }
             #[cfg(not(target_arch = "wasm32"))]
             TextureFormat::LuminanceAlpha => (GL_RG, GL_RG, GL_UNSIGNED_BYTE), // texture updates will swizzle Green -> Alpha to match WASM
+
+            // compressed data goes through glCompressedTexImage2D, which only takes the internal format
+            TextureFormat::Bc1 => (GL_COMPRESSED_RGBA_S3TC_DXT1_EXT, 0, 0),
+            TextureFormat::Bc3 => (GL_COMPRESSED_RGBA_S3TC_DXT5_EXT, 0, 0),
+            TextureFormat::Bc7 => (GL_COMPRESSED_RGBA_BPTC_UNORM, 0, 0),
+            TextureFormat::Etc2Rgb8 => (GL_COMPRESSED_RGB8_ETC2, 0, 0),
+            TextureFormat::Etc2Rgba8 => (GL_COMPRESSED_RGBA8_ETC2_EAC, 0, 0),
+            TextureFormat::Astc4x4 => (GL_COMPRESSED_RGBA_ASTC_4x4_KHR, 0, 0),
+        }
+    }
+
+    /// Whether the format stores 4x4 texel blocks instead of single texels
+    pub fn is_compressed(self) -> bool {
+        self.block_bytes().is_some()
+    }
+
+    /// Bytes per 4x4 block of a compressed format
+    fn block_bytes(self) -> Option<u32> {
+        match self {
+            TextureFormat::Bc1 | TextureFormat::Etc2Rgb8 => Some(8),
+            TextureFormat::Bc3
+            | TextureFormat::Bc7
+            | TextureFormat::Etc2Rgba8
+            | TextureFormat::Astc4x4 => Some(16),
+            _ => None,
         }
     }
 
     /// Returns the size in bytes of texture with `dimensions`.
+    /// Compressed formats round the dimensions up to whole 4x4 blocks.
     pub fn size(self, width: u32, height: u32) -> u32 {
+        if let Some(block_bytes) = self.block_bytes() {
+            return block_bytes * width.div_ceil(4) * height.div_ceil(4);
+        }
         let square = width * height;
         match self {
             TextureFormat::RGB8 => 3 * square,
@@ -94,6 +135,7 @@ impl TextureFormat {
             TextureFormat::Depth => 2 * square,
             TextureFormat::Alpha => 1 * square,
             TextureFormat::LuminanceAlpha => 2 * square,
+            _ => unreachable!(),
         }
     }
 }
@@ -175,20 +217,36 @@ impl Texture {
             ctx.cache.bind_texture(0, texture);
             glPixelStorei(GL_UNPACK_ALIGNMENT, 1); // miniquad always uses row alignment of 1
 
-            glTexImage2D(
-                GL_TEXTURE_2D,
-                0,
-                internal_format as i32,
-                params.width as i32,
-                params.height as i32,
-                0,
-                format,
-                pixel_type,
-                match bytes {
-                    Some(bytes) => bytes.as_ptr() as *const _,
-                    Option::None => std::ptr::null(),
-                },
-            );
+            if params.format.is_compressed() {
+                glCompressedTexImage2D(
+                    GL_TEXTURE_2D,
+                    0,
+                    internal_format,
+                    params.width as i32,
+                    params.height as i32,
+                    0,
+                    params.format.size(params.width, params.height) as i32,
+                    match bytes {
+                        Some(bytes) => bytes.as_ptr() as *const _,
+                        Option::None => std::ptr::null(),
+                    },
+                );
+            } else {
+                glTexImage2D(
+                    GL_TEXTURE_2D,
+                    0,
+                    internal_format as i32,
+                    params.width as i32,
+                    params.height as i32,
+                    0,
+                    format,
+                    pixel_type,
+                    match bytes {
+                        Some(bytes) => bytes.as_ptr() as *const _,
+                        Option::None => std::ptr::null(),
+                    },
+                );
+            }
 
             glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, params.wrap as i32);
             glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, params.wrap as i32);
@@ -265,6 +323,7 @@ impl Texture {
     }
 
     pub fn resize(&mut self, ctx: &mut Context, width: u32, height: u32, bytes: Option<&[u8]>) {
+        assert!(!self.format.is_compressed(), "compressed textures can't be resized");
         ctx.cache.store_texture_binding(0);
         ctx.cache.bind_texture(0, self.texture);
 
@@ -320,6 +379,7 @@ impl Texture {
         height: i32,
         bytes: &[u8],
     ) {
+        assert!(!self.format.is_compressed(), "compressed textures can't be updated");
         assert_eq!(self.size(width as _, height as _), bytes.len());
         assert!(x_offset + width <= self.width as _);
         assert!(y_offset + height <= self.height as _);
@@ -353,6 +413,9 @@ impl Texture {
         if self.format == TextureFormat::Alpha || self.format == TextureFormat::LuminanceAlpha {
             unimplemented!("read_pixels is not implement for Alpha and LuminanceAlpha textures");
         }
+        if self.format.is_compressed() {
+            unimplemented!("read_pixels is not implement for compressed textures");
+        }
         let (_, format, pixel_type) = self.format.into_gl_params(false);
 
         let mut fbo = 0;
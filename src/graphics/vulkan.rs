@@ -4,5 +4,16 @@
 //! offering modern graphics capabilities while maintaining the same
 //! high-level API as the OpenGL backend.
 
+#[cfg(feature = "vulkan")]
+mod frame_graph;
+#[cfg(feature = "vulkan")]
+mod glsl;
+#[cfg(feature = "vulkan")]
+mod ktx2;
+#[cfg(feature = "vulkan")]
+pub mod projection;
 #[cfg(feature = "vulkan")]
 pub mod vk;
+
+#[cfg(all(test, feature = "vulkan"))]
+mod tests;
@@ -0,0 +1,187 @@
+//! Linear frame graph
+//!
+//! Passes run in the order they were added. Each one declares the textures
+//! and buffers it touches and how; the barriers and layout transitions
+//! between passes are derived from the last known use of every resource
+//! instead of being written by hand. Passes are never reordered or culled.
+
+use ash_037::vk;
+
+use super::vk::{VulkanContext, VulkanError};
+
+/// Texture or buffer id a pass uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameResource {
+    Texture(usize),
+    Buffer(usize),
+}
+
+/// How a pass uses a resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceUsage {
+    /// Color attachment of a render target pass. Target passes load from and
+    /// store to `SHADER_READ_ONLY_OPTIMAL` themselves.
+    ColorAttachment,
+    /// Depth attachment of a render target pass
+    DepthAttachment,
+    /// Sampled or read as input attachment by graphics or compute shaders
+    Sampled,
+    /// Storage image or buffer read by a compute shader
+    StorageRead,
+    /// Storage image or buffer written by a compute shader
+    StorageWrite,
+    /// Source of a copy or blit
+    TransferSrc,
+    /// Destination of a copy, blit or clear
+    TransferDst,
+    /// Vertex, index or indirect buffer of draws
+    VertexInput,
+}
+
+/// Pipeline stages, accesses and image layout of one use of a resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceState {
+    pub stage: vk::PipelineStageFlags,
+    pub access: vk::AccessFlags,
+    /// `UNDEFINED` for buffers
+    pub layout: vk::ImageLayout,
+    pub writes: bool,
+}
+
+impl ResourceState {
+    /// Where textures are between frame graphs: sampled textures and color
+    /// targets in `SHADER_READ_ONLY_OPTIMAL`, depth targets in
+    /// `DEPTH_STENCIL_ATTACHMENT_OPTIMAL`, nothing in flight
+    pub fn resting(layout: vk::ImageLayout) -> ResourceState {
+        ResourceState {
+            stage: vk::PipelineStageFlags::TOP_OF_PIPE,
+            access: vk::AccessFlags::empty(),
+            layout,
+            writes: false,
+        }
+    }
+}
+
+impl ResourceUsage {
+    /// State a resource has to be in for this use. `image` picks between the
+    /// image layout and `UNDEFINED` for buffers.
+    pub fn state(self, image: bool) -> ResourceState {
+        use vk::AccessFlags as A;
+        use vk::ImageLayout as L;
+        use vk::PipelineStageFlags as S;
+
+        let shaders = S::VERTEX_SHADER | S::FRAGMENT_SHADER | S::COMPUTE_SHADER;
+        let (stage, access, layout, writes) = match self {
+            ResourceUsage::ColorAttachment => (
+                S::COLOR_ATTACHMENT_OUTPUT,
+                A::COLOR_ATTACHMENT_READ | A::COLOR_ATTACHMENT_WRITE,
+                L::SHADER_READ_ONLY_OPTIMAL,
+                true,
+            ),
+            ResourceUsage::DepthAttachment => (
+                S::EARLY_FRAGMENT_TESTS | S::LATE_FRAGMENT_TESTS,
+                A::DEPTH_STENCIL_ATTACHMENT_READ | A::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                L::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                true,
+            ),
+            ResourceUsage::Sampled => (
+                shaders,
+                A::SHADER_READ | A::INPUT_ATTACHMENT_READ,
+                L::SHADER_READ_ONLY_OPTIMAL,
+                false,
+            ),
+            ResourceUsage::StorageRead => (S::COMPUTE_SHADER, A::SHADER_READ, L::GENERAL, false),
+            ResourceUsage::StorageWrite => (
+                S::COMPUTE_SHADER,
+                A::SHADER_READ | A::SHADER_WRITE,
+                L::GENERAL,
+                true,
+            ),
+            ResourceUsage::TransferSrc => {
+                (S::TRANSFER, A::TRANSFER_READ, L::TRANSFER_SRC_OPTIMAL, false)
+            }
+            ResourceUsage::TransferDst => {
+                (S::TRANSFER, A::TRANSFER_WRITE, L::TRANSFER_DST_OPTIMAL, true)
+            }
+            ResourceUsage::VertexInput => (
+                S::VERTEX_INPUT | S::DRAW_INDIRECT,
+                A::VERTEX_ATTRIBUTE_READ | A::INDEX_READ | A::INDIRECT_COMMAND_READ,
+                L::UNDEFINED,
+                false,
+            ),
+        };
+        ResourceState {
+            stage,
+            access,
+            layout: if image { layout } else { L::UNDEFINED },
+            writes,
+        }
+    }
+}
+
+/// Barrier to record between two uses of a resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition {
+    pub src_stage: vk::PipelineStageFlags,
+    pub dst_stage: vk::PipelineStageFlags,
+    pub src_access: vk::AccessFlags,
+    pub dst_access: vk::AccessFlags,
+    pub old_layout: vk::ImageLayout,
+    pub new_layout: vk::ImageLayout,
+}
+
+/// The barrier needed before a resource in state `previous` can be used as
+/// `next`, `None` when reads follow reads in the same layout.
+pub fn transition(previous: ResourceState, next: ResourceState) -> Option<Transition> {
+    let relayout = previous.layout != next.layout;
+    if !relayout && !previous.writes && !next.writes {
+        return None;
+    }
+    Some(Transition {
+        src_stage: previous.stage,
+        dst_stage: next.stage,
+        // Only writes have to be made available, reads just need to finish
+        src_access: if previous.writes {
+            previous.access
+        } else {
+            vk::AccessFlags::empty()
+        },
+        dst_access: next.access,
+        old_layout: previous.layout,
+        new_layout: next.layout,
+    })
+}
+
+type Record<'a> = Box<dyn FnOnce(&mut VulkanContext) -> Result<(), VulkanError> + 'a>;
+
+pub(crate) struct FramePass<'a> {
+    pub name: String,
+    pub uses: Vec<(FrameResource, ResourceUsage)>,
+    pub record: Record<'a>,
+}
+
+/// Passes to run with `VulkanContext::execute_frame_graph`
+#[derive(Default)]
+pub struct FrameGraph<'a> {
+    pub(crate) passes: Vec<FramePass<'a>>,
+}
+
+impl<'a> FrameGraph<'a> {
+    pub fn new() -> FrameGraph<'a> {
+        FrameGraph { passes: vec![] }
+    }
+
+    /// Append a pass using `uses`. `record` gets the context once the
+    /// barriers for those uses are recorded; it must not touch resources it
+    /// didn't declare and has to end any render pass it begins.
+    pub fn add_pass<F>(&mut self, name: &str, uses: &[(FrameResource, ResourceUsage)], record: F)
+    where
+        F: FnOnce(&mut VulkanContext) -> Result<(), VulkanError> + 'a,
+    {
+        self.passes.push(FramePass {
+            name: name.to_string(),
+            uses: uses.to_vec(),
+            record: Box::new(record),
+        });
+    }
+}
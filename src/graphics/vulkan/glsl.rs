@@ -0,0 +1,245 @@
+//! GLSL preprocessing before compilation
+//!
+//! Resolves `#include` directives and inserts the `#define`s given in
+//! `ShaderOptions`, so shaders can share code from a library of files. The
+//! rest of the preprocessor, macros and `#if`, is left to the compiler.
+//! `lint_glsl` points out built-ins that compile but likely misbehave.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::vk::VulkanError;
+
+/// Source of an included name, `None` when the resolver doesn't know it
+pub type IncludeResolver = Box<dyn Fn(&str) -> Option<String>>;
+
+/// How shader sources are preprocessed, see `VulkanContext::create_shader_ex`
+#[derive(Default)]
+pub struct ShaderOptions {
+    /// Directories `#include "name"` and `#include <name>` are looked up in,
+    /// in order, after the directory of the including file
+    pub include_paths: Vec<PathBuf>,
+    /// Asked for includes that aren't files in `include_paths`, e.g. to serve
+    /// a shader library embedded with `include_str!`
+    pub include_resolver: Option<IncludeResolver>,
+    /// `#define NAME VALUE` lines inserted after `#version`. An empty value
+    /// defines the name without one.
+    pub defines: BTreeMap<String, String>,
+}
+
+/// `source` with every `#include` replaced by the included file and
+/// `options.defines` inserted after the `#version` line. `#line` directives
+/// keep line numbers in compiler errors pointing into the right file. Files
+/// containing `#pragma once` are only included the first time.
+pub fn preprocess_glsl(source: &str, options: &ShaderOptions) -> Result<String, VulkanError> {
+    let mut preprocessor = Preprocessor {
+        options,
+        stack: vec![],
+        once: HashSet::new(),
+        output: String::new(),
+    };
+    let has_version = source.lines().any(|line| directive(line, "version").is_some());
+    if !has_version {
+        preprocessor.write_defines(1);
+    }
+    preprocessor.expand(source, None, has_version)?;
+    Ok(preprocessor.output)
+}
+
+struct Preprocessor<'a> {
+    options: &'a ShaderOptions,
+    /// Files being included, to catch include cycles
+    stack: Vec<String>,
+    /// Files that had `#pragma once`
+    once: HashSet<String>,
+    output: String,
+}
+
+impl<'a> Preprocessor<'a> {
+    fn expand(
+        &mut self,
+        source: &str,
+        dir: Option<&Path>,
+        mut defines_pending: bool,
+    ) -> Result<(), VulkanError> {
+        for (index, line) in source.lines().enumerate() {
+            let next_line = index + 2;
+            if defines_pending && directive(line, "version").is_some() {
+                self.output.push_str(line);
+                self.output.push('\n');
+                self.write_defines(next_line);
+                defines_pending = false;
+            } else if let Some(argument) = directive(line, "include") {
+                self.include(include_name(argument)?, dir)?;
+                self.output.push_str(&format!("#line {}\n", next_line));
+            } else if directive(line, "pragma").map(str::trim) == Some("once") {
+                if let Some(file) = self.stack.last() {
+                    self.once.insert(file.clone());
+                }
+                self.output.push('\n');
+            } else {
+                self.output.push_str(line);
+                self.output.push('\n');
+            }
+        }
+        Ok(())
+    }
+
+    fn include(&mut self, name: &str, dir: Option<&Path>) -> Result<(), VulkanError> {
+        let (key, source, included_dir) = self.resolve(name, dir)?;
+        if self.once.contains(&key) {
+            return Ok(());
+        }
+        if self.stack.contains(&key) {
+            return Err(VulkanError::ShaderCompilation(format!(
+                "\"{}\" includes itself through {}",
+                name,
+                self.stack.join(" -> ")
+            )));
+        }
+        self.stack.push(key);
+        self.output.push_str("#line 1\n");
+        self.expand(&source, included_dir.as_deref(), false)?;
+        self.stack.pop();
+        Ok(())
+    }
+
+    /// Key identifying the file for `#pragma once`, its source and the
+    /// directory its own includes are relative to
+    fn resolve(
+        &self,
+        name: &str,
+        dir: Option<&Path>,
+    ) -> Result<(String, String, Option<PathBuf>), VulkanError> {
+        let include_paths = self.options.include_paths.iter().map(PathBuf::as_path);
+        for path in dir.into_iter().chain(include_paths).map(|dir| dir.join(name)) {
+            if !path.is_file() {
+                continue;
+            }
+            let source = fs::read_to_string(&path).map_err(|e| {
+                VulkanError::ShaderCompilation(format!("can't read {}: {}", path.display(), e))
+            })?;
+            let path = path.canonicalize().unwrap_or(path);
+            let dir = path.parent().map(Path::to_path_buf);
+            return Ok((path.display().to_string(), source, dir));
+        }
+        let resolved = self.options.include_resolver.as_ref().and_then(|resolve| resolve(name));
+        match resolved {
+            Some(source) => Ok((name.to_string(), source, None)),
+            None => Err(VulkanError::ShaderCompilation(format!(
+                "can't find included file \"{}\"",
+                name
+            ))),
+        }
+    }
+
+    fn write_defines(&mut self, next_line: usize) {
+        if self.options.defines.is_empty() {
+            return;
+        }
+        for (name, value) in &self.options.defines {
+            self.output.push_str(&format!("#define {} {}\n", name, value));
+        }
+        self.output.push_str(&format!("#line {}\n", next_line));
+    }
+}
+
+/// Identifiers that aren't Vulkan GLSL, and what to use instead
+const SUSPICIOUS_IDENTIFIERS: [(&str, &str); 4] = [
+    ("gl_DispatchSize", "not a GLSL built-in, use gl_NumWorkGroups"),
+    ("gl_LocalSize", "not a GLSL built-in, use gl_WorkGroupSize"),
+    ("gl_FragColor", "deprecated, declare a layout(location = 0) out variable"),
+    ("texture2D", "deprecated, use texture"),
+];
+
+/// Lints for `source`, prefixed with their line: uses of a few
+/// non-standard or deprecated built-ins outside comments. This is a word
+/// match on the source, not compiler output, so it can't tell a built-in
+/// from a user declaration shadowing its name. Line numbers follow `#line`
+/// directives like compiler errors do.
+pub fn lint_glsl(source: &str) -> Vec<String> {
+    let mut lints = vec![];
+    let mut line = 1;
+    for code in strip_comments(source) {
+        let line_directive = directive(&code, "line").and_then(|n| n.trim().parse().ok());
+        if let Some(next_line) = line_directive {
+            line = next_line;
+            continue;
+        }
+        let words = code.split(|c: char| !c.is_ascii_alphanumeric() && c != '_');
+        let mut seen = HashSet::new();
+        for word in words {
+            let found = SUSPICIOUS_IDENTIFIERS.iter().find(|(name, _)| *name == word);
+            if let Some((name, message)) = found {
+                if seen.insert(name) {
+                    lints.push(format!("line {}: {} is {}", line, name, message));
+                }
+            }
+        }
+        line += 1;
+    }
+    lints
+}
+
+/// Lines of `source` with `//` and `/* */` comments cut out
+fn strip_comments(source: &str) -> Vec<String> {
+    let mut in_comment = false;
+    let mut lines = vec![];
+    for mut rest in source.lines() {
+        let mut code = String::new();
+        loop {
+            if in_comment {
+                match rest.find("*/") {
+                    Some(end) => {
+                        in_comment = false;
+                        code.push(' ');
+                        rest = &rest[end + 2..];
+                    }
+                    None => break,
+                }
+                continue;
+            }
+            match (rest.find("//"), rest.find("/*")) {
+                (Some(line), block) if line < block.unwrap_or(rest.len()) => {
+                    code.push_str(&rest[..line]);
+                    break;
+                }
+                (_, Some(block)) => {
+                    code.push_str(&rest[..block]);
+                    in_comment = true;
+                    rest = &rest[block + 2..];
+                }
+                _ => {
+                    code.push_str(rest);
+                    break;
+                }
+            }
+        }
+        lines.push(code);
+    }
+    lines
+}
+
+/// What follows `#name` on a preprocessor line, `None` for other lines
+fn directive<'l>(line: &'l str, name: &str) -> Option<&'l str> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start().strip_prefix(name)?;
+    match rest.chars().next() {
+        None => Some(rest),
+        Some(c) if c.is_whitespace() || c == '"' || c == '<' => Some(rest),
+        _ => None,
+    }
+}
+
+/// The name in `"name"` or `<name>`
+fn include_name(argument: &str) -> Result<&str, VulkanError> {
+    let argument = argument.trim();
+    let name = match argument.chars().next() {
+        Some('"') => argument[1..].strip_suffix('"'),
+        Some('<') => argument[1..].strip_suffix('>'),
+        _ => None,
+    };
+    name.ok_or_else(|| {
+        VulkanError::ShaderCompilation(format!("malformed #include {}", argument))
+    })
+}
@@ -0,0 +1,102 @@
+//! Minimal KTX2 container parser
+//!
+//! Only reads what is needed to upload the image data as is: the header and
+//! the level index. Data format descriptors and key/value data are skipped.
+
+use ash_037::vk;
+
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+/// Supercompression scheme of a KTX2 file, `supercompressionScheme` in the header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Supercompression {
+    None,
+    BasisLz,
+    Zstandard,
+    Zlib,
+    Other(u32),
+}
+
+/// Location of one mip level inside the file
+#[derive(Debug, Clone, Copy)]
+pub struct Ktx2Level {
+    pub offset: usize,
+    pub length: usize,
+}
+
+/// Header and level index of a KTX2 file
+#[derive(Debug, Clone)]
+pub struct Ktx2Header {
+    /// `VK_FORMAT_UNDEFINED` for Basis Universal payloads
+    pub format: vk::Format,
+    pub width: u32,
+    /// 0 for 1D textures
+    pub height: u32,
+    /// 0 for everything but 3D textures
+    pub depth: u32,
+    /// 0 when the texture is not an array
+    pub layers: u32,
+    /// 6 for cubemaps, 1 otherwise
+    pub faces: u32,
+    pub supercompression: Supercompression,
+    /// Largest level first. A file asking for mips to be generated at load
+    /// time (`levelCount` 0) still has one entry for the base level.
+    pub levels: Vec<Ktx2Level>,
+}
+
+impl Ktx2Header {
+    pub fn parse(bytes: &[u8]) -> Result<Ktx2Header, String> {
+        if bytes.len() < HEADER_SIZE || bytes[..12] != IDENTIFIER {
+            return Err("not a KTX2 file".to_string());
+        }
+        let read_u32 = |offset: usize| {
+            let mut word = [0; 4];
+            word.copy_from_slice(&bytes[offset..offset + 4]);
+            u32::from_le_bytes(word)
+        };
+        let read_u64 = |offset: usize| {
+            let mut word = [0; 8];
+            word.copy_from_slice(&bytes[offset..offset + 8]);
+            u64::from_le_bytes(word)
+        };
+
+        let supercompression = match read_u32(44) {
+            0 => Supercompression::None,
+            1 => Supercompression::BasisLz,
+            2 => Supercompression::Zstandard,
+            3 => Supercompression::Zlib,
+            other => Supercompression::Other(other),
+        };
+        let level_count = read_u32(40).max(1) as usize;
+        let index_end = HEADER_SIZE + level_count * LEVEL_INDEX_ENTRY_SIZE;
+        if bytes.len() < index_end {
+            return Err("truncated level index".to_string());
+        }
+
+        let mut levels = Vec::with_capacity(level_count);
+        for level in 0..level_count {
+            let entry = HEADER_SIZE + level * LEVEL_INDEX_ENTRY_SIZE;
+            let offset = read_u64(entry) as usize;
+            let length = read_u64(entry + 8) as usize;
+            if offset.checked_add(length).is_none_or(|end| end > bytes.len()) {
+                return Err(format!("level {} lies outside of the file", level));
+            }
+            levels.push(Ktx2Level { offset, length });
+        }
+
+        Ok(Ktx2Header {
+            format: vk::Format::from_raw(read_u32(12) as i32),
+            width: read_u32(20),
+            height: read_u32(24),
+            depth: read_u32(28),
+            layers: read_u32(32),
+            faces: read_u32(36),
+            supercompression,
+            levels,
+        })
+    }
+}
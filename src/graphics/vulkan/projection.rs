@@ -0,0 +1,74 @@
+//! Projection matrices for Vulkan's clip space
+//!
+//! Vulkan clip space differs from OpenGL's in two ways: depth goes from 0 at
+//! the near plane to 1 at the far plane instead of -1 to 1, and Y points
+//! down. The matrices here are column major, like the `Mat4` uniforms, and
+//! take right handed view space looking down -Z with Y up, the way GL style
+//! matrices do, so only the projection changes between the backends.
+//!
+//! With `Platform::flip_viewport_y`, the default, the viewport takes care of
+//! Y and the matrices here keep Y up like on OpenGL; pass them through
+//! `flip_y` when the flip is off.
+//!
+//! GL style projections can be kept instead: remap their depth with
+//! `gl_to_vulkan` or, when the device has `VK_EXT_depth_clip_control`, ask
+//! for `DepthRange::NegOneToOne` and use them as they are.
+
+/// Perspective projection with a vertical field of view of `fov_y` radians,
+/// mapping `near` to depth 0 and `far` to depth 1.
+pub fn perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> [f32; 16] {
+    let f = 1.0 / (fov_y / 2.0).tan();
+    let mut m = [0.0; 16];
+    m[0] = f / aspect;
+    m[5] = f;
+    m[10] = far / (near - far);
+    m[11] = -1.0;
+    m[14] = near * far / (near - far);
+    m
+}
+
+/// Reversed-Z perspective projection, mapping `near` to depth 1 and `far` to
+/// depth 0. Floating point depth keeps much more precision in the distance
+/// this way; clear depth to 0 and test with `Comparison::Greater`.
+pub fn perspective_reversed_z(fov_y: f32, aspect: f32, near: f32, far: f32) -> [f32; 16] {
+    let mut m = perspective(fov_y, aspect, near, far);
+    m[10] = near / (far - near);
+    m[14] = near * far / (far - near);
+    m
+}
+
+/// Orthographic projection of the box between `left`..`right`,
+/// `bottom`..`top` and the `near` and `far` planes, the latter at depth 0
+/// and 1.
+pub fn ortho(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> [f32; 16] {
+    let mut m = [0.0; 16];
+    m[0] = 2.0 / (right - left);
+    m[5] = 2.0 / (top - bottom);
+    m[10] = 1.0 / (near - far);
+    m[12] = -(right + left) / (right - left);
+    m[13] = -(top + bottom) / (top - bottom);
+    m[14] = near / (near - far);
+    m[15] = 1.0;
+    m
+}
+
+/// `projection` made for OpenGL's clip space, with depth remapped from -1..1
+/// to 0..1.
+pub fn gl_to_vulkan(projection: [f32; 16]) -> [f32; 16] {
+    let mut m = projection;
+    for column in 0..4 {
+        let (z, w) = (projection[column * 4 + 2], projection[column * 4 + 3]);
+        m[column * 4 + 2] = 0.5 * z + 0.5 * w;
+    }
+    m
+}
+
+/// `projection` with clip space Y negated, for rendering without
+/// `Platform::flip_viewport_y`.
+pub fn flip_y(projection: [f32; 16]) -> [f32; 16] {
+    let mut m = projection;
+    for column in 0..4 {
+        m[column * 4 + 1] = -m[column * 4 + 1];
+    }
+    m
+}
@@ -1,6 +1,19 @@
 #[cfg(test)]
 mod vulkan_tests {
-    use super::*;
+    use super::super::frame_graph::{transition, ResourceState};
+    use super::super::projection;
+    use super::super::vk::*;
+    use crate::conf::AdapterType;
+    use crate::graphics::{
+        BufferLayout, BufferType, DynamicStateFlags, PassAction, PipelineParams, TextureFormat,
+        UniformDesc, UniformType, VertexAttribute, VertexFormat,
+    };
+    use crate::native::gl::{GL_COLOR_BUFFER_BIT, GL_DEPTH_BUFFER_BIT, GL_STENCIL_BUFFER_BIT};
+    use ash_037::vk::{self, Handle};
+    use std::ffi::CStr;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
     
     #[test]
     #[cfg(feature = "vulkan")]
@@ -9,6 +22,134 @@ mod vulkan_tests {
         assert!(context.msaa_samples == vk::SampleCountFlags::TYPE_4);
     }
     
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_context_for_testing_prefers_cpu_adapter() {
+        // Without a Vulkan driver there is nothing to render with
+        let context = match VulkanContext::new_for_testing() {
+            Ok(context) => context,
+            Err(_) => return,
+        };
+        assert!(context.device.is_some());
+        let software = VulkanContext::enumerate_adapters()
+            .iter()
+            .any(|adapter| adapter.supports_graphics && adapter.adapter_type == AdapterType::Cpu);
+        if software {
+            let instance = context.instance.as_ref().unwrap();
+            let properties = unsafe {
+                instance.get_physical_device_properties(context.physical_device.unwrap())
+            };
+            assert_eq!(properties.device_type, vk::PhysicalDeviceType::CPU);
+        }
+
+        let report = context.init_report();
+        assert!(report.failure.is_none());
+        let selected = report.selected_adapter.unwrap();
+        assert!(report.adapters.iter().any(|adapter| adapter.index == selected));
+        assert_eq!(report.device.name, context.device_info().name);
+        assert_eq!(report.queues.graphics_family, context.queue_family_index);
+        assert!(context.enabled_extensions().instance.is_empty());
+    }
+
+    /// `layout(location = 0) in vec2 pos; void main() { gl_Position = vec4(pos, 0, 1); }`
+    const TRIANGLE_VERTEX_SPIRV: &[u32] = &[
+        0x0723_0203, 0x0001_0000, 0, 18, 0,
+        (2 << 16) | 17, 1,
+        (3 << 16) | 14, 0, 1,
+        (7 << 16) | 15, 0, 1, 0x6e69_616d, 0, 8, 10,
+        (4 << 16) | 71, 8, 30, 0,
+        (4 << 16) | 71, 10, 11, 0,
+        (2 << 16) | 19, 2,
+        (3 << 16) | 33, 3, 2,
+        (3 << 16) | 22, 4, 32,
+        (4 << 16) | 23, 5, 4, 2,
+        (4 << 16) | 23, 6, 4, 4,
+        (4 << 16) | 32, 7, 1, 5,
+        (4 << 16) | 59, 7, 8, 1,
+        (4 << 16) | 32, 9, 3, 6,
+        (4 << 16) | 59, 9, 10, 3,
+        (4 << 16) | 43, 4, 11, 0,
+        (4 << 16) | 43, 4, 12, 0x3f80_0000,
+        (5 << 16) | 54, 2, 1, 0, 3,
+        (2 << 16) | 248, 13,
+        (4 << 16) | 61, 5, 14, 8,
+        (5 << 16) | 81, 4, 15, 14, 0,
+        (5 << 16) | 81, 4, 16, 14, 1,
+        (7 << 16) | 80, 6, 17, 15, 16, 11, 12,
+        (3 << 16) | 62, 10, 17,
+        (1 << 16) | 253,
+        (1 << 16) | 56,
+    ];
+
+    /// `layout(location = 0) out vec4 color; void main() { color = vec4(1, 0, 0, 1); }`
+    const RED_FRAGMENT_SPIRV: &[u32] = &[
+        0x0723_0203, 0x0001_0000, 0, 12, 0,
+        (2 << 16) | 17, 1,
+        (3 << 16) | 14, 0, 1,
+        (6 << 16) | 15, 4, 1, 0x6e69_616d, 0, 7,
+        (3 << 16) | 16, 1, 7,
+        (4 << 16) | 71, 7, 30, 0,
+        (2 << 16) | 19, 2,
+        (3 << 16) | 33, 3, 2,
+        (3 << 16) | 22, 4, 32,
+        (4 << 16) | 23, 5, 4, 4,
+        (4 << 16) | 32, 6, 3, 5,
+        (4 << 16) | 59, 6, 7, 3,
+        (4 << 16) | 43, 4, 8, 0,
+        (4 << 16) | 43, 4, 9, 0x3f80_0000,
+        (7 << 16) | 44, 5, 10, 9, 8, 8, 9,
+        (5 << 16) | 54, 2, 1, 0, 3,
+        (2 << 16) | 248, 11,
+        (3 << 16) | 62, 7, 10,
+        (1 << 16) | 253,
+        (1 << 16) | 56,
+    ];
+
+    #[test]
+    #[cfg(feature = "vulkan")]
+    #[ignore = "needs a Vulkan device, e.g. lavapipe in headless CI"]
+    fn test_offscreen_pass_reads_back() {
+        let mut context =
+            VulkanContext::new_for_testing().expect("no Vulkan device to render with");
+        let color = context.create_render_texture(8, 8, TextureFormat::RGBA8).unwrap();
+        let target = context.create_render_target(&[color], None).unwrap();
+
+        let meta = ShaderMeta { vertex_format: None, texture_slots: vec![] };
+        let shader = context
+            .create_shader_from_spirv(TRIANGLE_VERTEX_SPIRV, RED_FRAGMENT_SPIRV, meta)
+            .unwrap();
+        let attributes = [VertexAttribute::new("pos", VertexFormat::Float2)];
+        let pipeline = context
+            .create_pipeline(
+                shader,
+                &[BufferLayout::default()],
+                &attributes,
+                PipelineParams::default(),
+                Some(target),
+            )
+            .unwrap();
+        let vertices: [f32; 6] = [-0.5, -0.5, 0.5, -0.5, 0.0, 0.5];
+        let vertices = context.create_immutable_buffer(BufferType::VertexBuffer, &vertices);
+        let indices = context.create_immutable_buffer(BufferType::IndexBuffer, &[0u16, 1, 2]);
+        let (vertices, indices) = (vertices.unwrap(), indices.unwrap());
+
+        context.begin_frame().unwrap();
+        let clear = LoadOp::Clear([0.0, 0.0, 1.0, 1.0]);
+        context.begin_render_pass_to(target, &[clear], LoadOp::DontCare, 0).unwrap();
+        context.apply_pipeline(pipeline).unwrap();
+        context.apply_vertex_buffers(&[vertices]).unwrap();
+        context.apply_index_buffer(indices).unwrap();
+        context.draw(0, 3, 1).unwrap();
+        context.end_render_pass().unwrap();
+        context.end_frame().unwrap();
+
+        let texels = context.read_texture(color).unwrap();
+        assert_eq!(texels.len(), 8 * 8 * 4);
+        let center = (4 * 8 + 4) * 4;
+        assert_eq!(&texels[center..center + 4], &[255, 0, 0, 255]);
+        assert_eq!(&texels[0..4], &[0, 0, 255, 255]);
+    }
+
     #[test]
     #[cfg(feature = "vulkan")]
     fn test_msaa_sample_setting() {
@@ -30,6 +171,31 @@ mod vulkan_tests {
         // Test invalid sample count
         assert!(context.set_msaa_samples(3).is_err());
     }
+
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_sample_count_from_conf() {
+        let mut context = VulkanContext::new();
+        context.set_display(crate::conf::Conf {
+            sample_count: 1,
+            ..Default::default()
+        });
+        assert!(context.msaa_samples == vk::SampleCountFlags::TYPE_1);
+        assert!(!context.get_performance_stats().msaa_enabled);
+
+        context.set_display(crate::conf::Conf {
+            sample_count: 8,
+            ..Default::default()
+        });
+        assert!(context.msaa_samples == vk::SampleCountFlags::TYPE_8);
+
+        // Unsupported counts fall back to no MSAA instead of failing
+        context.set_display(crate::conf::Conf {
+            sample_count: 3,
+            ..Default::default()
+        });
+        assert!(context.msaa_samples == vk::SampleCountFlags::TYPE_1);
+    }
     
     #[test]
     #[cfg(feature = "vulkan")]
@@ -56,4 +222,514 @@ mod vulkan_tests {
         assert!(!stats.msaa_enabled);
         assert!(stats.msaa_samples == vk::SampleCountFlags::TYPE_1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_geometry_shader_stages() {
+        let mut recipe = GraphicsPipelineRecipe {
+            vertex_module: vk::ShaderModule::null(),
+            tessellation_modules: None,
+            geometry_module: None,
+            fragment_module: vk::ShaderModule::null(),
+            bindings: vec![],
+            attributes: vec![],
+            topology: vk::PrimitiveTopology::POINT_LIST,
+            patch_control_points: 0,
+            params: Default::default(),
+            blend_attachments: vec![],
+            render_pass: vk::RenderPass::null(),
+            subpass: 0,
+            samples: vk::SampleCountFlags::TYPE_1,
+        };
+        let entry = CStr::from_bytes_with_nul(b"main\0").unwrap();
+        assert_eq!(shader_stages(&recipe, entry).len(), 2);
+
+        recipe.geometry_module = Some(vk::ShaderModule::null());
+        let stages: Vec<_> = shader_stages(&recipe, entry).iter().map(|s| s.stage).collect();
+        assert_eq!(
+            stages,
+            [
+                vk::ShaderStageFlags::VERTEX,
+                vk::ShaderStageFlags::GEOMETRY,
+                vk::ShaderStageFlags::FRAGMENT,
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_swapchain_image_count_respects_min_image_count() {
+        // What Adreno and Mali drivers report
+        let mut capabilities = vk::SurfaceCapabilitiesKHR {
+            min_image_count: 3,
+            max_image_count: 0,
+            ..Default::default()
+        };
+        assert_eq!(swapchain_image_count_for(Some(2), &capabilities), 3);
+        assert_eq!(swapchain_image_count_for(None, &capabilities), 4);
+        assert_eq!(swapchain_image_count_for(Some(16), &capabilities), 16);
+
+        capabilities.max_image_count = 3;
+        assert_eq!(swapchain_image_count_for(Some(2), &capabilities), 3);
+        assert_eq!(swapchain_image_count_for(None, &capabilities), 3);
+    }
+
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_composite_alpha_falls_back_to_opaque() {
+        use crate::conf::CompositeAlphaMode;
+
+        let desktop =
+            vk::CompositeAlphaFlagsKHR::OPAQUE | vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED;
+        let overlay = choose_composite_alpha(CompositeAlphaMode::PreMultiplied, desktop);
+        assert_eq!(overlay, vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED);
+        let post = choose_composite_alpha(CompositeAlphaMode::PostMultiplied, desktop);
+        assert_eq!(post, vk::CompositeAlphaFlagsKHR::OPAQUE);
+
+        // Android surfaces often only know INHERIT
+        let android = vk::CompositeAlphaFlagsKHR::INHERIT;
+        let opaque = choose_composite_alpha(CompositeAlphaMode::Opaque, android);
+        assert_eq!(opaque, vk::CompositeAlphaFlagsKHR::INHERIT);
+    }
+
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_present_mode_for_vsync() {
+        use vk::PresentModeKHR as Mode;
+
+        let desktop = [Mode::FIFO, Mode::MAILBOX, Mode::IMMEDIATE];
+        assert_eq!(choose_present_mode(true, &desktop), Mode::FIFO);
+        assert_eq!(choose_present_mode(false, &desktop), Mode::IMMEDIATE);
+        assert_eq!(choose_present_mode(false, &[Mode::FIFO, Mode::MAILBOX]), Mode::MAILBOX);
+        // Plenty of Android drivers only offer FIFO, vsync stays on
+        assert_eq!(choose_present_mode(false, &[Mode::FIFO]), Mode::FIFO);
+    }
+
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_set_vsync_recreates_swapchain() {
+        use ash_037::extensions::ext::HeadlessSurface;
+        use vk::PresentModeKHR as Mode;
+
+        let mut context = VulkanContext::try_new().unwrap();
+        let mut conf = crate::conf::Conf {
+            headless: true,
+            ..Default::default()
+        };
+        conf.platform.extra_instance_extensions =
+            vec!["VK_KHR_surface".to_string(), "VK_EXT_headless_surface".to_string()];
+        context.set_display(conf);
+        // Needs a driver with VK_EXT_headless_surface, e.g. lavapipe
+        if context.init_vulkan().is_err() {
+            return;
+        }
+        let headless = HeadlessSurface::new(
+            context.entry.as_ref().unwrap(),
+            context.instance.as_ref().unwrap(),
+        );
+        let surface_info = vk::HeadlessSurfaceCreateInfoEXT::default();
+        let surface = unsafe { headless.create_headless_surface(&surface_info, None) };
+        context.surface = Some(surface.unwrap());
+        context.create_swapchain().unwrap();
+        context.create_swapchain_images().unwrap();
+        assert!(context.vsync_enabled());
+
+        let modes = context.get_present_modes(context.physical_device.unwrap()).unwrap();
+        let tearing = modes.contains(&Mode::IMMEDIATE) || modes.contains(&Mode::MAILBOX);
+        assert_eq!(context.set_vsync(false).unwrap(), !tearing);
+        assert_eq!(context.present_mode, choose_present_mode(false, &modes));
+        if tearing {
+            assert_ne!(context.present_mode, Mode::FIFO);
+        }
+        assert!(context.swapchain.is_some());
+        assert_eq!(context.framebuffers.len(), context.swapchain_images.len());
+
+        assert!(context.set_vsync(true).unwrap());
+        assert_eq!(context.present_mode, Mode::FIFO);
+        context.cleanup();
+    }
+
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_pass_action_load_ops_match_opengl() {
+        // OpenGL clears exactly the values given and keeps the rest
+        let actions = LoadActions::from(PassAction::clear_color(0.2, 0.4, 0.6, 1.0));
+        assert_eq!(actions.color, LoadOp::Clear([0.2, 0.4, 0.6, 1.0]));
+        assert_eq!(actions.depth, LoadOp::Clear(1.0));
+        assert_eq!(actions.stencil, LoadOp::Load);
+
+        let actions = LoadActions::from(PassAction::Clear {
+            color: None,
+            depth: None,
+            stencil: Some(7),
+        });
+        assert_eq!(actions.color, LoadOp::Load);
+        assert_eq!(actions.depth, LoadOp::Load);
+        assert_eq!(actions.stencil, LoadOp::Clear(7));
+
+        let actions = LoadActions::from(PassAction::Nothing);
+        assert_eq!(actions.color, LoadOp::Load);
+    }
+
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_pass_action_clears_the_same_buffers_on_both_backends() {
+        let cleared_by_vulkan = |action: PassAction| {
+            let actions = LoadActions::from(action);
+            [
+                matches!(actions.color, LoadOp::Clear(_)),
+                matches!(actions.depth, LoadOp::Clear(_)),
+                matches!(actions.stencil, LoadOp::Clear(_)),
+            ]
+        };
+        let cleared_by_gl = |action: &PassAction| {
+            let bits = action.gl_clear_bits();
+            [
+                bits & GL_COLOR_BUFFER_BIT != 0,
+                bits & GL_DEPTH_BUFFER_BIT != 0,
+                bits & GL_STENCIL_BUFFER_BIT != 0,
+            ]
+        };
+
+        // Nothing keeps every attachment on both backends
+        assert_eq!(PassAction::Nothing.gl_clear_bits(), 0);
+        assert_eq!(cleared_by_vulkan(PassAction::Nothing), [false; 3]);
+
+        for mask in 0..8 {
+            let action = PassAction::Clear {
+                color: Some((0.1, 0.2, 0.3, 1.0)).filter(|_| mask & 1 != 0),
+                depth: Some(0.5).filter(|_| mask & 2 != 0),
+                stencil: Some(3).filter(|_| mask & 4 != 0),
+            };
+            assert_eq!(cleared_by_gl(&action), cleared_by_vulkan(action), "clear mask {}", mask);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_resource_uploader_is_shareable() {
+        fn assert_shareable<T: Send + Sync + Clone>() {}
+        fn assert_send<T: Send>() {}
+        assert_shareable::<ResourceUploader>();
+        assert_send::<UploadedResource>();
+    }
+
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_frame_graph_transitions() {
+        let sampled = ResourceUsage::Sampled.state(true);
+        let target = ResourceUsage::ColorAttachment.state(true);
+        let storage = ResourceUsage::StorageWrite.state(true);
+
+        // Reads in the same layout need nothing
+        assert_eq!(transition(sampled, sampled), None);
+        let resting = ResourceState::resting(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        assert_eq!(transition(resting, sampled), None);
+
+        // Sampling what a pass rendered waits for its writes
+        let t = transition(target, sampled).unwrap();
+        assert_eq!(t.src_access, target.access);
+        assert_eq!(t.old_layout, t.new_layout);
+
+        // Writing what was read only waits for the reads to finish
+        let t = transition(sampled, storage).unwrap();
+        assert_eq!(t.src_access, vk::AccessFlags::empty());
+        assert_eq!(t.old_layout, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        assert_eq!(t.new_layout, vk::ImageLayout::GENERAL);
+
+        // Buffers have no layout to change
+        let read = ResourceUsage::VertexInput.state(false);
+        assert_eq!(transition(read, ResourceUsage::StorageRead.state(false)), None);
+        assert!(transition(ResourceUsage::StorageWrite.state(false), read).is_some());
+    }
+
+    /// Depth and y of `point` after `m` and the perspective divide
+    fn project(m: &[f32; 16], point: [f32; 3]) -> (f32, f32) {
+        let row = |r: usize| {
+            m[r] * point[0] + m[4 + r] * point[1] + m[8 + r] * point[2] + m[12 + r]
+        };
+        (row(2) / row(3), row(1) / row(3))
+    }
+
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_projection_depth_range() {
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+
+        let m = projection::perspective(1.0, 1.5, 0.1, 100.0);
+        assert!(close(project(&m, [0.0, 0.0, -0.1]).0, 0.0));
+        assert!(close(project(&m, [0.0, 0.0, -100.0]).0, 1.0));
+        // Y stays up for the flipped viewport, `flip_y` turns it down
+        assert!(project(&m, [0.0, 1.0, -1.0]).1 > 0.0);
+        assert!(project(&projection::flip_y(m), [0.0, 1.0, -1.0]).1 < 0.0);
+
+        let m = projection::perspective_reversed_z(1.0, 1.5, 0.1, 100.0);
+        assert!(close(project(&m, [0.0, 0.0, -0.1]).0, 1.0));
+        assert!(close(project(&m, [0.0, 0.0, -100.0]).0, 0.0));
+
+        let m = projection::ortho(-1.0, 1.0, -1.0, 1.0, 1.0, 10.0);
+        assert!(close(project(&m, [0.0, 0.0, -1.0]).0, 0.0));
+        assert!(close(project(&m, [0.0, 0.0, -10.0]).0, 1.0));
+        assert!(close(project(&m, [0.0, 1.0, -5.0]).1, 1.0));
+
+        // A GL perspective ends up where the Vulkan one would
+        let (n, f, t) = (0.1f32, 100.0f32, 1.0 / 0.5f32.tan());
+        let mut gl = [0.0; 16];
+        gl[0] = t / 1.5;
+        gl[5] = t;
+        gl[10] = (f + n) / (n - f);
+        gl[11] = -1.0;
+        gl[14] = 2.0 * f * n / (n - f);
+        let converted = projection::gl_to_vulkan(gl);
+        let expected = projection::perspective(1.0, 1.5, n, f);
+        assert!(converted.iter().zip(&expected).all(|(&a, &b)| (a - b).abs() < 1e-4));
+    }
+
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_flipped_viewport() {
+        let plain = viewport(10.0, 20.0, 300.0, 200.0, false);
+        assert_eq!((plain.y, plain.height), (20.0, 200.0));
+
+        // Same rectangle, starting from its bottom edge
+        let flipped = viewport(10.0, 20.0, 300.0, 200.0, true);
+        assert_eq!((flipped.x, flipped.width), (10.0, 300.0));
+        assert_eq!((flipped.y, flipped.height), (220.0, -200.0));
+    }
+
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_std140_mixed_block() {
+        let uniforms = [
+            UniformDesc::new("time", UniformType::Float1),
+            UniformDesc::new("light_dir", UniformType::Float3),
+            UniformDesc::new("normal_matrix", UniformType::Mat3),
+            UniformDesc::new("tile", UniformType::Int2),
+            UniformDesc::new("flags", UniformType::UInt1),
+            UniformDesc::new("weights", UniformType::Float1).array(2),
+            UniformDesc::new("model", UniformType::Mat4),
+        ];
+        let (offsets, size) = std140_offsets(&uniforms);
+        assert_eq!(offsets, vec![0, 16, 32, 80, 88, 96, 128]);
+        assert_eq!(size, 192);
+
+        let packed_size = 4 + 12 + 36 + 8 + 4 + 8 + 64;
+        let data: Vec<u8> = (0..packed_size / 4)
+            .flat_map(|i| (i as u32 + 1).to_ne_bytes())
+            .collect();
+        let block = pack_std140(&uniforms, &data).unwrap();
+        let word = |offset: usize| u32::from_ne_bytes([
+            block[offset],
+            block[offset + 1],
+            block[offset + 2],
+            block[offset + 3],
+        ]);
+        // light_dir starts on 16 bytes, normal_matrix columns pad to vec4
+        assert_eq!((word(0), word(16), word(24)), (1, 2, 4));
+        assert_eq!((word(32), word(40), word(44)), (5, 7, 0));
+        assert_eq!((word(48), word(64), word(72)), (8, 11, 13));
+        // Integers follow tightly, array elements pad to 16 bytes
+        assert_eq!((word(80), word(84), word(88)), (14, 15, 16));
+        assert_eq!((word(96), word(100), word(112)), (17, 0, 18));
+        assert_eq!((word(128), word(188)), (19, 34));
+
+        assert!(pack_std140(&uniforms, &data[..data.len() - 4]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_swapchain_usage_is_validated() {
+        use vk::ImageUsageFlags as U;
+
+        let supported = U::COLOR_ATTACHMENT | U::TRANSFER_SRC | U::TRANSFER_DST;
+        let requested = U::COLOR_ATTACHMENT | U::TRANSFER_SRC | U::STORAGE;
+        let granted = choose_swapchain_usage(requested, supported);
+        assert_eq!(granted, U::COLOR_ATTACHMENT | U::TRANSFER_SRC);
+        let requested = U::COLOR_ATTACHMENT | U::TRANSFER_DST;
+        assert_eq!(choose_swapchain_usage(requested, supported), requested);
+        // Rendering stays possible whatever the surface reports
+        assert_eq!(choose_swapchain_usage(U::STORAGE, U::empty()), U::COLOR_ATTACHMENT);
+    }
+
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_spirv_capabilities() {
+        // Header, OpCapability Shader, OpCapability StorageImageWriteWithoutFormat,
+        // OpMemoryModel Logical GLSL450
+        let code = [
+            0x0723_0203, 0x0001_0000, 0, 1, 0,
+            (2 << 16) | 17, 1,
+            (2 << 16) | 17, 56,
+            (3 << 16) | 14, 0, 1,
+        ];
+        assert_eq!(spirv_capabilities(&code), vec![1, 56]);
+        assert!(spirv_capabilities(&code[1..]).is_empty());
+
+        let context = VulkanContext::new();
+        assert!(context.check_shader_capabilities(&code).is_err());
+        assert!(context.check_shader_capabilities(&code[..7]).is_ok());
+    }
+    
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_graphics_dynamic_states() {
+        let all = graphics_dynamic_states(DynamicStateFlags::default());
+        assert_eq!(all.len(), 4);
+        assert!(all.contains(&vk::DynamicState::STENCIL_REFERENCE));
+        assert!(all.contains(&vk::DynamicState::LINE_WIDTH));
+
+        let line_width = graphics_dynamic_states(DynamicStateFlags {
+            line_width: true,
+            ..DynamicStateFlags::NONE
+        });
+        assert_eq!(line_width.len(), 3);
+        assert_eq!(line_width[2], vk::DynamicState::LINE_WIDTH);
+    }
+    
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_preprocess_glsl() {
+        let mut options = ShaderOptions {
+            include_resolver: Some(Box::new(|name| match name {
+                "lighting.glsl" => {
+                    Some("#pragma once\n#include \"common.glsl\"\nvec3 light;".into())
+                }
+                "common.glsl" => Some("#pragma once\nfloat common;".into()),
+                "cycle.glsl" => Some("#include <cycle.glsl>".into()),
+                _ => None,
+            })),
+            ..Default::default()
+        };
+        options.defines.insert("SHADOWS".to_string(), "1".to_string());
+
+        let source =
+            "#version 450\n#include \"lighting.glsl\"\n#include <common.glsl>\nvoid main() {}";
+        let output = preprocess_glsl(source, &options).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "#version 450");
+        assert_eq!(lines[1], "#define SHADOWS 1");
+        assert_eq!(lines[2], "#line 2");
+        assert_eq!(output.matches("float common;").count(), 1);
+        assert!(output.contains("vec3 light;"));
+        assert!(output.ends_with("#line 4\nvoid main() {}\n"));
+
+        assert!(preprocess_glsl("#include \"cycle.glsl\"", &options).is_err());
+        assert!(preprocess_glsl("#include \"missing.glsl\"", &options).is_err());
+        assert!(preprocess_glsl("#include missing.glsl", &options).is_err());
+    }
+    
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_create_shader_ex_compiles_preprocessed_stages() {
+        let mut context = VulkanContext::new();
+        let options = ShaderOptions {
+            include_resolver: Some(Box::new(|name| match name {
+                "output.glsl" => {
+                    Some("vec4 shade();\nvoid main() { gl_FragColor = shade(); }".into())
+                }
+                _ => None,
+            })),
+            ..Default::default()
+        };
+        let vertex = "#version 450\nvoid main() {}";
+        let meta = || ShaderMeta { vertex_format: None, texture_slots: vec![] };
+
+        let error = context
+            .create_shader_ex(vertex, "#include \"missing.glsl\"", meta(), &options)
+            .unwrap_err();
+        assert!(error.to_string().contains("missing.glsl"), "{}", error);
+        assert!(context.last_shader_lints().is_empty());
+
+        // The included source reaches the compiler, which isn't built in
+        let fragment = "#version 450\nvec4 shade() { return vec4(1.0); }\n#include \"output.glsl\"";
+        let error = context.create_shader_ex(vertex, fragment, meta(), &options).unwrap_err();
+        assert!(error.to_string().contains("no GLSL compiler"), "{}", error);
+        assert_eq!(context.last_shader_lints().len(), 1);
+        assert!(context.last_shader_lints()[0].starts_with("FRAGMENT shader line 2: gl_FragColor"));
+        assert!(context.shaders.is_empty());
+    }
+    
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_init_step_finishing_after_timeout_is_discarded() {
+        let (sender, receiver) = mpsc::channel();
+        let slow_step = || {
+            thread::sleep(Duration::from_millis(200));
+            Ok(7)
+        };
+        let discard = move |value| sender.send(value).unwrap();
+        let timeout = Some(Duration::from_millis(10));
+        let result = run_with_timeout("slow step", timeout, slow_step, discard);
+        assert!(result.is_err());
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(7));
+
+        let discard = |_| panic!("a result received in time was discarded");
+        let timeout = Some(Duration::from_secs(5));
+        let result = run_with_timeout("quick step", timeout, || Ok(1), discard);
+        assert_eq!(result.ok(), Some(1));
+    }
+    
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_lint_glsl() {
+        let source = "void main() {\n  uint w = gl_DispatchSize.x * gl_LocalSize.x; // gl_FragColor\n}";
+        let lints = lint_glsl(source);
+        assert_eq!(lints.len(), 2);
+        assert!(lints[0].starts_with("line 2: gl_DispatchSize"));
+        assert!(lint_glsl("uint w = gl_NumWorkGroups.x; float varyings;").is_empty());
+
+        // Old keywords that are fine as identifiers, and commented out code
+        let source = "float attribute, varying;\n/* gl_FragColor = vec4(1.0);\n texture2D */ x";
+        assert!(lint_glsl(source).is_empty());
+        let lints = lint_glsl("/* gl_FragColor */ gl_LocalSize");
+        assert_eq!(lints, ["line 1: gl_LocalSize is not a GLSL built-in, use gl_WorkGroupSize"]);
+        let lints = lint_glsl("#line 10\nvoid main() {}\ntexture2D");
+        assert!(lints[0].starts_with("line 11: texture2D"));
+    }
+    
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_reload_broken_shader_keeps_old_stages() {
+        // Stands in for a shader made by `create_shader_from_spirv`, reloading
+        // fails before its modules are touched
+        let mut context = VulkanContext::new();
+        context.shaders.push(VulkanShader {
+            vertex_module: vk::ShaderModule::from_raw(1),
+            fragment_module: vk::ShaderModule::from_raw(2),
+            geometry_module: None,
+            tessellation_modules: None,
+            compute_module: None,
+            texture_count: 0,
+        });
+
+        let broken = "void main() { gl_Position = ; }";
+        assert!(context.reload_shader(0, broken, broken).is_err());
+        assert!(context.reload_shader_spirv(0, &[0x0723_0203, 0], &[]).is_err());
+        assert!(context.reload_shader(1, broken, broken).is_err());
+        let meta = ShaderMeta { vertex_format: None, texture_slots: vec![] };
+        assert!(context.create_shader(broken, broken, meta).is_err());
+
+        assert_eq!(context.shaders.len(), 1);
+        assert_eq!(context.shaders[0].vertex_module, vk::ShaderModule::from_raw(1));
+        assert_eq!(context.shaders[0].fragment_module, vk::ShaderModule::from_raw(2));
+    }
+    
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn test_minimized_window_skips_frames() {
+        let mut context = VulkanContext::new();
+        context.set_display(crate::conf::Conf::default());
+        context.resize(0, 0);
+        assert!(matches!(context.begin_frame(), Ok(FRAME_SKIPPED)));
+        assert!(context.is_frame_skipped());
+        assert!(context.end_frame().is_ok());
+        assert!(context.present().is_ok());
+        // Recreating the swapchain doesn't wait for the window to come back
+        assert!(context.set_vsync(false).is_ok());
+        assert!(context.is_frame_skipped());
+
+        context.resize(640, 480);
+        assert!(!matches!(context.begin_frame(), Ok(FRAME_SKIPPED)));
+        assert!(!context.is_frame_skipped());
+    }
+}
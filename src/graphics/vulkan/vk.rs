@@ -4,8 +4,13 @@
 //! Note: This is a placeholder implementation for compilation purposes.
 //! A full Vulkan backend would require extensive additional implementation.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::{CStr, CString};
 use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::mpsc::{RecvTimeoutError, SendError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
 use std::error::Error as StdError;
 
 #[cfg(feature = "vulkan")]
@@ -13,7 +18,32 @@ use ash_037::{Entry, Instance, Device};
 #[cfg(feature = "vulkan")]
 use ash_037::vk;
 #[cfg(feature = "vulkan")]
-use gpu_allocator_022::{vulkan::Allocator, MemoryLocation};
+use ash_037::extensions::khr;
+#[cfg(feature = "vulkan")]
+use raw_window_handle_05::{
+    HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle,
+};
+#[cfg(feature = "vulkan")]
+use gpu_allocator_022::{
+    vulkan::{Allocation, AllocationCreateDesc, AllocationScheme, Allocator, AllocatorCreateDesc},
+    AllocationError, AllocatorDebugSettings, MemoryLocation,
+};
+
+use crate::conf::{
+    AdapterInfo, AdapterSelection, AdapterType, AllocatorConfig, ColorSpace, ColorSpaceMode,
+    CommandBufferReset, CompositeAlphaMode, DepthRange, DescriptorPoolSizes, RequestedFeatures,
+    SamplerDesc, SwapchainUsage,
+};
+use super::frame_graph::{transition, ResourceState, Transition};
+pub use super::frame_graph::{FrameGraph, FrameResource, ResourceUsage};
+pub use super::glsl::{lint_glsl, preprocess_glsl, IncludeResolver, ShaderOptions};
+use super::ktx2::{Ktx2Header, Supercompression};
+use crate::graphics::{
+    BlendFactor, BlendValue, BufferLayout, BufferType, CompareFunc, Comparison, CullFace,
+    DynamicStateFlags, Equation, FilterMode, FrontFaceOrder, IndexType, PassAction,
+    PipelineParams, PrimitiveType, StencilFaceState, StencilOp, TextureFormat, TextureWrap,
+    UniformDesc, UniformType, Usage, VertexAttribute, VertexFormat, VertexStep,
+};
 
 /// Simple error type for Vulkan operations
 #[derive(Debug)]
@@ -26,10 +56,39 @@ pub enum VulkanError {
     ShaderCompilation(String),
     MappingFailed(String),
     SynchronizationFailed(String),
+    /// The GPU was reset or hung, call `VulkanContext::reinitialize` to recover
+    DeviceLost,
     InvalidHandle,
+    /// A Vulkan call failed with the given result code
+    Api(vk::Result),
+    /// The GPU memory allocator could not satisfy a request
+    Allocation(AllocationError),
+}
+
+impl StdError for VulkanError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            VulkanError::Api(result) => Some(result),
+            VulkanError::Allocation(error) => Some(error),
+            _ => None,
+        }
+    }
 }
 
-impl StdError for VulkanError {}
+impl From<vk::Result> for VulkanError {
+    fn from(result: vk::Result) -> Self {
+        match result {
+            vk::Result::ERROR_DEVICE_LOST => VulkanError::DeviceLost,
+            _ => VulkanError::Api(result),
+        }
+    }
+}
+
+impl From<AllocationError> for VulkanError {
+    fn from(error: AllocationError) -> Self {
+        VulkanError::Allocation(error)
+    }
+}
 
 impl fmt::Display for VulkanError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -42,7 +101,106 @@ impl fmt::Display for VulkanError {
             VulkanError::ShaderCompilation(msg) => write!(f, "Shader compilation failed: {}", msg),
             VulkanError::MappingFailed(msg) => write!(f, "Memory mapping failed: {}", msg),
             VulkanError::SynchronizationFailed(msg) => write!(f, "Synchronization failed: {}", msg),
+            VulkanError::DeviceLost => write!(f, "Vulkan device lost"),
             VulkanError::InvalidHandle => write!(f, "Invalid Vulkan handle"),
+            VulkanError::Api(result) => write!(f, "Vulkan call failed: {}", result),
+            VulkanError::Allocation(error) => write!(f, "GPU memory allocation failed: {}", error),
+        }
+    }
+}
+
+/// What happens to the previous contents of an attachment when a render pass begins
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoadOp<T> {
+    /// Clear the attachment to the given value
+    Clear(T),
+    /// Preserve whatever was rendered into the attachment before
+    Load,
+    /// Previous contents are undefined, cheapest option when everything is overwritten
+    DontCare,
+}
+
+impl<T> LoadOp<T> {
+    fn vk_load_op(&self) -> vk::AttachmentLoadOp {
+        match self {
+            LoadOp::Clear(_) => vk::AttachmentLoadOp::CLEAR,
+            LoadOp::Load => vk::AttachmentLoadOp::LOAD,
+            LoadOp::DontCare => vk::AttachmentLoadOp::DONT_CARE,
+        }
+    }
+}
+
+/// Per-attachment load operations for `begin_render_pass`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LoadActions {
+    pub color: LoadOp<[f32; 4]>,
+    /// 1.0 clears to the far plane, reversed Z projections need 0.0
+    pub depth: LoadOp<f32>,
+    pub stencil: LoadOp<u32>,
+}
+
+impl LoadActions {
+    fn vk_load_ops(&self) -> [vk::AttachmentLoadOp; 3] {
+        [
+            self.color.vk_load_op(),
+            self.depth.vk_load_op(),
+            self.stencil.vk_load_op(),
+        ]
+    }
+
+    fn clear_values(&self, mode: ColorSpaceMode) -> (vk::ClearValue, vk::ClearValue) {
+        let color = match self.color {
+            LoadOp::Clear(color) => clear_color_value(mode, color),
+            _ => [0.0; 4],
+        };
+        let depth = match self.depth {
+            LoadOp::Clear(depth) => depth,
+            _ => 1.0,
+        };
+        let stencil = match self.stencil {
+            LoadOp::Clear(stencil) => stencil,
+            _ => 0,
+        };
+        (
+            vk::ClearValue {
+                color: vk::ClearColorValue { float32: color },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue { depth, stencil },
+            },
+        )
+    }
+}
+
+/// Translates the front-end pass action, `None` clear values keep the
+/// attachment contents just like on OpenGL.
+impl From<PassAction> for LoadActions {
+    fn from(action: PassAction) -> Self {
+        match action {
+            PassAction::Nothing => LoadActions {
+                color: LoadOp::Load,
+                depth: LoadOp::Load,
+                stencil: LoadOp::Load,
+            },
+            PassAction::Clear {
+                color,
+                depth,
+                stencil,
+            } => LoadActions {
+                color: color.map_or(LoadOp::Load, |(r, g, b, a)| LoadOp::Clear([r, g, b, a])),
+                depth: depth.map_or(LoadOp::Load, LoadOp::Clear),
+                stencil: stencil.map_or(LoadOp::Load, |stencil| LoadOp::Clear(stencil as u32)),
+            },
+        }
+    }
+}
+
+impl Default for LoadActions {
+    fn default() -> Self {
+        LoadActions {
+            color: LoadOp::Clear([0.0, 0.0, 0.0, 1.0]),
+            depth: LoadOp::Clear(1.0),
+            stencil: LoadOp::Clear(0),
         }
     }
 }
@@ -54,291 +212,10380 @@ pub struct ShaderMeta {
     pub texture_slots: Vec<String>,
 }
 
+/// Texture handle of an immediate mode UI. Matches egui's `TextureId`:
+/// `Managed` textures such as the font atlas are uploaded and freed through
+/// `set_overlay_texture`, `User` ones wrap textures owned by the application.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OverlayTextureId {
+    Managed(u64),
+    User(u64),
+}
+
+/// Window rendered by a `VulkanContext`, see `VulkanContext::add_window`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WindowId(pub u32);
+
+impl WindowId {
+    /// The window the context was initialized with
+    pub const MAIN: WindowId = WindowId(0);
+}
+
+/// Surface and swapchain state of a window that is not current
+#[derive(Default)]
+struct VulkanWindow {
+    window_handles: Option<(RawDisplayHandle, RawWindowHandle)>,
+    surface: Option<vk::SurfaceKHR>,
+    swapchain: Option<vk::SwapchainKHR>,
+    swapchain_images: Vec<vk::Image>,
+    swapchain_min_image_count: u32,
+    swapchain_image_views: Vec<vk::ImageView>,
+    swapchain_depth: Option<(vk::Image, vk::ImageView, Allocation)>,
+    swapchain_image_format: vk::Format,
+    swapchain_color_space: vk::ColorSpaceKHR,
+    swapchain_composite_alpha: vk::CompositeAlphaFlagsKHR,
+    swapchain_usage: vk::ImageUsageFlags,
+    present_mode: vk::PresentModeKHR,
+    swapchain_extent: vk::Extent2D,
+    window_extent: Option<vk::Extent2D>,
+    render_pass: Option<vk::RenderPass>,
+    framebuffers: Vec<vk::Framebuffer>,
+    render_pass_cache: HashMap<[vk::AttachmentLoadOp; 3], vk::RenderPass>,
+    current_image_index: u32,
+    image_available_semaphores: Vec<vk::Semaphore>,
+    render_finished_semaphores: Vec<vk::Semaphore>,
+    images_in_flight: Vec<vk::Fence>,
+}
+
+/// Completion point of a `dispatch_compute` on the compute timeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ComputeTicket(pub u64);
+
+/// Handle of a buffer read started with `read_buffer_async`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReadbackToken(u64);
+
+/// Queue families chosen for the device, see `VulkanContext::queue_info`.
+#[derive(Debug, Clone, Default)]
+pub struct QueueInfo {
+    pub graphics_family: Option<u32>,
+    pub present_family: Option<u32>,
+    /// Dedicated transfer family, `None` when uploads go through graphics
+    pub transfer_family: Option<u32>,
+    /// Compute-only family if the device has one, the graphics family otherwise
+    pub compute_family: Option<u32>,
+    /// Presentation happens on the graphics queue
+    pub present_shares_graphics: bool,
+    /// Queue flags of every family on the device, indexed by family index
+    pub family_flags: Vec<vk::QueueFlags>,
+}
+
+/// GPU and driver the context runs on, see `VulkanContext::device_info`.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceInfo {
+    pub name: String,
+    /// PCI vendor id, e.g. 0x10DE for NVIDIA, 0x1002 for AMD, 0x8086 for Intel
+    pub vendor_id: u32,
+    pub device_id: u32,
+    /// Driver version decoded the way the vendor packs it, e.g. "535.104.5.0"
+    /// for NVIDIA
+    pub driver_version: String,
+    /// Vulkan version supported by the driver, e.g. "1.3.250"
+    pub api_version: String,
+    pub device_type: vk::PhysicalDeviceType,
+}
+
+/// Extensions enabled on the instance and device, see
+/// `VulkanContext::enabled_extensions`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnabledExtensions {
+    pub instance: Vec<String>,
+    pub device: Vec<String>,
+}
+
+/// An extension initialization asked for and whether it was enabled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionRequest {
+    pub name: String,
+    pub granted: bool,
+}
+
+/// What `VulkanContext::initialize` found and decided, for bug reports.
+/// Filled in as far as initialization got, `failure` says where it stopped.
+#[derive(Debug, Clone, Default)]
+pub struct InitReport {
+    /// Vulkan version the instance was created for, e.g. "1.2.0"
+    pub instance_api_version: String,
+    /// Instance extensions asked for, those in
+    /// `Platform::extra_instance_extensions`
+    pub instance_extensions: Vec<ExtensionRequest>,
+    /// Optional device extensions asked for, granted when the device has them
+    pub device_extensions: Vec<ExtensionRequest>,
+    /// Every physical device the driver reported
+    pub adapters: Vec<AdapterInfo>,
+    /// `AdapterInfo::index` of the device in use
+    pub selected_adapter: Option<usize>,
+    pub device: DeviceInfo,
+    pub queues: QueueInfo,
+    /// Features requested in `Platform::requested_features` the device lacks
+    pub missing_features: Vec<String>,
+    /// Fallbacks taken because something wasn't supported, as printed
+    pub fallbacks: Vec<String>,
+    /// The error initialization stopped with, timeouts included
+    pub failure: Option<String>,
+}
+
+impl InitReport {
+    fn fallback(&mut self, message: String) {
+        log_04::warn!("Vulkan: {}", message);
+        self.fallbacks.push(message);
+    }
+}
+
+/// Snapshot returned by `VulkanContext::get_performance_stats`.
+#[derive(Debug, Clone)]
+pub struct VulkanStats {
+    pub buffer_count: usize,
+    pub texture_count: usize,
+    pub shader_count: usize,
+    pub pipeline_count: usize,
+    /// Bytes held by buffer and texture allocations
+    pub allocated_memory: u64,
+    /// Seconds between the last two presents
+    pub frame_time: f32,
+    /// Seconds between presents for the last `FRAME_HISTORY_LEN` frames
+    pub cpu_frame_times: FrameTimeHistory,
+    /// Seconds the GPU spent on each of the last `FRAME_HISTORY_LEN` frames,
+    /// empty when the device has no timestamp support
+    pub gpu_frame_times: FrameTimeHistory,
+    pub msaa_enabled: bool,
+    pub msaa_samples: vk::SampleCountFlags,
+    pub queue_info: QueueInfo,
+    pub device_info: DeviceInfo,
+    /// Descriptor sets allocated so far, including the uniform ring sets
+    pub descriptor_set_count: usize,
+    /// `vkQueueSubmit` calls between the last two presents, not counting
+    /// those of a `ResourceUploader`
+    pub submit_count: u32,
+    /// Device memory blocks behind live resources, see
+    /// `VulkanContext::allocation_count`
+    pub allocation_count: usize,
+    /// `maxMemoryAllocationCount` of the device
+    pub max_allocation_count: u32,
+}
+
+impl Default for VulkanStats {
+    /// Stats of a context without any resources, MSAA off
+    fn default() -> Self {
+        VulkanStats {
+            buffer_count: 0,
+            texture_count: 0,
+            shader_count: 0,
+            pipeline_count: 0,
+            allocated_memory: 0,
+            frame_time: 0.0,
+            cpu_frame_times: FrameTimeHistory::default(),
+            gpu_frame_times: FrameTimeHistory::default(),
+            msaa_enabled: false,
+            msaa_samples: vk::SampleCountFlags::TYPE_1,
+            queue_info: QueueInfo::default(),
+            device_info: DeviceInfo::default(),
+            descriptor_set_count: 0,
+            submit_count: 0,
+            allocation_count: 0,
+            max_allocation_count: 0,
+        }
+    }
+}
+
+impl VulkanStats {
+    /// CPU frame times, oldest first
+    pub fn frame_time_history(&self) -> &[f32] {
+        self.cpu_frame_times.as_slice()
+    }
+
+    /// Frame time at percentile `p` of the CPU history, `percentile(99.0)`
+    /// gives the frame time behind the 1% low FPS.
+    pub fn percentile(&self, p: f32) -> f32 {
+        self.cpu_frame_times.percentile(p)
+    }
+}
+
+/// Number of frames kept by `FrameTimeHistory`
+pub const FRAME_HISTORY_LEN: usize = 240;
+
+/// Ring buffer of the last `FRAME_HISTORY_LEN` frame times in seconds.
+///
+/// Every sample is stored twice, `FRAME_HISTORY_LEN` apart, so the history
+/// is always one contiguous slice and pushing never allocates.
+#[derive(Clone)]
+pub struct FrameTimeHistory {
+    samples: [f32; 2 * FRAME_HISTORY_LEN],
+    next: usize,
+    len: usize,
+}
+
+impl FrameTimeHistory {
+    pub fn push(&mut self, frame_time: f32) {
+        self.samples[self.next] = frame_time;
+        self.samples[self.next + FRAME_HISTORY_LEN] = frame_time;
+        self.next = (self.next + 1) % FRAME_HISTORY_LEN;
+        self.len = (self.len + 1).min(FRAME_HISTORY_LEN);
+    }
+
+    /// Recorded frame times, oldest first
+    pub fn as_slice(&self) -> &[f32] {
+        let end = self.next + FRAME_HISTORY_LEN;
+        &self.samples[end - self.len..end]
+    }
+
+    /// Frame time below which `p` percent of the recorded frames fall, 0.0
+    /// when nothing was recorded yet
+    pub fn percentile(&self, p: f32) -> f32 {
+        let mut sorted = [0.0f32; FRAME_HISTORY_LEN];
+        let sorted = &mut sorted[..self.len];
+        if sorted.is_empty() {
+            return 0.0;
+        }
+        sorted.copy_from_slice(self.as_slice());
+        sorted.sort_unstable_by(|a, b| a.total_cmp(b));
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * (sorted.len() - 1) as f32).round();
+        sorted[rank as usize]
+    }
+}
+
+impl Default for FrameTimeHistory {
+    fn default() -> Self {
+        FrameTimeHistory {
+            samples: [0.0; 2 * FRAME_HISTORY_LEN],
+            next: 0,
+            len: 0,
+        }
+    }
+}
+
+impl fmt::Debug for FrameTimeHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
+
+/// OS handle to memory exported by another API or process, see
+/// `VulkanContext::import_external_image`.
+#[derive(Debug, Clone, Copy)]
+pub enum ExternalMemoryHandle {
+    /// Opaque POSIX file descriptor (`VK_KHR_external_memory_fd`)
+    #[cfg(unix)]
+    Fd(std::os::raw::c_int),
+    /// Opaque Win32 handle (`VK_KHR_external_memory_win32`)
+    #[cfg(windows)]
+    Win32(vk::HANDLE),
+}
+
+/// Raw Vulkan handles returned by `VulkanContext::raw_handles`.
+#[derive(Debug, Clone, Copy)]
+pub struct VulkanRawHandles {
+    pub instance: vk::Instance,
+    pub physical_device: vk::PhysicalDevice,
+    pub device: vk::Device,
+    /// Graphics queue, also used for presenting
+    pub queue: vk::Queue,
+    pub queue_family_index: u32,
+    /// Command buffer being recorded for the current frame
+    pub command_buffer: vk::CommandBuffer,
+}
+
 /// The main Vulkan context
 #[cfg(feature = "vulkan")]
 pub struct VulkanContext {
     pub entry: Option<Entry>,
     pub instance: Option<Instance>,
     pub device: Option<Device>,
-    pub allocator: Option<Allocator>,
+    /// Shared with `ResourceUploader`s, `None` while there is no device
+    pub allocator: Arc<Mutex<Option<Allocator>>>,
+    /// Held around submissions to the graphics and transfer queues, which
+    /// `ResourceUploader`s may use from other threads
+    queue_lock: Arc<Mutex<()>>,
+    /// Created by the first `resource_uploader` call
+    uploader: Option<ResourceUploader>,
     pub physical_device: Option<vk::PhysicalDevice>,
     pub queue_family_index: Option<u32>,
     pub present_queue_family_index: Option<u32>,
     pub graphics_queue: Option<vk::Queue>,
     pub present_queue: Option<vk::Queue>,
     pub command_pool: Option<vk::CommandPool>,
+    /// Queue family with transfer but no graphics support, used for texture uploads
+    pub transfer_queue_family_index: Option<u32>,
+    pub transfer_queue: Option<vk::Queue>,
+    pub transfer_command_pool: Option<vk::CommandPool>,
+    pub compute_queue_family_index: Option<u32>,
+    /// Queue flags of every family of the physical device, by family index
+    pub queue_family_flags: Vec<vk::QueueFlags>,
+    /// Set when `Conf::prefer_async_compute` got a dedicated compute queue
+    pub compute_queue: Option<vk::Queue>,
+    pub compute_command_pool: Option<vk::CommandPool>,
+    /// Timeline semaphore signalled by async compute submissions
+    pub compute_timeline: Option<vk::Semaphore>,
+    pub compute_timeline_value: u64,
+    /// Timeline value the next graphics submission has to wait on
+    pub compute_wait_value: u64,
+    compute_in_flight: Vec<(u64, vk::CommandBuffer)>,
     
-    // Surface and swapchain
+    // Surface and swapchain of `current_window`, see `make_current`
+    pub current_window: WindowId,
+    /// Windows other than the current one, with their surface state parked
+    windows: HashMap<WindowId, VulkanWindow>,
+    next_window_id: u32,
+    /// Native window the surface is created on, from
+    /// `NativeDisplay::raw_window_handles` when initializing
+    window_handles: Option<(RawDisplayHandle, RawWindowHandle)>,
+    /// Loaders of `VK_KHR_surface` and `VK_KHR_swapchain`, `None` before
+    /// the instance or device exist
+    surface_loader: Option<khr::Surface>,
+    swapchain_loader: Option<khr::Swapchain>,
     pub surface: Option<vk::SurfaceKHR>,
     pub swapchain: Option<vk::SwapchainKHR>,
     pub swapchain_images: Vec<vk::Image>,
+    /// Image count passed to the driver when creating the swapchain
+    pub swapchain_min_image_count: u32,
     pub swapchain_image_views: Vec<vk::ImageView>,
+    /// Depth attachment of the swapchain framebuffers, with `depth_format`
+    swapchain_depth: Option<(vk::Image, vk::ImageView, Allocation)>,
     pub swapchain_image_format: vk::Format,
+    pub swapchain_color_space: vk::ColorSpaceKHR,
+    /// How the swapchain images are composited with what is behind the window
+    pub swapchain_composite_alpha: vk::CompositeAlphaFlagsKHR,
+    /// Usage the swapchain images were created with, see `swapchain_usage`
+    swapchain_usage: vk::ImageUsageFlags,
+    /// Present mode of the swapchain, see `set_vsync`
+    pub present_mode: vk::PresentModeKHR,
+    /// Whether the swapchain should wait for vertical blanks, from
+    /// `Platform::swap_interval` until `set_vsync`
+    vsync: bool,
     pub swapchain_extent: vk::Extent2D,
+    /// Size of the attachments of the render pass being recorded
+    pub pass_extent: vk::Extent2D,
+    /// Color attachment count and depth format of the render pass being
+    /// recorded, `None` outside of a render pass
+    pub pass_attachments: Option<(u32, Option<vk::Format>)>,
+    /// Render target and subpass being recorded on targets from
+    /// `create_subpass_render_target`
+    active_subpass: Option<(usize, u32)>,
     
     // Render pass and framebuffers
     pub render_pass: Option<vk::RenderPass>,
     pub framebuffers: Vec<vk::Framebuffer>,
+    pub depth_format: Option<vk::Format>,
+    /// Render passes compatible with `render_pass`, keyed by color/depth/stencil load ops
+    pub render_pass_cache: HashMap<[vk::AttachmentLoadOp; 3], vk::RenderPass>,
+    pub current_image_index: u32,
     
     // Command buffers and synchronization
     pub command_buffers: Vec<vk::CommandBuffer>,
+    /// One pool per frame slot holding just its command buffer, with
+    /// `CommandBufferReset::Pool`. Empty when the frame command buffers come
+    /// from `command_pool`.
+    frame_command_pools: Vec<vk::CommandPool>,
     pub image_available_semaphores: Vec<vk::Semaphore>,
     pub render_finished_semaphores: Vec<vk::Semaphore>,
     pub in_flight_fences: Vec<vk::Fence>,
     pub images_in_flight: Vec<vk::Fence>,
+    /// Whether the device has timeline semaphores enabled. Frames are then
+    /// tracked by `frame_timeline` instead of `in_flight_fences`.
+    pub timeline_semaphores: bool,
+    /// Whether render passes can broadcast draws to several layers, see
+    /// `create_multiview_render_target`
+    pub multiview: bool,
+    /// Set between `on_surface_lost` and `on_surface_recreated`
+    surface_lost: bool,
+    /// Set when `begin_frame` returned `FRAME_SKIPPED` for a minimized window
+    frame_skipped: bool,
+    /// Whether the frame being recorded rendered into its swapchain image,
+    /// which otherwise gets a cleared one in `end_frame`
+    swapchain_pass_begun: bool,
+    /// Last size passed to `resize`, `None` to go by `Conf`
+    window_extent: Option<vk::Extent2D>,
+    /// See `last_shader_lints`
+    shader_lints: Vec<String>,
+    /// See `init_report`
+    init_report: InitReport,
+    /// See `enabled_extensions`
+    enabled_extensions: EnabledExtensions,
+    /// Whether descriptor indexing is enabled, see `create_bindless_texture_set`
+    pub bindless: bool,
+    /// Whether pipelines take clip space depth from -1 to 1 through
+    /// `VK_EXT_depth_clip_control`, as asked for by `DepthRange::NegOneToOne`
+    pub depth_clip_control: bool,
+    /// Loader of `VK_KHR_buffer_device_address` on devices below Vulkan
+    /// 1.2, which lack the core entry point
+    device_address_ext: Option<ash_037::extensions::khr::BufferDeviceAddress>,
+    bindless_set: Option<BindlessSet>,
+    pub frame_timeline: Option<vk::Semaphore>,
+    /// Value signalled by the most recently submitted frame
+    pub frame_timeline_value: u64,
+    /// Value each frame slot signals on its last submission
+    pub frame_values: Vec<u64>,
     
     // Resources
     pub buffers: HashMap<usize, VulkanBuffer>,
     pub textures: HashMap<usize, VulkanTexture>,
     pub shaders: Vec<VulkanShader>,
     pub pipelines: Vec<VulkanPipeline>,
+    pub samplers: Vec<vk::Sampler>,
+    /// Sampler for textures bound without one, see `default_sampler`
+    default_sampler: Option<usize>,
+    /// Set once the allocation count warning was printed, cleared when the
+    /// count drops again
+    allocation_count_warned: bool,
+    pub render_targets: HashMap<usize, VulkanRenderTarget>,
+    pending_uploads: Vec<PendingUpload>,
+    pending_readbacks: HashMap<ReadbackToken, PendingReadback>,
+    next_readback: u64,
+    /// Deleted textures with the frame value after which they are unused
+    retired_textures: Vec<(u64, VulkanTexture)>,
+    /// Commands recorded by `batched_commands` for the current frame
+    batch: Option<CommandBatch>,
+    /// Submitted batches with the frame value after which they are done
+    batches_in_flight: Vec<(u64, CommandBatch)>,
+    /// `Conf::batch_submissions`, updates submit one by one when off
+    pub batch_submissions: bool,
+    /// `Conf::flip_viewport_y`, cleared when the device can't take negative
+    /// viewport heights
+    pub flip_viewport_y: bool,
+    /// Queue submissions since the last present, and between the two before
+    submit_count: u32,
+    last_submit_count: u32,
+    /// Textures of the UI overlay, see `set_overlay_texture`
+    pub overlay_textures: HashMap<OverlayTextureId, usize>,
+    pub next_user_texture: u64,
+    /// Set 0 of every graphics pipeline layout: one dynamic uniform buffer
+    /// at binding 0, fed by `apply_uniforms`. Pipelines with
+    /// `global_uniforms` also take it as `GLOBAL_UNIFORM_SET`.
+    pub uniform_set_layout: Option<vk::DescriptorSetLayout>,
+    pub uniform_descriptor_pool: Option<vk::DescriptorPool>,
+    uniform_rings: Vec<UniformRing>,
+    /// Pools behind `allocate_descriptor_set`
+    descriptor_pools: DescriptorAllocator,
+    /// Sets written by `apply_textures`, by sampler set layout and textures
+    texture_sets: HashMap<(vk::DescriptorSetLayout, Vec<usize>), vk::DescriptorSet>,
+    /// Largest uniform block `apply_uniforms` accepts
+    pub uniform_range: vk::DeviceSize,
+    /// Pipeline layout the global uniforms were last bound with, so
+    /// `apply_pipeline` only binds them again when the layout changes
+    global_uniforms_layout: Option<vk::PipelineLayout>,
+    
+    // Device capabilities
+    pub device_properties: vk::PhysicalDeviceProperties,
+    pub device_features: vk::PhysicalDeviceFeatures,
+    granted_features: RequestedFeatures,
+    /// API version the instance was created with
+    pub api_version: u32,
+    /// Handle type `import_external_image` accepts, `None` when the external
+    /// memory extensions are not enabled
+    pub external_memory_handle_type: Option<vk::ExternalMemoryHandleTypeFlags>,
     
     // Frame management
     pub current_frame: usize,
     pub max_frames_in_flight: usize,
+    /// When the next frame may be presented under `Conf::target_fps`
+    pub frame_deadline: Option<Instant>,
+    pub last_present: Option<Instant>,
+    /// Seconds between the last two presents
+    pub frame_time: f32,
+    /// Loader of `VK_GOOGLE_display_timing`, `None` when the device lacks it
+    display_timing: Option<vk::GoogleDisplayTimingFn>,
+    /// Presents waiting for the driver to report their timing, by present
+    /// id with the monotonic clock time they were queued at in nanoseconds
+    pending_presents: VecDeque<(u32, u64)>,
+    next_present_id: u32,
+    /// When the swapchain image of the frame being recorded was acquired
+    acquire_time: Option<Instant>,
+    present_latency: Option<f32>,
+    pub cpu_frame_times: FrameTimeHistory,
+    pub gpu_frame_times: FrameTimeHistory,
+    /// Two timestamps per frame slot bracketing its command buffer, `None`
+    /// when the graphics queue can't write timestamps
+    pub timestamp_pool: Option<vk::QueryPool>,
+    /// `OCCLUSION_QUERY_COUNT` occlusion queries per frame slot
+    pub occlusion_pool: Option<vk::QueryPool>,
+    /// Query ids begun in each frame slot, read back when the slot is reused
+    occlusion_written: Vec<Vec<u32>>,
+    active_occlusion_query: Option<u32>,
+    /// Latest samples passed per query id from a completed frame
+    occlusion_results: HashMap<u32, u64>,
+    /// Samples per pixel from `Conf::sample_count`, see `set_msaa_samples`
     pub msaa_samples: vk::SampleCountFlags,
     
     pub display: Option<crate::conf::Conf>,
+    pub dpi_scale: f32,
     pub next_buffer_id: usize,
+    /// Index type of the buffer last passed to `apply_index_buffer`
+    pub bound_index_type: Option<IndexType>,
+    /// Pipeline last passed to `apply_pipeline`
+    pub bound_pipeline: Option<usize>,
+    /// Set by `set_debug_wireframe`, makes `apply_pipeline` bind line variants
+    debug_wireframe: bool,
     pub next_texture_id: usize,
+    pub next_render_target_id: usize,
 }
 
 impl VulkanContext {
     /// Create a new Vulkan context
+    ///
+    /// Panics when the crate is built without the `vulkan` feature, libraries
+    /// that don't control the feature flags should use `try_new`.
     pub fn new() -> Self {
+        Self::try_new().unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Create a headless context with its device, allocator and frame
+    /// resources ready, for tests that render and read back pixels. A
+    /// software implementation (`AdapterType::Cpu`) is preferred when one is
+    /// installed, so results don't depend on the GPU the tests run on;
+    /// otherwise the best adapter is used. Fails when Vulkan can't be loaded
+    /// or has no usable device, which tests should treat as a skip.
+    ///
+    /// Mesa's lavapipe is the usual software implementation on Linux, e.g.
+    /// `apt install mesa-vulkan-drivers` on Debian and Ubuntu or
+    /// `dnf install mesa-vulkan-drivers` on Fedora. To hide the hardware
+    /// drivers and run on lavapipe alone, point the loader at its manifest:
+    /// `VK_ICD_FILENAMES=/usr/share/vulkan/icd.d/lvp_icd.x86_64.json`.
+    /// SwiftShader works the same way through its own `vk_swiftshader_icd.json`.
+    pub fn new_for_testing() -> Result<Self, VulkanError> {
+        let mut context = Self::try_new()?;
+        let mut conf = crate::conf::Conf {
+            headless: true,
+            ..Default::default()
+        };
+        conf.platform.adapter =
+            AdapterSelection::Predicate(|adapter| adapter.adapter_type == AdapterType::Cpu);
+        context.set_display(conf);
+        context.init_vulkan()?;
+        context.create_sync_objects()?;
+        context.create_uniform_rings()?;
+        Ok(context)
+    }
+
+    /// Create a new Vulkan context, or `VulkanError::InitializationFailed`
+    /// when the crate is built without the `vulkan` feature
+    pub fn try_new() -> Result<Self, VulkanError> {
         #[cfg(feature = "vulkan")]
         {
-            Self {
+            Ok(Self {
                 entry: None,
                 instance: None,
                 device: None,
-                allocator: None,
+                allocator: Arc::new(Mutex::new(None)),
+                queue_lock: Arc::new(Mutex::new(())),
+                uploader: None,
                 physical_device: None,
                 queue_family_index: None,
                 present_queue_family_index: None,
                 graphics_queue: None,
                 present_queue: None,
                 command_pool: None,
+                transfer_queue_family_index: None,
+                transfer_queue: None,
+                transfer_command_pool: None,
+                compute_queue_family_index: None,
+                queue_family_flags: Vec::new(),
+                compute_queue: None,
+                compute_command_pool: None,
+                compute_timeline: None,
+                compute_timeline_value: 0,
+                compute_wait_value: 0,
+                compute_in_flight: Vec::new(),
                 
                 // Surface and swapchain
+                current_window: WindowId::MAIN,
+                windows: HashMap::new(),
+                next_window_id: 1,
+                window_handles: None,
+                surface_loader: None,
+                swapchain_loader: None,
                 surface: None,
                 swapchain: None,
                 swapchain_images: Vec::new(),
+                swapchain_min_image_count: 0,
                 swapchain_image_views: Vec::new(),
-                swapchain_image_format: vk::Format::R8G8B8A8_SRGB,
+                swapchain_depth: None,
+                swapchain_image_format: vk::Format::R8G8B8A8_UNORM,
+                swapchain_color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+                swapchain_composite_alpha: vk::CompositeAlphaFlagsKHR::OPAQUE,
+                swapchain_usage: vk::ImageUsageFlags::COLOR_ATTACHMENT,
+                present_mode: vk::PresentModeKHR::FIFO,
+                vsync: true,
                 swapchain_extent: vk::Extent2D { width: 800, height: 600 },
+                pass_extent: vk::Extent2D::default(),
+                pass_attachments: None,
+                active_subpass: None,
                 
                 // Render pass and framebuffers
                 render_pass: None,
                 framebuffers: Vec::new(),
+                depth_format: None,
+                render_pass_cache: HashMap::new(),
+                current_image_index: 0,
                 
                 // Command buffers and synchronization
                 command_buffers: Vec::new(),
+                frame_command_pools: Vec::new(),
                 image_available_semaphores: Vec::new(),
                 render_finished_semaphores: Vec::new(),
                 in_flight_fences: Vec::new(),
                 images_in_flight: Vec::new(),
+                timeline_semaphores: false,
+                multiview: false,
+                surface_lost: false,
+                frame_skipped: false,
+                swapchain_pass_begun: false,
+                window_extent: None,
+                shader_lints: Vec::new(),
+                init_report: InitReport::default(),
+                enabled_extensions: EnabledExtensions::default(),
+                bindless: false,
+                device_address_ext: None,
+                depth_clip_control: false,
+                bindless_set: None,
+                frame_timeline: None,
+                frame_timeline_value: 0,
+                frame_values: Vec::new(),
                 
                 // Resources
                 buffers: HashMap::new(),
                 textures: HashMap::new(),
                 shaders: Vec::new(),
                 pipelines: Vec::new(),
+                samplers: Vec::new(),
+                default_sampler: None,
+                allocation_count_warned: false,
+                render_targets: HashMap::new(),
+                pending_uploads: Vec::new(),
+                pending_readbacks: HashMap::new(),
+                next_readback: 0,
+                retired_textures: Vec::new(),
+                batch: None,
+                batches_in_flight: Vec::new(),
+                batch_submissions: true,
+                flip_viewport_y: true,
+                submit_count: 0,
+                last_submit_count: 0,
+                overlay_textures: HashMap::new(),
+                next_user_texture: 0,
+                uniform_set_layout: None,
+                global_uniforms_layout: None,
+                uniform_descriptor_pool: None,
+                uniform_rings: Vec::new(),
+                descriptor_pools: DescriptorAllocator::default(),
+                texture_sets: HashMap::new(),
+                uniform_range: 0,
+                
+                // Device capabilities
+                device_properties: vk::PhysicalDeviceProperties::default(),
+                device_features: vk::PhysicalDeviceFeatures::default(),
+                granted_features: RequestedFeatures::default(),
+                api_version: vk::API_VERSION_1_0,
+                external_memory_handle_type: None,
                 
                 // Frame management
                 current_frame: 0,
                 max_frames_in_flight: 2,
+                frame_deadline: None,
+                last_present: None,
+                frame_time: 0.0,
+                display_timing: None,
+                pending_presents: VecDeque::new(),
+                next_present_id: 0,
+                acquire_time: None,
+                present_latency: None,
+                cpu_frame_times: FrameTimeHistory::default(),
+                gpu_frame_times: FrameTimeHistory::default(),
+                timestamp_pool: None,
+                occlusion_pool: None,
+                occlusion_written: Vec::new(),
+                active_occlusion_query: None,
+                occlusion_results: HashMap::new(),
                 msaa_samples: vk::SampleCountFlags::TYPE_4, // Default to 4x MSAA
                 display: None,
+                dpi_scale: 1.0,
                 next_buffer_id: 0,
+                bound_index_type: None,
+                bound_pipeline: None,
+                debug_wireframe: false,
                 next_texture_id: 0,
-            }
+                next_render_target_id: 0,
+            })
         }
         
         #[cfg(not(feature = "vulkan"))]
         {
-            panic!("Vulkan feature not enabled")
+            Err(VulkanError::InitializationFailed("Vulkan feature not enabled".to_string()))
         }
     }
     
-    // Simplified placeholder implementations
+    /// Load the Vulkan library and create the instance, device, allocator and
+    /// command pool. Surface and swapchain are set up separately. Loading the
+    /// library, creating the instance and listing the devices time out after
+    /// `Platform::init_timeout_secs`. See `init_report` for the outcome.
     pub fn init_vulkan(&mut self) -> Result<(), VulkanError> {
-        println!("Vulkan initialization (placeholder)");
-        // Placeholder implementation - in real implementation would:
-        // 1. Load Vulkan instance
-        // 2. Create logical device
-        // 3. Initialize allocator
-        // 4. Create command pools
-        // 5. Set up swapchain
-        
-        // For placeholder, we'll skip actual entry creation
-        println!("Skipping actual Vulkan entry creation for placeholder");
-        // self.entry = Some(Entry::new().map_err(|e| VulkanError::InitializationFailed(e.to_string()))?);
-        Ok(())
-    }
-    
-    pub fn get_physical_device(&self) -> Option<vk::PhysicalDevice> {
-        self.physical_device
-    }
-    
-    pub fn set_display(&mut self, conf: crate::conf::Conf) {
-        self.display = Some(conf);
+        self.init_report = InitReport::default();
+        let result = self.init_vulkan_steps();
+        if let Err(e) = &result {
+            self.init_report.failure = Some(e.to_string());
+        }
+        result
     }
-    
-    pub fn create_surface(&mut self) -> Result<(), VulkanError> {
-        println!("Creating Vulkan surface (placeholder implementation)");
-        // Placeholder - would create actual surface
+
+    fn init_vulkan_steps(&mut self) -> Result<(), VulkanError> {
+        let timeout = self.init_timeout();
+        // The window's surface extensions, then the ones `Conf` asks for
+        let mut extra: Vec<String> = match self.window_handles {
+            Some((display_handle, _)) => {
+                ash_window_012::enumerate_required_extensions(display_handle)
+                    .map_err(|e| VulkanError::InitializationFailed(e.to_string()))?
+                    .iter()
+                    .map(|&name| unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned())
+                    .collect()
+            }
+            None => vec![],
+        };
+        let configured = self
+            .display
+            .as_ref()
+            .map_or_else(Vec::new, |conf| conf.platform.extra_instance_extensions.clone());
+        for name in configured {
+            if !extra.contains(&name) {
+                extra.push(name);
+            }
+        }
+        self.init_report.instance_extensions = extra
+            .iter()
+            .map(|name| ExtensionRequest {
+                name: name.clone(),
+                granted: false,
+            })
+            .collect();
+        let requested = extra.clone();
+        let create_instance = move || {
+            let entry = unsafe { Entry::load() }
+                .map_err(|e| VulkanError::InitializationFailed(e.to_string()))?;
+            let available = entry
+                .enumerate_instance_extension_properties(None)
+                .map_err(|e| VulkanError::InitializationFailed(e.to_string()))?;
+            let names = available
+                .iter()
+                .map(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) });
+            let names: Vec<&CStr> = names.collect();
+            let extensions = extension_names(&requested, &names).map_err(|missing| {
+                VulkanError::InitializationFailed(format!(
+                    "instance extensions not available: {}",
+                    missing.join(", ")
+                ))
+            })?;
+            let extension_pointers: Vec<_> = extensions.iter().map(|name| name.as_ptr()).collect();
+
+            let api_version = match entry.try_enumerate_instance_version() {
+                Ok(Some(version)) => version.min(vk::API_VERSION_1_2),
+                _ => vk::API_VERSION_1_0,
+            };
+            let app_name = CString::new("miniquad").unwrap();
+            let app_info = vk::ApplicationInfo::builder()
+                .application_name(&app_name)
+                .engine_name(&app_name)
+                .api_version(api_version);
+            let instance_info = vk::InstanceCreateInfo::builder()
+                .application_info(&app_info)
+                .enabled_extension_names(&extension_pointers);
+            let instance = unsafe { entry.create_instance(&instance_info, None) }
+                .map_err(|e| VulkanError::InitializationFailed(e.to_string()))?;
+            Ok((entry, instance, api_version))
+        };
+        // An instance created after the timeout is destroyed right away
+        let destroy_instance = |(_entry, instance, _): (Entry, Instance, u32)| unsafe {
+            instance.destroy_instance(None)
+        };
+        let (entry, instance, api_version) = run_with_timeout(
+            "creating the Vulkan instance",
+            timeout,
+            create_instance,
+            destroy_instance,
+        )?;
+
+        self.surface_loader = Some(khr::Surface::new(&entry, &instance));
+        self.entry = Some(entry);
+        self.instance = Some(instance);
+        self.api_version = api_version;
+        for extension in &mut self.init_report.instance_extensions {
+            extension.granted = true;
+        }
+        self.enabled_extensions = EnabledExtensions {
+            instance: extra,
+            device: vec![],
+        };
+        self.init_report.instance_api_version = format!(
+            "{}.{}.{}",
+            vk::api_version_major(api_version),
+            vk::api_version_minor(api_version),
+            vk::api_version_patch(api_version)
+        );
+        if self.window_handles.is_some() {
+            self.create_surface()?;
+        }
+        self.select_physical_device()?;
+        self.init_report.device = self.device_info();
+        self.create_logical_device()?;
+        self.init_report.queues = self.queue_info();
+        self.select_depth_format()?;
+        self.create_allocator()?;
+        self.create_command_pool()?;
         Ok(())
     }
-    
-    pub fn get_surface_support(&self, _device: vk::PhysicalDevice, _queue_family_index: u32) -> bool {
-        // Simplified surface support check
-        true
-    }
-    
-    pub fn get_surface_capabilities(&self, _device: vk::PhysicalDevice) -> Result<vk::SurfaceCapabilitiesKHR, VulkanError> {
-        // Placeholder surface capabilities
-        let capabilities = vk::SurfaceCapabilitiesKHR {
-            min_image_count: 2,
-            max_image_count: 8,
-            current_extent: self.swapchain_extent,
-            min_image_extent: vk::Extent2D { width: 1, height: 1 },
-            max_image_extent: vk::Extent2D { width: 4096, height: 4096 },
-            max_image_array_layers: 1,
-            supported_transforms: vk::SurfaceTransformFlagsKHR::IDENTITY,
-            current_transform: vk::SurfaceTransformFlagsKHR::IDENTITY,
-            supported_composite_alpha: vk::CompositeAlphaFlagsKHR::OPAQUE,
-            supported_usage_flags: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
-        };
-        Ok(capabilities)
+
+    fn init_timeout(&self) -> Option<Duration> {
+        let seconds = match &self.display {
+            Some(conf) => conf.platform.init_timeout_secs,
+            None => crate::conf::Platform::default().init_timeout_secs,
+        };
+        seconds.map(|seconds| Duration::from_secs(seconds as u64))
     }
-    
-    pub fn get_surface_formats(&self, _device: vk::PhysicalDevice) -> Result<Vec<(vk::Format, vk::ColorSpaceKHR)>, VulkanError> {
-        // Placeholder surface formats
-        Ok(vec![(vk::Format::R8G8B8A8_SRGB, vk::ColorSpaceKHR::SRGB_NONLINEAR)])
+
+    /// Extensions enabled by the last `initialize`: the ones the backend
+    /// picked and those from `Platform::extra_instance_extensions` and
+    /// `Platform::extra_device_extensions`
+    pub fn enabled_extensions(&self) -> &EnabledExtensions {
+        &self.enabled_extensions
     }
-    
-    pub fn get_present_modes(&self, _device: vk::PhysicalDevice) -> Result<Vec<vk::PresentModeKHR>, VulkanError> {
-        // Placeholder present modes
-        Ok(vec![vk::PresentModeKHR::FIFO])
+
+    /// Extensions, devices, queues and fallbacks of the last `initialize`,
+    /// also when it failed. Attach it to bug reports.
+    pub fn init_report(&self) -> InitReport {
+        self.init_report.clone()
     }
-    
-    pub fn create_swapchain(&mut self, _surface_format: (vk::Format, vk::ColorSpaceKHR)) -> Result<(), VulkanError> {
-        println!("Creating Vulkan swapchain (placeholder implementation)");
-        // Placeholder - would create actual swapchain
+
+    /// Pick the highest scoring physical device that has a graphics queue,
+    /// preferring discrete GPUs over integrated ones.
+    fn select_physical_device(&mut self) -> Result<(), VulkanError> {
+        let instance = self.instance.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let enumerating = instance.clone();
+        let listing = move || {
+            unsafe { enumerating.enumerate_physical_devices() }
+                .map_err(|e| VulkanError::InitializationFailed(e.to_string()))
+        };
+        // Physical devices belong to the instance, there is nothing to free
+        let devices =
+            run_with_timeout("listing physical devices", self.init_timeout(), listing, drop)?;
+        let adapters: Vec<AdapterInfo> = devices
+            .iter()
+            .enumerate()
+            .map(|(index, &device)| adapter_info(instance, index, device))
+            .collect();
+
+        let selection = self
+            .display
+            .as_ref()
+            .map_or_else(AdapterSelection::default, |conf| conf.platform.adapter);
+        let chosen = match selection {
+            AdapterSelection::Best => None,
+            AdapterSelection::Index(index) => {
+                adapters.get(index).filter(|adapter| adapter.supports_graphics)
+            }
+            AdapterSelection::Predicate(accept) => adapters
+                .iter()
+                .find(|adapter| adapter.supports_graphics && accept(adapter)),
+        };
+        self.init_report.adapters = adapters.clone();
+        if chosen.is_none() && !matches!(selection, AdapterSelection::Best) {
+            self.init_report.fallback(format!(
+                "no usable adapter matches {:?}, picking the best one",
+                selection
+            ));
+        }
+
+        let mut best: Option<(u32, &AdapterInfo)> = None;
+        for adapter in adapters.iter().filter(|adapter| adapter.supports_graphics) {
+            let score = match adapter.adapter_type {
+                AdapterType::Discrete => 1000,
+                AdapterType::Integrated => 100,
+                AdapterType::Virtual => 50,
+                AdapterType::Cpu => 10,
+                AdapterType::Other => 1,
+            };
+            if best.is_none_or(|(best_score, _)| score > best_score) {
+                best = Some((score, adapter));
+            }
+        }
+
+        let adapter = chosen.or(best.map(|(_, adapter)| adapter)).ok_or_else(|| {
+            VulkanError::InitializationFailed("no physical device with a graphics queue".to_string())
+        })?;
+        let device = devices[adapter.index];
+        self.init_report.selected_adapter = Some(adapter.index);
+        let queue_families = unsafe { instance.get_physical_device_queue_family_properties(device) };
+        // Frames are presented from the graphics queue
+        let graphics_family = (0..queue_families.len() as u32)
+            .find(|&index| {
+                let flags = queue_families[index as usize].queue_flags;
+                flags.contains(vk::QueueFlags::GRAPHICS) && self.get_surface_support(device, index)
+            })
+            .ok_or_else(|| {
+                VulkanError::InitializationFailed(
+                    "no graphics queue of the device can present to the window".to_string(),
+                )
+            })?;
+        let transfer_family = queue_families.iter().position(|family| {
+            family.queue_flags.contains(vk::QueueFlags::TRANSFER)
+                && !family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+        });
+        // Prefer a compute-only family, any graphics family is guaranteed to do compute too
+        let compute_family = queue_families
+            .iter()
+            .position(|family| {
+                family.queue_flags.contains(vk::QueueFlags::COMPUTE)
+                    && !family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+            })
+            .unwrap_or(graphics_family as usize);
+
+        self.physical_device = Some(device);
+        self.queue_family_index = Some(graphics_family);
+        self.present_queue_family_index = Some(graphics_family);
+        self.transfer_queue_family_index = transfer_family.map(|index| index as u32);
+        self.compute_queue_family_index = Some(compute_family as u32);
+        self.queue_family_flags = queue_families.iter().map(|family| family.queue_flags).collect();
+        self.device_properties = unsafe { instance.get_physical_device_properties(device) };
         Ok(())
     }
-    
-    pub fn destroy_swapchain(&mut self) -> Result<(), VulkanError> {
-        // Placeholder swapchain destruction
-        println!("Destroying Vulkan swapchain (placeholder)");
+
+    /// Create the logical device, enabling the optional features requested
+    /// in `Conf` that the physical device supports.
+    fn create_logical_device(&mut self) -> Result<(), VulkanError> {
+        let instance = self.instance.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let physical_device = self.physical_device.ok_or(VulkanError::InvalidHandle)?;
+        let queue_family = self.queue_family_index.ok_or(VulkanError::InvalidHandle)?;
+
+        let supported = unsafe { instance.get_physical_device_features(physical_device) };
+        let mut requested = self
+            .display
+            .as_ref()
+            .map_or_else(RequestedFeatures::default, |conf| conf.platform.requested_features);
+        requested.buffer_device_address |= self
+            .display
+            .as_ref()
+            .is_some_and(|conf| conf.platform.allocator.buffer_device_address);
+        let mut features = vk::PhysicalDeviceFeatures::default();
+        let mut granted = RequestedFeatures {
+            strict: requested.strict,
+            ..Default::default()
+        };
+        let mut missing = vec![];
+        let candidates = [
+            (
+                "samplerAnisotropy",
+                requested.sampler_anisotropy,
+                supported.sampler_anisotropy,
+                &mut features.sampler_anisotropy,
+                &mut granted.sampler_anisotropy,
+            ),
+            (
+                "fillModeNonSolid",
+                requested.fill_mode_non_solid,
+                supported.fill_mode_non_solid,
+                &mut features.fill_mode_non_solid,
+                &mut granted.fill_mode_non_solid,
+            ),
+            (
+                "multiDrawIndirect",
+                requested.multi_draw_indirect,
+                supported.multi_draw_indirect,
+                &mut features.multi_draw_indirect,
+                &mut granted.multi_draw_indirect,
+            ),
+            (
+                "pipelineStatisticsQuery",
+                requested.pipeline_statistics_query,
+                supported.pipeline_statistics_query,
+                &mut features.pipeline_statistics_query,
+                &mut granted.pipeline_statistics_query,
+            ),
+            (
+                "occlusionQueryPrecise",
+                requested.occlusion_query_precise,
+                supported.occlusion_query_precise,
+                &mut features.occlusion_query_precise,
+                &mut granted.occlusion_query_precise,
+            ),
+            (
+                "sparseBinding",
+                requested.sparse_binding,
+                supported.sparse_binding,
+                &mut features.sparse_binding,
+                &mut granted.sparse_binding,
+            ),
+            (
+                "sparseResidencyImage2D",
+                requested.sparse_residency_image_2d,
+                supported.sparse_residency_image2_d,
+                &mut features.sparse_residency_image2_d,
+                &mut granted.sparse_residency_image_2d,
+            ),
+            (
+                "wideLines",
+                requested.wide_lines,
+                supported.wide_lines,
+                &mut features.wide_lines,
+                &mut granted.wide_lines,
+            ),
+            (
+                "geometryShader",
+                requested.geometry_shader,
+                supported.geometry_shader,
+                &mut features.geometry_shader,
+                &mut granted.geometry_shader,
+            ),
+            (
+                "tessellationShader",
+                requested.tessellation_shader,
+                supported.tessellation_shader,
+                &mut features.tessellation_shader,
+                &mut granted.tessellation_shader,
+            ),
+            (
+                "shaderStorageImageWriteWithoutFormat",
+                requested.shader_storage_image_write_without_format,
+                supported.shader_storage_image_write_without_format,
+                &mut features.shader_storage_image_write_without_format,
+                &mut granted.shader_storage_image_write_without_format,
+            ),
+        ];
+        for (name, wanted, available, enable, grant) in candidates {
+            if !wanted {
+                continue;
+            }
+            if available == vk::TRUE {
+                *enable = vk::TRUE;
+                *grant = true;
+            } else {
+                missing.push(name);
+            }
+        }
+
+        let available_extensions =
+            unsafe { instance.enumerate_device_extension_properties(physical_device) }
+                .map_err(|e| VulkanError::DeviceCreationFailed(e.to_string()))?;
+        let is_available = |name: &CStr| {
+            available_extensions
+                .iter()
+                .any(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) } == name)
+        };
+        let mut extensions = vec![];
+        // What was asked for and granted, for `init_report`
+        let mut considered: Vec<(&CStr, bool)> = vec![];
+        let swapchain_name = ash_037::extensions::khr::Swapchain::name();
+        considered.push((swapchain_name, is_available(swapchain_name)));
+        if is_available(swapchain_name) {
+            extensions.push(swapchain_name.as_ptr());
+        }
+        // Reports when presents reach the display, see `last_present_latency_ms`
+        let display_timing = is_available(swapchain_name)
+            && is_available(vk::GoogleDisplayTimingFn::name());
+        considered.push((vk::GoogleDisplayTimingFn::name(), display_timing));
+        if display_timing {
+            extensions.push(vk::GoogleDisplayTimingFn::name().as_ptr());
+        }
+        // Negative viewport heights need maintenance1, core in 1.1
+        let api_1_1 =
+            self.api_version.min(self.device_properties.api_version) >= vk::API_VERSION_1_1;
+        if self.flip_viewport_y && !api_1_1 {
+            let available = is_available(vk::KhrMaintenance1Fn::name());
+            considered.push((vk::KhrMaintenance1Fn::name(), available));
+            if available {
+                extensions.push(vk::KhrMaintenance1Fn::name().as_ptr());
+            } else {
+                self.init_report.fallback(
+                    "VK_KHR_maintenance1 is not supported, viewports are not flipped".to_string(),
+                );
+                self.flip_viewport_y = false;
+            }
+        }
+
+        // External memory needs 1.1 on both sides so the capability queries
+        // and dedicated allocations it depends on are core
+        #[cfg(unix)]
+        let external_memory = (
+            vk::KhrExternalMemoryFdFn::name(),
+            vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD,
+        );
+        #[cfg(windows)]
+        let external_memory = (
+            vk::KhrExternalMemoryWin32Fn::name(),
+            vk::ExternalMemoryHandleTypeFlags::OPAQUE_WIN32,
+        );
+        #[cfg(any(unix, windows))]
+        {
+            let (name, handle_type) = external_memory;
+            let api_version = self.api_version.min(self.device_properties.api_version);
+            self.external_memory_handle_type = None;
+            let granted = api_version >= vk::API_VERSION_1_1
+                && is_available(vk::KhrExternalMemoryFn::name())
+                && is_available(name);
+            considered.push((vk::KhrExternalMemoryFn::name(), granted));
+            considered.push((name, granted));
+            if granted {
+                extensions.push(vk::KhrExternalMemoryFn::name().as_ptr());
+                extensions.push(name.as_ptr());
+                self.external_memory_handle_type = Some(handle_type);
+            }
+        }
+
+        // Async compute needs its own family and timeline semaphores (core in 1.2)
+        let prefer_async_compute = self
+            .display
+            .as_ref()
+            .is_some_and(|conf| conf.prefer_async_compute);
+        let async_compute_family = self
+            .compute_queue_family_index
+            .filter(|&family| prefer_async_compute && family != queue_family);
+        let mut supported_12 = vk::PhysicalDeviceVulkan12Features::default();
+        let api_version = self.api_version.min(self.device_properties.api_version);
+        let api_12 = api_version >= vk::API_VERSION_1_2;
+        if api_12 {
+            let mut features2 = vk::PhysicalDeviceFeatures2::builder().push_next(&mut supported_12);
+            unsafe { instance.get_physical_device_features2(physical_device, &mut features2) };
+        }
+        let timeline_supported = api_12 && supported_12.timeline_semaphore == vk::TRUE;
+        let async_compute_family = async_compute_family.filter(|_| timeline_supported);
+        if prefer_async_compute && async_compute_family.is_none() {
+            self.init_report.fallback(
+                "no dedicated compute queue with timeline semaphores, compute runs on the \
+                 graphics queue"
+                    .to_string(),
+            );
+        }
+        let mut vulkan12_features = vk::PhysicalDeviceVulkan12Features {
+            timeline_semaphore: timeline_supported.into(),
+            ..Default::default()
+        };
+
+        // Descriptor indexing (VK_EXT_descriptor_indexing, core in 1.2) for
+        // `create_bindless_texture_set`
+        let bindless_supported = api_12
+            && [
+                supported_12.descriptor_indexing,
+                supported_12.runtime_descriptor_array,
+                supported_12.descriptor_binding_partially_bound,
+                supported_12.descriptor_binding_variable_descriptor_count,
+                supported_12.descriptor_binding_sampled_image_update_after_bind,
+                supported_12.shader_sampled_image_array_non_uniform_indexing,
+            ]
+            .iter()
+            .all(|&feature| feature == vk::TRUE);
+        if bindless_supported {
+            vulkan12_features.descriptor_indexing = vk::TRUE;
+            vulkan12_features.runtime_descriptor_array = vk::TRUE;
+            vulkan12_features.descriptor_binding_partially_bound = vk::TRUE;
+            vulkan12_features.descriptor_binding_variable_descriptor_count = vk::TRUE;
+            vulkan12_features.descriptor_binding_sampled_image_update_after_bind = vk::TRUE;
+            vulkan12_features.shader_sampled_image_array_non_uniform_indexing = vk::TRUE;
+        }
+
+        // GL style depth from -1 to 1, the features query needs 1.1
+        let depth_range = self
+            .display
+            .as_ref()
+            .map(|conf| conf.platform.depth_range)
+            .unwrap_or_default();
+        let depth_clip_control = depth_range == DepthRange::NegOneToOne
+            && self.api_version.min(self.device_properties.api_version) >= vk::API_VERSION_1_1
+            && is_available(vk::ExtDepthClipControlFn::name())
+            && {
+                let mut supported_clip_control =
+                    vk::PhysicalDeviceDepthClipControlFeaturesEXT::default();
+                let mut features2 =
+                    vk::PhysicalDeviceFeatures2::builder().push_next(&mut supported_clip_control);
+                unsafe { instance.get_physical_device_features2(physical_device, &mut features2) };
+                supported_clip_control.depth_clip_control == vk::TRUE
+            };
+        if depth_range == DepthRange::NegOneToOne {
+            considered.push((vk::ExtDepthClipControlFn::name(), depth_clip_control));
+        }
+        if depth_clip_control {
+            extensions.push(vk::ExtDepthClipControlFn::name().as_ptr());
+        } else if depth_range == DepthRange::NegOneToOne {
+            self.init_report.fallback(
+                "VK_EXT_depth_clip_control is not supported, depth stays 0 to 1; pass GL style \
+                 projections through projection::gl_to_vulkan"
+                    .to_string(),
+            );
+        }
+        let mut clip_control_features = vk::PhysicalDeviceDepthClipControlFeaturesEXT {
+            depth_clip_control: vk::TRUE,
+            ..Default::default()
+        };
+
+        // Multiview (VK_KHR_multiview) is core in 1.1, older devices render
+        // each view in a pass of its own
+        let multiview_supported =
+            self.api_version.min(self.device_properties.api_version) >= vk::API_VERSION_1_1 && {
+                let mut supported_multiview = vk::PhysicalDeviceMultiviewFeatures::default();
+                let mut features2 =
+                    vk::PhysicalDeviceFeatures2::builder().push_next(&mut supported_multiview);
+                unsafe { instance.get_physical_device_features2(physical_device, &mut features2) };
+                supported_multiview.multiview == vk::TRUE
+            };
+        let mut multiview_features = vk::PhysicalDeviceMultiviewFeatures {
+            multiview: vk::TRUE,
+            ..Default::default()
+        };
+
+        // Buffer device address is core in 1.2, 1.1 devices may have the extension
+        let mut address_features = vk::PhysicalDeviceBufferDeviceAddressFeatures::default();
+        let mut address_ext = false;
+        if requested.buffer_device_address {
+            if api_12 && supported_12.buffer_device_address == vk::TRUE {
+                vulkan12_features.buffer_device_address = vk::TRUE;
+                granted.buffer_device_address = true;
+            } else if api_version >= vk::API_VERSION_1_1
+                && is_available(vk::KhrBufferDeviceAddressFn::name())
+            {
+                let mut features2 =
+                    vk::PhysicalDeviceFeatures2::builder().push_next(&mut address_features);
+                unsafe { instance.get_physical_device_features2(physical_device, &mut features2) };
+                address_ext = address_features.buffer_device_address == vk::TRUE;
+                address_features = vk::PhysicalDeviceBufferDeviceAddressFeatures {
+                    buffer_device_address: vk::TRUE,
+                    ..Default::default()
+                };
+            }
+            if !granted.buffer_device_address {
+                considered.push((vk::KhrBufferDeviceAddressFn::name(), address_ext));
+            }
+            if address_ext {
+                extensions.push(vk::KhrBufferDeviceAddressFn::name().as_ptr());
+                granted.buffer_device_address = true;
+            } else if !granted.buffer_device_address {
+                missing.push("bufferDeviceAddress");
+            }
+        }
+        // Extensions asked for in `Conf` come on top of the ones picked above
+        let extra = self
+            .display
+            .as_ref()
+            .map_or_else(Vec::new, |conf| conf.platform.extra_device_extensions.clone());
+        let available_names: Vec<&CStr> = available_extensions
+            .iter()
+            .map(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) })
+            .collect();
+        let extra_names = extension_names(&extra, &available_names).map_err(|missing| {
+            VulkanError::DeviceCreationFailed(format!(
+                "device extensions not available: {}",
+                missing.join(", ")
+            ))
+        })?;
+        for name in &extra_names {
+            considered.push((name, true));
+            let enabled = extensions.iter().any(|&ptr| unsafe { CStr::from_ptr(ptr) } == &**name);
+            if !enabled {
+                extensions.push(name.as_ptr());
+            }
+        }
+        self.init_report.device_extensions = considered
+            .iter()
+            .map(|&(name, granted)| ExtensionRequest {
+                name: name.to_string_lossy().into_owned(),
+                granted,
+            })
+            .collect();
+        self.init_report.missing_features = missing.iter().map(|name| name.to_string()).collect();
+        if !missing.is_empty() {
+            let missing = missing.join(", ");
+            if requested.strict {
+                return Err(VulkanError::DeviceCreationFailed(format!(
+                    "requested features not supported: {}",
+                    missing
+                )));
+            }
+            self.init_report.fallback(format!(
+                "requested features not supported, continuing without: {}",
+                missing
+            ));
+        }
+
+        let priorities = [1.0];
+        let mut families = vec![queue_family];
+        families.extend(self.transfer_queue_family_index);
+        families.extend(async_compute_family);
+        families.dedup();
+        let queue_infos: Vec<vk::DeviceQueueCreateInfo> = families
+            .iter()
+            .map(|&family| {
+                vk::DeviceQueueCreateInfo::builder()
+                    .queue_family_index(family)
+                    .queue_priorities(&priorities)
+                    .build()
+            })
+            .collect();
+        let mut device_info = vk::DeviceCreateInfo::builder()
+            .queue_create_infos(&queue_infos)
+            .enabled_extension_names(&extensions)
+            .enabled_features(&features);
+        let core_address = vulkan12_features.buffer_device_address == vk::TRUE;
+        if timeline_supported || bindless_supported || core_address {
+            device_info = device_info.push_next(&mut vulkan12_features);
+        }
+        if address_ext {
+            device_info = device_info.push_next(&mut address_features);
+        }
+        if multiview_supported {
+            device_info = device_info.push_next(&mut multiview_features);
+        }
+        if depth_clip_control {
+            device_info = device_info.push_next(&mut clip_control_features);
+        }
+        let device = unsafe { instance.create_device(physical_device, &device_info, None) }
+            .map_err(|e| VulkanError::DeviceCreationFailed(e.to_string()))?;
+
+        let queue = unsafe { device.get_device_queue(queue_family, 0) };
+        self.graphics_queue = Some(queue);
+        self.present_queue = Some(queue);
+        self.transfer_queue = self
+            .transfer_queue_family_index
+            .map(|family| unsafe { device.get_device_queue(family, 0) });
+        self.compute_queue =
+            async_compute_family.map(|family| unsafe { device.get_device_queue(family, 0) });
+        self.timeline_semaphores = timeline_supported;
+        self.multiview = multiview_supported;
+        self.bindless = bindless_supported;
+        self.depth_clip_control = depth_clip_control;
+        self.device_address_ext = if address_ext {
+            Some(ash_037::extensions::khr::BufferDeviceAddress::new(instance, &device))
+        } else {
+            None
+        };
+        self.swapchain_loader = if is_available(swapchain_name) {
+            Some(khr::Swapchain::new(instance, &device))
+        } else {
+            None
+        };
+        self.display_timing = if display_timing {
+            let handle = device.handle();
+            Some(vk::GoogleDisplayTimingFn::load(|name| unsafe {
+                std::mem::transmute(instance.get_device_proc_addr(handle, name.as_ptr()))
+            }))
+        } else {
+            None
+        };
+        self.enabled_extensions.device = extensions
+            .iter()
+            .map(|&ptr| unsafe { CStr::from_ptr(ptr) }.to_string_lossy().into_owned())
+            .collect();
+        self.device_features = features;
+        self.granted_features = granted;
+        self.device = Some(device);
         Ok(())
     }
-    
-    pub fn create_swapchain_images(&mut self) -> Result<(), VulkanError> {
-        // Placeholder swapchain image creation
-        println!("Creating swapchain images (placeholder)");
+
+    /// Pick the format of the swapchain depth buffer, preferring ones with a
+    /// stencil aspect so pipelines can use `stencil_test`.
+    fn select_depth_format(&mut self) -> Result<(), VulkanError> {
+        let instance = self.instance.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let physical_device = self.physical_device.ok_or(VulkanError::InvalidHandle)?;
+        let candidates = [
+            vk::Format::D24_UNORM_S8_UINT,
+            vk::Format::D32_SFLOAT_S8_UINT,
+            vk::Format::D16_UNORM_S8_UINT,
+            vk::Format::D32_SFLOAT,
+        ];
+        let format = candidates.iter().copied().find(|&format| {
+            unsafe { instance.get_physical_device_format_properties(physical_device, format) }
+                .optimal_tiling_features
+                .contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+        });
+        self.depth_format = Some(format.ok_or_else(|| {
+            VulkanError::DeviceCreationFailed("no supported depth format".to_string())
+        })?);
         Ok(())
     }
-    
-    pub fn begin_frame(&mut self) -> Result<usize, VulkanError> {
-        println!("Beginning frame (placeholder)");
-        Ok(self.current_frame)
+
+    fn create_allocator(&mut self) -> Result<(), VulkanError> {
+        let instance = self.instance.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let physical_device = self.physical_device.ok_or(VulkanError::InvalidHandle)?;
+        let debug = self
+            .display
+            .as_ref()
+            .map_or_else(AllocatorConfig::default, |conf| conf.platform.allocator)
+            .debug_settings;
+
+        let allocator = Allocator::new(&AllocatorCreateDesc {
+            instance: instance.clone(),
+            device: device.clone(),
+            physical_device,
+            debug_settings: AllocatorDebugSettings {
+                log_memory_information: debug.log_memory_information,
+                log_leaks_on_shutdown: debug.log_leaks_on_shutdown,
+                store_stack_traces: debug.store_stack_traces,
+                log_allocations: debug.log_allocations,
+                log_frees: debug.log_frees,
+                log_stack_traces: debug.log_stack_traces,
+            },
+            buffer_device_address: self.granted_features.buffer_device_address,
+        })
+        .map_err(|e| VulkanError::InitializationFailed(e.to_string()))?;
+        *lock(&self.allocator) = Some(allocator);
+        Ok(())
     }
-    
-    pub fn end_frame(&mut self) -> Result<(), VulkanError> {
-        println!("Ending frame (placeholder)");
+
+    fn create_command_pool(&mut self) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let queue_family = self.queue_family_index.ok_or(VulkanError::InvalidHandle)?;
+
+        let pool_info = vk::CommandPoolCreateInfo::builder()
+            .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+            .queue_family_index(queue_family);
+        let pool = unsafe { device.create_command_pool(&pool_info, None) }
+            .map_err(|e| VulkanError::InitializationFailed(e.to_string()))?;
+        self.command_pool = Some(pool);
+
+        if let Some(transfer_family) = self.transfer_queue_family_index {
+            let pool_info = vk::CommandPoolCreateInfo::builder()
+                .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+                .queue_family_index(transfer_family);
+            let pool = unsafe { device.create_command_pool(&pool_info, None) }
+                .map_err(|e| VulkanError::InitializationFailed(e.to_string()))?;
+            self.transfer_command_pool = Some(pool);
+        }
+
+        if let (Some(_), Some(compute_family)) =
+            (self.compute_queue, self.compute_queue_family_index)
+        {
+            let pool_info = vk::CommandPoolCreateInfo::builder()
+                .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+                .queue_family_index(compute_family);
+            let pool = unsafe { device.create_command_pool(&pool_info, None) }
+                .map_err(|e| VulkanError::InitializationFailed(e.to_string()))?;
+            self.compute_command_pool = Some(pool);
+
+            let mut type_info = vk::SemaphoreTypeCreateInfo::builder()
+                .semaphore_type(vk::SemaphoreType::TIMELINE)
+                .initial_value(0);
+            let semaphore_info = vk::SemaphoreCreateInfo::builder().push_next(&mut type_info);
+            let timeline = unsafe { device.create_semaphore(&semaphore_info, None) }
+                .map_err(|e| VulkanError::InitializationFailed(e.to_string()))?;
+            self.compute_timeline = Some(timeline);
+            self.compute_timeline_value = 0;
+        }
         Ok(())
     }
-    
-    pub fn render_target_width(&self) -> u32 {
-        self.swapchain_extent.width
+
+    /// Log every allocation that has not been freed yet, at warn level
+    /// through the `log` crate. Meant to be called at shutdown once the
+    /// application released its resources; turn on `store_stack_traces` in
+    /// `Conf::platform.allocator` to see where each one was made.
+    pub fn report_leaks(&self) {
+        if let Some(allocator) = lock(&self.allocator).as_ref() {
+            allocator.report_memory_leaks(log_04::Level::Warn);
+        }
     }
-    
-    pub fn render_target_height(&self) -> u32 {
-        self.swapchain_extent.height
+
+    /// Optional features from `Conf::platform.requested_features` that the
+    /// device granted. `strict` mirrors the request.
+    pub fn enabled_features(&self) -> RequestedFeatures {
+        self.granted_features
     }
-    
-    // Placeholder implementations for various methods
-    pub fn create_buffer(&mut self, _size: vk::DeviceSize, _usage: vk::BufferUsageFlags, _location: MemoryLocation) -> Result<usize, VulkanError> {
-        let id = self.next_buffer_id;
-        self.next_buffer_id += 1;
-        println!("Creating buffer {} (placeholder)", id);
-        Ok(id)
+
+    /// Highest anisotropy a sampler can use, 1.0 when anisotropic filtering
+    /// was not enabled on the device.
+    pub fn max_anisotropy(&self) -> f32 {
+        if self.granted_features.sampler_anisotropy {
+            self.device_properties.limits.max_sampler_anisotropy
+        } else {
+            1.0
+        }
     }
-    
-    pub fn delete_buffer(&mut self, _id: usize) -> Result<(), VulkanError> {
-        println!("Deleting buffer {} (placeholder)", _id);
-        Ok(())
+
+    /// Create a sampler, clamping the requested anisotropy to what the device allows.
+    pub fn create_sampler(&mut self, desc: SamplerDesc) -> Result<usize, VulkanError> {
+        let max_anisotropy = desc.max_anisotropy.min(self.max_anisotropy()).max(1.0);
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+
+        let address_mode = vk_address_mode(desc.wrap);
+        let sampler_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(vk_filter(desc.mag_filter))
+            .min_filter(vk_filter(desc.min_filter))
+            .mipmap_mode(match desc.mipmap_filter {
+                FilterMode::Linear => vk::SamplerMipmapMode::LINEAR,
+                FilterMode::Nearest => vk::SamplerMipmapMode::NEAREST,
+            })
+            .address_mode_u(address_mode)
+            .address_mode_v(address_mode)
+            .address_mode_w(address_mode)
+            .anisotropy_enable(max_anisotropy > 1.0)
+            .max_anisotropy(max_anisotropy)
+            .min_lod(0.0)
+            .max_lod(vk::LOD_CLAMP_NONE)
+            .border_color(vk::BorderColor::FLOAT_TRANSPARENT_BLACK);
+        let sampler = unsafe { device.create_sampler(&sampler_info, None) }
+            .map_err(|e| VulkanError::TextureCreationFailed(e.to_string()))?;
+
+        self.samplers.push(sampler);
+        Ok(self.samplers.len() - 1)
     }
-    
-    pub fn update_texture(&mut self, _texture_id: usize, _width: u32, _height: u32, _data: &[u8]) -> Result<(), VulkanError> {
-        println!("Updating texture {} (placeholder)", _texture_id);
-        Ok(())
+
+    /// Sampler id used for textures bound without a sampler of their own,
+    /// created from `Conf::default_sampler` on first use.
+    pub fn default_sampler(&mut self) -> Result<usize, VulkanError> {
+        if let Some(sampler_id) = self.default_sampler {
+            return Ok(sampler_id);
+        }
+        let desc = self
+            .display
+            .as_ref()
+            .map_or_else(SamplerDesc::default, |conf| conf.platform.default_sampler);
+        let sampler_id = self.create_sampler(desc)?;
+        self.default_sampler = Some(sampler_id);
+        Ok(sampler_id)
     }
-    
-    pub fn create_texture(&mut self, _width: u32, _height: u32, _data: &[u8]) -> Result<usize, VulkanError> {
-        let id = self.next_texture_id;
-        self.next_texture_id += 1;
-        println!("Creating texture {}x{} (placeholder)", _width, _height);
-        Ok(id)
+
+    /// Replace the default sampler for textures bound from now on. Existing
+    /// descriptors, including the bindless set, keep the previous sampler
+    /// until they are written again.
+    pub fn set_default_sampler(&mut self, desc: SamplerDesc) -> Result<usize, VulkanError> {
+        let sampler_id = self.create_sampler(desc)?;
+        self.default_sampler = Some(sampler_id);
+        Ok(sampler_id)
     }
     
-    pub fn create_shader(&mut self, _vertex_shader: &str, _fragment_shader: &str, _meta: ShaderMeta) -> Result<usize, VulkanError> {
-        println!("Creating shader (placeholder)");
-        Ok(0)
+    pub fn get_physical_device(&self) -> Option<vk::PhysicalDevice> {
+        self.physical_device
     }
-    
-    pub fn create_compute_shader(&mut self, _compute_shader: &str, _meta: ShaderMeta) -> Result<usize, VulkanError> {
-        println!("Creating compute shader (placeholder)");
-        Ok(0)
+
+    /// Raw handles of the objects owned by this context, for sharing the
+    /// device with other Vulkan code. Handles that have not been created yet
+    /// are null.
+    ///
+    /// # Safety
+    ///
+    /// The handles stay owned by miniquad: you must not destroy these. They
+    /// are only valid until the context is cleaned up or reinitialized, and
+    /// submitting to the queue from another thread needs external
+    /// synchronization with miniquad's own submissions.
+    pub unsafe fn raw_handles(&self) -> VulkanRawHandles {
+        VulkanRawHandles {
+            instance: self.instance.as_ref().map(|instance| instance.handle()).unwrap_or_default(),
+            physical_device: self.physical_device.unwrap_or_default(),
+            device: self.device.as_ref().map(|device| device.handle()).unwrap_or_default(),
+            queue: self.graphics_queue.unwrap_or_default(),
+            queue_family_index: self.queue_family_index.unwrap_or_default(),
+            command_buffer: self
+                .command_buffers
+                .get(self.current_frame)
+                .copied()
+                .unwrap_or_default(),
+        }
     }
-    
-    pub fn compile_shader(&self, _source: &str, _kind: u32) -> Result<Vec<u32>, VulkanError> {
-        // Placeholder SPIR-V compilation
-        println!("Compiling shader (placeholder)");
-        Ok(vec![0x07230203u32, 0x00010000u32]) // Minimal SPIR-V header
+
+    /// The `vk::Image` behind a texture id. Must not be destroyed.
+    pub fn raw_image(&self, texture_id: usize) -> Option<vk::Image> {
+        self.textures.get(&texture_id).map(|texture| texture.image)
+    }
+
+    /// The `vk::Buffer` behind a buffer id. Must not be destroyed.
+    pub fn raw_buffer(&self, buffer_id: usize) -> Option<vk::Buffer> {
+        self.buffers.get(&buffer_id).map(|buffer| buffer.buffer)
+    }
+    
+    pub fn set_display(&mut self, conf: crate::conf::Conf) {
+        self.max_frames_in_flight = conf.platform.frames_in_flight.clamp(1, 3) as usize;
+        self.batch_submissions = conf.platform.batch_submissions;
+        self.flip_viewport_y = conf.platform.flip_viewport_y;
+        self.vsync = conf.platform.swap_interval != Some(0);
+        // 0 and 1 both mean no MSAA, like on OpenGL
+        let samples = conf.sample_count.max(1) as u32;
+        if self.set_msaa_samples(samples).is_err() {
+            log_04::warn!(
+                "Vulkan: {} MSAA samples are not supported, rendering without MSAA",
+                samples
+            );
+            self.msaa_samples = vk::SampleCountFlags::TYPE_1;
+        }
+        self.current_frame = 0;
+        self.display = Some(conf);
+        self.swapchain_extent = self.configured_extent();
+    }
+
+    /// Framebuffer size requested by `Conf`, in physical pixels when
+    /// `high_dpi` is set. Falls back to the current extent without a `Conf`.
+    fn configured_extent(&self) -> vk::Extent2D {
+        match &self.display {
+            Some(conf) => {
+                let scale = if conf.high_dpi { self.dpi_scale } else { 1.0 };
+                vk::Extent2D {
+                    width: (conf.window_width.max(1) as f32 * scale) as u32,
+                    height: (conf.window_height.max(1) as f32 * scale) as u32,
+                }
+            }
+            None => self.swapchain_extent,
+        }
+    }
+
+    /// The window's framebuffer is now `width` x `height` physical pixels,
+    /// e.g. from `EventHandler::resize_event`. Swapchains on surfaces that
+    /// don't report their own size take it instead of the `Conf` size. 0x0
+    /// means the window is minimized, see `begin_frame`.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.window_extent = Some(vk::Extent2D { width, height });
+    }
+
+    /// Size from the last `resize`, or else from `Conf`
+    fn window_extent(&self) -> vk::Extent2D {
+        self.window_extent.unwrap_or_else(|| self.configured_extent())
+    }
+
+    /// Number of frames that may be in flight at once.
+    pub fn frames_in_flight(&self) -> usize {
+        self.max_frames_in_flight
+    }
+
+    /// Create one command buffer, one pair of semaphores and one fence per
+    /// frame in flight, replacing any previously created ones.
+    pub fn create_sync_objects(&mut self) -> Result<(), VulkanError> {
+        self.destroy_sync_objects();
+
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let command_pool = self.command_pool.ok_or(VulkanError::InvalidHandle)?;
+        let queue_family = self.queue_family_index.ok_or(VulkanError::InvalidHandle)?;
+        let frames = self.max_frames_in_flight;
+        let reset = self
+            .display
+            .as_ref()
+            .map(|conf| conf.platform.command_buffer_reset)
+            .unwrap_or_default();
+
+        if reset == CommandBufferReset::Pool {
+            let pool_info = vk::CommandPoolCreateInfo::builder()
+                .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+                .queue_family_index(queue_family);
+            for _ in 0..frames {
+                // Pushed first so destroy_sync_objects cleans up after a failure
+                let pool = unsafe { device.create_command_pool(&pool_info, None) }
+                    .map_err(|e| VulkanError::CommandBufferCreationFailed(e.to_string()))?;
+                self.frame_command_pools.push(pool);
+                let cmd = allocate_command_buffer(device, pool)?;
+                self.command_buffers.push(cmd);
+            }
+        } else {
+            let alloc_info = vk::CommandBufferAllocateInfo::builder()
+                .command_pool(command_pool)
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(frames as u32);
+            self.command_buffers = unsafe { device.allocate_command_buffers(&alloc_info) }
+                .map_err(|e| VulkanError::CommandBufferCreationFailed(e.to_string()))?;
+        }
+
+        let semaphore_info = vk::SemaphoreCreateInfo::default();
+        // Fences start signaled so the first wait in begin_frame returns immediately
+        let fence_info = vk::FenceCreateInfo::builder().flags(vk::FenceCreateFlags::SIGNALED);
+        for _ in 0..frames {
+            unsafe {
+                self.image_available_semaphores.push(
+                    device
+                        .create_semaphore(&semaphore_info, None)
+                        .map_err(|e| VulkanError::SynchronizationFailed(e.to_string()))?,
+                );
+                self.render_finished_semaphores.push(
+                    device
+                        .create_semaphore(&semaphore_info, None)
+                        .map_err(|e| VulkanError::SynchronizationFailed(e.to_string()))?,
+                );
+                if !self.timeline_semaphores {
+                    self.in_flight_fences.push(
+                        device
+                            .create_fence(&fence_info, None)
+                            .map_err(|e| VulkanError::SynchronizationFailed(e.to_string()))?,
+                    );
+                }
+            }
+        }
+
+        if self.timeline_semaphores {
+            let mut type_info = vk::SemaphoreTypeCreateInfo::builder()
+                .semaphore_type(vk::SemaphoreType::TIMELINE)
+                .initial_value(0);
+            let timeline_info = vk::SemaphoreCreateInfo::builder().push_next(&mut type_info);
+            self.frame_timeline = Some(
+                unsafe { device.create_semaphore(&timeline_info, None) }
+                    .map_err(|e| VulkanError::SynchronizationFailed(e.to_string()))?,
+            );
+        }
+        if self.device_properties.limits.timestamp_compute_and_graphics == vk::TRUE {
+            let pool_info = vk::QueryPoolCreateInfo::builder()
+                .query_type(vk::QueryType::TIMESTAMP)
+                .query_count(2 * frames as u32);
+            self.timestamp_pool = Some(
+                unsafe { device.create_query_pool(&pool_info, None) }
+                    .map_err(|e| VulkanError::InitializationFailed(e.to_string()))?,
+            );
+        }
+        let pool_info = vk::QueryPoolCreateInfo::builder()
+            .query_type(vk::QueryType::OCCLUSION)
+            .query_count(OCCLUSION_QUERY_COUNT * frames as u32);
+        self.occlusion_pool = Some(
+            unsafe { device.create_query_pool(&pool_info, None) }
+                .map_err(|e| VulkanError::InitializationFailed(e.to_string()))?,
+        );
+        self.occlusion_written = vec![Vec::new(); frames];
+        self.active_occlusion_query = None;
+        self.occlusion_results.clear();
+        self.frame_timeline_value = 0;
+        self.frame_values = vec![0; frames];
+        self.current_frame = 0;
+        Ok(())
+    }
+
+    fn destroy_sync_objects(&mut self) {
+        let device = match self.device.as_ref() {
+            Some(device) => device,
+            None => return,
+        };
+        unsafe {
+            for semaphore in self
+                .image_available_semaphores
+                .drain(..)
+                .chain(self.render_finished_semaphores.drain(..))
+            {
+                device.destroy_semaphore(semaphore, None);
+            }
+            for fence in self.in_flight_fences.drain(..) {
+                device.destroy_fence(fence, None);
+            }
+            if let Some(timeline) = self.frame_timeline.take() {
+                device.destroy_semaphore(timeline, None);
+            }
+            if let Some(pool) = self.timestamp_pool.take() {
+                device.destroy_query_pool(pool, None);
+            }
+            if let Some(pool) = self.occlusion_pool.take() {
+                device.destroy_query_pool(pool, None);
+            }
+            if !self.frame_command_pools.is_empty() {
+                // Destroying the pools frees their command buffers
+                for pool in self.frame_command_pools.drain(..) {
+                    device.destroy_command_pool(pool, None);
+                }
+            } else if let Some(command_pool) = self.command_pool {
+                if !self.command_buffers.is_empty() {
+                    device.free_command_buffers(command_pool, &self.command_buffers);
+                }
+            }
+        }
+        self.command_buffers.clear();
+        self.images_in_flight.clear();
+        self.frame_values.clear();
+    }
+
+    /// Create the uniform descriptor set layout and one host visible uniform
+    /// ring buffer per frame in flight, with a descriptor set for
+    /// `apply_uniforms` and one for `set_global_uniforms`.
+    fn create_uniform_rings(&mut self) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let mut allocator = lock(&self.allocator);
+        let allocator = allocator.as_mut().ok_or(VulkanError::InvalidHandle)?;
+        let frames = self.max_frames_in_flight as u32;
+
+        let bindings = [vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .build()];
+        let layout_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+        let layout = unsafe { device.create_descriptor_set_layout(&layout_info, None) }
+            .map_err(|e| VulkanError::InitializationFailed(e.to_string()))?;
+        self.uniform_set_layout = Some(layout);
+
+        let pool_sizes = [vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+            descriptor_count: frames * 2,
+        }];
+        let pool_info = vk::DescriptorPoolCreateInfo::builder()
+            .max_sets(frames * 2)
+            .pool_sizes(&pool_sizes);
+        let pool = unsafe { device.create_descriptor_pool(&pool_info, None) }
+            .map_err(|e| VulkanError::InitializationFailed(e.to_string()))?;
+        self.uniform_descriptor_pool = Some(pool);
+
+        let layouts = vec![layout; frames as usize * 2];
+        let alloc_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(pool)
+            .set_layouts(&layouts);
+        let mut sets = unsafe { device.allocate_descriptor_sets(&alloc_info) }
+            .map_err(|e| VulkanError::InitializationFailed(e.to_string()))?;
+        let global_sets = sets.split_off(frames as usize);
+
+        let range = (self.device_properties.limits.max_uniform_buffer_range as vk::DeviceSize)
+            .min(UNIFORM_RING_SIZE);
+        self.uniform_range = range;
+        for (set, global_set) in sets.into_iter().zip(global_sets) {
+            let buffer_info = vk::BufferCreateInfo::builder()
+                .size(UNIFORM_RING_SIZE)
+                .usage(vk::BufferUsageFlags::UNIFORM_BUFFER)
+                .sharing_mode(vk::SharingMode::EXCLUSIVE);
+            let buffer = unsafe { device.create_buffer(&buffer_info, None) }
+                .map_err(|e| VulkanError::BufferCreationFailed(e.to_string()))?;
+            let requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+            let allocation = match allocator.allocate(&AllocationCreateDesc {
+                name: "uniform ring",
+                requirements,
+                location: MemoryLocation::CpuToGpu,
+                linear: true,
+                allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+            }) {
+                Ok(allocation) => allocation,
+                Err(e) => {
+                    unsafe { device.destroy_buffer(buffer, None) };
+                    return Err(e.into());
+                }
+            };
+            // Pushed first so destroy_device_objects cleans up after a failed bind
+            self.uniform_rings.push(UniformRing {
+                buffer,
+                allocation,
+                set,
+                global_set,
+                global_offset: None,
+                offset: 0,
+            });
+            let ring = &self.uniform_rings[self.uniform_rings.len() - 1];
+            unsafe {
+                device.bind_buffer_memory(buffer, ring.allocation.memory(), ring.allocation.offset())
+            }
+            .map_err(|e| VulkanError::BufferCreationFailed(e.to_string()))?;
+
+            let buffer_infos = [vk::DescriptorBufferInfo {
+                buffer,
+                offset: 0,
+                range,
+            }];
+            let writes = [set, global_set].map(|set| {
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(set)
+                    .dst_binding(0)
+                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+                    .buffer_info(&buffer_infos)
+                    .build()
+            });
+            unsafe { device.update_descriptor_sets(&writes, &[]) };
+        }
+        Ok(())
+    }
+    
+    /// Create the surface of the native window from
+    /// `NativeDisplay::raw_window_handles`
+    pub fn create_surface(&mut self) -> Result<(), VulkanError> {
+        let (display_handle, window_handle) = self.window_handles.ok_or_else(|| {
+            VulkanError::InitializationFailed("no native window to create a surface on".to_string())
+        })?;
+        let entry = self.entry.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let instance = self.instance.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let surface = unsafe {
+            ash_window_012::create_surface(entry, instance, display_handle, window_handle, None)
+        }
+        .map_err(|e| VulkanError::InitializationFailed(e.to_string()))?;
+        self.surface = Some(surface);
+        Ok(())
+    }
+
+    fn destroy_surface(&mut self) {
+        if let (Some(loader), Some(surface)) = (self.surface_loader.as_ref(), self.surface.take()) {
+            unsafe { loader.destroy_surface(surface, None) };
+        }
+    }
+
+    /// The surface and its loader, `InvalidHandle` without a surface
+    fn surface_and_loader(&self) -> Result<(vk::SurfaceKHR, &khr::Surface), VulkanError> {
+        match (self.surface, self.surface_loader.as_ref()) {
+            (Some(surface), Some(loader)) => Ok((surface, loader)),
+            _ => Err(VulkanError::InvalidHandle),
+        }
+    }
+
+    /// Whether queue family `queue_family_index` of `device` can present to
+    /// the surface. Without a surface any family will do.
+    pub fn get_surface_support(&self, device: vk::PhysicalDevice, queue_family_index: u32) -> bool {
+        match self.surface_and_loader() {
+            Ok((surface, loader)) => unsafe {
+                loader
+                    .get_physical_device_surface_support(device, queue_family_index, surface)
+                    .unwrap_or(false)
+            },
+            Err(_) => true,
+        }
+    }
+
+    pub fn get_surface_capabilities(
+        &self,
+        device: vk::PhysicalDevice,
+    ) -> Result<vk::SurfaceCapabilitiesKHR, VulkanError> {
+        let (surface, loader) = self.surface_and_loader()?;
+        unsafe { loader.get_physical_device_surface_capabilities(device, surface) }
+            .map_err(VulkanError::from)
+    }
+
+    pub fn get_surface_formats(
+        &self,
+        device: vk::PhysicalDevice,
+    ) -> Result<Vec<(vk::Format, vk::ColorSpaceKHR)>, VulkanError> {
+        let (surface, loader) = self.surface_and_loader()?;
+        let formats = unsafe { loader.get_physical_device_surface_formats(device, surface) }?;
+        Ok(formats
+            .iter()
+            .map(|format| (format.format, format.color_space))
+            .collect())
+    }
+
+    pub fn get_present_modes(
+        &self,
+        device: vk::PhysicalDevice,
+    ) -> Result<Vec<vk::PresentModeKHR>, VulkanError> {
+        let (surface, loader) = self.surface_and_loader()?;
+        unsafe { loader.get_physical_device_surface_present_modes(device, surface) }
+            .map_err(VulkanError::from)
+    }
+
+    /// Create the swapchain of the surface. Headless contexts have no
+    /// surface, and nothing to create.
+    pub fn create_swapchain(&mut self) -> Result<(), VulkanError> {
+        let surface = match self.surface {
+            Some(surface) => surface,
+            None => return Ok(()),
+        };
+        let physical_device = self.physical_device.ok_or(VulkanError::InvalidHandle)?;
+        let preferred = self
+            .display
+            .as_ref()
+            .map(|conf| conf.platform.preferred_color_space)
+            .unwrap_or_default();
+        let mode = self.color_space_mode();
+        let formats = self.get_surface_formats(physical_device)?;
+        let chosen = choose_surface_format(&formats, preferred, mode);
+        let (format, color_space) = chosen.ok_or_else(|| {
+            VulkanError::InitializationFailed("surface reports no formats".to_string())
+        })?;
+        self.swapchain_image_format = format;
+        self.swapchain_color_space = color_space;
+
+        let capabilities = self.get_surface_capabilities(physical_device)?;
+        self.swapchain_extent = if capabilities.current_extent.width != u32::MAX {
+            capabilities.current_extent
+        } else {
+            let configured = self.window_extent();
+            vk::Extent2D {
+                width: configured.width.clamp(
+                    capabilities.min_image_extent.width,
+                    capabilities.max_image_extent.width,
+                ),
+                height: configured.height.clamp(
+                    capabilities.min_image_extent.height,
+                    capabilities.max_image_extent.height,
+                ),
+            }
+        };
+
+        let composite_alpha = self
+            .display
+            .as_ref()
+            .map(|conf| conf.platform.composite_alpha)
+            .unwrap_or_default();
+        let supported = capabilities.supported_composite_alpha;
+        self.swapchain_composite_alpha = choose_composite_alpha(composite_alpha, supported);
+        if self.swapchain_composite_alpha != vk_composite_alpha(composite_alpha) {
+            log_04::warn!(
+                "Vulkan: {:?} composite alpha is not supported by the surface ({:?}), using {:?}",
+                composite_alpha, supported, self.swapchain_composite_alpha
+            );
+        }
+
+        let extra_usage = self
+            .display
+            .as_ref()
+            .map(|conf| conf.platform.swapchain_usage)
+            .unwrap_or_default();
+        let requested = vk_swapchain_usage(extra_usage);
+        // Storage writes also depend on the format, sRGB ones rarely allow them
+        let mut supported = capabilities.supported_usage_flags;
+        if let Some(instance) = self.instance.as_ref() {
+            let properties = unsafe {
+                instance.get_physical_device_format_properties(physical_device, format)
+            };
+            let features = properties.optimal_tiling_features;
+            if !features.contains(vk::FormatFeatureFlags::STORAGE_IMAGE) {
+                supported &= !vk::ImageUsageFlags::STORAGE;
+            }
+        }
+        self.swapchain_usage = choose_swapchain_usage(requested, supported);
+        if !self.swapchain_usage.contains(requested) {
+            log_04::warn!(
+                "Vulkan: swapchain usage {:?} is not supported with {:?}, using {:?}",
+                requested, format, self.swapchain_usage
+            );
+        }
+
+        let present_modes = self.get_present_modes(physical_device)?;
+        self.present_mode = choose_present_mode(self.vsync, &present_modes);
+        if self.vsync_enabled() != self.vsync {
+            log_04::warn!(
+                "Vulkan: no present mode with vsync {}, using {:?}",
+                if self.vsync { "on" } else { "off" },
+                self.present_mode
+            );
+        }
+
+        let requested = self
+            .display
+            .as_ref()
+            .and_then(|conf| conf.platform.swapchain_image_count);
+        self.swapchain_min_image_count = swapchain_image_count_for(requested, &capabilities);
+        log_04::info!(
+            "Vulkan swapchain: requesting {} images (surface allows {} to {})",
+            self.swapchain_min_image_count,
+            capabilities.min_image_count,
+            capabilities.max_image_count
+        );
+
+        let loader = self.swapchain_loader.as_ref().ok_or_else(|| {
+            VulkanError::InitializationFailed("VK_KHR_swapchain is not available".to_string())
+        })?;
+        // Presents go through the graphics queue, which owns the images
+        let swapchain_info = vk::SwapchainCreateInfoKHR::builder()
+            .surface(surface)
+            .min_image_count(self.swapchain_min_image_count)
+            .image_format(format)
+            .image_color_space(color_space)
+            .image_extent(self.swapchain_extent)
+            .image_array_layers(1)
+            .image_usage(self.swapchain_usage)
+            .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .pre_transform(capabilities.current_transform)
+            .composite_alpha(self.swapchain_composite_alpha)
+            .present_mode(self.present_mode)
+            .clipped(true);
+        let swapchain = unsafe { loader.create_swapchain(&swapchain_info, None) }
+            .map_err(|e| VulkanError::InitializationFailed(e.to_string()))?;
+        self.swapchain = Some(swapchain);
+        Ok(())
+    }
+
+    /// Turn waiting for vertical blanks on or off, recreating the swapchain
+    /// with `FIFO` for on and `IMMEDIATE`, or `MAILBOX` where tearing isn't
+    /// available, for off. Returns whether vsync is on afterwards, which
+    /// stays on when the surface reports no present mode without it.
+    pub fn set_vsync(&mut self, enabled: bool) -> Result<bool, VulkanError> {
+        if enabled == self.vsync && enabled == self.vsync_enabled() {
+            return Ok(enabled);
+        }
+        self.vsync = enabled;
+        if !self.surface_lost {
+            self.recreate_swapchain()?;
+        }
+        Ok(self.vsync_enabled())
+    }
+
+    /// Format of the swapchain images, for tools rendering or copying into
+    /// them directly
+    pub fn swapchain_format(&self) -> vk::Format {
+        self.swapchain_image_format
+    }
+
+    /// Usage the swapchain images were created with: `COLOR_ATTACHMENT`,
+    /// `TRANSFER_SRC` where supported and what `Platform::swapchain_usage`
+    /// asked for and got
+    pub fn swapchain_usage(&self) -> vk::ImageUsageFlags {
+        self.swapchain_usage
+    }
+
+    /// Whether presents wait for vertical blanks with the current present mode
+    pub fn vsync_enabled(&self) -> bool {
+        matches!(
+            self.present_mode,
+            vk::PresentModeKHR::FIFO | vk::PresentModeKHR::FIFO_RELAXED
+        )
+    }
+
+    /// Rebuild the swapchain and the views and framebuffers on its images,
+    /// e.g. after its present mode changed. Render passes are kept, the
+    /// surface format stays the same.
+    ///
+    /// No swapchain can be created for the 0x0 surface of a minimized window.
+    /// Then nothing is rebuilt and frames are skipped as in `begin_frame`,
+    /// which recreates the swapchain once the window is restored.
+    fn recreate_swapchain(&mut self) -> Result<(), VulkanError> {
+        if is_zero_area(self.surface_extent()?) {
+            self.frame_skipped = true;
+            return Ok(());
+        }
+        self.destroy_swapchain_views();
+        self.destroy_swapchain()?;
+        self.images_in_flight.clear();
+        self.pending_presents.clear();
+        self.create_swapchain()?;
+        self.create_swapchain_images()
+    }
+
+    /// Size of the window surface right now, 0x0 while it is minimized.
+    /// Without a surface it is the size from `resize` or `Conf`.
+    fn surface_extent(&self) -> Result<vk::Extent2D, VulkanError> {
+        let physical_device = match (self.surface, self.physical_device) {
+            (Some(_), Some(physical_device)) => physical_device,
+            _ => return Ok(self.window_extent()),
+        };
+        let capabilities = self.get_surface_capabilities(physical_device)?;
+        if capabilities.current_extent.width != u32::MAX {
+            Ok(capabilities.current_extent)
+        } else {
+            Ok(self.window_extent())
+        }
+    }
+
+    /// Wait for the GPU and destroy the framebuffers and image views of the
+    /// swapchain images, and their depth attachment
+    fn destroy_swapchain_views(&mut self) {
+        if let Some(device) = self.device.as_ref() {
+            unsafe {
+                // A lost device is reported by the next frame
+                let _ = device.device_wait_idle();
+                for framebuffer in self.framebuffers.drain(..) {
+                    device.destroy_framebuffer(framebuffer, None);
+                }
+                for view in self.swapchain_image_views.drain(..) {
+                    device.destroy_image_view(view, None);
+                }
+                if let Some((image, view, allocation)) = self.swapchain_depth.take() {
+                    device.destroy_image_view(view, None);
+                    device.destroy_image(image, None);
+                    if let Some(allocator) = lock(&self.allocator).as_mut() {
+                        let _ = allocator.free(allocation);
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Number of swapchain images. The driver may create more than was
+    /// requested through `Platform::swapchain_image_count`.
+    pub fn swapchain_image_count(&self) -> u32 {
+        if self.swapchain_images.is_empty() {
+            self.swapchain_min_image_count
+        } else {
+            self.swapchain_images.len() as u32
+        }
+    }
+
+    /// Color space of the swapchain images, use it to pick the tone mapping
+    /// applied before presenting.
+    pub fn swapchain_color_space(&self) -> vk::ColorSpaceKHR {
+        self.swapchain_color_space
+    }
+    
+    /// The window surface went away, e.g. when an Android app is sent to the
+    /// background. Destroys the swapchain and everything built on its images
+    /// while buffers, textures, pipelines and the device stay alive.
+    ///
+    /// Until `on_surface_recreated` `begin_frame`, `end_frame` and `present`
+    /// do nothing; skip drawing while `is_surface_lost` is set.
+    pub fn on_surface_lost(&mut self) {
+        if self.surface_lost {
+            return;
+        }
+        self.surface_lost = true;
+        self.destroy_swapchain_views();
+        if let Some(device) = self.device.as_ref() {
+            // The new surface may come with a different format
+            for (_, render_pass) in self.render_pass_cache.drain() {
+                unsafe { device.destroy_render_pass(render_pass, None) };
+            }
+        }
+        // Fails only on device loss, which the next frame reports as well
+        let _ = self.destroy_swapchain();
+        self.images_in_flight.clear();
+        self.pending_presents.clear();
+        self.destroy_surface();
+    }
+
+    /// Rebuild the surface and swapchain for the new native window after
+    /// `on_surface_lost`, e.g. when an Android app returns to the foreground.
+    pub fn on_surface_recreated(
+        &mut self,
+        display: &dyn crate::native::NativeDisplay,
+    ) -> Result<(), VulkanError> {
+        if !self.surface_lost {
+            return Ok(());
+        }
+        self.dpi_scale = display.dpi_scale();
+        self.swapchain_extent = self.configured_extent();
+        self.window_handles = display.raw_window_handles();
+        self.create_surface()?;
+        self.create_swapchain()?;
+        self.create_swapchain_images()?;
+        self.surface_lost = false;
+        Ok(())
+    }
+
+    /// Whether the window surface is gone, see `on_surface_lost`
+    pub fn is_surface_lost(&self) -> bool {
+        self.surface_lost
+    }
+
+    /// Whether the last `begin_frame` returned `FRAME_SKIPPED`
+    pub fn is_frame_skipped(&self) -> bool {
+        self.frame_skipped
+    }
+
+    /// Destroy the swapchain and with it its images. The views and
+    /// framebuffers on them have to be gone already.
+    pub fn destroy_swapchain(&mut self) -> Result<(), VulkanError> {
+        if let (Some(loader), Some(swapchain)) = (&self.swapchain_loader, self.swapchain.take()) {
+            unsafe { loader.destroy_swapchain(swapchain, None) };
+        }
+        self.swapchain_images.clear();
+        Ok(())
+    }
+
+    /// Create the views on the images of a new swapchain, the depth buffer
+    /// shared by them and a framebuffer for each
+    pub fn create_swapchain_images(&mut self) -> Result<(), VulkanError> {
+        let swapchain = match self.swapchain {
+            Some(swapchain) => swapchain,
+            None => return Ok(()),
+        };
+        let loader = self.swapchain_loader.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        self.swapchain_images = unsafe { loader.get_swapchain_images(swapchain) }?;
+        self.images_in_flight = vec![vk::Fence::null(); self.swapchain_images.len()];
+        let extent = self.swapchain_extent;
+
+        if let Some(depth_format) = self.depth_format {
+            let image_info = vk::ImageCreateInfo::builder()
+                .image_type(vk::ImageType::TYPE_2D)
+                .format(depth_format)
+                .extent(vk::Extent3D {
+                    width: extent.width,
+                    height: extent.height,
+                    depth: 1,
+                })
+                .mip_levels(1)
+                .array_layers(1)
+                .samples(vk::SampleCountFlags::TYPE_1)
+                .tiling(vk::ImageTiling::OPTIMAL)
+                .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
+                .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                .initial_layout(vk::ImageLayout::UNDEFINED)
+                .build();
+            let depth = self.create_image_with_view(&image_info, vk::ImageViewType::TYPE_2D)?;
+            self.swapchain_depth = Some(depth);
+        }
+
+        // Any render pass compatible with the swapchain ones will do
+        let render_pass = self.swapchain_render_pass(&LoadActions::default())?;
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        for &image in &self.swapchain_images {
+            let view_info = vk::ImageViewCreateInfo::builder()
+                .image(image)
+                .view_type(vk::ImageViewType::TYPE_2D)
+                .format(self.swapchain_image_format)
+                .subresource_range(vk::ImageSubresourceRange {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    base_mip_level: 0,
+                    level_count: 1,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                });
+            // Pushed right away so destroy_swapchain_views cleans up after a failure
+            let view = unsafe { device.create_image_view(&view_info, None) }
+                .map_err(|e| VulkanError::TextureCreationFailed(e.to_string()))?;
+            self.swapchain_image_views.push(view);
+
+            let mut attachments = vec![view];
+            attachments.extend(self.swapchain_depth.as_ref().map(|&(_, view, _)| view));
+            let framebuffer_info = vk::FramebufferCreateInfo::builder()
+                .render_pass(render_pass)
+                .attachments(&attachments)
+                .width(extent.width)
+                .height(extent.height)
+                .layers(1);
+            let framebuffer = unsafe { device.create_framebuffer(&framebuffer_info, None) }
+                .map_err(|e| VulkanError::InitializationFailed(e.to_string()))?;
+            self.framebuffers.push(framebuffer);
+        }
+        Ok(())
+    }
+
+    /// Render pass on the swapchain images with the load ops of `actions`,
+    /// created once per combination
+    fn swapchain_render_pass(
+        &mut self,
+        actions: &LoadActions,
+    ) -> Result<vk::RenderPass, VulkanError> {
+        let key = actions.vk_load_ops();
+        if let Some(&render_pass) = self.render_pass_cache.get(&key) {
+            return Ok(render_pass);
+        }
+        let render_pass = self.create_swapchain_render_pass(actions)?;
+        self.render_pass_cache.insert(key, render_pass);
+        Ok(render_pass)
+    }
+    
+    /// Start recording the next frame and return its frame slot.
+    ///
+    /// While the window is minimized its surface is 0x0 and nothing can be
+    /// presented: this returns `FRAME_SKIPPED` after a short sleep instead,
+    /// and `end_frame` and `present` do nothing until a frame is begun again.
+    /// Skip drawing for such frames. The swapchain is recreated once the
+    /// window is restored.
+    pub fn begin_frame(&mut self) -> Result<usize, VulkanError> {
+        if self.surface_lost {
+            return Ok(self.current_frame);
+        }
+        if is_zero_area(self.surface_extent()?) {
+            // Keep a minimized app from spinning through empty frames
+            std::thread::sleep(ZERO_EXTENT_POLL);
+            self.frame_skipped = true;
+            return Ok(FRAME_SKIPPED);
+        }
+        if std::mem::take(&mut self.frame_skipped) {
+            // Restored windows usually come back at a different size
+            self.recreate_swapchain()?;
+        }
+        // Wait for the previous submission from this frame slot before reusing it
+        let mut slot_submitted = false;
+        if let Some(&value) = self.frame_values.get(self.current_frame) {
+            self.wait_for_frame(value)?;
+            self.frame_values[self.current_frame] = 0;
+            slot_submitted = value != 0;
+        }
+        // Latency estimates without display timing count from here
+        self.acquire_time = Some(Instant::now());
+        // Bindings do not carry over into the re-recorded command buffer
+        self.bound_index_type = None;
+        self.bound_pipeline = None;
+        self.pass_attachments = None;
+        self.active_subpass = None;
+        if let Some(ring) = self.uniform_rings.get_mut(self.current_frame) {
+            ring.offset = 0;
+            ring.global_offset = None;
+        }
+        self.global_uniforms_layout = None;
+        self.release_retired_textures()?;
+        self.release_submitted_batches()?;
+        if !self.acquire_next_image()? {
+            self.frame_skipped = true;
+            return Ok(FRAME_SKIPPED);
+        }
+        self.swapchain_pass_begun = false;
+
+        let device = match self.device.as_ref() {
+            Some(device) => device,
+            None => return Ok(self.current_frame),
+        };
+        if let Some(&fence) = self.in_flight_fences.get(self.current_frame) {
+            unsafe { device.reset_fences(&[fence]) }.map_err(sync_error)?;
+        }
+        if let Some(&cmd) = self.command_buffers.get(self.current_frame) {
+            let begin_info = vk::CommandBufferBeginInfo::builder()
+                .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+            // The slot's last submission is complete, nothing in its pool is in use
+            let reset = match self.frame_command_pools.get(self.current_frame) {
+                Some(&pool) => unsafe {
+                    device.reset_command_pool(pool, vk::CommandPoolResetFlags::empty())
+                },
+                None => unsafe {
+                    device.reset_command_buffer(cmd, vk::CommandBufferResetFlags::empty())
+                },
+            };
+            reset
+                .and_then(|_| unsafe { device.begin_command_buffer(cmd, &begin_info) })
+                .map_err(|e| VulkanError::CommandBufferCreationFailed(e.to_string()))?;
+
+            if let Some(pool) = self.timestamp_pool {
+                let first = 2 * self.current_frame as u32;
+                // The slot's last submission is complete, so its timestamps are available
+                if slot_submitted {
+                    let mut timestamps = [0u64; 2];
+                    let fetched = unsafe {
+                        device.get_query_pool_results(
+                            pool,
+                            first,
+                            2,
+                            &mut timestamps,
+                            vk::QueryResultFlags::TYPE_64,
+                        )
+                    };
+                    if fetched.is_ok() {
+                        let ticks = timestamps[1].saturating_sub(timestamps[0]) as f64;
+                        let period = self.device_properties.limits.timestamp_period as f64;
+                        self.gpu_frame_times.push((ticks * period * 1e-9) as f32);
+                    }
+                }
+                unsafe {
+                    device.cmd_reset_query_pool(cmd, pool, first, 2);
+                    device.cmd_write_timestamp(cmd, vk::PipelineStageFlags::TOP_OF_PIPE, pool, first);
+                }
+            }
+
+            if let Some(pool) = self.occlusion_pool {
+                let first = OCCLUSION_QUERY_COUNT * self.current_frame as u32;
+                let written = self.occlusion_written.get_mut(self.current_frame);
+                for query_id in written.into_iter().flat_map(|written| written.drain(..)) {
+                    let mut samples = [0u64];
+                    let fetched = unsafe {
+                        device.get_query_pool_results(
+                            pool,
+                            first + query_id,
+                            1,
+                            &mut samples,
+                            vk::QueryResultFlags::TYPE_64,
+                        )
+                    };
+                    if fetched.is_ok() {
+                        self.occlusion_results.insert(query_id, samples[0]);
+                    }
+                }
+                unsafe { device.cmd_reset_query_pool(cmd, pool, first, OCCLUSION_QUERY_COUNT) };
+            }
+        }
+        self.active_occlusion_query = None;
+        Ok(self.current_frame)
+    }
+    
+    /// Acquire the swapchain image the frame renders to, recreating an out of
+    /// date swapchain on the way. `false` when the frame has to be skipped.
+    fn acquire_next_image(&mut self) -> Result<bool, VulkanError> {
+        // A swapchain recreated for an out of date one is not retried again
+        for _ in 0..2 {
+            let (loader, swapchain) = match (self.swapchain_loader.as_ref(), self.swapchain) {
+                (Some(loader), Some(swapchain)) => (loader, swapchain),
+                _ => return Ok(true),
+            };
+            let semaphore = self.image_available_semaphores[self.current_frame];
+            let acquired = unsafe {
+                loader.acquire_next_image(swapchain, u64::MAX, semaphore, vk::Fence::null())
+            };
+            match acquired {
+                Ok((index, _suboptimal)) => {
+                    self.current_image_index = index;
+                    return Ok(true);
+                }
+                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                    self.recreate_swapchain()?;
+                    if self.frame_skipped {
+                        return Ok(false);
+                    }
+                }
+                Err(e) => return Err(sync_error(e)),
+            }
+        }
+        Ok(false)
+    }
+
+    /// Finish recording the current frame and submit it to the graphics queue.
+    ///
+    /// The submission waits for the acquired swapchain image and any consumed
+    /// async compute work, and signals the frame timeline (or the frame's
+    /// fence when timeline semaphores are unavailable).
+    pub fn end_frame(&mut self) -> Result<(), VulkanError> {
+        if self.surface_lost || self.frame_skipped {
+            return Ok(());
+        }
+        if self.swapchain.is_some() && !self.swapchain_pass_begun {
+            // The acquired image only reaches the present layout through a pass
+            self.begin_render_pass(LoadActions::default())?;
+            self.end_render_pass()?;
+        }
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let queue = self.graphics_queue.ok_or(VulkanError::InvalidHandle)?;
+        let frame = self.current_frame;
+        let cmd = match self.command_buffers.get(frame) {
+            Some(&cmd) => cmd,
+            None => return Ok(()),
+        };
+        if let Some(pool) = self.timestamp_pool {
+            let stage = vk::PipelineStageFlags::BOTTOM_OF_PIPE;
+            unsafe { device.cmd_write_timestamp(cmd, stage, pool, 2 * frame as u32 + 1) };
+        }
+        unsafe { device.end_command_buffer(cmd) }
+            .map_err(|e| VulkanError::CommandBufferCreationFailed(e.to_string()))?;
+
+        // Binary semaphores take a dummy 0 in the timeline value arrays
+        let mut wait_semaphores = vec![];
+        let mut wait_stages = vec![];
+        let mut wait_values = vec![];
+        let mut signal_semaphores = vec![];
+        let mut signal_values = vec![];
+        if self.swapchain.is_some() {
+            wait_semaphores.push(self.image_available_semaphores[frame]);
+            wait_stages.push(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT);
+            wait_values.push(0);
+            signal_semaphores.push(self.render_finished_semaphores[frame]);
+            signal_values.push(0);
+        }
+        if let (Some(timeline), true) = (self.compute_timeline, self.compute_wait_value > 0) {
+            wait_semaphores.push(timeline);
+            wait_stages
+                .push(vk::PipelineStageFlags::VERTEX_INPUT | vk::PipelineStageFlags::COMPUTE_SHADER);
+            wait_values.push(self.compute_wait_value);
+        }
+        let value = self.frame_timeline_value + 1;
+        if let Some(timeline) = self.frame_timeline {
+            signal_semaphores.push(timeline);
+            signal_values.push(value);
+        }
+
+        // Batched updates run ahead of the frame's commands in the same submission
+        let mut command_buffers = vec![];
+        if let Some(batch) = &self.batch {
+            unsafe { device.end_command_buffer(batch.cmd) }
+                .map_err(|e| VulkanError::CommandBufferCreationFailed(e.to_string()))?;
+            command_buffers.push(batch.cmd);
+        }
+        command_buffers.push(cmd);
+        let mut timeline_info = vk::TimelineSemaphoreSubmitInfo::builder()
+            .wait_semaphore_values(&wait_values)
+            .signal_semaphore_values(&signal_values);
+        let mut submit = vk::SubmitInfo::builder()
+            .wait_semaphores(&wait_semaphores)
+            .wait_dst_stage_mask(&wait_stages)
+            .command_buffers(&command_buffers)
+            .signal_semaphores(&signal_semaphores);
+        if self.timeline_semaphores {
+            submit = submit.push_next(&mut timeline_info);
+        }
+        let fence = self
+            .in_flight_fences
+            .get(frame)
+            .copied()
+            .unwrap_or_else(vk::Fence::null);
+        let _queue = lock(&self.queue_lock);
+        unsafe { device.queue_submit(queue, &[submit.build()], fence) }.map_err(sync_error)?;
+        self.submit_count += 1;
+        if let Some(batch) = self.batch.take() {
+            self.batches_in_flight.push((value, batch));
+        }
+
+        self.frame_timeline_value = value;
+        if let Some(slot) = self.frame_values.get_mut(frame) {
+            *slot = value;
+        }
+        self.compute_wait_value = 0;
+        Ok(())
+    }
+
+    /// Block until the frame that signalled timeline `value` has finished on
+    /// the GPU. Frames are numbered from 1 in submission order, 0 is always
+    /// complete.
+    pub fn wait_for_frame(&self, value: u64) -> Result<(), VulkanError> {
+        if value == 0 {
+            return Ok(());
+        }
+        if value > self.frame_timeline_value {
+            return Err(VulkanError::SynchronizationFailed(format!(
+                "frame {} has not been submitted yet",
+                value
+            )));
+        }
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+
+        if let Some(timeline) = self.frame_timeline {
+            let semaphores = [timeline];
+            let values = [value];
+            let wait_info = vk::SemaphoreWaitInfo::builder()
+                .semaphores(&semaphores)
+                .values(&values);
+            return unsafe { device.wait_semaphores(&wait_info, u64::MAX) }.map_err(sync_error);
+        }
+
+        // Binary fallback: the frame is either still owned by its slot's
+        // fence, or the slot has been reused, which means it was waited on
+        match self.frame_values.iter().position(|&slot_value| slot_value == value) {
+            Some(slot) => {
+                let fence = self.in_flight_fences[slot];
+                unsafe { device.wait_for_fences(&[fence], true, u64::MAX) }.map_err(sync_error)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Timeline value of the most recent frame the GPU has finished.
+    pub fn current_timeline_value(&self) -> Result<u64, VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        if let Some(timeline) = self.frame_timeline {
+            return unsafe { device.get_semaphore_counter_value(timeline) }.map_err(sync_error);
+        }
+
+        let mut completed = self.frame_timeline_value;
+        for (slot, &value) in self.frame_values.iter().enumerate() {
+            if value == 0 {
+                continue;
+            }
+            let done = unsafe { device.get_fence_status(self.in_flight_fences[slot]) }
+                .map_err(sync_error)?;
+            if !done {
+                completed = completed.min(value - 1);
+            }
+        }
+        Ok(completed)
+    }
+    
+    /// Width of the default framebuffer in physical pixels.
+    pub fn render_target_width(&self) -> u32 {
+        self.swapchain_extent.width
+    }
+    
+    /// Height of the default framebuffer in physical pixels.
+    pub fn render_target_height(&self) -> u32 {
+        self.swapchain_extent.height
+    }
+    
+    // Placeholder implementations for various methods
+    /// Create a buffer of `size` bytes.
+    ///
+    /// `usage_hint` picks where the buffer lives:
+    /// - `Usage::Immutable` buffers are placed in `GpuOnly` memory. They are not
+    ///   host visible, so every `update_buffer` on them goes through a staging
+    ///   buffer and a `vkCmdCopyBuffer`.
+    /// - `Usage::Stream` and `Usage::Dynamic` buffers are placed in `CpuToGpu`
+    ///   memory and updated by writing directly into the mapped allocation.
+    ///
+    /// Buffers with `SHADER_DEVICE_ADDRESS` usage need
+    /// `RequestedFeatures::buffer_device_address`, see `buffer_device_address`.
+    pub fn create_buffer(
+        &mut self,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        usage_hint: Usage,
+    ) -> Result<usize, VulkanError> {
+        if usage.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS)
+            && !self.granted_features.buffer_device_address
+        {
+            return Err(VulkanError::BufferCreationFailed(
+                "SHADER_DEVICE_ADDRESS usage needs the bufferDeviceAddress feature".to_string(),
+            ));
+        }
+        self.check_allocation_count();
+        let (usage, location) = match usage_hint {
+            Usage::Immutable => (usage | vk::BufferUsageFlags::TRANSFER_DST, MemoryLocation::GpuOnly),
+            Usage::Stream | Usage::Dynamic => (usage, MemoryLocation::CpuToGpu),
+        };
+
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let mut allocator = lock(&self.allocator);
+        let allocator = allocator.as_mut().ok_or(VulkanError::InvalidHandle)?;
+
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(size)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+        let buffer = unsafe { device.create_buffer(&buffer_info, None) }
+            .map_err(|e| VulkanError::BufferCreationFailed(e.to_string()))?;
+        let requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+        let allocation = allocator.allocate(&AllocationCreateDesc {
+            name: "buffer",
+            requirements,
+            location,
+            linear: true,
+            allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+        });
+        let allocation = match allocation {
+            Ok(allocation) => allocation,
+            Err(e) => {
+                unsafe { device.destroy_buffer(buffer, None) };
+                return Err(e.into());
+            }
+        };
+        let bound = unsafe {
+            device.bind_buffer_memory(buffer, allocation.memory(), allocation.offset())
+        };
+        if let Err(e) = bound {
+            unsafe { device.destroy_buffer(buffer, None) };
+            let _ = allocator.free(allocation);
+            return Err(VulkanError::BufferCreationFailed(e.to_string()));
+        }
+
+        // Same default as `Buffer::stream`, immutable buffers know their element size
+        let index_type = if usage.contains(vk::BufferUsageFlags::INDEX_BUFFER) {
+            Some(IndexType::Short)
+        } else {
+            None
+        };
+
+        let id = self.next_buffer_id;
+        self.next_buffer_id += 1;
+        self.buffers.insert(
+            id,
+            VulkanBuffer {
+                buffer,
+                allocation,
+                size,
+                usage,
+                location,
+                index_type,
+            },
+        );
+        Ok(id)
+    }
+
+    /// Create a device local buffer filled with `data`, the counterpart of
+    /// `Buffer::immutable`. Index buffers take their index type from `T`,
+    /// `BufferType::Staging` buffers are host visible instead.
+    pub fn create_immutable_buffer<T>(
+        &mut self,
+        buffer_type: BufferType,
+        data: &[T],
+    ) -> Result<usize, VulkanError> {
+        let (usage, index_type) = match buffer_type {
+            BufferType::VertexBuffer => (vk::BufferUsageFlags::VERTEX_BUFFER, None),
+            BufferType::IndexBuffer => (
+                vk::BufferUsageFlags::INDEX_BUFFER,
+                Some(IndexType::for_type::<T>()),
+            ),
+            BufferType::UniformBuffer => (vk::BufferUsageFlags::UNIFORM_BUFFER, None),
+            BufferType::StorageBuffer => (vk::BufferUsageFlags::STORAGE_BUFFER, None),
+            BufferType::Staging => (vk::BufferUsageFlags::TRANSFER_SRC, None),
+        };
+        let size = std::mem::size_of_val(data);
+        let bytes = unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, size) };
+
+        // Staging buffers stay host visible whatever their contents
+        let usage_hint = if buffer_type == BufferType::Staging {
+            Usage::Stream
+        } else {
+            Usage::Immutable
+        };
+        let id = self.create_buffer(size as vk::DeviceSize, usage, usage_hint)?;
+        if let Some(buffer) = self.buffers.get_mut(&id) {
+            buffer.index_type = index_type;
+        }
+        if let Err(e) = self.update_buffer(id, bytes) {
+            let _ = self.delete_buffer(id);
+            return Err(e);
+        }
+        Ok(id)
+    }
+
+    /// GPU address of a buffer created with `SHADER_DEVICE_ADDRESS` usage,
+    /// for shaders to dereference through `GL_EXT_buffer_reference`. Stays
+    /// valid until the buffer is deleted.
+    pub fn buffer_device_address(&self, buffer_id: usize) -> Result<u64, VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let buffer = self.buffers.get(&buffer_id).ok_or(VulkanError::InvalidHandle)?;
+        if !buffer.usage.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS) {
+            return Err(VulkanError::BufferCreationFailed(format!(
+                "buffer {} was not created with SHADER_DEVICE_ADDRESS usage",
+                buffer_id
+            )));
+        }
+        let info = vk::BufferDeviceAddressInfo::builder().buffer(buffer.buffer);
+        let address = match self.device_address_ext.as_ref() {
+            Some(ext) => unsafe { ext.get_buffer_device_address(&info) },
+            None => unsafe { device.get_buffer_device_address(&info) },
+        };
+        Ok(address)
+    }
+
+    /// Set the viewport in framebuffer pixels, origin at the top left.
+    /// `begin_render_pass` resets it to the whole attachment.
+    pub fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+        let viewport = viewport(x as f32, y as f32, w as f32, h as f32, self.flip_viewport_y);
+        unsafe { device.cmd_set_viewport(cmd, 0, &[viewport]) };
+        Ok(())
+    }
+
+    /// Restrict the following draws to a rectangle in framebuffer pixels,
+    /// origin at the top left. The rectangle is clamped to the current pass,
+    /// `begin_render_pass` resets it to the whole attachment.
+    pub fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+        // Vulkan rejects negative offsets, so clip against the attachment here
+        let extent = self.pass_extent;
+        let x0 = x.clamp(0, extent.width as i32);
+        let y0 = y.clamp(0, extent.height as i32);
+        let x1 = x.saturating_add(w).clamp(x0, extent.width as i32);
+        let y1 = y.saturating_add(h).clamp(y0, extent.height as i32);
+        let scissor = vk::Rect2D {
+            offset: vk::Offset2D { x: x0, y: y0 },
+            extent: vk::Extent2D {
+                width: (x1 - x0) as u32,
+                height: (y1 - y0) as u32,
+            },
+        };
+        unsafe { device.cmd_set_scissor(cmd, 0, &[scissor]) };
+        Ok(())
+    }
+
+    /// Scissor to a clip rectangle given in logical points, like the
+    /// `clip_rect` of an egui mesh. Scaled by the DPI factor and rounded
+    /// outwards so partially covered pixels are still drawn.
+    pub fn apply_clip_rect(&mut self, min: [f32; 2], max: [f32; 2]) -> Result<(), VulkanError> {
+        let scale = self.dpi_scale;
+        let x0 = (min[0] * scale).floor() as i32;
+        let y0 = (min[1] * scale).floor() as i32;
+        let x1 = (max[0] * scale).ceil() as i32;
+        let y1 = (max[1] * scale).ceil() as i32;
+        self.apply_scissor_rect(x0, y0, x1 - x0, y1 - y0)
+    }
+
+    /// Change the stencil reference value for the following draws. Fails when
+    /// the bound pipeline wasn't created with
+    /// `DynamicStateFlags::stencil_reference`.
+    pub fn set_stencil_reference(&mut self, reference: u32) -> Result<(), VulkanError> {
+        self.check_dynamic_state(|flags| flags.stencil_reference, "stencil reference")?;
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+        let faces = vk::StencilFaceFlags::FRONT_AND_BACK;
+        unsafe { device.cmd_set_stencil_reference(cmd, faces, reference) };
+        Ok(())
+    }
+
+    /// Width in pixels of the lines rasterized by the following draws,
+    /// clamped to the device's `lineWidthRange`. Anything but 1.0 needs
+    /// `RequestedFeatures::wide_lines`; without it the width falls back to
+    /// 1.0 with a warning. `begin_render_pass` resets it to 1.0. Fails when
+    /// the bound pipeline wasn't created with `DynamicStateFlags::line_width`.
+    pub fn set_line_width(&mut self, width: f32) -> Result<(), VulkanError> {
+        self.check_dynamic_state(|flags| flags.line_width, "line width")?;
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+        let width = if !self.granted_features.wide_lines {
+            if width != 1.0 {
+                log_04::warn!("Line width {} needs the wideLines device feature, using 1.0", width);
+            }
+            1.0
+        } else {
+            let [min, max] = self.device_properties.limits.line_width_range;
+            width.max(min).min(max)
+        };
+        unsafe { device.cmd_set_line_width(cmd, width) };
+        Ok(())
+    }
+
+    /// Whether the bound graphics pipeline leaves the state `enabled` picks
+    /// dynamic. Without a bound pipeline the state is only recorded for the
+    /// next one, which is always fine.
+    fn check_dynamic_state(
+        &self,
+        enabled: fn(&DynamicStateFlags) -> bool,
+        name: &str,
+    ) -> Result<(), VulkanError> {
+        let recipe = self
+            .bound_pipeline
+            .and_then(|id| self.pipelines.get(id))
+            .and_then(|pipeline| pipeline.recipe.as_ref());
+        match recipe {
+            Some(recipe) if !enabled(&recipe.params.dynamic_states) => {
+                Err(VulkanError::ShaderCompilation(format!(
+                    "bound pipeline was created without dynamic {}, see PipelineParams::dynamic_states",
+                    name
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Start counting the samples passing the depth and stencil tests of the
+    /// following draws into occlusion query `query_id`, below
+    /// `OCCLUSION_QUERY_COUNT`. Each id can be used once per frame and queries
+    /// can't be nested. Begin and end have to be in the same render pass.
+    pub fn begin_occlusion_query(&mut self, query_id: u32) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let pool = self.occlusion_pool.ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+        let written = self
+            .occlusion_written
+            .get_mut(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+        if query_id >= OCCLUSION_QUERY_COUNT {
+            return Err(VulkanError::InvalidHandle);
+        }
+        if let Some(active) = self.active_occlusion_query {
+            return Err(VulkanError::CommandBufferCreationFailed(format!(
+                "occlusion query {} is still active",
+                active
+            )));
+        }
+        if written.contains(&query_id) {
+            return Err(VulkanError::CommandBufferCreationFailed(format!(
+                "occlusion query {} was already used this frame",
+                query_id
+            )));
+        }
+
+        // Without occlusionQueryPrecise the count only tells zero from non zero
+        let flags = if self.granted_features.occlusion_query_precise {
+            vk::QueryControlFlags::PRECISE
+        } else {
+            vk::QueryControlFlags::empty()
+        };
+        let query = OCCLUSION_QUERY_COUNT * self.current_frame as u32 + query_id;
+        unsafe { device.cmd_begin_query(cmd, pool, query, flags) };
+        written.push(query_id);
+        self.active_occlusion_query = Some(query_id);
+        Ok(())
+    }
+
+    /// Stop the occlusion query started by `begin_occlusion_query`
+    pub fn end_occlusion_query(&mut self) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let pool = self.occlusion_pool.ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+        let query_id = self.active_occlusion_query.take().ok_or_else(|| {
+            VulkanError::CommandBufferCreationFailed("no active occlusion query".to_string())
+        })?;
+        let query = OCCLUSION_QUERY_COUNT * self.current_frame as u32 + query_id;
+        unsafe { device.cmd_end_query(cmd, pool, query) };
+        Ok(())
+    }
+
+    /// Samples that passed in the latest completed frame using `query_id`.
+    ///
+    /// Results are collected when a frame slot is reused, so they lag
+    /// `frames_in_flight` frames behind and never stall the CPU. `None` until
+    /// the query completed once. Conditional rendering is not supported,
+    /// skip occluded draws based on this value instead.
+    pub fn occlusion_result(&self, query_id: u32) -> Option<u64> {
+        self.occlusion_results.get(&query_id).copied()
+    }
+
+    /// Bind graphics pipeline `pipeline_id` for the following draws
+    pub fn apply_pipeline(&mut self, pipeline_id: usize) -> Result<(), VulkanError> {
+        let mut pipeline = self
+            .pipelines
+            .get(pipeline_id)
+            .ok_or(VulkanError::InvalidHandle)?
+            .pipeline;
+        if self.debug_wireframe {
+            if let Some(wireframe) = self.wireframe_pipeline(pipeline_id)? {
+                pipeline = wireframe;
+            }
+        }
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+        unsafe { device.cmd_bind_pipeline(cmd, vk::PipelineBindPoint::GRAPHICS, pipeline) };
+        self.bound_pipeline = Some(pipeline_id);
+        self.bind_global_uniforms()
+    }
+
+    /// Draw everything as wireframe from the next `apply_pipeline` on.
+    ///
+    /// The `PolygonMode::LINE` variant of a pipeline is created the first
+    /// time it is applied with the overlay on and kept until the pipeline is
+    /// destroyed. Needs `RequestedFeatures::fill_mode_non_solid`; without it
+    /// this only logs and leaves rendering untouched.
+    pub fn set_debug_wireframe(&mut self, enabled: bool) {
+        if enabled && !self.granted_features.fill_mode_non_solid {
+            log_04::warn!("Wireframe overlay needs the fillModeNonSolid device feature, ignoring");
+            return;
+        }
+        self.debug_wireframe = enabled;
+    }
+
+    pub fn debug_wireframe(&self) -> bool {
+        self.debug_wireframe
+    }
+
+    /// Cached line variant of graphics pipeline `pipeline_id`, built on first
+    /// use. `None` for pipelines without a `recipe` to rebuild them from.
+    fn wireframe_pipeline(
+        &mut self,
+        pipeline_id: usize,
+    ) -> Result<Option<vk::Pipeline>, VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let clip_control = self.depth_clip_control;
+        let pipeline = self.pipelines.get_mut(pipeline_id).ok_or(VulkanError::InvalidHandle)?;
+        if pipeline.wireframe.is_some() {
+            return Ok(pipeline.wireframe);
+        }
+        let recipe = match &pipeline.recipe {
+            Some(recipe) => recipe,
+            None => return Ok(None),
+        };
+        let line = vk::PolygonMode::LINE;
+        let wireframe =
+            create_graphics_pipeline(device, pipeline.layout, recipe, line, clip_control)?;
+        pipeline.wireframe = Some(wireframe);
+        Ok(Some(wireframe))
+    }
+
+    /// Upload `uniforms` into this frame's uniform ring and bind it to set 0
+    /// of the current pipeline with a dynamic offset. Unlike push constants
+    /// this takes blocks of up to `uniform_range` bytes.
+    pub fn apply_uniforms<U>(&mut self, uniforms: &U) -> Result<(), VulkanError> {
+        let size = std::mem::size_of::<U>();
+        let bytes = unsafe { std::slice::from_raw_parts(uniforms as *const U as *const u8, size) };
+        self.apply_uniforms_from_bytes(bytes)
+    }
+
+    /// `apply_uniforms` for blocks described by `uniforms`, laid out back
+    /// to back like for the OpenGL backend. The data is padded to std140
+    /// first, see `pack_std140`, so the same struct works on both backends.
+    pub fn apply_uniforms_std140(
+        &mut self,
+        uniforms: &[UniformDesc],
+        data: &[u8],
+    ) -> Result<(), VulkanError> {
+        let block = pack_std140(uniforms, data)?;
+        self.apply_uniforms_from_bytes(&block)
+    }
+
+    /// Same as `apply_uniforms` with the block already laid out as bytes
+    pub fn apply_uniforms_from_bytes(&mut self, data: &[u8]) -> Result<(), VulkanError> {
+        let layout = self
+            .bound_pipeline
+            .and_then(|id| self.pipelines.get(id))
+            .ok_or(VulkanError::InvalidHandle)?
+            .layout;
+        let offset = self.write_uniforms(data)?;
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+        let set = self.uniform_rings[self.current_frame].set;
+        unsafe {
+            device.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                layout,
+                0,
+                &[set],
+                &[offset],
+            )
+        };
+        Ok(())
+    }
+
+    /// Upload `uniforms` shared by every pipeline for the rest of the frame,
+    /// e.g. the camera. They are written once and bound as
+    /// `GLOBAL_UNIFORM_SET` by each `apply_pipeline` of a pipeline with
+    /// `global_uniforms`, leaving set 0 to per material `apply_uniforms`.
+    /// Calling it again mid frame switches the following draws to the new
+    /// block.
+    pub fn set_global_uniforms<U>(&mut self, uniforms: &U) -> Result<(), VulkanError> {
+        let size = std::mem::size_of::<U>();
+        let bytes = unsafe { std::slice::from_raw_parts(uniforms as *const U as *const u8, size) };
+        self.set_global_uniforms_from_bytes(bytes)
+    }
+
+    /// Same as `set_global_uniforms` with the block already laid out as bytes
+    pub fn set_global_uniforms_from_bytes(&mut self, data: &[u8]) -> Result<(), VulkanError> {
+        let offset = self.write_uniforms(data)?;
+        self.uniform_rings[self.current_frame].global_offset = Some(offset);
+        self.global_uniforms_layout = None;
+        if self.bound_pipeline.is_some() {
+            self.bind_global_uniforms()?;
+        }
+        Ok(())
+    }
+
+    /// Bind this frame's global uniforms to the current pipeline, unless it
+    /// has no `global_uniforms` or they are bound with its layout already
+    fn bind_global_uniforms(&mut self) -> Result<(), VulkanError> {
+        let pipeline = self
+            .bound_pipeline
+            .and_then(|id| self.pipelines.get(id))
+            .ok_or(VulkanError::InvalidHandle)?;
+        let ring = match self.uniform_rings.get(self.current_frame) {
+            Some(ring) => ring,
+            None => return Ok(()),
+        };
+        let offset = match ring.global_offset {
+            Some(offset) if pipeline.global_uniforms => offset,
+            _ => return Ok(()),
+        };
+        if self.global_uniforms_layout == Some(pipeline.layout) {
+            return Ok(());
+        }
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+        unsafe {
+            device.cmd_bind_descriptor_sets(
+                cmd,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.layout,
+                GLOBAL_UNIFORM_SET,
+                &[ring.global_set],
+                &[offset],
+            )
+        };
+        self.global_uniforms_layout = Some(pipeline.layout);
+        Ok(())
+    }
+
+    /// Copy `data` into this frame's uniform ring, returning its dynamic
+    /// offset
+    fn write_uniforms(&mut self, data: &[u8]) -> Result<u32, VulkanError> {
+        let ring = self
+            .uniform_rings
+            .get_mut(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+
+        let size = data.len() as vk::DeviceSize;
+        if size > self.uniform_range {
+            return Err(VulkanError::MappingFailed(format!(
+                "uniform block of {} bytes exceeds the {} byte limit",
+                size, self.uniform_range
+            )));
+        }
+        let alignment = self
+            .device_properties
+            .limits
+            .min_uniform_buffer_offset_alignment
+            .max(1);
+        let offset = ring.offset.div_ceil(alignment) * alignment;
+        // The descriptor always covers `uniform_range` bytes from the dynamic offset
+        if offset + self.uniform_range > UNIFORM_RING_SIZE {
+            return Err(VulkanError::MappingFailed(
+                "uniform ring buffer is full for this frame".to_string(),
+            ));
+        }
+        let mapped = ring
+            .allocation
+            .mapped_slice_mut()
+            .ok_or_else(|| VulkanError::MappingFailed("uniform ring is not mapped".to_string()))?;
+        mapped[offset as usize..(offset + size) as usize].copy_from_slice(data);
+        ring.offset = offset + size;
+        Ok(offset as u32)
+    }
+
+    /// Bind `buffer_ids` as vertex buffers 0, 1, ... for the following draws.
+    /// Fails when they are not vertex buffers or their count differs from the
+    /// buffer layouts of the applied pipeline.
+    pub fn apply_vertex_buffers(&mut self, buffer_ids: &[usize]) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let recipe = self
+            .bound_pipeline
+            .and_then(|id| self.pipelines.get(id))
+            .and_then(|pipeline| pipeline.recipe.as_ref());
+        if let Some(recipe) = recipe {
+            if buffer_ids.len() != recipe.bindings.len() {
+                return Err(VulkanError::BufferCreationFailed(format!(
+                    "{} vertex buffers bound to a pipeline with {} buffer layouts",
+                    buffer_ids.len(),
+                    recipe.bindings.len()
+                )));
+            }
+        }
+        let mut buffers = Vec::with_capacity(buffer_ids.len());
+        for id in buffer_ids {
+            let buffer = self.buffers.get(id).ok_or(VulkanError::InvalidHandle)?;
+            if !buffer.usage.contains(vk::BufferUsageFlags::VERTEX_BUFFER) {
+                return Err(VulkanError::BufferCreationFailed(format!(
+                    "buffer {} is not a vertex buffer",
+                    id
+                )));
+            }
+            buffers.push(buffer.buffer);
+        }
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+
+        let offsets = vec![0; buffers.len()];
+        unsafe { device.cmd_bind_vertex_buffers(cmd, 0, &buffers, &offsets) };
+        Ok(())
+    }
+
+    /// Bind `buffer_id` as the index buffer for the following `draw` calls,
+    /// using the index type the buffer was created with.
+    pub fn apply_index_buffer(&mut self, buffer_id: usize) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let buffer = self.buffers.get(&buffer_id).ok_or(VulkanError::InvalidHandle)?;
+        let index_type = buffer.index_type.ok_or_else(|| {
+            VulkanError::BufferCreationFailed(format!("buffer {} is not an index buffer", buffer_id))
+        })?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+
+        unsafe { device.cmd_bind_index_buffer(cmd, buffer.buffer, 0, vk_index_type(index_type)?) };
+        self.bound_index_type = Some(index_type);
+        Ok(())
+    }
+
+    /// Bind `texture_ids` as the samplers of the current pipeline, sampled
+    /// with the `default_sampler`: texture `N` at binding `N` of
+    /// `TEXTURE_SET`. There has to be one per `ShaderMeta::texture_slots` of
+    /// its shader. The descriptor set for a combination of textures is
+    /// written once and reused by later calls.
+    pub fn apply_textures(&mut self, texture_ids: &[usize]) -> Result<(), VulkanError> {
+        let pipeline = self
+            .bound_pipeline
+            .and_then(|id| self.pipelines.get(id))
+            .ok_or(VulkanError::InvalidHandle)?;
+        let layout = pipeline.layout;
+        let (set_layout, count) = pipeline.texture_set_layout.unwrap_or_default();
+        if texture_ids.len() != count as usize {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "{} textures bound to a pipeline with {} texture slots",
+                texture_ids.len(),
+                count
+            )));
+        }
+        if count == 0 {
+            return Ok(());
+        }
+        if let Some(&id) = texture_ids.iter().find(|id| !self.textures.contains_key(id)) {
+            return Err(VulkanError::TextureCreationFailed(format!("unknown texture {}", id)));
+        }
+
+        let key = (set_layout, texture_ids.to_vec());
+        let set = match self.texture_sets.get(&key) {
+            Some(&set) => set,
+            None => {
+                let set = self.allocate_descriptor_set(set_layout)?;
+                for (binding, &texture_id) in texture_ids.iter().enumerate() {
+                    self.write_texture_descriptor(set, binding as u32, texture_id, None)?;
+                }
+                self.texture_sets.insert(key, set);
+                set
+            }
+        };
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+        let bind_point = vk::PipelineBindPoint::GRAPHICS;
+        unsafe {
+            device.cmd_bind_descriptor_sets(cmd, bind_point, layout, TEXTURE_SET, &[set], &[]);
+        }
+        Ok(())
+    }
+
+    /// Draw `num_elements` indices of the bound index buffer starting at
+    /// `base_element`, `num_instances` times.
+    pub fn draw(
+        &mut self,
+        base_element: u32,
+        num_elements: u32,
+        num_instances: u32,
+    ) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        if self.bound_index_type.is_none() {
+            return Err(VulkanError::InvalidHandle);
+        }
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+
+        unsafe { device.cmd_draw_indexed(cmd, num_elements, num_instances, base_element, 0, 0) };
+        Ok(())
+    }
+
+    pub fn delete_buffer(&mut self, id: usize) -> Result<(), VulkanError> {
+        let buffer = self.buffers.remove(&id).ok_or(VulkanError::InvalidHandle)?;
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let mut allocator = lock(&self.allocator);
+        let allocator = allocator.as_mut().ok_or(VulkanError::InvalidHandle)?;
+        unsafe { device.destroy_buffer(buffer.buffer, None) };
+        allocator.free(buffer.allocation)?;
+        Ok(())
+    }
+    
+    /// Replace the whole contents of `texture_id`, which has to be `width` x
+    /// `height`, e.g. with the next frame of a video. Goes through
+    /// `update_texture_part`, which copies from a staging buffer between
+    /// layout transitions. Fails with `InvalidHandle` for unknown textures
+    /// and with `MappingFailed` when the size or the amount of data differs.
+    pub fn update_texture(
+        &mut self,
+        texture_id: usize,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> Result<(), VulkanError> {
+        let texture = self.textures.get(&texture_id).ok_or(VulkanError::InvalidHandle)?;
+        if (texture.width, texture.height) != (width, height) {
+            return Err(VulkanError::MappingFailed(format!(
+                "texture {} is {}x{}, not {}x{}",
+                texture_id, texture.width, texture.height, width, height
+            )));
+        }
+        let expected = vk_texel_block(texture.format).map(|(block_width, block_height, bytes)| {
+            let blocks = width.div_ceil(block_width) * height.div_ceil(block_height);
+            (blocks * bytes) as usize
+        });
+        if let Some(expected) = expected.filter(|&expected| expected != data.len()) {
+            return Err(VulkanError::MappingFailed(format!(
+                "expected {} bytes for texture {}, got {}",
+                expected,
+                texture_id,
+                data.len()
+            )));
+        }
+        self.update_texture_part(texture_id, 0, 0, width, height, data)
+    }
+
+    /// Overwrite the `width` x `height` region at `x`, `y` of the first layer
+    /// of `texture_id`, keeping the rest of its contents. Draws recorded
+    /// earlier in the current frame will also see the new texels.
+    ///
+    /// Only the region goes through the staging buffer and the copy, which
+    /// makes this the way to patch glyph atlases and other partly changing
+    /// textures. Layout transitions cover the whole level, Vulkan has no
+    /// per-rectangle layouts, but keep the texels outside of the region.
+    pub fn update_texture_part(
+        &mut self,
+        texture_id: usize,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> Result<(), VulkanError> {
+        let texture = self.textures.get(&texture_id).ok_or(VulkanError::InvalidHandle)?;
+        let inside = |offset: u32, size: u32, limit: u32| {
+            offset.checked_add(size).is_some_and(|end| end <= limit)
+        };
+        if !inside(x, width, texture.width) || !inside(y, height, texture.height) {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "region {}x{} at ({}, {}) is outside of texture {}",
+                width, height, x, y, texture_id
+            )));
+        }
+        let (block_width, block_height, block_bytes) = vk_texel_block(texture.format)
+            .ok_or_else(|| {
+                VulkanError::TextureCreationFailed(format!(
+                    "cannot upload texels of format {:?}",
+                    texture.format
+                ))
+            })?;
+        // Compressed regions have to cover whole blocks, except at the right and bottom edge
+        let aligned = |offset: u32, size: u32, block: u32, limit: u32| {
+            offset.is_multiple_of(block) && (size.is_multiple_of(block) || offset + size == limit)
+        };
+        if !aligned(x, width, block_width, texture.width)
+            || !aligned(y, height, block_height, texture.height)
+        {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "region {}x{} at ({}, {}) is not aligned to the {}x{} blocks of texture {}",
+                width, height, x, y, block_width, block_height, texture_id
+            )));
+        }
+        let blocks = width.div_ceil(block_width) * height.div_ceil(block_height);
+        let expected = (blocks * block_bytes) as usize;
+        if data.len() != expected {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "expected {} bytes for a {}x{} region, got {}",
+                expected,
+                width,
+                height,
+                data.len()
+            )));
+        }
+
+        let image = texture.image;
+        let layout = texture.current_layout;
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let region = vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: vk::Offset3D {
+                x: x as i32,
+                y: y as i32,
+                z: 0,
+            },
+            image_extent: vk::Extent3D { width, height, depth: 1 },
+        };
+
+        let (staging, staging_allocation) = self.create_staging_buffer(data)?;
+        // Coming from the current layout keeps the texels outside of the region
+        // and orders the copy after every earlier submission using the texture.
+        // The texture goes back to that layout, or to SHADER_READ_ONLY if it
+        // had no contents yet.
+        let restored = match layout {
+            vk::ImageLayout::UNDEFINED => vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            layout => layout,
+        };
+        let result = self.batched_commands(|device, cmd| unsafe {
+            let transfer_dst = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
+            transition_image_layout(device, cmd, image, range, layout, transfer_dst);
+            device.cmd_copy_buffer_to_image(cmd, staging, image, transfer_dst, &[region]);
+            transition_image_layout(device, cmd, image, range, transfer_dst, restored);
+        });
+        self.release_staging_buffer(staging, staging_allocation);
+        if result.is_ok() {
+            if let Some(texture) = self.textures.get_mut(&texture_id) {
+                texture.current_layout = restored;
+            }
+        }
+        result
+    }
+
+    /// Delete `texture_id` once the GPU is done with every frame submitted so
+    /// far, including the one being recorded.
+    pub fn delete_texture(&mut self, texture_id: usize) -> Result<(), VulkanError> {
+        let texture = self.textures.remove(&texture_id).ok_or(VulkanError::InvalidHandle)?;
+        self.retired_textures.push((self.frame_timeline_value + 1, texture));
+        self.texture_sets.retain(|(_, textures), _| !textures.contains(&texture_id));
+        Ok(())
+    }
+
+    /// Map a UI texture onto a new handle for a texture created by the application
+    pub fn register_user_texture(
+        &mut self,
+        texture_id: usize,
+    ) -> Result<OverlayTextureId, VulkanError> {
+        if !self.textures.contains_key(&texture_id) {
+            return Err(VulkanError::InvalidHandle);
+        }
+        let id = OverlayTextureId::User(self.next_user_texture);
+        self.next_user_texture += 1;
+        self.overlay_textures.insert(id, texture_id);
+        Ok(id)
+    }
+
+    /// Apply a texture change of the UI, e.g. egui's `TexturesDelta::set`.
+    ///
+    /// With `pos` only the given region of the existing texture is patched.
+    /// A full image replaces the texture in place when the size is unchanged
+    /// and otherwise uploads a new one, so a font atlas growing mid-frame
+    /// does not affect draws already recorded with the old one.
+    pub fn set_overlay_texture(
+        &mut self,
+        id: OverlayTextureId,
+        pos: Option<[u32; 2]>,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> Result<usize, VulkanError> {
+        if let OverlayTextureId::User(_) = id {
+            // Owned by the application, which updates it through its texture id
+            return Err(VulkanError::InvalidHandle);
+        }
+        let current = self.overlay_textures.get(&id).copied();
+
+        if let Some([x, y]) = pos {
+            let texture_id = current.ok_or(VulkanError::InvalidHandle)?;
+            self.update_texture_part(texture_id, x, y, width, height, rgba)?;
+            return Ok(texture_id);
+        }
+        if let Some(texture_id) = current {
+            let texture = &self.textures[&texture_id];
+            if (texture.width, texture.height) == (width, height) {
+                self.update_texture(texture_id, width, height, rgba)?;
+                return Ok(texture_id);
+            }
+        }
+
+        let texture_id = self.create_texture(width, height, rgba)?;
+        if let Some(old) = self.overlay_textures.insert(id, texture_id) {
+            self.delete_texture(old)?;
+        }
+        Ok(texture_id)
+    }
+
+    /// Vulkan texture currently backing a UI texture
+    pub fn overlay_texture(&self, id: OverlayTextureId) -> Option<usize> {
+        self.overlay_textures.get(&id).copied()
+    }
+
+    /// Forget a UI texture, e.g. from egui's `TexturesDelta::free`. Managed
+    /// textures are deleted, user textures stay alive for the application.
+    pub fn free_overlay_texture(&mut self, id: OverlayTextureId) -> Result<(), VulkanError> {
+        match (id, self.overlay_textures.remove(&id)) {
+            (OverlayTextureId::Managed(_), Some(texture_id)) => self.delete_texture(texture_id),
+            (_, Some(_)) => Ok(()),
+            (_, None) => Err(VulkanError::InvalidHandle),
+        }
+    }
+    
+    /// Create a sampled RGBA8 texture from `data`
+    pub fn create_texture(
+        &mut self,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> Result<usize, VulkanError> {
+        let format = TextureFormat::RGBA8;
+        let size = format.size(width, height) as usize;
+        if data.len() != size {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "expected {} bytes for {}x{}, got {}",
+                size,
+                width,
+                height,
+                data.len()
+            )));
+        }
+
+        let image_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(self.texture_format(format))
+            .extent(vk::Extent3D { width, height, depth: 1 })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .build();
+
+        self.create_layered_texture(&image_info, vk::ImageViewType::TYPE_2D, format, size, data)
+    }
+
+    /// Create a texture compute shaders write as a storage image and
+    /// graphics shaders sample afterwards, without copying through a buffer.
+    /// The format is always the UNORM variant, storage images can't be
+    /// sRGB. Its contents start out undefined.
+    ///
+    /// Bind it with `write_storage_image_descriptor` and bracket the
+    /// dispatches writing it with `graphics_to_compute_image_barrier` and
+    /// `compute_to_graphics_image_barrier`.
+    pub fn create_storage_texture(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Result<usize, VulkanError> {
+        let vk_format = vk_unorm_format(vk_texture_format(format));
+        let instance = self.instance.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let physical_device = self.physical_device.ok_or(VulkanError::InvalidHandle)?;
+        let properties =
+            unsafe { instance.get_physical_device_format_properties(physical_device, vk_format) };
+        let needed = vk::FormatFeatureFlags::STORAGE_IMAGE | vk::FormatFeatureFlags::SAMPLED_IMAGE;
+        if !properties.optimal_tiling_features.contains(needed) {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "texture format {:?} can't be used as a storage image on this device",
+                format
+            )));
+        }
+
+        let image_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(vk_format)
+            .extent(vk::Extent3D { width, height, depth: 1 })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(
+                vk::ImageUsageFlags::STORAGE
+                    | vk::ImageUsageFlags::SAMPLED
+                    | vk::ImageUsageFlags::TRANSFER_SRC,
+            )
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .build();
+        let (image, view, allocation) =
+            self.create_image_with_view(&image_info, vk::ImageViewType::TYPE_2D)?;
+        Ok(self.register_texture(image, view, allocation, &image_info, vk::ImageLayout::UNDEFINED))
+    }
+    
+    /// Create a 2D texture array with `layers` layers.
+    ///
+    /// `data` holds the layers tightly packed one after another, each layer being
+    /// `format.size(width, height)` bytes.
+    pub fn create_texture_array(
+        &mut self,
+        width: u32,
+        height: u32,
+        layers: u32,
+        format: TextureFormat,
+        data: &[u8],
+    ) -> Result<usize, VulkanError> {
+        if layers == 0 {
+            return Err(VulkanError::TextureCreationFailed(
+                "texture array must have at least one layer".to_string(),
+            ));
+        }
+        let layer_size = format.size(width, height) as usize;
+        if data.len() != layer_size * layers as usize {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "expected {} bytes for {} layers of {}x{}, got {}",
+                layer_size * layers as usize,
+                layers,
+                width,
+                height,
+                data.len()
+            )));
+        }
+
+        let image_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(self.texture_format(format))
+            .extent(vk::Extent3D { width, height, depth: 1 })
+            .mip_levels(1)
+            .array_layers(layers)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .build();
+
+        self.create_layered_texture(
+            &image_info,
+            vk::ImageViewType::TYPE_2D_ARRAY,
+            format,
+            layer_size,
+            data,
+        )
+    }
+
+    /// Create a cubemap from six RGBA8 faces of `size`x`size` pixels.
+    ///
+    /// Faces are expected in the Vulkan layer order: +X, -X, +Y, -Y, +Z, -Z.
+    pub fn create_cubemap(&mut self, size: u32, faces: [&[u8]; 6]) -> Result<usize, VulkanError> {
+        let format = TextureFormat::RGBA8;
+        let face_size = format.size(size, size) as usize;
+
+        let mut data = Vec::with_capacity(face_size * 6);
+        for (i, face) in faces.iter().enumerate() {
+            if face.len() != face_size {
+                return Err(VulkanError::TextureCreationFailed(format!(
+                    "cubemap face {} is {} bytes, expected {}",
+                    i,
+                    face.len(),
+                    face_size
+                )));
+            }
+            data.extend_from_slice(face);
+        }
+
+        let image_info = vk::ImageCreateInfo::builder()
+            .flags(vk::ImageCreateFlags::CUBE_COMPATIBLE)
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(self.texture_format(format))
+            .extent(vk::Extent3D {
+                width: size,
+                height: size,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(6)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .build();
+
+        self.create_layered_texture(&image_info, vk::ImageViewType::CUBE, format, face_size, &data)
+    }
+
+    /// Create a 3D (volume) texture of `width`x`height`x`depth` texels.
+    ///
+    /// `data` holds the slices tightly packed along the depth axis and is
+    /// uploaded with a single copy covering the whole volume.
+    pub fn create_texture_3d(
+        &mut self,
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: TextureFormat,
+        data: &[u8],
+    ) -> Result<usize, VulkanError> {
+        let volume_size = format.size(width, height) as usize * depth as usize;
+        if depth == 0 || data.len() != volume_size {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "expected {} bytes for a {}x{}x{} volume, got {}",
+                volume_size,
+                width,
+                height,
+                depth,
+                data.len()
+            )));
+        }
+
+        let image_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_3D)
+            .format(self.texture_format(format))
+            .extent(vk::Extent3D { width, height, depth })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .build();
+
+        self.create_layered_texture(&image_info, vk::ImageViewType::TYPE_3D, format, volume_size, data)
+    }
+
+    /// Create a texture from a KTX2 file, uploading every mip level, array
+    /// layer and cubemap face it contains.
+    ///
+    /// The image keeps the `vkFormat` of the file, which has to be one of the
+    /// uncompressed 8-bit formats or the BC1/3/7, ETC2 and ASTC 4x4 formats
+    /// `TextureFormat` knows, in UNORM or sRGB. Supercompressed payloads are
+    /// rejected: Basis Universal (BasisLZ/ETC1S and UASTC) data has to be
+    /// transcoded with the Basis transcoder first, Zstandard and zlib data
+    /// inflated. Files asking for mips to be generated at load time only get
+    /// their base level.
+    pub fn create_texture_from_ktx2(&mut self, bytes: &[u8]) -> Result<usize, VulkanError> {
+        let error =
+            |message: String| VulkanError::TextureCreationFailed(format!("KTX2: {}", message));
+        let header = Ktx2Header::parse(bytes).map_err(error)?;
+        match header.supercompression {
+            Supercompression::None => {}
+            Supercompression::BasisLz => {
+                return Err(error("Basis Universal data has to be transcoded first".to_string()))
+            }
+            other => return Err(error(format!("{:?} supercompression is not supported", other))),
+        }
+        if header.format == vk::Format::UNDEFINED {
+            return Err(error("Basis Universal data has to be transcoded first".to_string()));
+        }
+        let (block_width, block_height, block_bytes) = vk_texel_block(header.format)
+            .filter(|_| aspect_mask(header.format) == vk::ImageAspectFlags::COLOR)
+            .ok_or_else(|| error(format!("format {:?} is not supported", header.format)))?;
+        if !self.supports_sampled_format(header.format) {
+            return Err(error(format!(
+                "format {:?} is not supported by this device",
+                header.format
+            )));
+        }
+
+        let cube = header.faces == 6;
+        let volume = header.depth > 0;
+        if header.faces != 1 && !cube {
+            return Err(error(format!("{} faces per layer", header.faces)));
+        }
+        if volume && (cube || header.layers > 0) {
+            return Err(error("3D texture arrays and cubemaps are not supported".to_string()));
+        }
+        let extent = vk::Extent3D {
+            width: header.width,
+            height: header.height.max(1),
+            depth: header.depth.max(1),
+        };
+        let max_levels = 32 - extent.width.max(extent.height).max(extent.depth).leading_zeros();
+        let levels = header.levels.len() as u32;
+        if header.width == 0 || levels > max_levels {
+            return Err(error(format!(
+                "{} levels for a {}x{}x{} image",
+                levels, extent.width, extent.height, extent.depth
+            )));
+        }
+        let (image_type, view_type) = if volume {
+            (vk::ImageType::TYPE_3D, vk::ImageViewType::TYPE_3D)
+        } else if cube && header.layers > 0 {
+            (vk::ImageType::TYPE_2D, vk::ImageViewType::CUBE_ARRAY)
+        } else if cube {
+            (vk::ImageType::TYPE_2D, vk::ImageViewType::CUBE)
+        } else if header.layers > 0 {
+            (vk::ImageType::TYPE_2D, vk::ImageViewType::TYPE_2D_ARRAY)
+        } else {
+            (vk::ImageType::TYPE_2D, vk::ImageViewType::TYPE_2D)
+        };
+        // Layers and faces are stored layer major, matching Vulkan's layer = 6 * layer + face
+        let layers = header.layers.max(1) * header.faces;
+
+        // The whole file gets staged so the level offsets can be used as is
+        let mut regions = Vec::with_capacity((levels * layers) as usize);
+        for (level, entry) in header.levels.iter().enumerate() {
+            let level_extent = vk::Extent3D {
+                width: (extent.width >> level).max(1),
+                height: (extent.height >> level).max(1),
+                depth: (extent.depth >> level).max(1),
+            };
+            let blocks = level_extent.width.div_ceil(block_width)
+                * level_extent.height.div_ceil(block_height);
+            let image_size = (blocks * block_bytes) as usize * level_extent.depth as usize;
+            if entry.length != image_size * layers as usize {
+                return Err(error(format!(
+                    "level {} is {} bytes, expected {}",
+                    level,
+                    entry.length,
+                    image_size * layers as usize
+                )));
+            }
+            for layer in 0..layers {
+                regions.push(vk::BufferImageCopy {
+                    buffer_offset: (entry.offset + layer as usize * image_size) as vk::DeviceSize,
+                    buffer_row_length: 0,
+                    buffer_image_height: 0,
+                    image_subresource: vk::ImageSubresourceLayers {
+                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        mip_level: level as u32,
+                        base_array_layer: layer,
+                        layer_count: 1,
+                    },
+                    image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+                    image_extent: level_extent,
+                });
+            }
+        }
+
+        let flags = if cube {
+            vk::ImageCreateFlags::CUBE_COMPATIBLE
+        } else {
+            vk::ImageCreateFlags::empty()
+        };
+        let image_info = vk::ImageCreateInfo::builder()
+            .flags(flags)
+            .image_type(image_type)
+            .format(header.format)
+            .extent(extent)
+            .mip_levels(levels)
+            .array_layers(layers)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .build();
+
+        let (image, view, allocation) = self.create_image_with_view(&image_info, view_type)?;
+        if let Err(e) = self.upload_image_data(image, levels, layers, bytes, &regions) {
+            self.destroy_image(image, view, allocation);
+            return Err(e);
+        }
+        let layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+        Ok(self.register_texture(image, view, allocation, &image_info, layout))
+    }
+
+    /// Create a partially resident 2D texture. No memory backs its pages
+    /// until they are bound with `bind_sparse_page`, so huge virtual textures
+    /// only cost memory for the pages actually in use. Unbound pages read as
+    /// undefined values.
+    ///
+    /// Needs `sparse_binding` and `sparse_residency_image_2d` in
+    /// `Conf::platform.requested_features`, a graphics queue that can bind
+    /// sparse memory and sparse support for `format`.
+    pub fn create_sparse_texture(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Result<usize, VulkanError> {
+        let unsupported = |reason: &str| {
+            VulkanError::TextureCreationFailed(format!(
+                "sparse textures are not supported: {}",
+                reason
+            ))
+        };
+        let features = self.granted_features;
+        if !features.sparse_binding || !features.sparse_residency_image_2d {
+            return Err(unsupported("sparse binding features were not granted"));
+        }
+        let instance = self.instance.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let physical_device = self.physical_device.ok_or(VulkanError::InvalidHandle)?;
+        let queue_family = self.queue_family_index.ok_or(VulkanError::InvalidHandle)?;
+        let queue_families =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+        let sparse_queue = queue_families
+            .get(queue_family as usize)
+            .is_some_and(|family| family.queue_flags.contains(vk::QueueFlags::SPARSE_BINDING));
+        if !sparse_queue {
+            return Err(unsupported("the graphics queue cannot bind sparse memory"));
+        }
+
+        let vk_format = self.texture_format(format);
+        let usage = vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST;
+        let format_properties = unsafe {
+            instance.get_physical_device_sparse_image_format_properties(
+                physical_device,
+                vk_format,
+                vk::ImageType::TYPE_2D,
+                vk::SampleCountFlags::TYPE_1,
+                usage,
+                vk::ImageTiling::OPTIMAL,
+            )
+        };
+        if !format_properties
+            .iter()
+            .any(|properties| properties.aspect_mask.contains(vk::ImageAspectFlags::COLOR))
+        {
+            return Err(unsupported("the format has no sparse support"));
+        }
+
+        let image_info = vk::ImageCreateInfo::builder()
+            .flags(vk::ImageCreateFlags::SPARSE_BINDING | vk::ImageCreateFlags::SPARSE_RESIDENCY)
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(vk_format)
+            .extent(vk::Extent3D { width, height, depth: 1 })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .build();
+        let image = unsafe { device.create_image(&image_info, None) }
+            .map_err(|e| VulkanError::TextureCreationFailed(e.to_string()))?;
+        let requirements = unsafe { device.get_image_memory_requirements(image) };
+        let sparse_requirements = unsafe { device.get_image_sparse_memory_requirements(image) }
+            .into_iter()
+            .find(|sparse| {
+                sparse
+                    .format_properties
+                    .aspect_mask
+                    .contains(vk::ImageAspectFlags::COLOR)
+            });
+        let sparse_requirements = match sparse_requirements {
+            Some(sparse) => sparse,
+            None => {
+                unsafe { device.destroy_image(image, None) };
+                return Err(unsupported("no sparse memory requirements for the color aspect"));
+            }
+        };
+
+        let mut residency = SparseResidency {
+            page_size: sparse_requirements.format_properties.image_granularity,
+            pages: HashMap::new(),
+            mip_tail: None,
+            page_requirements: vk::MemoryRequirements {
+                size: requirements.alignment,
+                alignment: requirements.alignment,
+                memory_type_bits: requirements.memory_type_bits,
+            },
+        };
+        let view_info = vk::ImageViewCreateInfo::builder()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(vk_format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            });
+        let view = match unsafe { device.create_image_view(&view_info, None) } {
+            Ok(view) => view,
+            Err(e) => {
+                unsafe { device.destroy_image(image, None) };
+                return Err(VulkanError::TextureCreationFailed(e.to_string()));
+            }
+        };
+
+        let layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+        // Levels past the first mip tail LOD are packed and bound as a whole
+        let id = self.register_texture(image, view, Allocation::default(), &image_info, layout);
+        if sparse_requirements.image_mip_tail_first_lod < image_info.mip_levels {
+            let tail_requirements = vk::MemoryRequirements {
+                size: sparse_requirements.image_mip_tail_size,
+                ..residency.page_requirements
+            };
+            let bound = self.allocate_sparse_memory(tail_requirements).and_then(|tail| {
+                let bind = vk::SparseMemoryBind {
+                    resource_offset: sparse_requirements.image_mip_tail_offset,
+                    size: sparse_requirements.image_mip_tail_size,
+                    memory: unsafe { tail.memory() },
+                    memory_offset: tail.offset(),
+                    flags: vk::SparseMemoryBindFlags::empty(),
+                };
+                let binds = [bind];
+                let opaque = [vk::SparseImageOpaqueMemoryBindInfo::builder()
+                    .image(image)
+                    .binds(&binds)
+                    .build()];
+                let info = vk::BindSparseInfo::builder().image_opaque_binds(&opaque);
+                residency.mip_tail = Some(tail);
+                self.submit_sparse_bind(&info)
+            });
+            if let Err(e) = bound {
+                self.free_sparse_texture(id, residency);
+                return Err(e);
+            }
+        }
+
+        let range = view_info.subresource_range;
+        let transitioned = self.batched_commands(|device, cmd| unsafe {
+            transition_image_layout(
+                device,
+                cmd,
+                image,
+                range,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            );
+        });
+        if let Err(e) = transitioned {
+            self.free_sparse_texture(id, residency);
+            return Err(e);
+        }
+        if let Some(texture) = self.textures.get_mut(&id) {
+            texture.sparse = Some(residency);
+        }
+        Ok(id)
+    }
+
+    /// Texels covered by one page of a sparse texture, `None` for regular textures
+    pub fn sparse_page_size(&self, texture_id: usize) -> Option<vk::Extent3D> {
+        let sparse = self.textures.get(&texture_id)?.sparse.as_ref()?;
+        Some(sparse.page_size)
+    }
+
+    /// Back page `page` (in page coordinates, see `sparse_page_size`) of a
+    /// sparse texture with freshly allocated memory. The page contents are
+    /// undefined until uploaded with `update_texture_part`. Binding an already
+    /// resident page does nothing.
+    pub fn bind_sparse_page(
+        &mut self,
+        texture_id: usize,
+        page: [u32; 2],
+    ) -> Result<(), VulkanError> {
+        let (image, offset, extent, requirements) = self.sparse_page_region(texture_id, page)?;
+        let sparse = self.textures[&texture_id].sparse.as_ref();
+        if sparse.is_some_and(|sparse| sparse.pages.contains_key(&page)) {
+            return Ok(());
+        }
+
+        let allocation = self.allocate_sparse_memory(requirements)?;
+        let memory = unsafe { allocation.memory() };
+        let bound = self.bind_sparse_image_page(image, offset, extent, memory, allocation.offset());
+        match bound {
+            Ok(()) => {
+                let texture = self.textures.get_mut(&texture_id);
+                if let Some(sparse) = texture.and_then(|texture| texture.sparse.as_mut()) {
+                    sparse.pages.insert(page, allocation);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                if let Some(allocator) = lock(&self.allocator).as_mut() {
+                    let _ = allocator.free(allocation);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Release the memory of a resident page of a sparse texture.
+    ///
+    /// Waits for every submitted frame, as they may still sample the page.
+    /// Draws recorded in the current frame must not sample it anymore.
+    pub fn unbind_sparse_page(
+        &mut self,
+        texture_id: usize,
+        page: [u32; 2],
+    ) -> Result<(), VulkanError> {
+        let (image, offset, extent, _) = self.sparse_page_region(texture_id, page)?;
+        let allocation = match self
+            .textures
+            .get_mut(&texture_id)
+            .and_then(|texture| texture.sparse.as_mut())
+            .and_then(|sparse| sparse.pages.remove(&page))
+        {
+            Some(allocation) => allocation,
+            None => return Ok(()),
+        };
+
+        let unbound = vk::DeviceMemory::null();
+        let result = self
+            .wait_for_frame(self.frame_timeline_value)
+            .and_then(|_| self.bind_sparse_image_page(image, offset, extent, unbound, 0));
+        if let Some(allocator) = lock(&self.allocator).as_mut() {
+            let _ = allocator.free(allocation);
+        }
+        result
+    }
+
+    /// Image, texel offset, extent and memory requirements of a page of a sparse texture
+    fn sparse_page_region(
+        &self,
+        texture_id: usize,
+        page: [u32; 2],
+    ) -> Result<(vk::Image, vk::Offset3D, vk::Extent3D, vk::MemoryRequirements), VulkanError> {
+        let texture = self.textures.get(&texture_id).ok_or(VulkanError::InvalidHandle)?;
+        let sparse = texture.sparse.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let [x, y] = [page[0] * sparse.page_size.width, page[1] * sparse.page_size.height];
+        if x >= texture.width || y >= texture.height {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "page {:?} is outside of sparse texture {}",
+                page, texture_id
+            )));
+        }
+        // Pages on the right and bottom edge are cut to the image size
+        let extent = vk::Extent3D {
+            width: sparse.page_size.width.min(texture.width - x),
+            height: sparse.page_size.height.min(texture.height - y),
+            depth: 1,
+        };
+        let offset = vk::Offset3D { x: x as i32, y: y as i32, z: 0 };
+        Ok((texture.image, offset, extent, sparse.page_requirements))
+    }
+
+    fn allocate_sparse_memory(
+        &mut self,
+        requirements: vk::MemoryRequirements,
+    ) -> Result<Allocation, VulkanError> {
+        let mut allocator = lock(&self.allocator);
+        let allocator = allocator.as_mut().ok_or(VulkanError::InvalidHandle)?;
+        Ok(allocator.allocate(&AllocationCreateDesc {
+            name: "sparse page",
+            requirements,
+            location: MemoryLocation::GpuOnly,
+            linear: false,
+            allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+        })?)
+    }
+
+    /// Bind `memory` (or nothing, with a null handle) to one page of `image`
+    fn bind_sparse_image_page(
+        &self,
+        image: vk::Image,
+        offset: vk::Offset3D,
+        extent: vk::Extent3D,
+        memory: vk::DeviceMemory,
+        memory_offset: vk::DeviceSize,
+    ) -> Result<(), VulkanError> {
+        let binds = [vk::SparseImageMemoryBind {
+            subresource: vk::ImageSubresource {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                array_layer: 0,
+            },
+            offset,
+            extent,
+            memory,
+            memory_offset,
+            flags: vk::SparseMemoryBindFlags::empty(),
+        }];
+        let image_binds = [vk::SparseImageMemoryBindInfo::builder()
+            .image(image)
+            .binds(&binds)
+            .build()];
+        let info = vk::BindSparseInfo::builder().image_binds(&image_binds);
+        self.submit_sparse_bind(&info)
+    }
+
+    /// Run a sparse binding operation on the graphics queue and wait for it,
+    /// so frames submitted afterwards see the new bindings.
+    fn submit_sparse_bind(&self, info: &vk::BindSparseInfo) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let queue = self.graphics_queue.ok_or(VulkanError::InvalidHandle)?;
+        let _queue = lock(&self.queue_lock);
+        unsafe {
+            let fence = device
+                .create_fence(&vk::FenceCreateInfo::default(), None)
+                .map_err(sync_error)?;
+            let result = device
+                .queue_bind_sparse(queue, std::slice::from_ref(info), fence)
+                .and_then(|_| device.wait_for_fences(&[fence], true, u64::MAX))
+                .map_err(sync_error);
+            device.destroy_fence(fence, None);
+            result
+        }
+    }
+
+    /// Undo a half created sparse texture
+    fn free_sparse_texture(&mut self, texture_id: usize, residency: SparseResidency) {
+        if let Some(texture) = self.textures.remove(&texture_id) {
+            self.destroy_image(texture.image, texture.view, texture.allocation);
+        }
+        if let Some(allocator) = lock(&self.allocator).as_mut() {
+            for page in residency.into_allocations() {
+                let _ = allocator.free(page);
+            }
+        }
+    }
+
+    /// Create a 2D texture without waiting for its data to reach the GPU.
+    ///
+    /// The copy runs on the dedicated transfer queue when the device has one
+    /// and is handed over to the graphics queue with a semaphore. Returns the
+    /// texture id and a fence to pass to `poll_upload`; the texture must not
+    /// be sampled before the upload has finished.
+    pub fn create_texture_async(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        data: &[u8],
+    ) -> Result<(usize, vk::Fence), VulkanError> {
+        let size = format.size(width, height) as usize;
+        if data.len() != size {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "expected {} bytes for {}x{}, got {}",
+                size,
+                width,
+                height,
+                data.len()
+            )));
+        }
+
+        let image_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(self.texture_format(format))
+            .extent(vk::Extent3D { width, height, depth: 1 })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .build();
+
+        self.check_sampled_format(format)?;
+        let (image, view, allocation) =
+            self.create_image_with_view(&image_info, vk::ImageViewType::TYPE_2D)?;
+        let regions = layer_copy_regions(&image_info, size);
+        let upload = match self.submit_image_upload(image, 1, 1, data, &regions) {
+            Ok(upload) => upload,
+            Err(e) => {
+                self.destroy_image(image, view, allocation);
+                return Err(e);
+            }
+        };
+
+        let fence = upload.fence;
+        self.pending_uploads.push(upload);
+        let layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+        let id = self.register_texture(image, view, allocation, &image_info, layout);
+        Ok((id, fence))
+    }
+
+    /// Check whether the upload signalling `fence` has finished, releasing its
+    /// staging buffer once it has. Unknown fences count as finished.
+    pub fn poll_upload(&mut self, fence: vk::Fence) -> Result<bool, VulkanError> {
+        let index = match self.pending_uploads.iter().position(|upload| upload.fence == fence) {
+            Some(index) => index,
+            None => return Ok(true),
+        };
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let done = unsafe { device.get_fence_status(fence) }.map_err(sync_error)?;
+        if done {
+            let upload = self.pending_uploads.swap_remove(index);
+            self.release_upload(upload);
+        }
+        Ok(done)
+    }
+
+    /// Handle for creating buffers and textures on other threads, e.g. to
+    /// stream assets in without stalling the render loop.
+    ///
+    /// All clones share one command pool, so uploads from several threads
+    /// are recorded one after the other. The handle stops working once the
+    /// device is destroyed or recreated; ask for a new one afterwards.
+    pub fn resource_uploader(&mut self) -> Result<ResourceUploader, VulkanError> {
+        if let Some(uploader) = self.uploader.as_ref() {
+            return Ok(uploader.clone());
+        }
+
+        let instance = self.instance.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let physical_device = self.physical_device.ok_or(VulkanError::InvalidHandle)?;
+        let graphics_queue = self.graphics_queue.ok_or(VulkanError::InvalidHandle)?;
+        let graphics_family = self.queue_family_index.ok_or(VulkanError::InvalidHandle)?;
+
+        let (queue, family) = match (self.transfer_queue, self.transfer_queue_family_index) {
+            (Some(queue), Some(family)) => (queue, family),
+            _ => (graphics_queue, graphics_family),
+        };
+        let mut families = vec![family];
+        if family != graphics_family {
+            families.push(graphics_family);
+        }
+
+        let pool_info = vk::CommandPoolCreateInfo::builder()
+            .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+            .queue_family_index(family);
+        let pool = unsafe { device.create_command_pool(&pool_info, None) }
+            .map_err(|e| VulkanError::CommandBufferCreationFailed(e.to_string()))?;
+
+        let uploader = ResourceUploader {
+            inner: Arc::new(UploaderInner {
+                instance: instance.clone(),
+                physical_device,
+                device: device.clone(),
+                allocator: self.allocator.clone(),
+                queue_lock: self.queue_lock.clone(),
+                queue,
+                families,
+                color_space_mode: self.color_space_mode(),
+                command_pool: Mutex::new(Some(pool)),
+            }),
+        };
+        self.uploader = Some(uploader.clone());
+        Ok(uploader)
+    }
+
+    /// Wait for `upload` to finish and register what it created. Returns a
+    /// buffer id for `ResourceUploader::upload_buffer` and a texture id for
+    /// `ResourceUploader::upload_texture`.
+    ///
+    /// Blocks until the upload's fence signals; check
+    /// `UploadedResource::is_ready` first to keep the render thread going.
+    pub fn adopt_upload(&mut self, upload: UploadedResource) -> Result<usize, VulkanError> {
+        let inner = upload.uploader.inner.clone();
+        let current = self
+            .uploader
+            .as_ref()
+            .is_some_and(|uploader| Arc::ptr_eq(&uploader.inner, &inner));
+        if !current {
+            // Made on a device that has been destroyed since
+            return Err(VulkanError::InvalidHandle);
+        }
+
+        let resource = {
+            let (_pool, pool) = inner.lock_pool()?;
+            unsafe { inner.device.wait_for_fences(&[upload.fence], true, u64::MAX) }
+                .map_err(sync_error)?;
+            inner.release(pool, upload)
+        };
+
+        Ok(match resource {
+            UploadedKind::Buffer(buffer) => {
+                let id = self.next_buffer_id;
+                self.next_buffer_id += 1;
+                self.buffers.insert(id, buffer);
+                id
+            }
+            UploadedKind::Texture {
+                image,
+                view,
+                allocation,
+                width,
+                height,
+                format,
+            } => {
+                let image_info = vk::ImageCreateInfo::builder()
+                    .format(format)
+                    .extent(vk::Extent3D { width, height, depth: 1 })
+                    .array_layers(1)
+                    .build();
+                let layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+                self.register_texture(image, view, allocation, &image_info, layout)
+            }
+        })
+    }
+
+    /// Create a 2D texture backed by memory exported from another API or
+    /// process (a video decoder, CUDA, ...), without copying it.
+    ///
+    /// The memory is expected to hold an optimally tiled image of the given
+    /// size and format. On success Vulkan takes ownership of the handle.
+    pub fn import_external_image(
+        &mut self,
+        handle: ExternalMemoryHandle,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Result<usize, VulkanError> {
+        let handle_type = self.external_memory_handle_type.ok_or_else(|| {
+            VulkanError::TextureCreationFailed(
+                "external memory import is not supported by this device".to_string(),
+            )
+        })?;
+        let instance = self.instance.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let physical_device = self.physical_device.ok_or(VulkanError::InvalidHandle)?;
+
+        let mut external_info =
+            vk::ExternalMemoryImageCreateInfo::builder().handle_types(handle_type);
+        let image_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(self.texture_format(format))
+            .extent(vk::Extent3D { width, height, depth: 1 })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::SAMPLED)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .push_next(&mut external_info)
+            .build();
+        let image = unsafe { device.create_image(&image_info, None) }
+            .map_err(|e| VulkanError::TextureCreationFailed(e.to_string()))?;
+
+        let requirements = unsafe { device.get_image_memory_requirements(image) };
+        let memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        let memory_type_index = (0..memory_properties.memory_type_count)
+            .filter(|index| requirements.memory_type_bits & (1 << index) != 0)
+            .max_by_key(|&index| {
+                memory_properties.memory_types[index as usize]
+                    .property_flags
+                    .contains(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+            });
+        let memory_type_index = match memory_type_index {
+            Some(index) => index,
+            None => {
+                unsafe { device.destroy_image(image, None) };
+                return Err(VulkanError::TextureCreationFailed(
+                    "no memory type can hold the imported image".to_string(),
+                ));
+            }
+        };
+
+        let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::builder().image(image);
+        let alloc_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_type_index)
+            .push_next(&mut dedicated_info);
+        let memory = match handle {
+            #[cfg(unix)]
+            ExternalMemoryHandle::Fd(fd) => {
+                let mut import_info = vk::ImportMemoryFdInfoKHR::builder()
+                    .handle_type(handle_type)
+                    .fd(fd);
+                unsafe { device.allocate_memory(&alloc_info.push_next(&mut import_info), None) }
+            }
+            #[cfg(windows)]
+            ExternalMemoryHandle::Win32(win32_handle) => {
+                let mut import_info = vk::ImportMemoryWin32HandleInfoKHR::builder()
+                    .handle_type(handle_type)
+                    .handle(win32_handle);
+                unsafe { device.allocate_memory(&alloc_info.push_next(&mut import_info), None) }
+            }
+        };
+        let memory = match memory {
+            Ok(memory) => memory,
+            Err(e) => {
+                unsafe { device.destroy_image(image, None) };
+                return Err(VulkanError::TextureCreationFailed(format!(
+                    "importing external memory failed: {}",
+                    e
+                )));
+            }
+        };
+
+        let view_info = vk::ImageViewCreateInfo::builder()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(image_info.format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            });
+        let view = unsafe {
+            device
+                .bind_image_memory(image, memory, 0)
+                .and_then(|_| device.create_image_view(&view_info, None))
+        };
+        let view = match view {
+            Ok(view) => view,
+            Err(e) => {
+                unsafe {
+                    device.destroy_image(image, None);
+                    device.free_memory(memory, None);
+                }
+                return Err(VulkanError::TextureCreationFailed(e.to_string()));
+            }
+        };
+
+        let range = view_info.subresource_range;
+        let transitioned = self.batched_commands(|device, cmd| unsafe {
+            transition_image_layout(
+                device,
+                cmd,
+                image,
+                range,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            );
+        });
+        if let Err(e) = transitioned {
+            if let Some(device) = self.device.as_ref() {
+                unsafe {
+                    device.destroy_image_view(view, None);
+                    device.destroy_image(image, None);
+                    device.free_memory(memory, None);
+                }
+            }
+            return Err(e);
+        }
+
+        let layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+        let id = self.register_texture(image, view, Allocation::default(), &image_info, layout);
+        if let Some(texture) = self.textures.get_mut(&id) {
+            texture.imported_memory = Some(memory);
+        }
+        Ok(id)
+    }
+
+    /// Shader from the GLSL sources of its vertex and fragment stages, see
+    /// `compile_shader`
+    pub fn create_shader(
+        &mut self,
+        vertex_shader: &str,
+        fragment_shader: &str,
+        meta: ShaderMeta,
+    ) -> Result<usize, VulkanError> {
+        self.shader_lints.clear();
+        let vertex = self.compile_shader(vertex_shader, vk::ShaderStageFlags::VERTEX.as_raw());
+        let fragment =
+            self.compile_shader(fragment_shader, vk::ShaderStageFlags::FRAGMENT.as_raw());
+        self.push_shader(&vertex?, &fragment?, &meta)
+    }
+
+    /// Shader from the SPIR-V of its vertex and fragment stages, e.g. as
+    /// compiled by `glslc` at build time. Both stages have `main` as their
+    /// entry point.
+    pub fn create_shader_from_spirv(
+        &mut self,
+        vertex_shader: &[u32],
+        fragment_shader: &[u32],
+        meta: ShaderMeta,
+    ) -> Result<usize, VulkanError> {
+        self.shader_lints.clear();
+        self.push_shader(vertex_shader, fragment_shader, &meta)
+    }
+
+    fn push_shader(
+        &mut self,
+        vertex: &[u32],
+        fragment: &[u32],
+        meta: &ShaderMeta,
+    ) -> Result<usize, VulkanError> {
+        let modules = self.create_shader_modules(&[vertex, fragment])?;
+        self.shaders.push(VulkanShader {
+            vertex_module: modules[0],
+            fragment_module: modules[1],
+            geometry_module: None,
+            tessellation_modules: None,
+            compute_module: None,
+            texture_count: meta.texture_slots.len() as u32,
+        });
+        Ok(self.shaders.len() - 1)
+    }
+    
+    /// `create_shader` with both stages run through `preprocess_glsl` first,
+    /// resolving their `#include`s and adding the `#define`s of `options`
+    pub fn create_shader_ex(
+        &mut self,
+        vertex_shader: &str,
+        fragment_shader: &str,
+        meta: ShaderMeta,
+        options: &ShaderOptions,
+    ) -> Result<usize, VulkanError> {
+        let vertex_shader = preprocess_glsl(vertex_shader, options)?;
+        let fragment_shader = preprocess_glsl(fragment_shader, options)?;
+        self.create_shader(&vertex_shader, &fragment_shader, meta)
+    }
+
+    /// Shader with only a compute stage, from its GLSL source
+    pub fn create_compute_shader(
+        &mut self,
+        compute_shader: &str,
+        _meta: ShaderMeta,
+    ) -> Result<usize, VulkanError> {
+        self.shader_lints.clear();
+        let compute =
+            self.compile_shader(compute_shader, vk::ShaderStageFlags::COMPUTE.as_raw())?;
+        let compute_module = self.create_shader_module(&compute)?;
+        // Destroying the null graphics modules along with the shader is a no-op
+        self.shaders.push(VulkanShader {
+            vertex_module: vk::ShaderModule::null(),
+            fragment_module: vk::ShaderModule::null(),
+            geometry_module: None,
+            tessellation_modules: None,
+            compute_module: Some(compute_module),
+            texture_count: 0,
+        });
+        Ok(self.shaders.len() - 1)
+    }
+    
+    /// Shader with a geometry stage between the vertex and fragment stages,
+    /// e.g. to expand points into billboards. Needs
+    /// `RequestedFeatures::geometry_shader`, which most mobile GPUs and
+    /// MoltenVK lack.
+    pub fn create_shader_with_geometry(
+        &mut self,
+        vertex_shader: &str,
+        geometry_shader: &str,
+        fragment_shader: &str,
+        meta: ShaderMeta,
+    ) -> Result<usize, VulkanError> {
+        if !self.granted_features.geometry_shader {
+            return Err(VulkanError::ShaderCompilation(
+                "geometry shaders need the geometryShader device feature, which was not granted"
+                    .to_string(),
+            ));
+        }
+        self.shader_lints.clear();
+        let vertex = self.compile_shader(vertex_shader, vk::ShaderStageFlags::VERTEX.as_raw())?;
+        let geometry =
+            self.compile_shader(geometry_shader, vk::ShaderStageFlags::GEOMETRY.as_raw())?;
+        let fragment =
+            self.compile_shader(fragment_shader, vk::ShaderStageFlags::FRAGMENT.as_raw())?;
+
+        let modules = self.create_shader_modules(&[&vertex, &geometry, &fragment])?;
+        self.shaders.push(VulkanShader {
+            vertex_module: modules[0],
+            fragment_module: modules[2],
+            geometry_module: Some(modules[1]),
+            tessellation_modules: None,
+            compute_module: None,
+            texture_count: meta.texture_slots.len() as u32,
+        });
+        Ok(self.shaders.len() - 1)
+    }
+
+    /// Shader with tessellation control and evaluation stages, e.g. for
+    /// terrain LOD. Pipelines using it have to draw `PrimitiveType::Patches`.
+    /// Needs `RequestedFeatures::tessellation_shader`.
+    pub fn create_shader_tessellated(
+        &mut self,
+        vertex_shader: &str,
+        control_shader: &str,
+        evaluation_shader: &str,
+        fragment_shader: &str,
+        meta: ShaderMeta,
+    ) -> Result<usize, VulkanError> {
+        if !self.granted_features.tessellation_shader {
+            return Err(VulkanError::ShaderCompilation(
+                "tessellation needs the tessellationShader device feature, which was not granted"
+                    .to_string(),
+            ));
+        }
+        self.shader_lints.clear();
+        let control_stage = vk::ShaderStageFlags::TESSELLATION_CONTROL;
+        let evaluation_stage = vk::ShaderStageFlags::TESSELLATION_EVALUATION;
+        let vertex = self.compile_shader(vertex_shader, vk::ShaderStageFlags::VERTEX.as_raw())?;
+        let control = self.compile_shader(control_shader, control_stage.as_raw())?;
+        let evaluation = self.compile_shader(evaluation_shader, evaluation_stage.as_raw())?;
+        let fragment =
+            self.compile_shader(fragment_shader, vk::ShaderStageFlags::FRAGMENT.as_raw())?;
+
+        let modules = self.create_shader_modules(&[&vertex, &control, &evaluation, &fragment])?;
+        self.shaders.push(VulkanShader {
+            vertex_module: modules[0],
+            fragment_module: modules[3],
+            geometry_module: None,
+            tessellation_modules: Some((modules[1], modules[2])),
+            compute_module: None,
+            texture_count: meta.texture_slots.len() as u32,
+        });
+        Ok(self.shaders.len() - 1)
+    }
+
+    /// Recompile the vertex and fragment stages of `shader_id` and rebuild
+    /// every graphics pipeline made from it, for editing shaders while the
+    /// application runs. Geometry and tessellation stages are kept.
+    ///
+    /// Nothing changes when compiling or building a pipeline fails: the old
+    /// shader and pipelines stay in use and the error is returned. On success
+    /// the device is idled once and the pipelines are swapped in place, so
+    /// pipeline ids stay valid.
+    pub fn reload_shader(
+        &mut self,
+        shader_id: usize,
+        vertex_shader: &str,
+        fragment_shader: &str,
+    ) -> Result<(), VulkanError> {
+        self.reloadable_stages(shader_id)?;
+        self.shader_lints.clear();
+        let vertex = self.compile_shader(vertex_shader, vk::ShaderStageFlags::VERTEX.as_raw());
+        let fragment =
+            self.compile_shader(fragment_shader, vk::ShaderStageFlags::FRAGMENT.as_raw());
+        self.replace_shader_stages(shader_id, &vertex?, &fragment?)
+    }
+
+    /// `reload_shader` from SPIR-V, for shaders made with
+    /// `create_shader_from_spirv`
+    pub fn reload_shader_spirv(
+        &mut self,
+        shader_id: usize,
+        vertex_shader: &[u32],
+        fragment_shader: &[u32],
+    ) -> Result<(), VulkanError> {
+        self.reloadable_stages(shader_id)?;
+        self.shader_lints.clear();
+        self.replace_shader_stages(shader_id, vertex_shader, fragment_shader)
+    }
+
+    /// The vertex and fragment modules of `shader_id`, unless it's a compute
+    /// shader
+    fn reloadable_stages(
+        &self,
+        shader_id: usize,
+    ) -> Result<(vk::ShaderModule, vk::ShaderModule), VulkanError> {
+        let shader = self.shaders.get(shader_id).ok_or(VulkanError::InvalidHandle)?;
+        if shader.compute_module.is_some() {
+            return Err(VulkanError::ShaderCompilation(
+                "compute shaders have no vertex and fragment stages to reload".to_string(),
+            ));
+        }
+        Ok((shader.vertex_module, shader.fragment_module))
+    }
+
+    fn replace_shader_stages(
+        &mut self,
+        shader_id: usize,
+        vertex: &[u32],
+        fragment: &[u32],
+    ) -> Result<(), VulkanError> {
+        let (old_vertex, old_fragment) = self.reloadable_stages(shader_id)?;
+        let modules = self.create_shader_modules(&[vertex, fragment])?;
+        let (vertex_module, fragment_module) = (modules[0], modules[1]);
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+
+        // Pipelines made from the shader share its modules
+        let clip_control = self.depth_clip_control;
+        let mut rebuilt = vec![];
+        let mut failure = None;
+        for (id, pipeline) in self.pipelines.iter().enumerate() {
+            let recipe = match &pipeline.recipe {
+                Some(recipe)
+                    if recipe.vertex_module == old_vertex
+                        && recipe.fragment_module == old_fragment =>
+                {
+                    recipe
+                }
+                _ => continue,
+            };
+            let recipe = GraphicsPipelineRecipe {
+                vertex_module,
+                fragment_module,
+                ..recipe.clone()
+            };
+            let fill = vk::PolygonMode::FILL;
+            match create_graphics_pipeline(device, pipeline.layout, &recipe, fill, clip_control) {
+                Ok(new_pipeline) => rebuilt.push((id, new_pipeline, recipe)),
+                Err(e) => {
+                    failure = Some(e);
+                    break;
+                }
+            }
+        }
+        if let Some(e) = failure {
+            unsafe {
+                for (_, pipeline, _) in rebuilt {
+                    device.destroy_pipeline(pipeline, None);
+                }
+                device.destroy_shader_module(vertex_module, None);
+                device.destroy_shader_module(fragment_module, None);
+            }
+            return Err(e);
+        }
+
+        unsafe { device.device_wait_idle() }.map_err(sync_error)?;
+        for (id, new_pipeline, recipe) in rebuilt {
+            let pipeline = &mut self.pipelines[id];
+            unsafe {
+                device.destroy_pipeline(pipeline.pipeline, None);
+                if let Some(wireframe) = pipeline.wireframe.take() {
+                    device.destroy_pipeline(wireframe, None);
+                }
+            }
+            pipeline.pipeline = new_pipeline;
+            pipeline.recipe = Some(recipe);
+        }
+        unsafe {
+            device.destroy_shader_module(old_vertex, None);
+            device.destroy_shader_module(old_fragment, None);
+        }
+        let shader = &mut self.shaders[shader_id];
+        shader.vertex_module = vertex_module;
+        shader.fragment_module = fragment_module;
+        Ok(())
+    }
+
+    /// Graphics pipeline drawing with `shader_id`, taking vertices laid out
+    /// by `buffer_layout` and `attributes` like on GL, see
+    /// `vertex_input_descriptions`. It is made for render target `target`,
+    /// or the swapchain for `None`, and works with any load ops of its
+    /// passes. `apply_uniforms` blocks are bound to set 0 of its layout and
+    /// `apply_textures` to `TEXTURE_SET`.
+    pub fn create_pipeline(
+        &mut self,
+        shader_id: usize,
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        params: PipelineParams,
+        target: Option<usize>,
+    ) -> Result<usize, VulkanError> {
+        let shader = self.shaders.get(shader_id).ok_or(VulkanError::InvalidHandle)?;
+        if shader.compute_module.is_some() {
+            return Err(VulkanError::ShaderCompilation(
+                "compute shaders can't be used in graphics pipelines".to_string(),
+            ));
+        }
+        let (vertex_module, fragment_module) = (shader.vertex_module, shader.fragment_module);
+        let (geometry_module, tessellation_modules) =
+            (shader.geometry_module, shader.tessellation_modules);
+        let texture_count = shader.texture_count;
+
+        let (render_pass, color_count, samples) = match target {
+            Some(target_id) => {
+                let target =
+                    self.render_targets.get(&target_id).ok_or(VulkanError::InvalidHandle)?;
+                let (color_count, samples) = (target.color_formats.len(), target.samples);
+                let render_pass = match target.render_passes.values().next() {
+                    Some(&render_pass) => render_pass,
+                    None => {
+                        let attachments = color_count + target.depth_format.iter().count();
+                        let load_ops = vec![vk::AttachmentLoadOp::CLEAR; attachments];
+                        let render_pass =
+                            self.create_target_render_pass(&target.pass_layout(), &load_ops)?;
+                        let target = self.render_targets.get_mut(&target_id).unwrap();
+                        target.render_passes.insert(load_ops, render_pass);
+                        render_pass
+                    }
+                };
+                (render_pass, color_count, samples)
+            }
+            None => {
+                let render_pass = self.swapchain_render_pass(&LoadActions::default())?;
+                (render_pass, 1, vk::SampleCountFlags::TYPE_1)
+            }
+        };
+
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let mut set_layouts: Vec<vk::DescriptorSetLayout> =
+            self.uniform_set_layout.into_iter().collect();
+        let texture_set_layout = if texture_count > 0 {
+            if set_layouts.is_empty() {
+                return Err(VulkanError::InvalidHandle);
+            }
+            let texture_set_layout = create_texture_set_layout(device, texture_count)?;
+            set_layouts.push(texture_set_layout);
+            Some((texture_set_layout, texture_count))
+        } else {
+            None
+        };
+        let layout_info = vk::PipelineLayoutCreateInfo::builder().set_layouts(&set_layouts);
+        let layout = match unsafe { device.create_pipeline_layout(&layout_info, None) } {
+            Ok(layout) => layout,
+            Err(e) => {
+                if let Some((texture_set_layout, _)) = texture_set_layout {
+                    unsafe { device.destroy_descriptor_set_layout(texture_set_layout, None) };
+                }
+                return Err(VulkanError::ShaderCompilation(e.to_string()));
+            }
+        };
+
+        let (bindings, attributes) = vertex_input_descriptions(buffer_layout, attributes);
+        let recipe = GraphicsPipelineRecipe {
+            vertex_module,
+            tessellation_modules,
+            geometry_module,
+            fragment_module,
+            bindings,
+            attributes,
+            topology: primitive_topology(params.primitive_type),
+            patch_control_points: 3,
+            params,
+            blend_attachments: vec![blend_attachment_state(&params); color_count],
+            render_pass,
+            subpass: 0,
+            samples,
+        };
+        let fill = vk::PolygonMode::FILL;
+        let clip_control = self.depth_clip_control;
+        let pipeline = match create_graphics_pipeline(device, layout, &recipe, fill, clip_control)
+        {
+            Ok(pipeline) => pipeline,
+            Err(e) => {
+                unsafe {
+                    device.destroy_pipeline_layout(layout, None);
+                    if let Some((texture_set_layout, _)) = texture_set_layout {
+                        device.destroy_descriptor_set_layout(texture_set_layout, None);
+                    }
+                }
+                return Err(e);
+            }
+        };
+        self.pipelines.push(VulkanPipeline {
+            pipeline,
+            layout,
+            local_size: None,
+            recipe: Some(recipe),
+            wireframe: None,
+            global_uniforms: false,
+            texture_set_layout,
+        });
+        Ok(self.pipelines.len() - 1)
+    }
+
+    /// A module for each of `stages`, destroying the ones already created
+    /// when a later stage fails
+    fn create_shader_modules(
+        &self,
+        stages: &[&[u32]],
+    ) -> Result<Vec<vk::ShaderModule>, VulkanError> {
+        let mut modules = Vec::with_capacity(stages.len());
+        for code in stages {
+            match self.create_shader_module(code) {
+                Ok(module) => modules.push(module),
+                Err(e) => {
+                    if let Some(device) = self.device.as_ref() {
+                        for module in modules {
+                            unsafe { device.destroy_shader_module(module, None) };
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(modules)
+    }
+
+    /// Module for the SPIR-V `code`, which is checked to at least start with
+    /// a SPIR-V header since drivers don't validate it
+    fn create_shader_module(&self, code: &[u32]) -> Result<vk::ShaderModule, VulkanError> {
+        const HEADER_WORDS: usize = 5;
+        if code.len() <= HEADER_WORDS || code[0] != 0x0723_0203 {
+            return Err(VulkanError::ShaderCompilation("malformed SPIR-V module".to_string()));
+        }
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let create_info = vk::ShaderModuleCreateInfo::builder().code(code);
+        unsafe { device.create_shader_module(&create_info, None) }
+            .map_err(|e| VulkanError::ShaderCompilation(e.to_string()))
+    }
+
+    /// `lint_glsl` results for the stages of the last shader created or
+    /// reloaded from GLSL, prefixed with the stage and line. They are
+    /// printed as well and never fail shader creation. These aren't
+    /// compiler diagnostics, errors come back as `VulkanError::ShaderCompilation`.
+    pub fn last_shader_lints(&self) -> &[String] {
+        &self.shader_lints
+    }
+
+    fn lint_shader(&mut self, source: &str, stage: vk::ShaderStageFlags) {
+        for lint in lint_glsl(source) {
+            let lint = format!("{:?} shader {}", stage, lint);
+            log_04::warn!("Vulkan: {}", lint);
+            self.shader_lints.push(lint);
+        }
+    }
+
+    /// SPIR-V for the GLSL `source` of the stage `kind`, a
+    /// `vk::ShaderStageFlags` value. No GLSL compiler is built in yet, so
+    /// after linting the source this always fails; compile shaders to SPIR-V
+    /// ahead of time and use `create_shader_from_spirv` instead.
+    pub fn compile_shader(&mut self, source: &str, kind: u32) -> Result<Vec<u32>, VulkanError> {
+        let stage = vk::ShaderStageFlags::from_raw(kind);
+        self.lint_shader(source, stage);
+        Err(VulkanError::ShaderCompilation(format!(
+            "can't compile the {:?} shader, no GLSL compiler is available",
+            stage
+        )))
+    }
+    
+    /// Begin rendering into the current swapchain image.
+    ///
+    /// Each attachment is cleared, loaded or left undefined according to
+    /// `actions`, which can also be a front-end `PassAction`. Load ops are
+    /// baked into Vulkan render passes, so one compatible render pass is
+    /// created and cached per combination.
+    pub fn begin_render_pass(&mut self, actions: impl Into<LoadActions>) -> Result<(), VulkanError> {
+        let actions = actions.into();
+        let render_pass = self.swapchain_render_pass(&actions)?;
+
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+        let framebuffer = *self
+            .framebuffers
+            .get(self.current_image_index as usize)
+            .ok_or(VulkanError::InvalidHandle)?;
+
+        let (color_clear, depth_clear) = actions.clear_values(self.color_space_mode());
+        let mut clear_values = vec![color_clear];
+        if self.depth_format.is_some() {
+            clear_values.push(depth_clear);
+        }
+
+        let begin_info = vk::RenderPassBeginInfo::builder()
+            .render_pass(render_pass)
+            .framebuffer(framebuffer)
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: self.swapchain_extent,
+            })
+            .clear_values(&clear_values);
+        unsafe {
+            device.cmd_begin_render_pass(cmd, &begin_info, vk::SubpassContents::INLINE);
+            reset_dynamic_state(device, cmd, self.swapchain_extent, self.flip_viewport_y);
+        }
+        self.pass_extent = self.swapchain_extent;
+        self.pass_attachments = Some((1, self.depth_format));
+        self.swapchain_pass_begun = true;
+        Ok(())
+    }
+    
+    pub fn end_render_pass(&mut self) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+        unsafe { device.cmd_end_render_pass(cmd) };
+        self.pass_attachments = None;
+        self.active_subpass = None;
+        Ok(())
+    }
+
+    /// Clear the attachments of the current render pass, leaving those passed
+    /// as `None` untouched. Outside of a render pass this records an empty
+    /// pass on the swapchain image whose load ops do the clearing.
+    pub fn clear(
+        &mut self,
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) -> Result<(), VulkanError> {
+        if color.is_none() && depth.is_none() && stencil.is_none() {
+            return Ok(());
+        }
+        let (color_count, depth_format) = match self.pass_attachments {
+            Some(attachments) => attachments,
+            None => {
+                self.begin_render_pass(PassAction::Clear {
+                    color,
+                    depth,
+                    stencil,
+                })?;
+                return self.end_render_pass();
+            }
+        };
+
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+
+        let mut clears = vec![];
+        if let Some((r, g, b, a)) = color {
+            let float32 = clear_color_value(self.color_space_mode(), [r, g, b, a]);
+            clears.extend((0..color_count).map(|attachment| vk::ClearAttachment {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                color_attachment: attachment,
+                clear_value: vk::ClearValue {
+                    color: vk::ClearColorValue { float32 },
+                },
+            }));
+        }
+        // Only clear the aspects the depth attachment actually has
+        let available = depth_format.map_or(vk::ImageAspectFlags::empty(), aspect_mask);
+        let mut aspect = vk::ImageAspectFlags::empty();
+        if depth.is_some() {
+            aspect |= vk::ImageAspectFlags::DEPTH;
+        }
+        if stencil.is_some() {
+            aspect |= vk::ImageAspectFlags::STENCIL;
+        }
+        aspect &= available;
+        if !aspect.is_empty() {
+            clears.push(vk::ClearAttachment {
+                aspect_mask: aspect,
+                color_attachment: 0,
+                clear_value: vk::ClearValue {
+                    depth_stencil: vk::ClearDepthStencilValue {
+                        depth: depth.unwrap_or(1.0),
+                        stencil: stencil.unwrap_or(0) as u32,
+                    },
+                },
+            });
+        }
+        if clears.is_empty() {
+            return Ok(());
+        }
+
+        let rect = vk::ClearRect {
+            rect: vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: self.pass_extent,
+            },
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        unsafe { device.cmd_clear_attachments(cmd, &clears, &[rect]) };
+        Ok(())
+    }
+
+    /// Record a pipeline barrier covering the whole of `buffer_id` on the
+    /// current frame's command buffer. Must be called outside a render pass.
+    pub fn buffer_memory_barrier(
+        &mut self,
+        buffer_id: usize,
+        src_stage: vk::PipelineStageFlags,
+        dst_stage: vk::PipelineStageFlags,
+        src_access: vk::AccessFlags,
+        dst_access: vk::AccessFlags,
+    ) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let buffer = self.buffers.get(&buffer_id).ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+
+        let barrier = vk::BufferMemoryBarrier::builder()
+            .src_access_mask(src_access)
+            .dst_access_mask(dst_access)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .buffer(buffer.buffer)
+            .offset(0)
+            .size(vk::WHOLE_SIZE)
+            .build();
+        unsafe {
+            device.cmd_pipeline_barrier(
+                cmd,
+                src_stage,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[barrier],
+                &[],
+            )
+        };
+        Ok(())
+    }
+
+    /// Record a pipeline barrier covering every layer of `texture_id` on the
+    /// current frame's command buffer, moving it from its current layout to
+    /// `new_layout`. Must be called outside a render pass.
+    pub fn image_memory_barrier(
+        &mut self,
+        texture_id: usize,
+        src_stage: vk::PipelineStageFlags,
+        dst_stage: vk::PipelineStageFlags,
+        src_access: vk::AccessFlags,
+        dst_access: vk::AccessFlags,
+        new_layout: vk::ImageLayout,
+    ) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let texture = self.textures.get(&texture_id).ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+
+        let barrier = vk::ImageMemoryBarrier::builder()
+            .old_layout(texture.current_layout)
+            .new_layout(new_layout)
+            .src_access_mask(src_access)
+            .dst_access_mask(dst_access)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(texture.image)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: vk::REMAINING_MIP_LEVELS,
+                base_array_layer: 0,
+                layer_count: vk::REMAINING_ARRAY_LAYERS,
+            })
+            .build();
+        unsafe {
+            device.cmd_pipeline_barrier(
+                cmd,
+                src_stage,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            )
+        };
+        self.textures.get_mut(&texture_id).unwrap().current_layout = new_layout;
+        Ok(())
+    }
+
+    /// Make compute shader writes to `buffer_id` visible to the following
+    /// draws, whether they read it as vertex, index or shader data.
+    pub fn compute_to_graphics_barrier(&mut self, buffer_id: usize) -> Result<(), VulkanError> {
+        self.buffer_memory_barrier(
+            buffer_id,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::VERTEX_INPUT
+                | vk::PipelineStageFlags::VERTEX_SHADER
+                | vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ
+                | vk::AccessFlags::INDEX_READ
+                | vk::AccessFlags::SHADER_READ,
+        )
+    }
+
+    /// Make compute shader writes to a storage image visible to fragment
+    /// shaders sampling it, moving it from its current layout, normally
+    /// `GENERAL`, to `SHADER_READ_ONLY_OPTIMAL`.
+    pub fn compute_to_graphics_image_barrier(&mut self, texture_id: usize) -> Result<(), VulkanError> {
+        self.image_memory_barrier(
+            texture_id,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::AccessFlags::SHADER_READ,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        )
+    }
+
+    /// Get a storage image ready for compute shaders to write, moving it
+    /// from its current layout to `GENERAL` once earlier sampling is done.
+    /// Follow the dispatch with `compute_to_graphics_image_barrier`.
+    pub fn graphics_to_compute_image_barrier(&mut self, texture_id: usize) -> Result<(), VulkanError> {
+        self.image_memory_barrier(
+            texture_id,
+            vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::empty(),
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+            vk::ImageLayout::GENERAL,
+        )
+    }
+
+    /// Layout `texture_id` is left in by the commands recorded so far
+    pub fn current_layout(&self, texture_id: usize) -> Result<vk::ImageLayout, VulkanError> {
+        let texture = self.textures.get(&texture_id).ok_or(VulkanError::InvalidHandle)?;
+        Ok(texture.current_layout)
+    }
+
+    /// Record a transition of `texture_id` from its current layout to
+    /// `layout` on the current frame's command buffer, nothing if it is
+    /// already there. Waits for and makes visible every earlier access, for
+    /// uses that don't declare a more precise one.
+    fn transition_texture(
+        &mut self,
+        texture_id: usize,
+        layout: vk::ImageLayout,
+    ) -> Result<(), VulkanError> {
+        let texture = self.textures.get(&texture_id).ok_or(VulkanError::InvalidHandle)?;
+        if texture.current_layout == layout {
+            return Ok(());
+        }
+        let transition = Transition {
+            src_stage: vk::PipelineStageFlags::ALL_COMMANDS,
+            dst_stage: vk::PipelineStageFlags::ALL_COMMANDS,
+            src_access: vk::AccessFlags::MEMORY_WRITE,
+            dst_access: vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+            old_layout: texture.current_layout,
+            new_layout: layout,
+        };
+        self.record_transitions(&[(FrameResource::Texture(texture_id), transition)])
+    }
+
+    /// Run the passes of `graph` in order on the current frame's command
+    /// buffer, recording the barriers and layout transitions their declared
+    /// uses need before each one. Must be called outside a render pass.
+    ///
+    /// Textures start out in their current layout and are moved to their
+    /// resting layout at the end, `DEPTH_STENCIL_ATTACHMENT_OPTIMAL` for
+    /// depth formats and `SHADER_READ_ONLY_OPTIMAL` for everything else, so
+    /// code outside of graphs can sample them right away.
+    pub fn execute_frame_graph(&mut self, graph: FrameGraph) -> Result<(), VulkanError> {
+        let mut states: HashMap<FrameResource, ResourceState> = HashMap::new();
+        for pass in graph.passes {
+            let mut transitions = vec![];
+            let mut entered = HashMap::new();
+            for &(resource, usage) in &pass.uses {
+                let next = usage.state(matches!(resource, FrameResource::Texture(_)));
+                match entered.insert(resource, next.layout) {
+                    Some(layout) if layout != next.layout => {
+                        return Err(VulkanError::SynchronizationFailed(format!(
+                            "pass \"{}\" uses {:?} in two layouts",
+                            pass.name, resource
+                        )));
+                    }
+                    Some(_) => continue,
+                    None => {}
+                }
+                let previous = match states.get(&resource) {
+                    Some(&state) => state,
+                    None => self.current_state(resource)?,
+                };
+                transitions.extend(transition(previous, next).map(|t| (resource, t)));
+                states.insert(resource, next);
+            }
+            self.record_transitions(&transitions)?;
+            (pass.record)(self)?;
+        }
+
+        let mut transitions = vec![];
+        for (resource, state) in states {
+            let id = match resource {
+                FrameResource::Texture(id) => id,
+                FrameResource::Buffer(_) => continue,
+            };
+            let texture = self.textures.get(&id).ok_or(VulkanError::InvalidHandle)?;
+            let resting = if aspect_mask(texture.format).contains(vk::ImageAspectFlags::DEPTH) {
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+            } else {
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            };
+            if state.layout != resting {
+                let next = ResourceState {
+                    stage: vk::PipelineStageFlags::ALL_COMMANDS,
+                    access: vk::AccessFlags::MEMORY_READ,
+                    ..ResourceState::resting(resting)
+                };
+                transitions.extend(transition(state, next).map(|t| (resource, t)));
+            }
+        }
+        self.record_transitions(&transitions)
+    }
+
+    /// State of `resource` before the first pass using it, in the layout
+    /// its last recorded use left it in
+    fn current_state(&self, resource: FrameResource) -> Result<ResourceState, VulkanError> {
+        let layout = match resource {
+            FrameResource::Texture(id) => {
+                self.textures.get(&id).ok_or(VulkanError::InvalidHandle)?.current_layout
+            }
+            FrameResource::Buffer(id) => {
+                self.buffers.get(&id).ok_or(VulkanError::InvalidHandle)?;
+                vk::ImageLayout::UNDEFINED
+            }
+        };
+        Ok(ResourceState::resting(layout))
+    }
+
+    /// Record `transitions` as a single pipeline barrier on the current
+    /// frame's command buffer, updating the current layout of the textures
+    fn record_transitions(
+        &mut self,
+        transitions: &[(FrameResource, Transition)],
+    ) -> Result<(), VulkanError> {
+        if transitions.is_empty() {
+            return Ok(());
+        }
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+
+        let mut src_stage = vk::PipelineStageFlags::empty();
+        let mut dst_stage = vk::PipelineStageFlags::empty();
+        let mut buffer_barriers = vec![];
+        let mut image_barriers = vec![];
+        for &(resource, t) in transitions {
+            src_stage |= t.src_stage;
+            dst_stage |= t.dst_stage;
+            match resource {
+                FrameResource::Buffer(id) => {
+                    let buffer = self.buffers.get(&id).ok_or(VulkanError::InvalidHandle)?;
+                    buffer_barriers.push(
+                        vk::BufferMemoryBarrier::builder()
+                            .src_access_mask(t.src_access)
+                            .dst_access_mask(t.dst_access)
+                            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                            .buffer(buffer.buffer)
+                            .offset(0)
+                            .size(vk::WHOLE_SIZE)
+                            .build(),
+                    );
+                }
+                FrameResource::Texture(id) => {
+                    let texture = self.textures.get(&id).ok_or(VulkanError::InvalidHandle)?;
+                    image_barriers.push(
+                        vk::ImageMemoryBarrier::builder()
+                            .old_layout(t.old_layout)
+                            .new_layout(t.new_layout)
+                            .src_access_mask(t.src_access)
+                            .dst_access_mask(t.dst_access)
+                            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                            .image(texture.image)
+                            .subresource_range(vk::ImageSubresourceRange {
+                                aspect_mask: aspect_mask(texture.format),
+                                base_mip_level: 0,
+                                level_count: vk::REMAINING_MIP_LEVELS,
+                                base_array_layer: 0,
+                                layer_count: vk::REMAINING_ARRAY_LAYERS,
+                            })
+                            .build(),
+                    );
+                }
+            }
+        }
+        unsafe {
+            device.cmd_pipeline_barrier(
+                cmd,
+                src_stage,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                &[],
+                &buffer_barriers,
+                &image_barriers,
+            )
+        };
+        for &(resource, t) in transitions {
+            if let FrameResource::Texture(id) = resource {
+                self.textures.get_mut(&id).unwrap().current_layout = t.new_layout;
+            }
+        }
+        Ok(())
+    }
+
+    /// The `local_size_x/y/z` declared by the compute shader of `pipeline_id`
+    pub fn compute_local_size(&self, pipeline_id: usize) -> Result<[u32; 3], VulkanError> {
+        self.pipelines
+            .get(pipeline_id)
+            .and_then(|pipeline| pipeline.local_size)
+            .ok_or(VulkanError::InvalidHandle)
+    }
+
+    /// Run compute `pipeline_id` with enough workgroups to cover
+    /// `width * height * depth` invocations, rounding the group count up.
+    /// Shaders should bounds check `gl_GlobalInvocationID` against the extent.
+    pub fn dispatch_for_extent(
+        &mut self,
+        pipeline_id: usize,
+        width: u32,
+        height: u32,
+        depth: u32,
+    ) -> Result<ComputeTicket, VulkanError> {
+        let [x, y, z] = self.compute_local_size(pipeline_id)?;
+        let groups = [width.div_ceil(x), height.div_ceil(y), depth.div_ceil(z)];
+        self.dispatch_compute(pipeline_id, groups)
+    }
+
+    /// Run compute `pipeline_id` with `groups` workgroups.
+    ///
+    /// With async compute the dispatch is submitted right away to the compute
+    /// queue and overlaps with rendering; pass the returned ticket to
+    /// `consume_compute` (or `wait_compute`) before using its results.
+    /// Otherwise it is recorded into the current frame's command buffer and
+    /// the ticket is already complete.
+    pub fn dispatch_compute(
+        &mut self,
+        pipeline_id: usize,
+        groups: [u32; 3],
+    ) -> Result<ComputeTicket, VulkanError> {
+        // Async dispatches may read what the batched updates write
+        if self.compute_queue.is_some() {
+            self.flush_commands()?;
+        }
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let pipeline = self
+            .pipelines
+            .get(pipeline_id)
+            .ok_or(VulkanError::InvalidHandle)?
+            .pipeline;
+        let [x, y, z] = groups;
+
+        let async_compute = (
+            self.compute_queue,
+            self.compute_command_pool,
+            self.compute_timeline,
+        );
+        let (queue, pool, timeline) = match async_compute {
+            (Some(queue), Some(pool), Some(timeline)) => (queue, pool, timeline),
+            _ => {
+                let cmd = *self
+                    .command_buffers
+                    .get(self.current_frame)
+                    .ok_or(VulkanError::InvalidHandle)?;
+                unsafe {
+                    device.cmd_bind_pipeline(cmd, vk::PipelineBindPoint::COMPUTE, pipeline);
+                    device.cmd_dispatch(cmd, x, y, z);
+                }
+                return Ok(ComputeTicket(0));
+            }
+        };
+
+        // Recycle command buffers of dispatches the GPU is done with
+        let completed = unsafe { device.get_semaphore_counter_value(timeline) }.map_err(sync_error)?;
+        let finished: Vec<vk::CommandBuffer> = self
+            .compute_in_flight
+            .iter()
+            .filter(|(value, _)| *value <= completed)
+            .map(|&(_, cmd)| cmd)
+            .collect();
+        if !finished.is_empty() {
+            unsafe { device.free_command_buffers(pool, &finished) };
+            self.compute_in_flight.retain(|(value, _)| *value > completed);
+        }
+
+        let cmd = allocate_command_buffer(device, pool)?;
+        let value = self.compute_timeline_value + 1;
+        let submitted = unsafe {
+            record_commands(device, cmd, |cmd| {
+                device.cmd_bind_pipeline(cmd, vk::PipelineBindPoint::COMPUTE, pipeline);
+                device.cmd_dispatch(cmd, x, y, z);
+            })
+            .and_then(|_| {
+                let signal_values = [value];
+                let mut timeline_info =
+                    vk::TimelineSemaphoreSubmitInfo::builder().signal_semaphore_values(&signal_values);
+                let command_buffers = [cmd];
+                let signal = [timeline];
+                let submit = vk::SubmitInfo::builder()
+                    .command_buffers(&command_buffers)
+                    .signal_semaphores(&signal)
+                    .push_next(&mut timeline_info);
+                device
+                    .queue_submit(queue, &[submit.build()], vk::Fence::null())
+                    .map_err(sync_error)
+            })
+        };
+        if let Err(e) = submitted {
+            unsafe { device.free_command_buffers(pool, &[cmd]) };
+            return Err(e);
+        }
+
+        self.submit_count += 1;
+        self.compute_timeline_value = value;
+        self.compute_in_flight.push((value, cmd));
+        Ok(ComputeTicket(value))
+    }
+
+    /// Make the next graphics submission wait for the dispatch behind `ticket`.
+    pub fn consume_compute(&mut self, ticket: ComputeTicket) {
+        self.compute_wait_value = self.compute_wait_value.max(ticket.0);
+    }
+
+    /// Block the calling thread until the dispatch behind `ticket` finished.
+    pub fn wait_compute(&self, ticket: ComputeTicket) -> Result<(), VulkanError> {
+        let timeline = match self.compute_timeline {
+            Some(timeline) if ticket.0 > 0 => timeline,
+            _ => return Ok(()),
+        };
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let semaphores = [timeline];
+        let values = [ticket.0];
+        let wait_info = vk::SemaphoreWaitInfo::builder()
+            .semaphores(&semaphores)
+            .values(&values);
+        unsafe { device.wait_semaphores(&wait_info, u64::MAX) }.map_err(sync_error)
+    }
+
+    /// Create a texture that can be rendered to and sampled afterwards.
+    /// `TextureFormat::Depth` creates a depth attachment.
+    pub fn create_render_texture(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Result<usize, VulkanError> {
+        self.create_render_texture_array(width, height, format, 1)
+    }
+
+    /// Render texture with `layers` array layers, one per view of a
+    /// multiview render target. Sampled as `sampler2DArray` when `layers > 1`.
+    pub fn create_render_texture_array(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        layers: u32,
+    ) -> Result<usize, VulkanError> {
+        let vk_format = self.texture_format(format);
+        let is_depth = aspect_mask(vk_format).contains(vk::ImageAspectFlags::DEPTH);
+        let (usage, layout) = if is_depth {
+            (
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+                    | vk::ImageUsageFlags::SAMPLED
+                    | vk::ImageUsageFlags::INPUT_ATTACHMENT,
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            )
+        } else {
+            (
+                vk::ImageUsageFlags::COLOR_ATTACHMENT
+                    | vk::ImageUsageFlags::SAMPLED
+                    | vk::ImageUsageFlags::TRANSFER_SRC
+                    | vk::ImageUsageFlags::INPUT_ATTACHMENT,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            )
+        };
+
+        let image_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(vk_format)
+            .extent(vk::Extent3D { width, height, depth: 1 })
+            .mip_levels(1)
+            .array_layers(layers)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .build();
+        let view_type = if layers > 1 {
+            vk::ImageViewType::TYPE_2D_ARRAY
+        } else {
+            vk::ImageViewType::TYPE_2D
+        };
+        let (image, view, allocation) = self.create_image_with_view(&image_info, view_type)?;
+
+        // Start out in the layout render passes expect when loading the contents
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: aspect_mask(vk_format),
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: layers,
+        };
+        let transitioned = self.batched_commands(|device, cmd| unsafe {
+            transition_image_layout(device, cmd, image, range, vk::ImageLayout::UNDEFINED, layout);
+        });
+        if let Err(e) = transitioned {
+            self.destroy_image(image, view, allocation);
+            return Err(e);
+        }
+
+        Ok(self.register_texture(image, view, allocation, &image_info, layout))
+    }
+
+    /// Create an offscreen render target from textures made by
+    /// `create_render_texture`. Fragment output `N` is written to
+    /// `color_textures[N]`; at most `maxColorAttachments` are allowed.
+    pub fn create_render_target(
+        &mut self,
+        color_textures: &[usize],
+        depth_texture: Option<usize>,
+    ) -> Result<usize, VulkanError> {
+        let layers = RenderTargetLayers::All(1);
+        self.build_render_target(color_textures, depth_texture, layers, &[])
+    }
+
+    /// Render target drawing every pass into `view_count` layers of its
+    /// attachments at once, e.g. both eyes for stereo rendering. Shaders
+    /// tell the views apart with `gl_ViewIndex` (`GL_EXT_multiview`). All
+    /// attachments need `view_count` layers, see `create_render_texture_array`.
+    ///
+    /// Fails when the device has no `multiview`; render each layer in a
+    /// pass of its own through `create_layer_render_target` instead.
+    pub fn create_multiview_render_target(
+        &mut self,
+        color_textures: &[usize],
+        depth_texture: Option<usize>,
+        view_count: u32,
+    ) -> Result<usize, VulkanError> {
+        if view_count > 1 && !self.multiview {
+            return Err(VulkanError::TextureCreationFailed(
+                "multiview is not supported, render the views one layer at a time".to_string(),
+            ));
+        }
+        // View masks are 32 bit, drivers usually allow 6 views or more
+        if view_count == 0 || view_count > 32 {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "multiview render target needs 1 to 32 views, got {}",
+                view_count
+            )));
+        }
+        let layers = RenderTargetLayers::All(view_count);
+        self.build_render_target(color_textures, depth_texture, layers, &[])
+    }
+
+    /// Render target for a single `layer` of array render textures, the
+    /// fallback for `create_multiview_render_target` on devices without
+    /// multiview.
+    pub fn create_layer_render_target(
+        &mut self,
+        color_textures: &[usize],
+        depth_texture: Option<usize>,
+        layer: u32,
+    ) -> Result<usize, VulkanError> {
+        let layers = RenderTargetLayers::Single(layer);
+        self.build_render_target(color_textures, depth_texture, layers, &[])
+    }
+
+    /// Anti-aliased offscreen render target without depth. Passes draw into
+    /// a multisampled color image with `samples` samples per pixel that is
+    /// resolved into a single sampled render texture when the pass ends.
+    /// That texture is `color_textures[0]` of the target and can be sampled
+    /// like any other render texture, e.g. to composite the result.
+    ///
+    /// A `transient` multisampled image is discarded after the resolve and
+    /// lives in lazily allocated memory where available, saving the memory
+    /// and bandwidth of all samples on tiled GPUs. Passes on such a target
+    /// can't use `LoadOp::Load`.
+    pub fn create_msaa_render_target(
+        &mut self,
+        width: u32,
+        height: u32,
+        samples: u32,
+        format: TextureFormat,
+        transient: bool,
+    ) -> Result<usize, VulkanError> {
+        let sample_count = sample_count_flags(samples)
+            .filter(|&flags| flags != vk::SampleCountFlags::TYPE_1)
+            .filter(|&flags| {
+                let limits = &self.device_properties.limits;
+                limits.framebuffer_color_sample_counts.contains(flags)
+            })
+            .ok_or_else(|| {
+                VulkanError::TextureCreationFailed(format!(
+                    "{} samples are not supported for multisampled render targets",
+                    samples
+                ))
+            })?;
+
+        let resolve_texture = self.create_render_texture(width, height, format)?;
+        let vk_format = self.textures[&resolve_texture].format;
+        let image_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(vk_format)
+            .extent(vk::Extent3D { width, height, depth: 1 })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(sample_count)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .build();
+        let attachment = match self.create_attachment_image(&image_info, transient) {
+            Ok(attachment) => attachment,
+            Err(e) => {
+                self.delete_texture(resolve_texture)?;
+                return Err(e);
+            }
+        };
+        let (image, view, ..) = attachment;
+        let color_attachment = vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL;
+        let msaa_texture =
+            self.register_attachment(attachment, &image_info, color_attachment, transient);
+
+        // Loading passes expect the multisampled image in its attachment layout
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let prepared = self
+            .batched_commands(|device, cmd| unsafe {
+                let undefined = vk::ImageLayout::UNDEFINED;
+                transition_image_layout(device, cmd, image, range, undefined, color_attachment);
+            })
+            .and_then(|_| {
+                let load_ops = vec![vk::AttachmentLoadOp::CLEAR];
+                let layout = TargetPassLayout {
+                    color_formats: &[vk_format],
+                    depth_format: None,
+                    view_count: 1,
+                    samples: sample_count,
+                    transient,
+                    subpasses: &[],
+                };
+                let render_pass = self.create_target_render_pass(&layout, &load_ops)?;
+                Ok((load_ops, render_pass))
+            });
+        let (load_ops, render_pass) = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                self.delete_texture(msaa_texture)?;
+                self.delete_texture(resolve_texture)?;
+                return Err(e);
+            }
+        };
+
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let views = [view, self.textures[&resolve_texture].view];
+        let framebuffer_info = vk::FramebufferCreateInfo::builder()
+            .render_pass(render_pass)
+            .attachments(&views)
+            .width(width)
+            .height(height)
+            .layers(1);
+        let framebuffer = match unsafe { device.create_framebuffer(&framebuffer_info, None) } {
+            Ok(framebuffer) => framebuffer,
+            Err(e) => {
+                unsafe { device.destroy_render_pass(render_pass, None) };
+                self.delete_texture(msaa_texture)?;
+                self.delete_texture(resolve_texture)?;
+                return Err(VulkanError::TextureCreationFailed(e.to_string()));
+            }
+        };
+
+        let mut render_passes = HashMap::new();
+        render_passes.insert(load_ops, render_pass);
+        let id = self.next_render_target_id;
+        self.next_render_target_id += 1;
+        self.render_targets.insert(
+            id,
+            VulkanRenderTarget {
+                color_textures: vec![resolve_texture],
+                depth_texture: None,
+                color_formats: vec![vk_format],
+                depth_format: None,
+                framebuffer,
+                extent: vk::Extent2D { width, height },
+                render_passes,
+                view_count: 1,
+                layer_views: Vec::new(),
+                samples: sample_count,
+                msaa_textures: vec![msaa_texture],
+                transient,
+                subpasses: Vec::new(),
+            },
+        );
+        Ok(id)
+    }
+
+    /// Depth (and stencil) texture that is only ever the depth attachment of
+    /// render targets and never sampled. A `transient` one is discarded at
+    /// the end of every pass and lives in lazily allocated memory where
+    /// available, which on tiled mobile GPUs means it never leaves tile
+    /// memory. Passes on its render targets can't load its depth.
+    pub fn create_depth_attachment(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        transient: bool,
+    ) -> Result<usize, VulkanError> {
+        let vk_format = self.texture_format(format);
+        let aspect_mask = aspect_mask(vk_format);
+        if !aspect_mask.contains(vk::ImageAspectFlags::DEPTH) {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "{:?} is not a depth format",
+                format
+            )));
+        }
+        let image_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(vk_format)
+            .extent(vk::Extent3D { width, height, depth: 1 })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+                    | vk::ImageUsageFlags::INPUT_ATTACHMENT,
+            )
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .build();
+        let attachment = self.create_attachment_image(&image_info, transient)?;
+        let image = attachment.0;
+        let depth_attachment = vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL;
+        let id = self.register_attachment(attachment, &image_info, depth_attachment, transient);
+
+        let range = vk::ImageSubresourceRange {
+            aspect_mask,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let transitioned = self.batched_commands(|device, cmd| unsafe {
+            transition_image_layout(
+                device,
+                cmd,
+                image,
+                range,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            );
+        });
+        if let Err(e) = transitioned {
+            self.delete_texture(id)?;
+            return Err(e);
+        }
+        Ok(id)
+    }
+
+    /// Render pass split into `subpasses`, e.g. a G-buffer fill followed by
+    /// a lighting subpass reading the G-buffer through `subpassLoad`. On
+    /// tiled GPUs the G-buffer then never has to leave tile memory, even
+    /// less so when its textures are transient, see `create_depth_attachment`.
+    ///
+    /// `begin_render_pass_to` starts in the first subpass, `next_subpass`
+    /// moves on to the next one. Bind the input attachments of a subpass
+    /// with `write_input_attachments`, and create pipelines for it with the
+    /// matching `GraphicsPipelineRecipe::subpass`. Color textures read as
+    /// input attachments have to come from `create_render_texture`.
+    pub fn create_subpass_render_target(
+        &mut self,
+        color_textures: &[usize],
+        depth_texture: Option<usize>,
+        subpasses: &[Subpass],
+    ) -> Result<usize, VulkanError> {
+        let depth_index = color_textures.len() as u32;
+        let attachment_count = depth_index + depth_texture.is_some() as u32;
+        let problem = if subpasses.is_empty() {
+            Some("render target needs at least one subpass".to_string())
+        } else {
+            subpasses.iter().enumerate().find_map(|(index, subpass)| {
+                let attachments = subpass.colors.iter().chain(&subpass.inputs);
+                if let Some(a) = attachments.clone().find(|&&a| a >= attachment_count) {
+                    Some(format!("subpass {} uses missing attachment {}", index, a))
+                } else if subpass.colors.contains(&depth_index) && depth_texture.is_some() {
+                    Some(format!("subpass {} writes the depth attachment as color", index))
+                } else if subpass.depth && depth_texture.is_none() {
+                    Some(format!("subpass {} tests depth without a depth texture", index))
+                } else if subpass.inputs.iter().any(|a| subpass.colors.contains(a))
+                    || subpass.depth && subpass.inputs.contains(&depth_index)
+                {
+                    Some(format!("subpass {} reads an attachment it writes", index))
+                } else {
+                    None
+                }
+            })
+        };
+        if let Some(problem) = problem {
+            return Err(VulkanError::TextureCreationFailed(problem));
+        }
+        let layers = RenderTargetLayers::All(1);
+        self.build_render_target(color_textures, depth_texture, layers, subpasses)
+    }
+
+    fn build_render_target(
+        &mut self,
+        color_textures: &[usize],
+        depth_texture: Option<usize>,
+        layers: RenderTargetLayers,
+        subpasses: &[Subpass],
+    ) -> Result<usize, VulkanError> {
+        let max_color_attachments = self.device_properties.limits.max_color_attachments as usize;
+        if color_textures.is_empty() || color_textures.len() > max_color_attachments {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "render target needs 1 to {} color attachments, got {}",
+                max_color_attachments,
+                color_textures.len()
+            )));
+        }
+
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let mut views = vec![];
+        let mut layer_views = vec![];
+        let mut color_formats = vec![];
+        let mut extent = None;
+        let mut problem = None;
+        for &id in color_textures.iter().chain(depth_texture.iter()) {
+            let texture = self.textures.get(&id).ok_or(VulkanError::InvalidHandle)?;
+            let size = (texture.width, texture.height);
+            if *extent.get_or_insert(size) != size {
+                problem = Some("render target attachments differ in size".to_string());
+                break;
+            }
+            match layers {
+                RenderTargetLayers::All(view_count) if texture.layers < view_count => {
+                    problem = Some(format!(
+                        "multiview render target needs {} layers, texture {} has {}",
+                        view_count, id, texture.layers
+                    ));
+                    break;
+                }
+                RenderTargetLayers::All(_) => views.push(texture.view),
+                RenderTargetLayers::Single(layer) if layer >= texture.layers => {
+                    problem = Some(format!("texture {} has no layer {}", id, layer));
+                    break;
+                }
+                RenderTargetLayers::Single(layer) => {
+                    let view_info = vk::ImageViewCreateInfo::builder()
+                        .image(texture.image)
+                        .view_type(vk::ImageViewType::TYPE_2D)
+                        .format(texture.format)
+                        .subresource_range(vk::ImageSubresourceRange {
+                            aspect_mask: aspect_mask(texture.format),
+                            base_mip_level: 0,
+                            level_count: 1,
+                            base_array_layer: layer,
+                            layer_count: 1,
+                        });
+                    match unsafe { device.create_image_view(&view_info, None) } {
+                        Ok(view) => {
+                            views.push(view);
+                            layer_views.push(view);
+                        }
+                        Err(e) => {
+                            problem = Some(e.to_string());
+                            break;
+                        }
+                    }
+                }
+            }
+            if color_formats.len() < color_textures.len() {
+                color_formats.push(texture.format);
+            }
+        }
+        if let Some(problem) = problem {
+            for view in layer_views {
+                unsafe { device.destroy_image_view(view, None) };
+            }
+            return Err(VulkanError::TextureCreationFailed(problem));
+        }
+        let depth_format = depth_texture.map(|id| self.textures[&id].format);
+        let transient = depth_texture.is_some_and(|id| self.textures[&id].transient);
+        let (width, height) = extent.unwrap_or_default();
+        let view_count = match layers {
+            RenderTargetLayers::All(view_count) => view_count,
+            RenderTargetLayers::Single(_) => 1,
+        };
+
+        let load_ops = vec![vk::AttachmentLoadOp::CLEAR; views.len()];
+        let layout = TargetPassLayout {
+            color_formats: &color_formats,
+            depth_format,
+            view_count,
+            samples: vk::SampleCountFlags::TYPE_1,
+            transient,
+            subpasses,
+        };
+        let render_pass = self.create_target_render_pass(&layout, &load_ops);
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let render_pass = match render_pass {
+            Ok(render_pass) => render_pass,
+            Err(e) => {
+                for view in layer_views {
+                    unsafe { device.destroy_image_view(view, None) };
+                }
+                return Err(e);
+            }
+        };
+        // Multiview broadcasts to the layers itself, the framebuffer stays single layered
+        let framebuffer_info = vk::FramebufferCreateInfo::builder()
+            .render_pass(render_pass)
+            .attachments(&views)
+            .width(width)
+            .height(height)
+            .layers(1);
+        let framebuffer = match unsafe { device.create_framebuffer(&framebuffer_info, None) } {
+            Ok(framebuffer) => framebuffer,
+            Err(e) => {
+                unsafe {
+                    device.destroy_render_pass(render_pass, None);
+                    for view in layer_views {
+                        device.destroy_image_view(view, None);
+                    }
+                }
+                return Err(VulkanError::TextureCreationFailed(e.to_string()));
+            }
+        };
+
+        let mut render_passes = HashMap::new();
+        render_passes.insert(load_ops, render_pass);
+        let id = self.next_render_target_id;
+        self.next_render_target_id += 1;
+        self.render_targets.insert(
+            id,
+            VulkanRenderTarget {
+                color_textures: color_textures.to_vec(),
+                depth_texture,
+                color_formats,
+                depth_format,
+                framebuffer,
+                extent: vk::Extent2D { width, height },
+                render_passes,
+                view_count,
+                layer_views,
+                samples: vk::SampleCountFlags::TYPE_1,
+                msaa_textures: Vec::new(),
+                transient,
+                subpasses: subpasses.to_vec(),
+            },
+        );
+        Ok(id)
+    }
+
+    /// Begin a render pass on an offscreen render target, with one load
+    /// action per color attachment and one for the depth attachment.
+    ///
+    /// `LoadOp::Load` keeps what earlier passes rendered, e.g. for TAA
+    /// history or trails. Every pass leaves the color attachments
+    /// `SHADER_READ_ONLY_OPTIMAL` and render textures are created in that
+    /// layout, which is where a loading pass expects them; attachments moved
+    /// elsewhere since are transitioned back first. A render pass is
+    /// created and cached per combination of load ops; they are all
+    /// compatible, so pipelines work with any of them. On a depth/stencil
+    /// attachment the depth op applies to the stencil aspect as well, which
+    /// a clearing op sets to `clear_stencil`.
+    ///
+    /// Clear depth to 1.0 normally and to 0.0 for reversed Z, see
+    /// `projection::perspective_reversed_z`.
+    pub fn begin_render_pass_to(
+        &mut self,
+        target_id: usize,
+        colors: &[LoadOp<[f32; 4]>],
+        depth: LoadOp<f32>,
+        clear_stencil: u32,
+    ) -> Result<(), VulkanError> {
+        let target = self.render_targets.get(&target_id).ok_or(VulkanError::InvalidHandle)?;
+        if colors.len() != target.color_textures.len() {
+            return Err(VulkanError::InvalidHandle);
+        }
+        // Transient attachments keep nothing from earlier passes
+        if target.transient && (colors.contains(&LoadOp::Load) || depth == LoadOp::Load) {
+            return Err(VulkanError::InvalidHandle);
+        }
+
+        // Passes leave every attachment in the layout a loading pass starts
+        // from, anything else recorded since has to be undone first
+        let (drawn, color_layout) = if target.msaa_textures.is_empty() {
+            (&target.color_textures, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        } else {
+            (&target.msaa_textures, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+        };
+        let depth_layout = vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL;
+        let mut attachments: Vec<(usize, vk::ImageLayout, bool)> = drawn
+            .iter()
+            .zip(colors)
+            .map(|(&texture, color)| (texture, color_layout, *color == LoadOp::Load))
+            .collect();
+        if !target.msaa_textures.is_empty() {
+            let read_only = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+            attachments.extend(target.color_textures.iter().map(|&t| (t, read_only, false)));
+        }
+        attachments.extend(target.depth_texture.map(|t| (t, depth_layout, depth == LoadOp::Load)));
+        for &(texture, layout, loaded) in &attachments {
+            if loaded {
+                self.transition_texture(texture, layout)?;
+            }
+        }
+
+        let target = &self.render_targets[&target_id];
+        let mode = self.color_space_mode();
+        let mut load_ops: Vec<vk::AttachmentLoadOp> = colors.iter().map(LoadOp::vk_load_op).collect();
+        let mut clear_values: Vec<vk::ClearValue> = colors
+            .iter()
+            .map(|color| vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: match color {
+                        LoadOp::Clear(color) => clear_color_value(mode, *color),
+                        _ => [0.0; 4],
+                    },
+                },
+            })
+            .collect();
+        if target.depth_format.is_some() {
+            load_ops.push(depth.vk_load_op());
+            clear_values.push(vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: match depth {
+                        LoadOp::Clear(depth) => depth,
+                        _ => 1.0,
+                    },
+                    stencil: clear_stencil,
+                },
+            });
+        }
+
+        let render_pass = match target.render_passes.get(&load_ops) {
+            Some(&render_pass) => render_pass,
+            None => {
+                let render_pass = self.create_target_render_pass(&target.pass_layout(), &load_ops)?;
+                self.render_targets
+                    .get_mut(&target_id)
+                    .unwrap()
+                    .render_passes
+                    .insert(load_ops, render_pass);
+                render_pass
+            }
+        };
+
+        let target = &self.render_targets[&target_id];
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+        let begin_info = vk::RenderPassBeginInfo::builder()
+            .render_pass(render_pass)
+            .framebuffer(target.framebuffer)
+            .render_area(vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: target.extent,
+            })
+            .clear_values(&clear_values);
+        unsafe {
+            device.cmd_begin_render_pass(cmd, &begin_info, vk::SubpassContents::INLINE);
+            reset_dynamic_state(device, cmd, target.extent, self.flip_viewport_y);
+        }
+        for (texture, layout, _) in attachments {
+            self.textures.get_mut(&texture).ok_or(VulkanError::InvalidHandle)?.current_layout =
+                layout;
+        }
+        let target = &self.render_targets[&target_id];
+        self.pass_extent = target.extent;
+        self.pass_attachments = Some(match target.subpasses.first() {
+            Some(subpass) => subpass.attachments(target.depth_format),
+            None => (target.color_textures.len() as u32, target.depth_format),
+        });
+        self.active_subpass = target.subpasses.first().map(|_| (target_id, 0));
+        Ok(())
+    }
+
+    /// Move on to the next subpass of a render target from
+    /// `create_subpass_render_target`. Its input attachments now hold what
+    /// the earlier subpasses wrote.
+    pub fn next_subpass(&mut self) -> Result<(), VulkanError> {
+        let (target_id, index) = self.active_subpass.ok_or(VulkanError::InvalidHandle)?;
+        let target = self.render_targets.get(&target_id).ok_or(VulkanError::InvalidHandle)?;
+        let subpass = target.subpasses.get(index as usize + 1).ok_or(VulkanError::InvalidHandle)?;
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+        unsafe { device.cmd_next_subpass(cmd, vk::SubpassContents::INLINE) };
+        self.pass_attachments = Some(subpass.attachments(target.depth_format));
+        self.active_subpass = Some((target_id, index + 1));
+        Ok(())
+    }
+
+    /// Point bindings 0, 1, ... of `set` at the input attachments of
+    /// `subpass` of a render target from `create_subpass_render_target`, in
+    /// the order of `Subpass::inputs`. The set layout needs one
+    /// `INPUT_ATTACHMENT` descriptor per binding, matching
+    /// `layout(input_attachment_index = N, set = S, binding = N) uniform subpassInput`.
+    pub fn write_input_attachments(
+        &self,
+        set: vk::DescriptorSet,
+        target_id: usize,
+        subpass: u32,
+    ) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let target = self.render_targets.get(&target_id).ok_or(VulkanError::InvalidHandle)?;
+        let subpass = target.subpasses.get(subpass as usize).ok_or(VulkanError::InvalidHandle)?;
+        let depth_index = target.color_textures.len() as u32;
+        let image_infos: Vec<vk::DescriptorImageInfo> = subpass
+            .inputs
+            .iter()
+            .map(|&attachment| {
+                let texture = match target.color_textures.get(attachment as usize) {
+                    Some(texture) => texture,
+                    None => target.depth_texture.as_ref().ok_or(VulkanError::InvalidHandle)?,
+                };
+                let texture = self.textures.get(texture).ok_or(VulkanError::InvalidHandle)?;
+                Ok(vk::DescriptorImageInfo {
+                    sampler: vk::Sampler::null(),
+                    image_view: texture.view,
+                    image_layout: input_attachment_layout(attachment, depth_index),
+                })
+            })
+            .collect::<Result<_, VulkanError>>()?;
+        let writes: Vec<vk::WriteDescriptorSet> = image_infos
+            .iter()
+            .zip(0..)
+            .map(|(info, binding)| {
+                vk::WriteDescriptorSet::builder()
+                    .dst_set(set)
+                    .dst_binding(binding)
+                    .descriptor_type(vk::DescriptorType::INPUT_ATTACHMENT)
+                    .image_info(std::slice::from_ref(info))
+                    .build()
+            })
+            .collect();
+        unsafe { device.update_descriptor_sets(&writes, &[]) };
+        Ok(())
+    }
+
+    /// Copy the first color attachment of `src_id` into the first color
+    /// attachment of `dst_id`, scaling between their extents with `filter`.
+    /// Records into the current frame's command buffer, so it must be called
+    /// outside a render pass. Both images are left `SHADER_READ_ONLY_OPTIMAL`.
+    pub fn blit_render_target(
+        &mut self,
+        src_id: usize,
+        dst_id: usize,
+        filter: FilterMode,
+    ) -> Result<(), VulkanError> {
+        let instance = self.instance.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let physical_device = self.physical_device.ok_or(VulkanError::InvalidHandle)?;
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+        if src_id == dst_id {
+            return Err(VulkanError::InvalidHandle);
+        }
+        let src = self.render_targets.get(&src_id).ok_or(VulkanError::InvalidHandle)?;
+        let dst = self.render_targets.get(&dst_id).ok_or(VulkanError::InvalidHandle)?;
+
+        let src_features = unsafe {
+            instance.get_physical_device_format_properties(physical_device, src.color_formats[0])
+        }
+        .optimal_tiling_features;
+        let dst_features = unsafe {
+            instance.get_physical_device_format_properties(physical_device, dst.color_formats[0])
+        }
+        .optimal_tiling_features;
+        if !src_features.contains(vk::FormatFeatureFlags::BLIT_SRC)
+            || !dst_features.contains(vk::FormatFeatureFlags::BLIT_DST)
+        {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "blitting {:?} to {:?} is not supported",
+                src.color_formats[0], dst.color_formats[0]
+            )));
+        }
+        let filter = vk_filter(filter);
+        if filter == vk::Filter::LINEAR
+            && !src_features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR)
+        {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "linear filtering of {:?} is not supported",
+                src.color_formats[0]
+            )));
+        }
+
+        let (src_texture, dst_texture) = (src.color_textures[0], dst.color_textures[0]);
+        let src_image = self.textures[&src_texture].image;
+        let dst_image = self.textures[&dst_texture].image;
+        let src_layout = self.textures[&src_texture].current_layout;
+        let dst_layout = self.textures[&dst_texture].current_layout;
+        let layers = vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let corner = |extent: vk::Extent2D| vk::Offset3D {
+            x: extent.width as i32,
+            y: extent.height as i32,
+            z: 1,
+        };
+        let region = vk::ImageBlit {
+            src_subresource: layers,
+            src_offsets: [vk::Offset3D::default(), corner(src.extent)],
+            dst_subresource: layers,
+            dst_offsets: [vk::Offset3D::default(), corner(dst.extent)],
+        };
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+
+        unsafe {
+            let read_only = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+            let transfer_src = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
+            let transfer_dst = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
+            transition_image_layout(device, cmd, src_image, range, src_layout, transfer_src);
+            transition_image_layout(device, cmd, dst_image, range, dst_layout, transfer_dst);
+            device.cmd_blit_image(
+                cmd,
+                src_image,
+                transfer_src,
+                dst_image,
+                transfer_dst,
+                &[region],
+                filter,
+            );
+            transition_image_layout(device, cmd, src_image, range, transfer_src, read_only);
+            transition_image_layout(device, cmd, dst_image, range, transfer_dst, read_only);
+        }
+        for texture in [src_texture, dst_texture] {
+            self.textures.get_mut(&texture).unwrap().current_layout =
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+        }
+        Ok(())
+    }
+
+    /// Render pass for an offscreen target. Color attachments end up in
+    /// `SHADER_READ_ONLY_OPTIMAL` so they can be sampled right after the pass.
+    /// With a `view_count` above 1 every draw goes to that many layers.
+    ///
+    /// With more than one sample the color attachments are multisampled and
+    /// stay `COLOR_ATTACHMENT_OPTIMAL`; each gets a single sampled resolve
+    /// attachment after the depth attachment, which is what ends up readable.
+    ///
+    /// `transient` discards the depth and multisampled color attachments at
+    /// the end of the pass instead of storing them.
+    fn create_target_render_pass(
+        &self,
+        layout: &TargetPassLayout,
+        load_ops: &[vk::AttachmentLoadOp],
+    ) -> Result<vk::RenderPass, VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let TargetPassLayout {
+            color_formats,
+            depth_format,
+            view_count,
+            samples,
+            transient,
+            ..
+        } = *layout;
+        let multisampled = samples != vk::SampleCountFlags::TYPE_1;
+        let color_layout = if multisampled {
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL
+        } else {
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+        };
+        let discarded_store_op = if transient {
+            vk::AttachmentStoreOp::DONT_CARE
+        } else {
+            vk::AttachmentStoreOp::STORE
+        };
+        let color_store_op = if multisampled {
+            discarded_store_op
+        } else {
+            vk::AttachmentStoreOp::STORE
+        };
+
+        let mut attachments: Vec<vk::AttachmentDescription> = color_formats
+            .iter()
+            .zip(load_ops)
+            .map(|(&format, &load_op)| vk::AttachmentDescription {
+                format,
+                samples,
+                load_op,
+                store_op: color_store_op,
+                stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+                stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+                initial_layout: if load_op == vk::AttachmentLoadOp::LOAD {
+                    color_layout
+                } else {
+                    vk::ImageLayout::UNDEFINED
+                },
+                final_layout: color_layout,
+                ..Default::default()
+            })
+            .collect();
+        if let Some(depth_format) = depth_format {
+            let load_op = load_ops[color_formats.len()];
+            // A loading pass has to keep the stencil aspect too
+            let has_stencil = aspect_mask(depth_format).contains(vk::ImageAspectFlags::STENCIL);
+            let (stencil_load_op, stencil_store_op) = if has_stencil {
+                (load_op, discarded_store_op)
+            } else {
+                (vk::AttachmentLoadOp::DONT_CARE, vk::AttachmentStoreOp::DONT_CARE)
+            };
+            attachments.push(vk::AttachmentDescription {
+                format: depth_format,
+                samples: vk::SampleCountFlags::TYPE_1,
+                load_op,
+                store_op: discarded_store_op,
+                stencil_load_op,
+                stencil_store_op,
+                initial_layout: if load_op == vk::AttachmentLoadOp::LOAD {
+                    vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+                } else {
+                    vk::ImageLayout::UNDEFINED
+                },
+                final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                ..Default::default()
+            });
+        }
+        let resolve_start = attachments.len() as u32;
+        if multisampled {
+            // Every sample gets written, the previous contents never matter
+            attachments.extend(color_formats.iter().map(|&format| vk::AttachmentDescription {
+                format,
+                samples: vk::SampleCountFlags::TYPE_1,
+                load_op: vk::AttachmentLoadOp::DONT_CARE,
+                store_op: vk::AttachmentStoreOp::STORE,
+                stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+                stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+                initial_layout: vk::ImageLayout::UNDEFINED,
+                final_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                ..Default::default()
+            }));
+        }
+
+        let depth_index = color_formats.len() as u32;
+        let implicit = [Subpass {
+            colors: (0..depth_index).collect(),
+            inputs: Vec::new(),
+            depth: depth_format.is_some(),
+        }];
+        let subpasses = if layout.subpasses.is_empty() {
+            &implicit[..]
+        } else {
+            layout.subpasses
+        };
+        let reference = |attachment, layout| vk::AttachmentReference { attachment, layout };
+        let color_layout = vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL;
+        let color_refs: Vec<Vec<vk::AttachmentReference>> = subpasses
+            .iter()
+            .map(|subpass| subpass.colors.iter().map(|&a| reference(a, color_layout)).collect())
+            .collect();
+        let resolve_refs: Vec<Vec<vk::AttachmentReference>> = subpasses
+            .iter()
+            .map(|subpass| {
+                let colors = subpass.colors.iter();
+                colors.map(|&a| reference(resolve_start + a, color_layout)).collect()
+            })
+            .collect();
+        let input_refs: Vec<Vec<vk::AttachmentReference>> = subpasses
+            .iter()
+            .map(|subpass| {
+                let inputs = subpass.inputs.iter();
+                inputs.map(|&a| reference(a, input_attachment_layout(a, depth_index))).collect()
+            })
+            .collect();
+        // Contents written before a subpass and read after it have to be preserved
+        let preserved: Vec<Vec<u32>> = (0..subpasses.len())
+            .map(|index| {
+                let (before, rest) = subpasses.split_at(index);
+                let (current, after) = rest.split_first().unwrap();
+                (0..resolve_start)
+                    .filter(|&a| !current.uses(a, depth_index))
+                    .filter(|&a| before.iter().any(|subpass| subpass.uses(a, depth_index)))
+                    .filter(|&a| after.iter().any(|subpass| subpass.uses(a, depth_index)))
+                    .collect()
+            })
+            .collect();
+        let depth_ref = reference(depth_index, vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+        let descriptions: Vec<vk::SubpassDescription> = subpasses
+            .iter()
+            .enumerate()
+            .map(|(index, subpass)| {
+                let mut description = vk::SubpassDescription::builder()
+                    .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+                    .color_attachments(&color_refs[index])
+                    .input_attachments(&input_refs[index])
+                    .preserve_attachments(&preserved[index]);
+                if multisampled {
+                    description = description.resolve_attachments(&resolve_refs[index]);
+                }
+                if subpass.depth {
+                    description = description.depth_stencil_attachment(&depth_ref);
+                }
+                description.build()
+            })
+            .collect();
+
+        // Earlier passes may still sample the attachments, later ones will
+        let attachment_stages = vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+            | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+            | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS;
+        let attachment_writes = vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE;
+        let last_subpass = descriptions.len() as u32 - 1;
+        let mut dependencies = vec![
+            vk::SubpassDependency {
+                src_subpass: vk::SUBPASS_EXTERNAL,
+                dst_subpass: 0,
+                src_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+                dst_stage_mask: attachment_stages,
+                src_access_mask: vk::AccessFlags::SHADER_READ,
+                dst_access_mask: attachment_writes,
+                dependency_flags: vk::DependencyFlags::BY_REGION,
+            },
+            vk::SubpassDependency {
+                src_subpass: last_subpass,
+                dst_subpass: vk::SUBPASS_EXTERNAL,
+                src_stage_mask: attachment_stages,
+                dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+                src_access_mask: attachment_writes,
+                dst_access_mask: vk::AccessFlags::SHADER_READ,
+                dependency_flags: vk::DependencyFlags::BY_REGION,
+            },
+        ];
+        // Each subpass reads what the previous one wrote at the same pixel
+        dependencies.extend((1..=last_subpass).map(|subpass| vk::SubpassDependency {
+            src_subpass: subpass - 1,
+            dst_subpass: subpass,
+            src_stage_mask: attachment_stages,
+            dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER | attachment_stages,
+            src_access_mask: attachment_writes,
+            dst_access_mask: vk::AccessFlags::INPUT_ATTACHMENT_READ | attachment_writes,
+            dependency_flags: vk::DependencyFlags::BY_REGION,
+        }));
+
+        let view_masks = vec![u32::MAX >> (32 - view_count); descriptions.len()];
+        let correlation_masks = [view_masks[0]];
+        let mut multiview_info = vk::RenderPassMultiviewCreateInfo::builder()
+            .view_masks(&view_masks)
+            .correlation_masks(&correlation_masks);
+        let mut create_info = vk::RenderPassCreateInfo::builder()
+            .attachments(&attachments)
+            .subpasses(&descriptions)
+            .dependencies(&dependencies);
+        if view_count > 1 {
+            create_info = create_info.push_next(&mut multiview_info);
+        }
+        unsafe { device.create_render_pass(&create_info, None) }
+            .map_err(|e| VulkanError::InitializationFailed(e.to_string()))
+    }
+
+    /// Create a render pass targeting the swapchain (plus the depth buffer if
+    /// there is one) with the load ops from `actions`.
+    fn create_swapchain_render_pass(&self, actions: &LoadActions) -> Result<vk::RenderPass, VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let [color_load, depth_load, stencil_load] = actions.vk_load_ops();
+
+        let mut attachments = vec![vk::AttachmentDescription {
+            format: self.swapchain_image_format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: color_load,
+            store_op: vk::AttachmentStoreOp::STORE,
+            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            // Loading needs the image to hold the previous frame's contents
+            initial_layout: if color_load == vk::AttachmentLoadOp::LOAD {
+                vk::ImageLayout::PRESENT_SRC_KHR
+            } else {
+                vk::ImageLayout::UNDEFINED
+            },
+            final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+            ..Default::default()
+        }];
+        if let Some(depth_format) = self.depth_format {
+            let loads_depth = depth_load == vk::AttachmentLoadOp::LOAD
+                || stencil_load == vk::AttachmentLoadOp::LOAD;
+            attachments.push(vk::AttachmentDescription {
+                format: depth_format,
+                samples: vk::SampleCountFlags::TYPE_1,
+                load_op: depth_load,
+                store_op: vk::AttachmentStoreOp::STORE,
+                stencil_load_op: stencil_load,
+                stencil_store_op: vk::AttachmentStoreOp::STORE,
+                initial_layout: if loads_depth {
+                    vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+                } else {
+                    vk::ImageLayout::UNDEFINED
+                },
+                final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                ..Default::default()
+            });
+        }
+
+        let color_refs = [vk::AttachmentReference {
+            attachment: 0,
+            layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+        }];
+        let depth_ref = vk::AttachmentReference {
+            attachment: 1,
+            layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        };
+        let mut subpass = vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_refs);
+        if self.depth_format.is_some() {
+            subpass = subpass.depth_stencil_attachment(&depth_ref);
+        }
+        let subpasses = [subpass.build()];
+
+        let stages = vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+            | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS;
+        let dependencies = [vk::SubpassDependency {
+            src_subpass: vk::SUBPASS_EXTERNAL,
+            dst_subpass: 0,
+            src_stage_mask: stages,
+            dst_stage_mask: stages,
+            src_access_mask: vk::AccessFlags::empty(),
+            dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            dependency_flags: vk::DependencyFlags::empty(),
+        }];
+
+        let create_info = vk::RenderPassCreateInfo::builder()
+            .attachments(&attachments)
+            .subpasses(&subpasses)
+            .dependencies(&dependencies);
+        unsafe { device.create_render_pass(&create_info, None) }
+            .map_err(|e| VulkanError::InitializationFailed(e.to_string()))
+    }
+    
+    pub fn get_memory_budget(&self) -> (u64, u64, u64, u64) {
+        (0, 0, 0, 0) // total_size, allocated_size, available_memory, peak_memory_usage
+    }
+    
+    pub fn initialize(&mut self, display: &dyn crate::native::NativeDisplay) -> Result<(), VulkanError> {
+        self.dpi_scale = display.dpi_scale();
+        self.swapchain_extent = self.configured_extent();
+        let headless = self.display.as_ref().is_some_and(|conf| conf.headless);
+        if !headless {
+            self.window_handles = display.raw_window_handles();
+        }
+        self.init_vulkan()?;
+        if self.device.is_some() {
+            self.create_sync_objects()?;
+            self.create_uniform_rings()?;
+            self.create_swapchain()?;
+            self.create_swapchain_images()?;
+        }
+        Ok(())
+    }
+    
+    /// Add another native window rendered with the shared device, with a
+    /// surface and swapchain of its own. `width` x `height` physical pixels
+    /// is its size for surfaces that don't report one, `resize` changes it
+    /// while the window is current.
+    ///
+    /// The graphics queue has to be able to present to the window, which
+    /// holds for windows of the display the context was initialized on.
+    pub fn add_window<W>(
+        &mut self,
+        window: &W,
+        width: u32,
+        height: u32,
+    ) -> Result<WindowId, VulkanError>
+    where
+        W: HasRawDisplayHandle + HasRawWindowHandle,
+    {
+        let window_handles = (window.raw_display_handle(), window.raw_window_handle());
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        // One acquire and one render semaphore per frame slot
+        let semaphore_info = vk::SemaphoreCreateInfo::default();
+        let mut semaphores = vec![];
+        for _ in 0..self.max_frames_in_flight * 2 {
+            match unsafe { device.create_semaphore(&semaphore_info, None) } {
+                Ok(semaphore) => semaphores.push(semaphore),
+                Err(e) => {
+                    for semaphore in semaphores {
+                        unsafe { device.destroy_semaphore(semaphore, None) };
+                    }
+                    return Err(VulkanError::SynchronizationFailed(e.to_string()));
+                }
+            }
+        }
+        let render_finished_semaphores = semaphores.split_off(self.max_frames_in_flight);
+        let window = VulkanWindow {
+            swapchain_image_format: self.swapchain_image_format,
+            swapchain_color_space: self.swapchain_color_space,
+            swapchain_composite_alpha: self.swapchain_composite_alpha,
+            swapchain_usage: self.swapchain_usage,
+            present_mode: self.present_mode,
+            swapchain_extent: vk::Extent2D { width, height },
+            window_extent: Some(vk::Extent2D { width, height }),
+            window_handles: Some(window_handles),
+            image_available_semaphores: semaphores,
+            render_finished_semaphores,
+            ..Default::default()
+        };
+
+        let id = WindowId(self.next_window_id);
+        self.next_window_id += 1;
+        self.windows.insert(id, window);
+
+        // The swapchain setup works on the current window
+        let previous = self.current_window;
+        self.make_current(id)?;
+        let created = self.create_window_swapchain();
+        self.make_current(previous)?;
+        if let Err(e) = created {
+            if let Some(window) = self.windows.remove(&id) {
+                self.destroy_window(window);
+            }
+            return Err(e);
+        }
+        Ok(id)
+    }
+
+    fn create_window_swapchain(&mut self) -> Result<(), VulkanError> {
+        self.create_surface()?;
+        let physical_device = self.physical_device.ok_or(VulkanError::InvalidHandle)?;
+        let queue_family = self.queue_family_index.ok_or(VulkanError::InvalidHandle)?;
+        if !self.get_surface_support(physical_device, queue_family) {
+            return Err(VulkanError::InitializationFailed(
+                "the graphics queue can't present to the window".to_string(),
+            ));
+        }
+        self.create_swapchain()?;
+        self.create_swapchain_images()
+    }
+
+    /// Destroy a window added with `add_window`. It can't be the current one.
+    pub fn remove_window(&mut self, id: WindowId) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let window = self.windows.remove(&id).ok_or(VulkanError::InvalidHandle)?;
+        // Its semaphores may still be waited on by submitted frames
+        unsafe { device.device_wait_idle() }.map_err(sync_error)?;
+        self.destroy_window(window);
+        Ok(())
+    }
+
+    /// Destroy what a parked window owns, down to its surface
+    fn destroy_window(&self, window: VulkanWindow) {
+        let device = match self.device.as_ref() {
+            Some(device) => device,
+            None => return,
+        };
+        unsafe {
+            for semaphore in window
+                .image_available_semaphores
+                .into_iter()
+                .chain(window.render_finished_semaphores)
+            {
+                device.destroy_semaphore(semaphore, None);
+            }
+            for framebuffer in window.framebuffers {
+                device.destroy_framebuffer(framebuffer, None);
+            }
+            for view in window.swapchain_image_views {
+                device.destroy_image_view(view, None);
+            }
+            if let Some((image, view, allocation)) = window.swapchain_depth {
+                device.destroy_image_view(view, None);
+                device.destroy_image(image, None);
+                if let Some(allocator) = lock(&self.allocator).as_mut() {
+                    let _ = allocator.free(allocation);
+                }
+            }
+            let cached = window.render_pass_cache.into_values();
+            for render_pass in window.render_pass.into_iter().chain(cached) {
+                device.destroy_render_pass(render_pass, None);
+            }
+            if let (Some(loader), Some(swapchain)) = (&self.swapchain_loader, window.swapchain) {
+                loader.destroy_swapchain(swapchain, None);
+            }
+            if let (Some(loader), Some(surface)) = (&self.surface_loader, window.surface) {
+                loader.destroy_surface(surface, None);
+            }
+        }
+    }
+
+    /// Direct the following swapchain operations (`begin_frame`,
+    /// `begin_render_pass`, `end_frame`, `present`, ...) at window `id`.
+    /// Frame slots and command buffers are shared, so each window gets its
+    /// own complete frame.
+    pub fn make_current(&mut self, id: WindowId) -> Result<(), VulkanError> {
+        if id == self.current_window {
+            return Ok(());
+        }
+        let mut window = self.windows.remove(&id).ok_or(VulkanError::InvalidHandle)?;
+        self.swap_window_state(&mut window);
+        self.windows.insert(self.current_window, window);
+        self.current_window = id;
+        Ok(())
+    }
+
+    /// `make_current` followed by `begin_frame`
+    pub fn begin_window_frame(&mut self, id: WindowId) -> Result<usize, VulkanError> {
+        self.make_current(id)?;
+        self.begin_frame()
+    }
+
+    /// Present the frame recorded for window `id`
+    pub fn present_window(&mut self, id: WindowId) -> Result<(), VulkanError> {
+        self.make_current(id)?;
+        self.present()
+    }
+
+    fn swap_window_state(&mut self, window: &mut VulkanWindow) {
+        use std::mem::swap;
+        swap(&mut self.window_handles, &mut window.window_handles);
+        swap(&mut self.surface, &mut window.surface);
+        swap(&mut self.swapchain, &mut window.swapchain);
+        swap(&mut self.swapchain_images, &mut window.swapchain_images);
+        swap(&mut self.swapchain_min_image_count, &mut window.swapchain_min_image_count);
+        swap(&mut self.swapchain_image_views, &mut window.swapchain_image_views);
+        swap(&mut self.swapchain_depth, &mut window.swapchain_depth);
+        swap(&mut self.swapchain_image_format, &mut window.swapchain_image_format);
+        swap(&mut self.swapchain_color_space, &mut window.swapchain_color_space);
+        swap(&mut self.swapchain_composite_alpha, &mut window.swapchain_composite_alpha);
+        swap(&mut self.swapchain_usage, &mut window.swapchain_usage);
+        swap(&mut self.present_mode, &mut window.present_mode);
+        swap(&mut self.swapchain_extent, &mut window.swapchain_extent);
+        swap(&mut self.window_extent, &mut window.window_extent);
+        swap(&mut self.render_pass, &mut window.render_pass);
+        swap(&mut self.framebuffers, &mut window.framebuffers);
+        swap(&mut self.render_pass_cache, &mut window.render_pass_cache);
+        swap(&mut self.current_image_index, &mut window.current_image_index);
+        swap(&mut self.image_available_semaphores, &mut window.image_available_semaphores);
+        swap(&mut self.render_finished_semaphores, &mut window.render_finished_semaphores);
+        swap(&mut self.images_in_flight, &mut window.images_in_flight);
+    }
+
+    pub fn present(&mut self) -> Result<(), VulkanError> {
+        if self.surface_lost || self.frame_skipped {
+            return Ok(());
+        }
+        let present_time = self.queue_present_time();
+        let presentable = (self.swapchain_loader.as_ref(), self.swapchain, self.graphics_queue);
+        if let (Some(loader), Some(swapchain), Some(queue)) = presentable {
+            let wait_semaphores = [self.render_finished_semaphores[self.current_frame]];
+            let swapchains = [swapchain];
+            let image_indices = [self.current_image_index];
+            let present_times: Vec<_> = present_time.into_iter().collect();
+            let mut times_info = vk::PresentTimesInfoGOOGLE::builder().times(&present_times);
+            let mut present_info = vk::PresentInfoKHR::builder()
+                .wait_semaphores(&wait_semaphores)
+                .swapchains(&swapchains)
+                .image_indices(&image_indices);
+            // Tags the present with the id display timing reports it under
+            if !present_times.is_empty() {
+                present_info = present_info.push_next(&mut times_info);
+            }
+            let presented = {
+                let _queue = lock(&self.queue_lock);
+                unsafe { loader.queue_present(queue, &present_info) }
+            };
+            match presented {
+                Ok(false) => {}
+                // Suboptimal still presented, the next frame gets a matching swapchain
+                Ok(true) | Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => self.recreate_swapchain()?,
+                Err(e) => return Err(sync_error(e)),
+            }
+        }
+        self.update_present_latency();
+        self.current_frame = (self.current_frame + 1) % self.max_frames_in_flight;
+        self.last_submit_count = std::mem::take(&mut self.submit_count);
+        self.limit_frame_rate();
+
+        let now = Instant::now();
+        if let Some(last_present) = self.last_present {
+            self.frame_time = (now - last_present).as_secs_f32();
+            self.cpu_frame_times.push(self.frame_time);
+        }
+        self.last_present = Some(now);
+        Ok(())
+    }
+
+    /// Milliseconds between presenting the latest measured frame and it
+    /// reaching the display, for tuning audio and input offsets.
+    ///
+    /// With `VK_GOOGLE_display_timing` this is what the driver reports, a
+    /// few frames after the fact. Elsewhere it is estimated from the CPU
+    /// time between acquiring the swapchain image and presenting it plus
+    /// one frame of scanout. `None` before the first measurement.
+    pub fn last_present_latency_ms(&self) -> Option<f32> {
+        self.present_latency
+    }
+
+    /// Id the next present is reported under by display timing, `None`
+    /// when the driver can't report timings or they can't be related to
+    /// the present call
+    fn queue_present_time(&mut self) -> Option<vk::PresentTimeGOOGLE> {
+        self.display_timing.as_ref()?;
+        let queued = monotonic_time_ns()?;
+        let present_id = self.next_present_id;
+        self.next_present_id = self.next_present_id.wrapping_add(1);
+        // Drivers drop timings of presents that never made it to the display
+        if self.pending_presents.len() >= 64 {
+            self.pending_presents.pop_front();
+        }
+        self.pending_presents.push_back((present_id, queued));
+        Some(vk::PresentTimeGOOGLE {
+            present_id,
+            desired_present_time: 0,
+        })
+    }
+
+    fn update_present_latency(&mut self) {
+        let acquired = self.acquire_time.take();
+        let measured = (self.device.as_ref(), self.swapchain, self.display_timing.as_ref());
+        let (device, swapchain, timing) = match measured {
+            (Some(device), Some(swapchain), Some(timing)) if monotonic_time_ns().is_some() => {
+                (device, swapchain, timing)
+            }
+            _ => {
+                if let Some(acquired) = acquired {
+                    let presenting = acquired.elapsed().as_secs_f32() + self.frame_time;
+                    self.present_latency = Some(presenting * 1000.0);
+                }
+                return;
+            }
+        };
+
+        let get_timings = timing.get_past_presentation_timing_google;
+        let mut count = 0;
+        let counted = unsafe {
+            get_timings(device.handle(), swapchain, &mut count, std::ptr::null_mut())
+        };
+        if counted != vk::Result::SUCCESS || count == 0 {
+            return;
+        }
+        let mut timings = vec![vk::PastPresentationTimingGOOGLE::default(); count as usize];
+        let fetched =
+            unsafe { get_timings(device.handle(), swapchain, &mut count, timings.as_mut_ptr()) };
+        if fetched != vk::Result::SUCCESS && fetched != vk::Result::INCOMPLETE {
+            return;
+        }
+        timings.truncate(count as usize);
+        for timing in timings {
+            // Timings come in present order, earlier ids were skipped
+            while let Some(&(present_id, queued)) = self.pending_presents.front() {
+                if present_id.wrapping_sub(timing.present_id) as i32 > 0 {
+                    break;
+                }
+                self.pending_presents.pop_front();
+                if present_id == timing.present_id {
+                    let latency = timing.actual_present_time.saturating_sub(queued);
+                    self.present_latency = Some(latency as f32 / 1_000_000.0);
+                }
+            }
+        }
+    }
+
+    /// Which queue families were picked for each kind of work.
+    pub fn queue_info(&self) -> QueueInfo {
+        QueueInfo {
+            graphics_family: self.queue_family_index,
+            present_family: self.present_queue_family_index,
+            transfer_family: self.transfer_queue_family_index,
+            compute_family: self.compute_queue_family_index,
+            present_shares_graphics: self.present_queue_family_index.is_some()
+                && self.present_queue_family_index == self.queue_family_index,
+            family_flags: self.queue_family_flags.clone(),
+        }
+    }
+
+    /// Allocate a descriptor set with `layout`, e.g. for the textures of a
+    /// material. Sets live as long as the device. When the current pool runs
+    /// out another one sized by `Platform::descriptor_pool_sizes` is added,
+    /// so this only fails when the device is out of memory.
+    pub fn allocate_descriptor_set(
+        &mut self,
+        layout: vk::DescriptorSetLayout,
+    ) -> Result<vk::DescriptorSet, VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let sizes = self
+            .display
+            .as_ref()
+            .map_or_else(DescriptorPoolSizes::default, |conf| {
+                conf.platform.descriptor_pool_sizes
+            });
+        self.descriptor_pools.allocate(device, layout, &sizes)
+    }
+
+    /// Point `binding` of `set` at `texture_id`, sampled with `sampler_id`
+    /// or, when `None`, with the `default_sampler`.
+    pub fn write_texture_descriptor(
+        &mut self,
+        set: vk::DescriptorSet,
+        binding: u32,
+        texture_id: usize,
+        sampler_id: Option<usize>,
+    ) -> Result<(), VulkanError> {
+        let sampler_id = match sampler_id {
+            Some(sampler_id) => sampler_id,
+            None => self.default_sampler()?,
+        };
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let texture = self.textures.get(&texture_id).ok_or(VulkanError::InvalidHandle)?;
+        let sampler = *self.samplers.get(sampler_id).ok_or(VulkanError::InvalidHandle)?;
+        let image_info = [vk::DescriptorImageInfo {
+            sampler,
+            image_view: texture.view,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        }];
+        let write = vk::WriteDescriptorSet::builder()
+            .dst_set(set)
+            .dst_binding(binding)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&image_info);
+        unsafe { device.update_descriptor_sets(&[write.build()], &[]) };
+        Ok(())
+    }
+
+    /// Point `binding` of `set` at the whole of `buffer_id`, as a storage
+    /// buffer descriptor when it was created with `STORAGE_BUFFER` usage and
+    /// as a uniform buffer one otherwise.
+    pub fn write_buffer_descriptor(
+        &mut self,
+        set: vk::DescriptorSet,
+        binding: u32,
+        buffer_id: usize,
+    ) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let buffer = self.buffers.get(&buffer_id).ok_or(VulkanError::InvalidHandle)?;
+        let descriptor_type = if buffer.usage.contains(vk::BufferUsageFlags::STORAGE_BUFFER) {
+            vk::DescriptorType::STORAGE_BUFFER
+        } else if buffer.usage.contains(vk::BufferUsageFlags::UNIFORM_BUFFER) {
+            vk::DescriptorType::UNIFORM_BUFFER
+        } else {
+            return Err(VulkanError::BufferCreationFailed(format!(
+                "buffer {} is neither a storage nor a uniform buffer",
+                buffer_id
+            )));
+        };
+        let buffer_info = [vk::DescriptorBufferInfo {
+            buffer: buffer.buffer,
+            offset: 0,
+            range: vk::WHOLE_SIZE,
+        }];
+        let write = vk::WriteDescriptorSet::builder()
+            .dst_set(set)
+            .dst_binding(binding)
+            .descriptor_type(descriptor_type)
+            .buffer_info(&buffer_info);
+        unsafe { device.update_descriptor_sets(&[write.build()], &[]) };
+        Ok(())
+    }
+
+    /// Point `binding` of `set` at `texture_id` as a storage image, which
+    /// compute shaders access in the `GENERAL` layout. The texture has to be
+    /// made by `create_storage_texture`.
+    pub fn write_storage_image_descriptor(
+        &mut self,
+        set: vk::DescriptorSet,
+        binding: u32,
+        texture_id: usize,
+    ) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let texture = self.textures.get(&texture_id).ok_or(VulkanError::InvalidHandle)?;
+        if !texture.usage.contains(vk::ImageUsageFlags::STORAGE) {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "texture {} was not created with storage usage",
+                texture_id
+            )));
+        }
+        let image_info = [vk::DescriptorImageInfo {
+            sampler: vk::Sampler::null(),
+            image_view: texture.view,
+            image_layout: vk::ImageLayout::GENERAL,
+        }];
+        let write = vk::WriteDescriptorSet::builder()
+            .dst_set(set)
+            .dst_binding(binding)
+            .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+            .image_info(&image_info);
+        unsafe { device.update_descriptor_sets(&[write.build()], &[]) };
+        Ok(())
+    }
+
+    /// Check that the device has what the SPIR-V `spirv` declares it needs,
+    /// before a pipeline is made from it. Currently that is
+    /// `RequestedFeatures::shader_storage_image_write_without_format` for
+    /// shaders writing storage images declared without a format qualifier.
+    pub fn check_shader_capabilities(&self, spirv: &[u32]) -> Result<(), VulkanError> {
+        const CAPABILITY_STORAGE_IMAGE_WRITE_WITHOUT_FORMAT: u32 = 56;
+
+        let capabilities = spirv_capabilities(spirv);
+        if capabilities.contains(&CAPABILITY_STORAGE_IMAGE_WRITE_WITHOUT_FORMAT)
+            && !self.granted_features.shader_storage_image_write_without_format
+        {
+            return Err(VulkanError::ShaderCompilation(
+                "writing storage images without a format qualifier needs the \
+                 shaderStorageImageWriteWithoutFormat device feature, which was not granted"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Create the bindless texture set: one descriptor array of up to
+    /// `capacity` textures that shaders index per draw, e.g. with a material
+    /// id from a push constant. Declare it in shaders as
+    /// `layout(set = 2, binding = 0) uniform sampler2D textures[];` and
+    /// index it with `nonuniformEXT` (`GL_EXT_nonuniform_qualifier`).
+    ///
+    /// Needs descriptor indexing (`descriptorIndexing`,
+    /// `runtimeDescriptorArray`), enabled on Vulkan 1.2 devices supporting it.
+    /// Replaces a previous set, forgetting its registered textures.
+    pub fn create_bindless_texture_set(&mut self, capacity: u32) -> Result<(), VulkanError> {
+        if !self.bindless {
+            return Err(VulkanError::InitializationFailed(
+                "bindless textures need the descriptorIndexing and runtimeDescriptorArray features"
+                    .to_string(),
+            ));
+        }
+        let sampler_id = match &self.bindless_set {
+            Some(bindless) => bindless.sampler_id,
+            None => self.default_sampler()?,
+        };
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        if let Some(old) = self.bindless_set.take() {
+            // Frames in flight may still use the old set
+            unsafe {
+                device.device_wait_idle().map_err(sync_error)?;
+                device.destroy_descriptor_pool(old.pool, None);
+                device.destroy_descriptor_set_layout(old.layout, None);
+            }
+        }
+
+        let bindings = [vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(capacity)
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .build()];
+        // Slots stay empty until registered and may be filled while frames are in flight
+        let binding_flags = [vk::DescriptorBindingFlags::PARTIALLY_BOUND
+            | vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT
+            | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND];
+        let mut flags_info =
+            vk::DescriptorSetLayoutBindingFlagsCreateInfo::builder().binding_flags(&binding_flags);
+        let layout_info = vk::DescriptorSetLayoutCreateInfo::builder()
+            .flags(vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL)
+            .bindings(&bindings)
+            .push_next(&mut flags_info);
+        let layout = unsafe { device.create_descriptor_set_layout(&layout_info, None) }
+            .map_err(VulkanError::Api)?;
+
+        let pool_sizes = [vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: capacity,
+        }];
+        let pool_info = vk::DescriptorPoolCreateInfo::builder()
+            .flags(vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND)
+            .max_sets(1)
+            .pool_sizes(&pool_sizes);
+        let pool = match unsafe { device.create_descriptor_pool(&pool_info, None) } {
+            Ok(pool) => pool,
+            Err(e) => {
+                unsafe { device.destroy_descriptor_set_layout(layout, None) };
+                return Err(VulkanError::Api(e));
+            }
+        };
+
+        let counts = [capacity];
+        let mut count_info = vk::DescriptorSetVariableDescriptorCountAllocateInfo::builder()
+            .descriptor_counts(&counts);
+        let layouts = [layout];
+        let alloc_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(pool)
+            .set_layouts(&layouts)
+            .push_next(&mut count_info);
+        let set = match unsafe { device.allocate_descriptor_sets(&alloc_info) } {
+            Ok(sets) => sets[0],
+            Err(e) => {
+                unsafe {
+                    device.destroy_descriptor_pool(pool, None);
+                    device.destroy_descriptor_set_layout(layout, None);
+                }
+                return Err(VulkanError::Api(e));
+            }
+        };
+
+        self.bindless_set = Some(BindlessSet {
+            layout,
+            pool,
+            set,
+            capacity,
+            sampler_id,
+            textures: vec![],
+        });
+        Ok(())
+    }
+
+    /// Layout of the bindless set, to include as set 2 when creating
+    /// pipeline layouts
+    pub fn bindless_set_layout(&self) -> Option<vk::DescriptorSetLayout> {
+        self.bindless_set.as_ref().map(|bindless| bindless.layout)
+    }
+
+    /// Put `texture_id` into the bindless set, returning the array index
+    /// shaders use to sample it. Registering a texture twice returns the
+    /// same index. Sampled with the `default_sampler` the set was created
+    /// with. There is no unregistering, keep registered textures alive while
+    /// the set is used.
+    pub fn register_bindless_texture(&mut self, texture_id: usize) -> Result<u32, VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let texture = self.textures.get(&texture_id).ok_or(VulkanError::InvalidHandle)?;
+        let bindless = self.bindless_set.as_mut().ok_or(VulkanError::InvalidHandle)?;
+        if let Some(index) = bindless.textures.iter().position(|&id| id == texture_id) {
+            return Ok(index as u32);
+        }
+        let index = bindless.textures.len() as u32;
+        if index >= bindless.capacity {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "bindless texture set is full ({} textures)",
+                bindless.capacity
+            )));
+        }
+
+        let image_info = [vk::DescriptorImageInfo {
+            sampler: self.samplers[bindless.sampler_id],
+            image_view: texture.view,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        }];
+        let write = vk::WriteDescriptorSet::builder()
+            .dst_set(bindless.set)
+            .dst_binding(0)
+            .dst_array_element(index)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&image_info);
+        unsafe { device.update_descriptor_sets(&[write.build()], &[]) };
+        bindless.textures.push(texture_id);
+        Ok(index)
+    }
+
+    /// Bind the bindless set as set 2 of the current pipeline. It stays
+    /// bound for later pipelines with a compatible layout, so once per
+    /// frame after the first `apply_pipeline` is enough.
+    pub fn apply_bindless_set(&mut self) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let set = self.bindless_set.as_ref().ok_or(VulkanError::InvalidHandle)?.set;
+        let layout = self
+            .bound_pipeline
+            .and_then(|id| self.pipelines.get(id))
+            .ok_or(VulkanError::InvalidHandle)?
+            .layout;
+        let cmd = *self
+            .command_buffers
+            .get(self.current_frame)
+            .ok_or(VulkanError::InvalidHandle)?;
+        let bind_point = vk::PipelineBindPoint::GRAPHICS;
+        unsafe { device.cmd_bind_descriptor_sets(cmd, bind_point, layout, 2, &[set], &[]) };
+        Ok(())
+    }
+
+    /// Sample count for multisampled rendering: 1, 2, 4 or 8. With 1 no
+    /// multisampled color image or resolve attachment is created.
+    pub fn set_msaa_samples(&mut self, samples: u32) -> Result<(), VulkanError> {
+        self.msaa_samples = sample_count_flags(samples).ok_or_else(|| {
+            VulkanError::InitializationFailed(format!("unsupported MSAA sample count {}", samples))
+        })?;
+        Ok(())
+    }
+
+    /// Resource counts, memory use and timing of the context.
+    pub fn get_performance_stats(&self) -> VulkanStats {
+        let allocated_memory = self
+            .buffers
+            .values()
+            .map(|buffer| buffer.allocation.size())
+            .chain(self.textures.values().map(|texture| texture.allocation.size()))
+            .sum();
+
+        VulkanStats {
+            buffer_count: self.buffers.len(),
+            texture_count: self.textures.len(),
+            shader_count: self.shaders.len(),
+            pipeline_count: self.pipelines.len(),
+            allocated_memory,
+            frame_time: self.frame_time,
+            cpu_frame_times: self.cpu_frame_times.clone(),
+            gpu_frame_times: self.gpu_frame_times.clone(),
+            msaa_enabled: self.msaa_samples != vk::SampleCountFlags::TYPE_1,
+            msaa_samples: self.msaa_samples,
+            queue_info: self.queue_info(),
+            device_info: self.device_info(),
+            descriptor_set_count: self.descriptor_pools.set_count + self.uniform_rings.len(),
+            submit_count: self.last_submit_count,
+            allocation_count: self.allocation_count(),
+            max_allocation_count: self.device_properties.limits.max_memory_allocation_count,
+        }
+    }
+
+    /// Distinct `vkAllocateMemory` blocks behind the live buffers, textures
+    /// and uniform rings. gpu-allocator sub-allocates most resources from
+    /// shared blocks, dedicated allocations and imported memory count one
+    /// each. Some drivers fail once this reaches `maxMemoryAllocationCount`.
+    pub fn allocation_count(&self) -> usize {
+        let mut blocks = HashSet::new();
+        let buffers = self.buffers.values().map(|buffer| &buffer.allocation);
+        let rings = self.uniform_rings.iter().map(|ring| &ring.allocation);
+        for allocation in buffers.chain(rings) {
+            blocks.insert(unsafe { allocation.memory() });
+        }
+        for texture in self.textures.values() {
+            blocks.insert(unsafe { texture.allocation.memory() });
+            blocks.extend(texture.imported_memory);
+            if let Some(sparse) = &texture.sparse {
+                let pages = sparse.pages.values().chain(&sparse.mip_tail);
+                blocks.extend(pages.map(|page| unsafe { page.memory() }));
+            }
+        }
+        blocks.remove(&vk::DeviceMemory::null());
+        blocks.len()
+    }
+
+    /// Warn once when the allocation count gets within a quarter of the
+    /// device limit. Blocks never outnumber resources by much, so counting
+    /// is skipped until there are enough resources to get there.
+    fn check_allocation_count(&mut self) {
+        let limit = self.device_properties.limits.max_memory_allocation_count as usize;
+        let threshold = limit / 4 * 3;
+        let resources = self.buffers.len() + self.textures.len() + self.uniform_rings.len();
+        if limit == 0 || resources < threshold {
+            self.allocation_count_warned = false;
+            return;
+        }
+        let count = self.allocation_count();
+        if count < threshold {
+            self.allocation_count_warned = false;
+        } else if !self.allocation_count_warned {
+            log_04::warn!(
+                "Vulkan: {} of at most {} device memory allocations in use, \
+                 merge small buffers and textures to stay below the limit",
+                count, limit
+            );
+            self.allocation_count_warned = true;
+        }
+    }
+
+    /// Name, ids and versions of the GPU and its driver, for bug reports.
+    /// Empty until the device is picked.
+    pub fn device_info(&self) -> DeviceInfo {
+        let properties = &self.device_properties;
+        let name = unsafe { CStr::from_ptr(properties.device_name.as_ptr()) };
+        let api = properties.api_version;
+        DeviceInfo {
+            name: name.to_string_lossy().into_owned(),
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            driver_version: driver_version_string(properties.vendor_id, properties.driver_version),
+            api_version: format!(
+                "{}.{}.{}",
+                vk::api_version_major(api),
+                vk::api_version_minor(api),
+                vk::api_version_patch(api)
+            ),
+            device_type: properties.device_type,
+        }
+    }
+
+    /// Read back the most recently presented swapchain image.
+    ///
+    /// Returns width, height and tightly packed RGBA8 pixels. BGRA swapchains
+    /// are swizzled; sRGB formats are returned as stored, which is already the
+    /// encoding image files expect. Formats other than 8 bit RGBA/BGRA (HDR
+    /// swapchains) are not supported.
+    pub fn capture_frame(&mut self) -> Result<(u32, u32, Vec<u8>), VulkanError> {
+        if !self.swapchain_usage.contains(vk::ImageUsageFlags::TRANSFER_SRC) {
+            return Err(VulkanError::MappingFailed(
+                "the surface doesn't allow copying from swapchain images".to_string(),
+            ));
+        }
+        let swizzle = match self.swapchain_image_format {
+            vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_UNORM => false,
+            vk::Format::B8G8R8A8_SRGB | vk::Format::B8G8R8A8_UNORM => true,
+            format => {
+                return Err(VulkanError::MappingFailed(format!(
+                    "capturing {:?} swapchain images is not supported",
+                    format
+                )))
+            }
+        };
+        let image = *self
+            .swapchain_images
+            .get(self.current_image_index as usize)
+            .ok_or(VulkanError::InvalidHandle)?;
+        let vk::Extent2D { width, height } = self.swapchain_extent;
+        let size = width as vk::DeviceSize * height as vk::DeviceSize * 4;
+
+        let (buffer, allocation) = self.create_readback_buffer(size, "frame capture")?;
+
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let region = vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+            image_extent: vk::Extent3D { width, height, depth: 1 },
+        };
+        let copied = self.one_time_commands(|device, cmd| unsafe {
+            transition_image_layout(
+                device,
+                cmd,
+                image,
+                range,
+                vk::ImageLayout::PRESENT_SRC_KHR,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            );
+            device.cmd_copy_image_to_buffer(
+                cmd,
+                image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                buffer,
+                &[region],
+            );
+            transition_image_layout(
+                device,
+                cmd,
+                image,
+                range,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::ImageLayout::PRESENT_SRC_KHR,
+            );
+        });
+
+        let pixels = copied.and_then(|_| {
+            let mapped = allocation.mapped_slice().ok_or_else(|| {
+                VulkanError::MappingFailed("readback buffer is not host visible".to_string())
+            })?;
+            let mut pixels = mapped[..size as usize].to_vec();
+            if swizzle {
+                for pixel in pixels.chunks_exact_mut(4) {
+                    pixel.swap(0, 2);
+                }
+            }
+            Ok(pixels)
+        });
+        self.destroy_staging_buffer(buffer, allocation);
+        Ok((width, height, pixels?))
+    }
+
+    /// Copy the first layer of a texture with 4 byte texels, e.g. an RGBA8
+    /// render texture, back to the CPU and wait for it. Texels come tightly
+    /// packed, top row first, in the texture's own channel order. The
+    /// texture needs `TRANSFER_SRC` usage, which render textures have, and
+    /// stays in its current layout.
+    pub fn read_texture(&mut self, texture_id: usize) -> Result<Vec<u8>, VulkanError> {
+        let texture = self.textures.get(&texture_id).ok_or(VulkanError::InvalidHandle)?;
+        if !texture.usage.contains(vk::ImageUsageFlags::TRANSFER_SRC) {
+            return Err(VulkanError::MappingFailed(format!(
+                "texture {} was not created with TRANSFER_SRC usage",
+                texture_id
+            )));
+        }
+        match texture.format {
+            vk::Format::R8G8B8A8_UNORM
+            | vk::Format::R8G8B8A8_SRGB
+            | vk::Format::B8G8R8A8_UNORM
+            | vk::Format::B8G8R8A8_SRGB => {}
+            format => {
+                return Err(VulkanError::MappingFailed(format!(
+                    "reading back {:?} textures is not supported",
+                    format
+                )))
+            }
+        }
+        let (image, layout) = (texture.image, texture.current_layout);
+        let (width, height) = (texture.width, texture.height);
+        let size = width as vk::DeviceSize * height as vk::DeviceSize * 4;
+        let (buffer, allocation) = self.create_readback_buffer(size, "texture readback")?;
+
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let region = vk::BufferImageCopy {
+            buffer_offset: 0,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+            image_extent: vk::Extent3D { width, height, depth: 1 },
+        };
+        let barrier = |old_layout, new_layout| {
+            vk::ImageMemoryBarrier::builder()
+                .old_layout(old_layout)
+                .new_layout(new_layout)
+                .src_access_mask(vk::AccessFlags::MEMORY_WRITE)
+                .dst_access_mask(vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(image)
+                .subresource_range(range)
+                .build()
+        };
+        let copied = self.one_time_commands(|device, cmd| unsafe {
+            // Whatever wrote the texture last, on this queue, has to be done
+            let all = vk::PipelineStageFlags::ALL_COMMANDS;
+            let to_source = [barrier(layout, vk::ImageLayout::TRANSFER_SRC_OPTIMAL)];
+            let flags = vk::DependencyFlags::empty();
+            device.cmd_pipeline_barrier(cmd, all, all, flags, &[], &[], &to_source);
+            device.cmd_copy_image_to_buffer(
+                cmd,
+                image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                buffer,
+                &[region],
+            );
+            let back = [barrier(vk::ImageLayout::TRANSFER_SRC_OPTIMAL, layout)];
+            device.cmd_pipeline_barrier(cmd, all, all, flags, &[], &[], &back);
+        });
+
+        let texels = copied.and_then(|_| {
+            allocation
+                .mapped_slice()
+                .map(|mapped| mapped[..size as usize].to_vec())
+                .ok_or_else(|| {
+                    VulkanError::MappingFailed("readback buffer is not host visible".to_string())
+                })
+        });
+        self.destroy_staging_buffer(buffer, allocation);
+        texels
+    }
+
+    /// Host visible buffer of `size` bytes for copies back to the CPU, freed
+    /// with `destroy_staging_buffer`
+    fn create_readback_buffer(
+        &mut self,
+        size: vk::DeviceSize,
+        name: &str,
+    ) -> Result<(vk::Buffer, Allocation), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(size)
+            .usage(vk::BufferUsageFlags::TRANSFER_DST)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+        let buffer = unsafe { device.create_buffer(&buffer_info, None) }
+            .map_err(|e| VulkanError::BufferCreationFailed(e.to_string()))?;
+        let requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+        let allocation = {
+            let mut allocator = lock(&self.allocator);
+            let allocator = allocator.as_mut().ok_or(VulkanError::InvalidHandle)?;
+            allocator.allocate(&AllocationCreateDesc {
+                name,
+                requirements,
+                location: MemoryLocation::GpuToCpu,
+                linear: true,
+                allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+            })
+        };
+        let allocation = match allocation {
+            Ok(allocation) => allocation,
+            Err(e) => {
+                unsafe { device.destroy_buffer(buffer, None) };
+                return Err(e.into());
+            }
+        };
+        if let Err(e) =
+            unsafe { device.bind_buffer_memory(buffer, allocation.memory(), allocation.offset()) }
+        {
+            self.destroy_staging_buffer(buffer, allocation);
+            return Err(VulkanError::BufferCreationFailed(e.to_string()));
+        }
+        Ok((buffer, allocation))
+    }
+
+    /// Hold `Conf::target_fps` by waiting out whatever is left of the frame
+    /// interval. Sleeps for the bulk of it and spins for the last
+    /// millisecond, as sleep granularity is too coarse for an accurate cap.
+    fn limit_frame_rate(&mut self) {
+        let target_fps = match self.display.as_ref().and_then(|conf| conf.target_fps) {
+            Some(fps) if fps > 0 => fps,
+            _ => {
+                self.frame_deadline = None;
+                return;
+            }
+        };
+        let interval = Duration::from_secs_f64(1.0 / target_fps as f64);
+
+        let now = Instant::now();
+        let deadline = match self.frame_deadline {
+            Some(deadline) if deadline > now => deadline,
+            // Running behind (or first frame): start pacing from now instead of
+            // rushing to catch up
+            _ => {
+                self.frame_deadline = Some(now + interval);
+                return;
+            }
+        };
+
+        let spin_threshold = Duration::from_millis(1);
+        let remaining = deadline - now;
+        if remaining > spin_threshold {
+            std::thread::sleep(remaining - spin_threshold);
+        }
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+        self.frame_deadline = Some(deadline + interval);
+    }
+    
+    /// Replace the contents of a buffer starting from offset 0.
+    ///
+    /// Host visible buffers are written through their mapping, device local
+    /// (`Usage::Immutable`) buffers are updated with a staging copy.
+    pub fn update_buffer(&mut self, buffer_id: usize, data: &[u8]) -> Result<(), VulkanError> {
+        self.update_buffer_at(buffer_id, 0, data)
+    }
+
+    /// Host visible memory of a `Usage::Stream`, `Usage::Dynamic` or
+    /// `BufferType::Staging` buffer, exactly its size, to write into
+    /// directly instead of going through `update_buffer`. Device local
+    /// buffers can't be mapped.
+    ///
+    /// The mapping is persistent and coherent, writes need no flush and are
+    /// seen by commands submitted afterwards. The slice borrows the context,
+    /// so nothing else can touch the buffer through it meanwhile; the GPU
+    /// however may still be reading the buffer for frames in flight, so
+    /// wait for those (`wait_for_frame`) before overwriting what they use,
+    /// or write to a part they don't. Pair it with `unmap_buffer`.
+    pub fn map_buffer(&mut self, buffer_id: usize) -> Result<&mut [u8], VulkanError> {
+        let buffer = self.buffers.get_mut(&buffer_id).ok_or(VulkanError::InvalidHandle)?;
+        let size = buffer.size as usize;
+        match buffer.allocation.mapped_slice_mut() {
+            Some(mapped) if mapped.len() >= size => Ok(&mut mapped[..size]),
+            _ => Err(VulkanError::MappingFailed(format!(
+                "buffer {} is device local and can't be mapped",
+                buffer_id
+            ))),
+        }
+    }
+
+    /// End CPU writes into a buffer from `map_buffer`. The memory stays
+    /// mapped, so this only checks that the buffer was mappable.
+    pub fn unmap_buffer(&mut self, buffer_id: usize) -> Result<(), VulkanError> {
+        let buffer = self.buffers.get(&buffer_id).ok_or(VulkanError::InvalidHandle)?;
+        if buffer.location == MemoryLocation::GpuOnly {
+            return Err(VulkanError::MappingFailed(format!(
+                "buffer {} is device local and was never mapped",
+                buffer_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Write `data` into a buffer starting at byte `offset`, leaving the rest
+    /// of the buffer untouched, e.g. to move one instance in a large instance
+    /// buffer.
+    ///
+    /// Host visible buffers are written through their mapping, device local
+    /// buffers get a copy of just that range from a staging buffer, which
+    /// runs with the current frame's batch, see `flush_commands`.
+    pub fn update_buffer_at(
+        &mut self,
+        buffer_id: usize,
+        offset: vk::DeviceSize,
+        data: &[u8],
+    ) -> Result<(), VulkanError> {
+        let buffer = self.buffers.get_mut(&buffer_id).ok_or(VulkanError::InvalidHandle)?;
+        let end = offset.checked_add(data.len() as vk::DeviceSize);
+        if end.is_none_or(|end| end > buffer.size) {
+            return Err(VulkanError::MappingFailed(format!(
+                "{} bytes at offset {} do not fit into buffer {} of {} bytes",
+                data.len(),
+                offset,
+                buffer_id,
+                buffer.size
+            )));
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(mapped) = buffer.allocation.mapped_slice_mut() {
+            let start = offset as usize;
+            mapped[start..start + data.len()].copy_from_slice(data);
+            return Ok(());
+        }
+
+        let dst = buffer.buffer;
+        let (staging, staging_allocation) = self.create_staging_buffer(data)?;
+        let region = vk::BufferCopy {
+            src_offset: 0,
+            dst_offset: offset,
+            size: data.len() as vk::DeviceSize,
+        };
+        let result = self.batched_commands(|device, cmd| unsafe {
+            device.cmd_copy_buffer(cmd, staging, dst, &[region]);
+        });
+        self.release_staging_buffer(staging, staging_allocation);
+        result
+    }
+    
+    /// Start copying `size` bytes at `offset` of a buffer back to the CPU
+    /// without waiting for it, e.g. for picking results a frame later.
+    ///
+    /// The copy sees everything submitted so far and the updates batched for
+    /// the current frame, which get flushed, but not the frame being
+    /// recorded. The buffer needs `TRANSFER_SRC` usage. Poll the token with
+    /// `poll_readback` until it yields the bytes, which also releases its
+    /// staging buffer.
+    pub fn read_buffer_async(
+        &mut self,
+        buffer_id: usize,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+    ) -> Result<ReadbackToken, VulkanError> {
+        let buffer = self.buffers.get(&buffer_id).ok_or(VulkanError::InvalidHandle)?;
+        if offset.checked_add(size).is_none_or(|end| end > buffer.size) || size == 0 {
+            return Err(VulkanError::MappingFailed(format!(
+                "cannot read {} bytes at offset {} of buffer {} of {} bytes",
+                size, offset, buffer_id, buffer.size
+            )));
+        }
+        if !buffer.usage.contains(vk::BufferUsageFlags::TRANSFER_SRC) {
+            return Err(VulkanError::MappingFailed(format!(
+                "buffer {} was not created with TRANSFER_SRC usage",
+                buffer_id
+            )));
+        }
+        let src = buffer.buffer;
+        self.flush_commands()?;
+        let command_pool = self.command_pool.ok_or(VulkanError::InvalidHandle)?;
+        let queue = self.graphics_queue.ok_or(VulkanError::InvalidHandle)?;
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(size)
+            .usage(vk::BufferUsageFlags::TRANSFER_DST)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+        let staging = unsafe { device.create_buffer(&buffer_info, None) }
+            .map_err(|e| VulkanError::BufferCreationFailed(e.to_string()))?;
+        let requirements = unsafe { device.get_buffer_memory_requirements(staging) };
+        let allocation = {
+            let mut allocator = lock(&self.allocator);
+            let allocator = allocator.as_mut().ok_or(VulkanError::InvalidHandle)?;
+            allocator.allocate(&AllocationCreateDesc {
+                name: "readback",
+                requirements,
+                location: MemoryLocation::GpuToCpu,
+                linear: true,
+                allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+            })
+        };
+        let staging_allocation = match allocation {
+            Ok(allocation) => allocation,
+            Err(e) => {
+                unsafe { device.destroy_buffer(staging, None) };
+                return Err(e.into());
+            }
+        };
+        let mut readback = PendingReadback {
+            fence: vk::Fence::null(),
+            cmd: vk::CommandBuffer::null(),
+            staging,
+            staging_allocation,
+            size: size as usize,
+        };
+
+        let submitted = unsafe {
+            let memory = readback.staging_allocation.memory();
+            let memory_offset = readback.staging_allocation.offset();
+            device
+                .bind_buffer_memory(staging, memory, memory_offset)
+                .map_err(|e| VulkanError::BufferCreationFailed(e.to_string()))
+                .and_then(|_| {
+                    readback.fence = device
+                        .create_fence(&vk::FenceCreateInfo::default(), None)
+                        .map_err(sync_error)?;
+                    readback.cmd = allocate_command_buffer(device, command_pool)?;
+                    record_commands(device, readback.cmd, |cmd| {
+                        // Make writes of earlier submissions visible to the copy
+                        let barrier = vk::MemoryBarrier::builder()
+                            .src_access_mask(vk::AccessFlags::MEMORY_WRITE)
+                            .dst_access_mask(vk::AccessFlags::TRANSFER_READ);
+                        device.cmd_pipeline_barrier(
+                            cmd,
+                            vk::PipelineStageFlags::ALL_COMMANDS,
+                            vk::PipelineStageFlags::TRANSFER,
+                            vk::DependencyFlags::empty(),
+                            &[barrier.build()],
+                            &[],
+                            &[],
+                        );
+                        let region = vk::BufferCopy {
+                            src_offset: offset,
+                            dst_offset: 0,
+                            size,
+                        };
+                        device.cmd_copy_buffer(cmd, src, staging, &[region]);
+                    })?;
+                    let cmds = [readback.cmd];
+                    let submit = vk::SubmitInfo::builder().command_buffers(&cmds);
+                    let _queue = lock(&self.queue_lock);
+                    device
+                        .queue_submit(queue, &[submit.build()], readback.fence)
+                        .map_err(sync_error)
+                })
+        };
+        if let Err(e) = submitted {
+            self.release_readback(readback);
+            return Err(e);
+        }
+
+        self.submit_count += 1;
+        let token = ReadbackToken(self.next_readback);
+        self.next_readback += 1;
+        self.pending_readbacks.insert(token, readback);
+        Ok(token)
+    }
+
+    /// Bytes of a `read_buffer_async` once its copy has finished, `None`
+    /// while it is still in flight. A token yields its bytes only once.
+    pub fn poll_readback(
+        &mut self,
+        token: ReadbackToken,
+    ) -> Result<Option<Vec<u8>>, VulkanError> {
+        let readback = self.pending_readbacks.get(&token).ok_or(VulkanError::InvalidHandle)?;
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        if !unsafe { device.get_fence_status(readback.fence) }.map_err(sync_error)? {
+            return Ok(None);
+        }
+
+        let readback = self.pending_readbacks.remove(&token).ok_or(VulkanError::InvalidHandle)?;
+        let bytes = readback
+            .staging_allocation
+            .mapped_slice()
+            .map(|mapped| mapped[..readback.size].to_vec())
+            .ok_or_else(|| {
+                VulkanError::MappingFailed("readback buffer is not host visible".to_string())
+            });
+        self.release_readback(readback);
+        bytes.map(Some)
+    }
+
+    fn release_readback(&mut self, readback: PendingReadback) {
+        if let Some(device) = self.device.as_ref() {
+            unsafe {
+                device.destroy_fence(readback.fence, None);
+                if let Some(pool) = self.command_pool {
+                    if readback.cmd != vk::CommandBuffer::null() {
+                        device.free_command_buffers(pool, &[readback.cmd]);
+                    }
+                }
+            }
+        }
+        self.destroy_staging_buffer(readback.staging, readback.staging_allocation);
+    }
+
+    /// Destroy every Vulkan object owned by the context, including the instance.
+    pub fn cleanup(&mut self) {
+        self.destroy_device_objects();
+        self.destroy_surface();
+        self.surface_loader = None;
+        if let Some(instance) = self.instance.take() {
+            unsafe { instance.destroy_instance(None) };
+        }
+        self.entry = None;
+    }
+
+    /// Recover from `VulkanError::DeviceLost` by tearing down everything
+    /// created on the lost device and initializing a fresh one.
+    ///
+    /// Buffers, textures, samplers, shaders and pipelines do not survive
+    /// this: their ids become invalid and the application has to create
+    /// them again, typically from `EventHandler::on_device_lost`.
+    pub fn reinitialize(&mut self, display: &dyn crate::native::NativeDisplay) -> Result<(), VulkanError> {
+        self.cleanup();
+        self.initialize(display)
+    }
+
+    /// Destroy all device level objects and the device itself, leaving the
+    /// instance alive.
+    fn destroy_device_objects(&mut self) {
+        // Waits for the GPU, and swapchain depth buffers go back to the
+        // allocator before it is dropped
+        self.destroy_swapchain_views();
+        let windows: Vec<_> = self.windows.drain().map(|(_, window)| window).collect();
+        for window in windows {
+            self.destroy_window(window);
+        }
+        // Fails only on device loss, and the swapchain is destroyed regardless
+        let _ = self.destroy_swapchain();
+        self.swapchain_loader = None;
+        self.destroy_sync_objects();
+
+        let device = match self.device.take() {
+            Some(device) => device,
+            None => return,
+        };
+        // Waits for uploads other threads are recording, later ones fail
+        let uploader_pool = self
+            .uploader
+            .take()
+            .and_then(|uploader| lock(&uploader.inner.command_pool).take());
+        unsafe {
+            // Fails with ERROR_DEVICE_LOST when recovering, nothing can be done about it
+            let _ = device.device_wait_idle();
+            if let Some(pool) = uploader_pool {
+                device.destroy_command_pool(pool, None);
+            }
+
+            let mut allocator = lock(&self.allocator).take();
+            for upload in self.pending_uploads.drain(..) {
+                device.destroy_fence(upload.fence, None);
+                device.destroy_semaphore(upload.semaphore, None);
+                device.destroy_buffer(upload.staging, None);
+                if let Some(allocator) = allocator.as_mut() {
+                    let _ = allocator.free(upload.staging_allocation);
+                }
+            }
+            let in_flight = self.batches_in_flight.drain(..).map(|(_, batch)| batch);
+            for batch in self.batch.take().into_iter().chain(in_flight) {
+                for (buffer, allocation) in batch.staging {
+                    device.destroy_buffer(buffer, None);
+                    if let Some(allocator) = allocator.as_mut() {
+                        let _ = allocator.free(allocation);
+                    }
+                }
+            }
+            for (_, readback) in self.pending_readbacks.drain() {
+                device.destroy_fence(readback.fence, None);
+                device.destroy_buffer(readback.staging, None);
+                if let Some(allocator) = allocator.as_mut() {
+                    let _ = allocator.free(readback.staging_allocation);
+                }
+            }
+            let retired = self.retired_textures.drain(..).map(|(_, texture)| texture);
+            for texture in self.textures.drain().map(|(_, texture)| texture).chain(retired) {
+                device.destroy_image_view(texture.view, None);
+                device.destroy_image(texture.image, None);
+                if let Some(memory) = texture.imported_memory {
+                    device.free_memory(memory, None);
+                }
+                if let Some(allocator) = allocator.as_mut() {
+                    let _ = allocator.free(texture.allocation);
+                    let sparse = texture.sparse.into_iter();
+                    for page in sparse.flat_map(SparseResidency::into_allocations) {
+                        let _ = allocator.free(page);
+                    }
+                }
+            }
+            for (_, buffer) in self.buffers.drain() {
+                device.destroy_buffer(buffer.buffer, None);
+                if let Some(allocator) = allocator.as_mut() {
+                    let _ = allocator.free(buffer.allocation);
+                }
+            }
+            for ring in self.uniform_rings.drain(..) {
+                device.destroy_buffer(ring.buffer, None);
+                if let Some(allocator) = allocator.as_mut() {
+                    let _ = allocator.free(ring.allocation);
+                }
+            }
+            // The allocator frees its memory blocks on drop and has to go before the device
+            drop(allocator);
+
+            for sampler in self.samplers.drain(..) {
+                device.destroy_sampler(sampler, None);
+            }
+            self.default_sampler = None;
+            // Destroying the pools frees their descriptor sets
+            if let Some(pool) = self.uniform_descriptor_pool.take() {
+                device.destroy_descriptor_pool(pool, None);
+            }
+            for pool in self.descriptor_pools.pools.drain(..) {
+                device.destroy_descriptor_pool(pool, None);
+            }
+            self.texture_sets.clear();
+            if let Some(bindless) = self.bindless_set.take() {
+                device.destroy_descriptor_pool(bindless.pool, None);
+                device.destroy_descriptor_set_layout(bindless.layout, None);
+            }
+            self.descriptor_pools.set_count = 0;
+            if let Some(layout) = self.uniform_set_layout.take() {
+                device.destroy_descriptor_set_layout(layout, None);
+            }
+            for (_, target) in self.render_targets.drain() {
+                device.destroy_framebuffer(target.framebuffer, None);
+                for view in target.layer_views {
+                    device.destroy_image_view(view, None);
+                }
+                for (_, render_pass) in target.render_passes {
+                    device.destroy_render_pass(render_pass, None);
+                }
+            }
+            for pipeline in self.pipelines.drain(..) {
+                device.destroy_pipeline(pipeline.pipeline, None);
+                if let Some(wireframe) = pipeline.wireframe {
+                    device.destroy_pipeline(wireframe, None);
+                }
+                device.destroy_pipeline_layout(pipeline.layout, None);
+                if let Some((layout, _)) = pipeline.texture_set_layout {
+                    device.destroy_descriptor_set_layout(layout, None);
+                }
+            }
+            for shader in self.shaders.drain(..) {
+                device.destroy_shader_module(shader.vertex_module, None);
+                device.destroy_shader_module(shader.fragment_module, None);
+                if let Some(module) = shader.geometry_module {
+                    device.destroy_shader_module(module, None);
+                }
+                if let Some((control, evaluation)) = shader.tessellation_modules {
+                    device.destroy_shader_module(control, None);
+                    device.destroy_shader_module(evaluation, None);
+                }
+                if let Some(module) = shader.compute_module {
+                    device.destroy_shader_module(module, None);
+                }
+            }
+            for framebuffer in self.framebuffers.drain(..) {
+                device.destroy_framebuffer(framebuffer, None);
+            }
+            for (_, render_pass) in self.render_pass_cache.drain() {
+                device.destroy_render_pass(render_pass, None);
+            }
+            if let Some(render_pass) = self.render_pass.take() {
+                device.destroy_render_pass(render_pass, None);
+            }
+            for view in self.swapchain_image_views.drain(..) {
+                device.destroy_image_view(view, None);
+            }
+            if let Some(command_pool) = self.command_pool.take() {
+                device.destroy_command_pool(command_pool, None);
+            }
+            if let Some(command_pool) = self.transfer_command_pool.take() {
+                device.destroy_command_pool(command_pool, None);
+            }
+            self.compute_in_flight.clear();
+            if let Some(command_pool) = self.compute_command_pool.take() {
+                device.destroy_command_pool(command_pool, None);
+            }
+            if let Some(timeline) = self.compute_timeline.take() {
+                device.destroy_semaphore(timeline, None);
+            }
+            device.destroy_device(None);
+        }
+
+        self.overlay_textures.clear();
+        self.current_window = WindowId::MAIN;
+        self.swapchain = None;
+        self.swapchain_images.clear();
+        self.graphics_queue = None;
+        self.present_queue = None;
+        self.transfer_queue = None;
+        self.compute_queue = None;
+        self.compute_timeline_value = 0;
+        self.compute_wait_value = 0;
+        self.current_frame = 0;
+        self.last_present = None;
+        self.frame_deadline = None;
+    }
+    
+    /// Check whether Vulkan can be used on this machine.
+    ///
+    /// Loads the Vulkan library and looks for a physical device that supports
+    /// the swapchain extension. The probe runs once, later calls return the
+    /// cached answer.
+    pub fn is_available() -> bool {
+        const UNKNOWN: u8 = 0;
+        const AVAILABLE: u8 = 1;
+        const UNAVAILABLE: u8 = 2;
+        static AVAILABILITY: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+        match AVAILABILITY.load(Ordering::Acquire) {
+            AVAILABLE => true,
+            UNAVAILABLE => false,
+            _ => {
+                let available = probe_vulkan();
+                let state = if available { AVAILABLE } else { UNAVAILABLE };
+                AVAILABILITY.store(state, Ordering::Release);
+                available
+            }
+        }
+    }
+
+    /// List the GPUs Vulkan can use, in the order `AdapterSelection::Index`
+    /// refers to. Works before any context exists, e.g. to fill a settings
+    /// menu ahead of `miniquad::start`. Empty when Vulkan can't be loaded.
+    pub fn enumerate_adapters() -> Vec<AdapterInfo> {
+        let entry = match unsafe { Entry::load() } {
+            Ok(entry) => entry,
+            Err(_) => return vec![],
+        };
+        let app_info = vk::ApplicationInfo::builder().api_version(vk::API_VERSION_1_0);
+        let instance_info = vk::InstanceCreateInfo::builder().application_info(&app_info);
+        let instance = match unsafe { entry.create_instance(&instance_info, None) } {
+            Ok(instance) => instance,
+            Err(_) => return vec![],
+        };
+        let adapters = unsafe { instance.enumerate_physical_devices() }
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+            .map(|(index, device)| adapter_info(&instance, index, device))
+            .collect();
+        unsafe { instance.destroy_instance(None) };
+        adapters
+    }
+
+    /// Create an image described by `image_info`, upload `data` into it (one
+    /// `layer_size` chunk per array layer, each covering the full extent
+    /// including depth) and register it as a texture.
+    fn create_layered_texture(
+        &mut self,
+        image_info: &vk::ImageCreateInfo,
+        view_type: vk::ImageViewType,
+        format: TextureFormat,
+        layer_size: usize,
+        data: &[u8],
+    ) -> Result<usize, VulkanError> {
+        self.check_sampled_format(format)?;
+        let layers = image_info.array_layers;
+        let (image, view, allocation) = self.create_image_with_view(image_info, view_type)?;
+
+        let regions = layer_copy_regions(image_info, layer_size);
+        if let Err(e) = self.upload_image_data(image, 1, layers, data, &regions) {
+            self.destroy_image(image, view, allocation);
+            return Err(e);
+        }
+
+        let layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+        Ok(self.register_texture(image, view, allocation, image_info, layout))
+    }
+
+    /// Whether the device can sample textures of `format` with optimal tiling,
+    /// which the block compressed formats depend on
+    pub fn supports_texture_format(&self, format: TextureFormat) -> bool {
+        self.supports_sampled_format(self.texture_format(format))
+    }
+
+    /// Color space mode from `Conf::platform.color_space_mode`
+    pub fn color_space_mode(&self) -> ColorSpaceMode {
+        self.display
+            .as_ref()
+            .map(|conf| conf.platform.color_space_mode)
+            .unwrap_or_default()
+    }
+
+    /// Vulkan format of `format`, sRGB or UNORM depending on the color space mode
+    fn texture_format(&self, format: TextureFormat) -> vk::Format {
+        mode_texture_format(self.color_space_mode(), format)
+    }
+
+    fn supports_sampled_format(&self, format: vk::Format) -> bool {
+        let (instance, physical_device) = match (self.instance.as_ref(), self.physical_device) {
+            (Some(instance), Some(physical_device)) => (instance, physical_device),
+            _ => return false,
+        };
+        sampled_format_supported(instance, physical_device, format)
+    }
+
+    fn check_sampled_format(&self, format: TextureFormat) -> Result<(), VulkanError> {
+        if self.supports_texture_format(format) {
+            Ok(())
+        } else {
+            Err(VulkanError::TextureCreationFailed(format!(
+                "texture format {:?} is not supported by this device",
+                format
+            )))
+        }
+    }
+
+    fn register_texture(
+        &mut self,
+        image: vk::Image,
+        view: vk::ImageView,
+        allocation: Allocation,
+        image_info: &vk::ImageCreateInfo,
+        layout: vk::ImageLayout,
+    ) -> usize {
+        let id = self.next_texture_id;
+        self.next_texture_id += 1;
+        self.textures.insert(
+            id,
+            VulkanTexture {
+                image,
+                view,
+                allocation,
+                width: image_info.extent.width,
+                height: image_info.extent.height,
+                depth: image_info.extent.depth,
+                layers: image_info.array_layers,
+                format: image_info.format,
+                usage: image_info.usage,
+                imported_memory: None,
+                transient: false,
+                sparse: None,
+                current_layout: layout,
+            },
+        );
+        self.check_allocation_count();
+        id
+    }
+
+    /// Register an image from `create_attachment_image`, taking ownership of
+    /// its lazily allocated memory if it has some.
+    fn register_attachment(
+        &mut self,
+        (image, view, allocation, memory): AttachmentImage,
+        image_info: &vk::ImageCreateInfo,
+        layout: vk::ImageLayout,
+        transient: bool,
+    ) -> usize {
+        let id = self.register_texture(image, view, allocation, image_info, layout);
+        if let Some(texture) = self.textures.get_mut(&id) {
+            texture.imported_memory = memory;
+            texture.transient = transient;
+        }
+        id
+    }
+
+    /// Destroy deleted textures no submitted frame can still be sampling
+    fn release_retired_textures(&mut self) -> Result<(), VulkanError> {
+        if self.retired_textures.is_empty() {
+            return Ok(());
+        }
+        let completed = self.current_timeline_value()?;
+        let (done, pending) = self
+            .retired_textures
+            .drain(..)
+            .partition(|(value, _)| *value <= completed);
+        self.retired_textures = pending;
+        for (_, texture) in done {
+            if let (Some(device), Some(memory)) = (self.device.as_ref(), texture.imported_memory) {
+                unsafe { device.free_memory(memory, None) };
+            }
+            self.destroy_image(texture.image, texture.view, texture.allocation);
+            let mut allocator = lock(&self.allocator);
+            if let (Some(allocator), Some(sparse)) = (allocator.as_mut(), texture.sparse) {
+                for page in sparse.into_allocations() {
+                    let _ = allocator.free(page);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn destroy_image(&mut self, image: vk::Image, view: vk::ImageView, allocation: Allocation) {
+        let mut allocator = lock(&self.allocator);
+        if let (Some(device), Some(allocator)) = (self.device.as_ref(), allocator.as_mut()) {
+            unsafe {
+                device.destroy_image_view(view, None);
+                device.destroy_image(image, None);
+            }
+            let _ = allocator.free(allocation);
+        }
+    }
+
+    /// Create a single layer image that is only used as a render pass
+    /// attachment, together with its view. A `transient` image gets
+    /// `TRANSIENT_ATTACHMENT` usage and dedicated lazily allocated memory,
+    /// returned for the texture to own. Devices without a lazily allocated
+    /// memory type, which is most desktop GPUs, fall back to regular device
+    /// local memory from the allocator.
+    fn create_attachment_image(
+        &mut self,
+        image_info: &vk::ImageCreateInfo,
+        transient: bool,
+    ) -> Result<AttachmentImage, VulkanError> {
+        let view_type = vk::ImageViewType::TYPE_2D;
+        if !transient {
+            let (image, view, allocation) = self.create_image_with_view(image_info, view_type)?;
+            return Ok((image, view, allocation, None));
+        }
+
+        let mut image_info = *image_info;
+        image_info.usage |= vk::ImageUsageFlags::TRANSIENT_ATTACHMENT;
+        let instance = self.instance.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let physical_device = self.physical_device.ok_or(VulkanError::InvalidHandle)?;
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let image = unsafe { device.create_image(&image_info, None) }
+            .map_err(|e| VulkanError::TextureCreationFailed(e.to_string()))?;
+        let requirements = unsafe { device.get_image_memory_requirements(image) };
+        let memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+        let lazy_type = (0..memory_properties.memory_type_count).find(|&index| {
+            requirements.memory_type_bits & (1 << index) != 0
+                && memory_properties.memory_types[index as usize]
+                    .property_flags
+                    .contains(vk::MemoryPropertyFlags::LAZILY_ALLOCATED)
+        });
+        let memory_type_index = match lazy_type {
+            Some(index) => index,
+            None => {
+                unsafe { device.destroy_image(image, None) };
+                let (image, view, allocation) =
+                    self.create_image_with_view(&image_info, view_type)?;
+                return Ok((image, view, allocation, None));
+            }
+        };
+
+        let alloc_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_type_index);
+        let memory = match unsafe { device.allocate_memory(&alloc_info, None) } {
+            Ok(memory) => memory,
+            Err(e) => {
+                unsafe { device.destroy_image(image, None) };
+                return Err(VulkanError::TextureCreationFailed(e.to_string()));
+            }
+        };
+        let view_info = vk::ImageViewCreateInfo::builder()
+            .image(image)
+            .view_type(view_type)
+            .format(image_info.format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: aspect_mask(image_info.format),
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            });
+        let view = unsafe {
+            device
+                .bind_image_memory(image, memory, 0)
+                .and_then(|_| device.create_image_view(&view_info, None))
+        };
+        match view {
+            Ok(view) => Ok((image, view, Allocation::default(), Some(memory))),
+            Err(e) => {
+                unsafe {
+                    device.destroy_image(image, None);
+                    device.free_memory(memory, None);
+                }
+                Err(VulkanError::TextureCreationFailed(e.to_string()))
+            }
+        }
+    }
+
+    /// Create an image in device local memory together with a view covering
+    /// all of its layers.
+    fn create_image_with_view(
+        &mut self,
+        image_info: &vk::ImageCreateInfo,
+        view_type: vk::ImageViewType,
+    ) -> Result<(vk::Image, vk::ImageView, Allocation), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let mut allocator = lock(&self.allocator);
+        let allocator = allocator.as_mut().ok_or(VulkanError::InvalidHandle)?;
+
+        let image = unsafe { device.create_image(image_info, None) }
+            .map_err(|e| VulkanError::TextureCreationFailed(e.to_string()))?;
+        let requirements = unsafe { device.get_image_memory_requirements(image) };
+        let allocation = allocator.allocate(&AllocationCreateDesc {
+            name: "texture",
+            requirements,
+            location: MemoryLocation::GpuOnly,
+            linear: false,
+            allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+        });
+        let allocation = match allocation {
+            Ok(allocation) => allocation,
+            Err(e) => {
+                unsafe { device.destroy_image(image, None) };
+                return Err(e.into());
+            }
+        };
+        let bound = unsafe {
+            device.bind_image_memory(image, allocation.memory(), allocation.offset())
+        };
+        if let Err(e) = bound {
+            unsafe { device.destroy_image(image, None) };
+            let _ = allocator.free(allocation);
+            return Err(VulkanError::TextureCreationFailed(e.to_string()));
+        }
+
+        let view_info = vk::ImageViewCreateInfo::builder()
+            .image(image)
+            .view_type(view_type)
+            .format(image_info.format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: aspect_mask(image_info.format),
+                base_mip_level: 0,
+                level_count: image_info.mip_levels,
+                base_array_layer: 0,
+                layer_count: image_info.array_layers,
+            });
+        match unsafe { device.create_image_view(&view_info, None) } {
+            Ok(view) => Ok((image, view, allocation)),
+            Err(e) => {
+                unsafe { device.destroy_image(image, None) };
+                let _ = allocator.free(allocation);
+                Err(VulkanError::TextureCreationFailed(e.to_string()))
+            }
+        }
+    }
+
+    /// Copy `data` into `image` through a staging buffer, as part of the
+    /// current batch or, without `batch_submissions`, waiting for the copy
+    /// to finish.
+    ///
+    /// The image is expected in `UNDEFINED` layout and is left in
+    /// `SHADER_READ_ONLY_OPTIMAL` once the copy is done.
+    fn upload_image_data(
+        &mut self,
+        image: vk::Image,
+        levels: u32,
+        layers: u32,
+        data: &[u8],
+        regions: &[vk::BufferImageCopy],
+    ) -> Result<(), VulkanError> {
+        if self.batch_submissions {
+            let (staging, staging_allocation) = self.create_staging_buffer(data)?;
+            let range = vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: levels,
+                base_array_layer: 0,
+                layer_count: layers,
+            };
+            let result = self.batched_commands(|device, cmd| unsafe {
+                let undefined = vk::ImageLayout::UNDEFINED;
+                let transfer_dst = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
+                let read_only = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+                transition_image_layout(device, cmd, image, range, undefined, transfer_dst);
+                device.cmd_copy_buffer_to_image(cmd, staging, image, transfer_dst, regions);
+                transition_image_layout(device, cmd, image, range, transfer_dst, read_only);
+            });
+            self.release_staging_buffer(staging, staging_allocation);
+            return result;
+        }
+
+        let upload = self.submit_image_upload(image, levels, layers, data, regions)?;
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let result = unsafe { device.wait_for_fences(&[upload.fence], true, u64::MAX) }
+            .map_err(sync_error);
+        self.release_upload(upload);
+        result
+    }
+
+    /// Stage `data` and submit the copy into `image` without waiting for it.
+    ///
+    /// With a dedicated transfer queue the copy is recorded there, ownership
+    /// of the image is released to the graphics family and acquired on the
+    /// graphics queue after a semaphore wait. Otherwise everything goes to the
+    /// graphics queue. The returned upload's fence signals once the image is
+    /// ready to be sampled.
+    fn submit_image_upload(
+        &mut self,
+        image: vk::Image,
+        levels: u32,
+        layers: u32,
+        data: &[u8],
+        regions: &[vk::BufferImageCopy],
+    ) -> Result<PendingUpload, VulkanError> {
+        let (staging, staging_allocation) = self.create_staging_buffer(data)?;
+        let mut upload = PendingUpload {
+            fence: vk::Fence::null(),
+            semaphore: vk::Semaphore::null(),
+            transfer_cmd: vk::CommandBuffer::null(),
+            graphics_cmd: vk::CommandBuffer::null(),
+            staging,
+            staging_allocation,
+        };
+        match self.record_image_upload(&mut upload, image, levels, layers, regions) {
+            Ok(()) => {
+                let transfer_submits = (upload.transfer_cmd != vk::CommandBuffer::null()) as u32;
+                self.submit_count += 1 + transfer_submits;
+                Ok(upload)
+            }
+            Err(e) => {
+                self.release_upload(upload);
+                Err(e)
+            }
+        }
+    }
+
+    fn record_image_upload(
+        &self,
+        upload: &mut PendingUpload,
+        image: vk::Image,
+        levels: u32,
+        layers: u32,
+        regions: &[vk::BufferImageCopy],
+    ) -> Result<(), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let command_pool = self.command_pool.ok_or(VulkanError::InvalidHandle)?;
+        let graphics_queue = self.graphics_queue.ok_or(VulkanError::InvalidHandle)?;
+        let graphics_family = self.queue_family_index.ok_or(VulkanError::InvalidHandle)?;
+
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: levels,
+            base_array_layer: 0,
+            layer_count: layers,
+        };
+        let staging = upload.staging;
+        let record_copy = |cmd: vk::CommandBuffer| unsafe {
+            transition_image_layout(
+                device,
+                cmd,
+                image,
+                range,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            );
+            device.cmd_copy_buffer_to_image(
+                cmd,
+                staging,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                regions,
+            );
+        };
+
+        let _queue = lock(&self.queue_lock);
+        unsafe {
+            upload.fence = device
+                .create_fence(&vk::FenceCreateInfo::default(), None)
+                .map_err(sync_error)?;
+            upload.graphics_cmd = allocate_command_buffer(device, command_pool)?;
+
+            let transfer = match (
+                self.transfer_queue,
+                self.transfer_command_pool,
+                self.transfer_queue_family_index,
+            ) {
+                (Some(queue), Some(pool), Some(family)) => Some((queue, pool, family)),
+                _ => None,
+            };
+
+            match transfer {
+                Some((transfer_queue, transfer_pool, transfer_family)) => {
+                    upload.semaphore = device
+                        .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)
+                        .map_err(sync_error)?;
+                    upload.transfer_cmd = allocate_command_buffer(device, transfer_pool)?;
+
+                    record_commands(device, upload.transfer_cmd, |cmd| {
+                        record_copy(cmd);
+                        transfer_image_ownership(
+                            device,
+                            cmd,
+                            image,
+                            range,
+                            transfer_family,
+                            graphics_family,
+                            true,
+                        );
+                    })?;
+                    record_commands(device, upload.graphics_cmd, |cmd| {
+                        transfer_image_ownership(
+                            device,
+                            cmd,
+                            image,
+                            range,
+                            transfer_family,
+                            graphics_family,
+                            false,
+                        );
+                    })?;
+
+                    let transfer_cmds = [upload.transfer_cmd];
+                    let signal = [upload.semaphore];
+                    let transfer_submit = vk::SubmitInfo::builder()
+                        .command_buffers(&transfer_cmds)
+                        .signal_semaphores(&signal);
+                    device
+                        .queue_submit(transfer_queue, &[transfer_submit.build()], vk::Fence::null())
+                        .map_err(sync_error)?;
+
+                    let graphics_cmds = [upload.graphics_cmd];
+                    let wait_stages = [vk::PipelineStageFlags::ALL_COMMANDS];
+                    let graphics_submit = vk::SubmitInfo::builder()
+                        .wait_semaphores(&signal)
+                        .wait_dst_stage_mask(&wait_stages)
+                        .command_buffers(&graphics_cmds);
+                    device
+                        .queue_submit(graphics_queue, &[graphics_submit.build()], upload.fence)
+                        .map_err(sync_error)
+                }
+                None => {
+                    record_commands(device, upload.graphics_cmd, |cmd| {
+                        record_copy(cmd);
+                        transition_image_layout(
+                            device,
+                            cmd,
+                            image,
+                            range,
+                            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                        );
+                    })?;
+
+                    let graphics_cmds = [upload.graphics_cmd];
+                    let submit = vk::SubmitInfo::builder().command_buffers(&graphics_cmds);
+                    device
+                        .queue_submit(graphics_queue, &[submit.build()], upload.fence)
+                        .map_err(sync_error)
+                }
+            }
+        }
+    }
+
+    /// Destroy the sync objects, command buffers and staging buffer of an
+    /// upload that has finished (or never got submitted).
+    fn release_upload(&mut self, upload: PendingUpload) {
+        let device = match self.device.as_ref() {
+            Some(device) => device,
+            None => return,
+        };
+        unsafe {
+            device.destroy_fence(upload.fence, None);
+            device.destroy_semaphore(upload.semaphore, None);
+            if let Some(pool) = self.command_pool {
+                if upload.graphics_cmd != vk::CommandBuffer::null() {
+                    device.free_command_buffers(pool, &[upload.graphics_cmd]);
+                }
+            }
+            if let Some(pool) = self.transfer_command_pool {
+                if upload.transfer_cmd != vk::CommandBuffer::null() {
+                    device.free_command_buffers(pool, &[upload.transfer_cmd]);
+                }
+            }
+        }
+        self.destroy_staging_buffer(upload.staging, upload.staging_allocation);
+    }
+
+    /// Create a host visible `TRANSFER_SRC` buffer filled with `data`.
+    fn create_staging_buffer(&mut self, data: &[u8]) -> Result<(vk::Buffer, Allocation), VulkanError> {
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let mut allocator = lock(&self.allocator);
+        let allocator = allocator.as_mut().ok_or(VulkanError::InvalidHandle)?;
+        create_staging_buffer(device, allocator, data)
+    }
+
+    fn destroy_staging_buffer(&mut self, buffer: vk::Buffer, allocation: Allocation) {
+        let mut allocator = lock(&self.allocator);
+        if let (Some(device), Some(allocator)) = (self.device.as_ref(), allocator.as_mut()) {
+            unsafe { device.destroy_buffer(buffer, None) };
+            let _ = allocator.free(allocation);
+        }
+    }
+
+    /// Record commands with `f` into a fresh command buffer, submit it to the
+    /// graphics queue and wait for it to finish. Batched commands are
+    /// flushed first so they stay ahead of it.
+    fn one_time_commands<F>(&mut self, f: F) -> Result<(), VulkanError>
+    where
+        F: FnOnce(&Device, vk::CommandBuffer),
+    {
+        self.flush_commands()?;
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let command_pool = self.command_pool.ok_or(VulkanError::InvalidHandle)?;
+        let queue = self.graphics_queue.ok_or(VulkanError::InvalidHandle)?;
+
+        let alloc_info = vk::CommandBufferAllocateInfo::builder()
+            .command_pool(command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(1);
+        let cmd = unsafe { device.allocate_command_buffers(&alloc_info) }
+            .map_err(|e| VulkanError::CommandBufferCreationFailed(e.to_string()))?[0];
+
+        let _queue = lock(&self.queue_lock);
+        let result = unsafe {
+            let begin_info = vk::CommandBufferBeginInfo::builder()
+                .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+            device
+                .begin_command_buffer(cmd, &begin_info)
+                .map_err(|e| VulkanError::CommandBufferCreationFailed(e.to_string()))
+                .and_then(|_| {
+                    f(device, cmd);
+                    device
+                        .end_command_buffer(cmd)
+                        .map_err(|e| VulkanError::CommandBufferCreationFailed(e.to_string()))
+                })
+                .and_then(|_| {
+                    let command_buffers = [cmd];
+                    let submit = vk::SubmitInfo::builder().command_buffers(&command_buffers);
+                    device
+                        .queue_submit(queue, &[submit.build()], vk::Fence::null())
+                        .and_then(|_| device.queue_wait_idle(queue))
+                        .map_err(sync_error)
+                })
+        };
+
+        unsafe { device.free_command_buffers(command_pool, &[cmd]) };
+        self.submit_count += 1;
+        result
+    }
+
+    /// Record commands with `f` into the batch submitted with the current
+    /// frame, ahead of the frame's own commands, instead of a submission of
+    /// their own. Without `batch_submissions` they go through
+    /// `one_time_commands`. Staging buffers the commands read from have to
+    /// be handed to `release_staging_buffer`.
+    fn batched_commands<F>(&mut self, f: F) -> Result<(), VulkanError>
+    where
+        F: FnOnce(&Device, vk::CommandBuffer),
+    {
+        if !self.batch_submissions {
+            return self.one_time_commands(f);
+        }
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let cmd = match &self.batch {
+            Some(batch) => batch.cmd,
+            None => {
+                let command_pool = self.command_pool.ok_or(VulkanError::InvalidHandle)?;
+                let cmd = allocate_command_buffer(device, command_pool)?;
+                let begin_info = vk::CommandBufferBeginInfo::builder()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+                if let Err(e) = unsafe { device.begin_command_buffer(cmd, &begin_info) } {
+                    unsafe { device.free_command_buffers(command_pool, &[cmd]) };
+                    return Err(VulkanError::CommandBufferCreationFailed(e.to_string()));
+                }
+                self.batch = Some(CommandBatch {
+                    cmd,
+                    staging: Vec::new(),
+                });
+                cmd
+            }
+        };
+        f(device, cmd);
+        Ok(())
+    }
+
+    /// Destroy a staging buffer once the commands reading it are done, which
+    /// is right away unless a batch is being recorded.
+    fn release_staging_buffer(&mut self, buffer: vk::Buffer, allocation: Allocation) {
+        match self.batch.as_mut() {
+            Some(batch) => batch.staging.push((buffer, allocation)),
+            None => self.destroy_staging_buffer(buffer, allocation),
+        }
+    }
+
+    /// Submit the commands batched so far instead of waiting for the end of
+    /// the frame and wait for them to finish, for when their results are
+    /// needed outside of the frame, e.g. by another API.
+    pub fn flush_commands(&mut self) -> Result<(), VulkanError> {
+        let batch = match self.batch.take() {
+            Some(batch) => batch,
+            None => return Ok(()),
+        };
+        let device = self.device.as_ref().ok_or(VulkanError::InvalidHandle)?;
+        let queue = self.graphics_queue.ok_or(VulkanError::InvalidHandle)?;
+        let result = unsafe {
+            device
+                .end_command_buffer(batch.cmd)
+                .map_err(|e| VulkanError::CommandBufferCreationFailed(e.to_string()))
+                .and_then(|_| {
+                    let command_buffers = [batch.cmd];
+                    let submit = vk::SubmitInfo::builder().command_buffers(&command_buffers);
+                    let _queue = lock(&self.queue_lock);
+                    device
+                        .queue_submit(queue, &[submit.build()], vk::Fence::null())
+                        .and_then(|_| device.queue_wait_idle(queue))
+                        .map_err(sync_error)
+                })
+        };
+        if result.is_ok() {
+            self.submit_count += 1;
+        }
+        self.release_batch(batch);
+        result
+    }
+
+    /// Free the command buffer and staging buffers of a batch the GPU is done with
+    fn release_batch(&mut self, batch: CommandBatch) {
+        if let (Some(device), Some(pool)) = (self.device.as_ref(), self.command_pool) {
+            unsafe { device.free_command_buffers(pool, &[batch.cmd]) };
+        }
+        for (buffer, allocation) in batch.staging {
+            self.destroy_staging_buffer(buffer, allocation);
+        }
+    }
+
+    /// Release the batches of frames the GPU has finished
+    fn release_submitted_batches(&mut self) -> Result<(), VulkanError> {
+        if self.batches_in_flight.is_empty() {
+            return Ok(());
+        }
+        let completed = self.current_timeline_value()?;
+        let (done, pending) = self
+            .batches_in_flight
+            .drain(..)
+            .partition(|(value, _)| *value <= completed);
+        self.batches_in_flight = pending;
+        for (_, batch) in done {
+            self.release_batch(batch);
+        }
+        Ok(())
+    }
+}
+
+/// Pick the surface format matching `preferred`, falling back to an sRGB
+/// color space format and then to whatever the surface lists first. For
+/// the sRGB color space the 8-bit format has to match `mode`: sRGB formats
+/// for the linear workflow, UNORM ones otherwise.
+fn choose_surface_format(
+    formats: &[(vk::Format, vk::ColorSpaceKHR)],
+    preferred: ColorSpace,
+    mode: ColorSpaceMode,
+) -> Option<(vk::Format, vk::ColorSpaceKHR)> {
+    let wanted = match preferred {
+        ColorSpace::Srgb => vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        ColorSpace::DisplayP3 => vk::ColorSpaceKHR::DISPLAY_P3_NONLINEAR_EXT,
+        ColorSpace::ExtendedSrgbLinear => vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+        ColorSpace::Hdr10 => vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+    };
+    let matches_mode = |format: vk::Format| {
+        let (rgba, bgra) = match mode {
+            ColorSpaceMode::Linear => (vk::Format::R8G8B8A8_SRGB, vk::Format::B8G8R8A8_SRGB),
+            ColorSpaceMode::Srgb => (vk::Format::R8G8B8A8_UNORM, vk::Format::B8G8R8A8_UNORM),
+        };
+        format == rgba || format == bgra
+    };
+    let srgb = vk::ColorSpaceKHR::SRGB_NONLINEAR;
+
+    formats
+        .iter()
+        .find(|(format, color_space)| {
+            *color_space == wanted && (wanted != srgb || matches_mode(*format))
+        })
+        .or_else(|| formats.iter().find(|(_, color_space)| *color_space == wanted))
+        .or_else(|| {
+            formats
+                .iter()
+                .find(|(format, color_space)| *color_space == srgb && matches_mode(*format))
+        })
+        .or_else(|| formats.first())
+        .copied()
+}
+
+/// Create a throwaway instance and check whether any physical device exposes
+/// a graphics queue and `VK_KHR_swapchain`.
+fn probe_vulkan() -> bool {
+    let entry = match unsafe { Entry::load() } {
+        Ok(entry) => entry,
+        Err(_) => return false,
+    };
+    let app_info = vk::ApplicationInfo::builder().api_version(vk::API_VERSION_1_0);
+    let instance_info = vk::InstanceCreateInfo::builder().application_info(&app_info);
+    let instance = match unsafe { entry.create_instance(&instance_info, None) } {
+        Ok(instance) => instance,
+        Err(_) => return false,
+    };
+
+    let swapchain_name = ash_037::extensions::khr::Swapchain::name();
+    let devices = unsafe { instance.enumerate_physical_devices() }.unwrap_or_default();
+    let found = devices.into_iter().any(|device| {
+        let has_graphics = unsafe { instance.get_physical_device_queue_family_properties(device) }
+            .iter()
+            .any(|family| family.queue_flags.contains(vk::QueueFlags::GRAPHICS));
+        let has_swapchain = unsafe { instance.enumerate_device_extension_properties(device) }
+            .unwrap_or_default()
+            .iter()
+            .any(|ext| unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) } == swapchain_name);
+        has_graphics && has_swapchain
+    });
+
+    unsafe { instance.destroy_instance(None) };
+    found
+}
+
+/// Describe the physical device at `index` of the instance's device list.
+/// `requested` extension names as C strings, or the ones missing from
+/// `available`
+fn extension_names(requested: &[String], available: &[&CStr]) -> Result<Vec<CString>, Vec<String>> {
+    let missing: Vec<String> = requested
+        .iter()
+        .filter(|name| !available.iter().any(|ext| ext.to_bytes() == name.as_bytes()))
+        .cloned()
+        .collect();
+    if !missing.is_empty() {
+        return Err(missing);
+    }
+    // Available names have no interior nul bytes
+    Ok(requested.iter().filter_map(|name| CString::new(name.as_str()).ok()).collect())
+}
+
+/// Run an initialization step on a thread of its own and stop waiting for
+/// it after `timeout`, so a driver hanging in it surfaces as an error rather
+/// than freezing the application. Vulkan calls can't be cancelled, a step
+/// that timed out is left running and hands a result it produces later to
+/// `discard`, e.g. to destroy an instance nobody is going to use.
+pub(crate) fn run_with_timeout<T, F, D>(
+    step: &str,
+    timeout: Option<Duration>,
+    f: F,
+    discard: D,
+) -> Result<T, VulkanError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, VulkanError> + Send + 'static,
+    D: FnOnce(T) + Send + 'static,
+{
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return f(),
+    };
+    // Without a buffer a send only succeeds when the result is received, so
+    // it can't get lost between the timeout and dropping the receiver
+    let (sender, receiver) = std::sync::mpsc::sync_channel(0);
+    std::thread::Builder::new()
+        .name("vulkan-init".to_string())
+        .spawn(move || {
+            if let Err(SendError(Ok(value))) = sender.send(f()) {
+                discard(value);
+            }
+        })
+        .map_err(|e| VulkanError::InitializationFailed(e.to_string()))?;
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => Err(VulkanError::InitializationFailed(format!(
+            "{} took longer than {} seconds, the driver may be hung",
+            step,
+            timeout.as_secs()
+        ))),
+        Err(RecvTimeoutError::Disconnected) => {
+            Err(VulkanError::InitializationFailed(format!("{} panicked", step)))
+        }
+    }
+}
+
+fn adapter_info(instance: &Instance, index: usize, device: vk::PhysicalDevice) -> AdapterInfo {
+    let properties = unsafe { instance.get_physical_device_properties(device) };
+    let memory = unsafe { instance.get_physical_device_memory_properties(device) };
+    let supports_graphics = unsafe { instance.get_physical_device_queue_family_properties(device) }
+        .iter()
+        .any(|family| family.queue_flags.contains(vk::QueueFlags::GRAPHICS));
+    let name = unsafe { CStr::from_ptr(properties.device_name.as_ptr()) };
+    let adapter_type = match properties.device_type {
+        vk::PhysicalDeviceType::DISCRETE_GPU => AdapterType::Discrete,
+        vk::PhysicalDeviceType::INTEGRATED_GPU => AdapterType::Integrated,
+        vk::PhysicalDeviceType::VIRTUAL_GPU => AdapterType::Virtual,
+        vk::PhysicalDeviceType::CPU => AdapterType::Cpu,
+        _ => AdapterType::Other,
+    };
+    let dedicated_memory = memory.memory_heaps[..memory.memory_heap_count as usize]
+        .iter()
+        .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+        .map(|heap| heap.size)
+        .sum();
+    AdapterInfo {
+        index,
+        name: name.to_string_lossy().into_owned(),
+        adapter_type,
+        vendor_id: properties.vendor_id,
+        device_id: properties.device_id,
+        dedicated_memory,
+        supports_graphics,
+    }
+}
+
+/// Create a host visible `TRANSFER_SRC` buffer filled with `data`.
+fn create_staging_buffer(
+    device: &Device,
+    allocator: &mut Allocator,
+    data: &[u8],
+) -> Result<(vk::Buffer, Allocation), VulkanError> {
+    let buffer_info = vk::BufferCreateInfo::builder()
+        .size(data.len() as vk::DeviceSize)
+        .usage(vk::BufferUsageFlags::TRANSFER_SRC)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE);
+    let buffer = unsafe { device.create_buffer(&buffer_info, None) }
+        .map_err(|e| VulkanError::BufferCreationFailed(e.to_string()))?;
+    let requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+    let mut allocation = allocator
+        .allocate(&AllocationCreateDesc {
+            name: "staging",
+            requirements,
+            location: MemoryLocation::CpuToGpu,
+            linear: true,
+            allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+        })?;
+    unsafe { device.bind_buffer_memory(buffer, allocation.memory(), allocation.offset()) }
+        .map_err(|e| VulkanError::BufferCreationFailed(e.to_string()))?;
+
+    let mapped = allocation
+        .mapped_slice_mut()
+        .ok_or_else(|| {
+            VulkanError::MappingFailed("staging buffer is not host visible".to_string())
+        })?;
+    mapped[..data.len()].copy_from_slice(data);
+
+    Ok((buffer, allocation))
+}
+
+/// One copy region per array layer, each `layer_size` bytes apart in the
+/// staging buffer and covering the full image extent.
+fn layer_copy_regions(image_info: &vk::ImageCreateInfo, layer_size: usize) -> Vec<vk::BufferImageCopy> {
+    (0..image_info.array_layers)
+        .map(|layer| vk::BufferImageCopy {
+            buffer_offset: (layer as usize * layer_size) as vk::DeviceSize,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: layer,
+                layer_count: 1,
+            },
+            image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+            image_extent: image_info.extent,
+        })
+        .collect()
+}
+
+fn allocate_command_buffer(
+    device: &Device,
+    pool: vk::CommandPool,
+) -> Result<vk::CommandBuffer, VulkanError> {
+    let alloc_info = vk::CommandBufferAllocateInfo::builder()
+        .command_pool(pool)
+        .level(vk::CommandBufferLevel::PRIMARY)
+        .command_buffer_count(1);
+    unsafe { device.allocate_command_buffers(&alloc_info) }
+        .map(|buffers| buffers[0])
+        .map_err(|e| VulkanError::CommandBufferCreationFailed(e.to_string()))
+}
+
+/// Record a one time submit command buffer with `f`.
+unsafe fn record_commands<F>(device: &Device, cmd: vk::CommandBuffer, f: F) -> Result<(), VulkanError>
+where
+    F: FnOnce(vk::CommandBuffer),
+{
+    let begin_info =
+        vk::CommandBufferBeginInfo::builder().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+    device
+        .begin_command_buffer(cmd, &begin_info)
+        .map_err(|e| VulkanError::CommandBufferCreationFailed(e.to_string()))?;
+    f(cmd);
+    device
+        .end_command_buffer(cmd)
+        .map_err(|e| VulkanError::CommandBufferCreationFailed(e.to_string()))
+}
+
+/// Record one half of a queue family ownership transfer of a freshly
+/// uploaded image, moving it from `TRANSFER_DST_OPTIMAL` to
+/// `SHADER_READ_ONLY_OPTIMAL`. `release` is recorded on the transfer queue,
+/// the matching acquire on the graphics queue.
+unsafe fn transfer_image_ownership(
+    device: &Device,
+    cmd: vk::CommandBuffer,
+    image: vk::Image,
+    range: vk::ImageSubresourceRange,
+    src_family: u32,
+    dst_family: u32,
+    release: bool,
+) {
+    let (src_access, dst_access, src_stage, dst_stage) = if release {
+        (
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::AccessFlags::empty(),
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+        )
+    } else {
+        (
+            vk::AccessFlags::empty(),
+            vk::AccessFlags::SHADER_READ,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+        )
+    };
+
+    let barrier = vk::ImageMemoryBarrier::builder()
+        .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+        .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        .src_queue_family_index(src_family)
+        .dst_queue_family_index(dst_family)
+        .image(image)
+        .subresource_range(range)
+        .src_access_mask(src_access)
+        .dst_access_mask(dst_access)
+        .build();
+    device.cmd_pipeline_barrier(
+        cmd,
+        src_stage,
+        dst_stage,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        &[barrier],
+    );
+}
+
+/// Capabilities a SPIR-V module declares with `OpCapability`, empty when
+/// `code` isn't SPIR-V
+pub(crate) fn spirv_capabilities(code: &[u32]) -> Vec<u32> {
+    const HEADER_WORDS: usize = 5;
+    const OP_CAPABILITY: u32 = 17;
+
+    let mut capabilities = vec![];
+    if code.len() < HEADER_WORDS || code[0] != 0x0723_0203 {
+        return capabilities;
+    }
+    let mut words = &code[HEADER_WORDS..];
+    while !words.is_empty() {
+        let word_count = (words[0] >> 16) as usize;
+        let opcode = words[0] & 0xffff;
+        if word_count == 0 || word_count > words.len() {
+            break;
+        }
+        if opcode == OP_CAPABILITY && word_count == 2 {
+            capabilities.push(words[1]);
+        }
+        words = &words[word_count..];
+    }
+    capabilities
+}
+
+/// Read the workgroup size from the `LocalSize` execution mode of a compute
+/// shader's SPIR-V. Sizes given through specialization constants
+/// (`LocalSizeId`) are not resolved and yield `None`.
+pub fn spirv_local_size(code: &[u32]) -> Option<[u32; 3]> {
+    const HEADER_WORDS: usize = 5;
+    const OP_EXECUTION_MODE: u32 = 16;
+    const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+
+    if code.len() < HEADER_WORDS || code[0] != 0x0723_0203 {
+        return None;
+    }
+    let mut words = &code[HEADER_WORDS..];
+    while !words.is_empty() {
+        let word_count = (words[0] >> 16) as usize;
+        let opcode = words[0] & 0xffff;
+        if word_count == 0 || word_count > words.len() {
+            return None;
+        }
+        // OpExecutionMode %entry LocalSize x y z
+        if opcode == OP_EXECUTION_MODE && word_count == 6 && words[2] == EXECUTION_MODE_LOCAL_SIZE {
+            return Some([words[3], words[4], words[5]]);
+        }
+        words = &words[word_count..];
+    }
+    None
+}
+
+/// Usage asked for by `Platform::swapchain_usage`, besides rendering and
+/// `capture_frame`
+fn vk_swapchain_usage(usage: SwapchainUsage) -> vk::ImageUsageFlags {
+    let mut flags = vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC;
+    if usage.transfer_dst {
+        flags |= vk::ImageUsageFlags::TRANSFER_DST;
+    }
+    if usage.sampled {
+        flags |= vk::ImageUsageFlags::SAMPLED;
+    }
+    if usage.storage {
+        flags |= vk::ImageUsageFlags::STORAGE;
+    }
+    flags
+}
+
+/// The part of `requested` swapchain image usage in `supported`.
+/// `COLOR_ATTACHMENT` is always kept, every surface has to support it.
+pub(crate) fn choose_swapchain_usage(
+    requested: vk::ImageUsageFlags,
+    supported: vk::ImageUsageFlags,
+) -> vk::ImageUsageFlags {
+    requested & supported | vk::ImageUsageFlags::COLOR_ATTACHMENT
+}
+
+fn vk_composite_alpha(mode: CompositeAlphaMode) -> vk::CompositeAlphaFlagsKHR {
+    match mode {
+        CompositeAlphaMode::Opaque => vk::CompositeAlphaFlagsKHR::OPAQUE,
+        CompositeAlphaMode::PreMultiplied => vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED,
+        CompositeAlphaMode::PostMultiplied => vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED,
+        CompositeAlphaMode::Inherit => vk::CompositeAlphaFlagsKHR::INHERIT,
+    }
+}
+
+/// Composite alpha for `requested` if the surface supports it, otherwise
+/// `OPAQUE`, or the first supported mode on surfaces without `OPAQUE`, which
+/// some Android drivers only report `INHERIT` for.
+pub fn choose_composite_alpha(
+    requested: CompositeAlphaMode,
+    supported: vk::CompositeAlphaFlagsKHR,
+) -> vk::CompositeAlphaFlagsKHR {
+    let requested = vk_composite_alpha(requested);
+    if supported.contains(requested) {
+        return requested;
+    }
+    let fallbacks = [
+        vk::CompositeAlphaFlagsKHR::OPAQUE,
+        vk::CompositeAlphaFlagsKHR::INHERIT,
+        vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED,
+        vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED,
+    ];
+    fallbacks
+        .iter()
+        .copied()
+        .find(|&mode| supported.contains(mode))
+        .unwrap_or(vk::CompositeAlphaFlagsKHR::OPAQUE)
+}
+
+/// `CLOCK_MONOTONIC` in nanoseconds, the clock display timing reports in.
+/// `None` where that clock isn't available to relate present times to.
+fn monotonic_time_ns() -> Option<u64> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let mut time = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut time) } != 0 {
+            return None;
+        }
+        Some(time.tv_sec as u64 * 1_000_000_000 + time.tv_nsec as u64)
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        None
+    }
+}
+
+/// `FIFO` with vsync, which every surface supports. Without, `IMMEDIATE`
+/// if the surface can tear, then `MAILBOX`, which drops frames instead,
+/// and `FIFO` as the last resort.
+pub fn choose_present_mode(vsync: bool, available: &[vk::PresentModeKHR]) -> vk::PresentModeKHR {
+    let preferred: &[vk::PresentModeKHR] = if vsync {
+        &[vk::PresentModeKHR::FIFO]
+    } else {
+        &[vk::PresentModeKHR::IMMEDIATE, vk::PresentModeKHR::MAILBOX]
+    };
+    preferred
+        .iter()
+        .copied()
+        .find(|mode| available.contains(mode))
+        .unwrap_or(vk::PresentModeKHR::FIFO)
+}
+
+/// Swapchain image count for `requested` (one more than the minimum by
+/// default), clamped to what the surface allows. Some mobile drivers
+/// reject anything below a `min_image_count` of 3.
+pub(crate) fn swapchain_image_count_for(
+    requested: Option<u32>,
+    capabilities: &vk::SurfaceCapabilitiesKHR,
+) -> u32 {
+    let requested = requested.unwrap_or(capabilities.min_image_count + 1);
+    // max_image_count of 0 means there is no upper limit
+    let max_image_count = if capabilities.max_image_count == 0 {
+        u32::MAX
+    } else {
+        capabilities.max_image_count
+    };
+    requested.clamp(capabilities.min_image_count, max_image_count.max(capabilities.min_image_count))
+}
+
+/// Viewport over the rectangle at `x`, `y` from the top left. `flip` makes
+/// the height negative, starting at the bottom edge, so clip space Y points
+/// up like on OpenGL.
+pub(crate) fn viewport(x: f32, y: f32, width: f32, height: f32, flip: bool) -> vk::Viewport {
+    let (y, height) = if flip { (y + height, -height) } else { (y, height) };
+    vk::Viewport {
+        x,
+        y,
+        width,
+        height,
+        min_depth: 0.0,
+        max_depth: 1.0,
+    }
+}
+
+/// Cover the whole attachment with 1.0 wide lines, graphics pipelines take
+/// viewport, scissor and line width as dynamic state
+unsafe fn reset_dynamic_state(
+    device: &Device,
+    cmd: vk::CommandBuffer,
+    extent: vk::Extent2D,
+    flip_y: bool,
+) {
+    let viewport = viewport(0.0, 0.0, extent.width as f32, extent.height as f32, flip_y);
+    let scissor = vk::Rect2D {
+        offset: vk::Offset2D { x: 0, y: 0 },
+        extent,
+    };
+    device.cmd_set_viewport(cmd, 0, &[viewport]);
+    device.cmd_set_scissor(cmd, 0, &[scissor]);
+    device.cmd_set_line_width(cmd, 1.0);
+}
+
+/// Columns of a uniform of type `ty`, each starting on a 16 byte boundary
+/// when it is a matrix or part of an array
+fn uniform_columns(ty: UniformType) -> usize {
+    match ty {
+        UniformType::Mat3 => 3,
+        UniformType::Mat4 => 4,
+        _ => 1,
+    }
+}
+
+/// Byte offset of every uniform in a std140 block made of `uniforms`, and
+/// the size of the whole block.
+///
+/// Vectors are aligned to their size, three component ones to 16 bytes.
+/// Matrices are arrays of column vectors and array elements are padded to
+/// 16 bytes, so a `Mat3` takes 48 bytes and a `Float1` array of 4 takes 64.
+pub fn std140_offsets(uniforms: &[UniformDesc]) -> (Vec<usize>, usize) {
+    let mut offsets = Vec::with_capacity(uniforms.len());
+    let mut offset = 0usize;
+    for uniform in uniforms {
+        let ty = uniform.uniform_type;
+        let columns = uniform_columns(ty);
+        let (align, size) = if columns > 1 || uniform.array_count > 1 {
+            (16, 16 * columns * uniform.array_count)
+        } else {
+            (ty.size().next_power_of_two(), ty.size())
+        };
+        offset = offset.div_ceil(align) * align;
+        offsets.push(offset);
+        offset += size;
+    }
+    (offsets, offset.div_ceil(16) * 16)
+}
+
+/// Lay out `data`, the uniforms packed back to back the way the OpenGL
+/// backend's `apply_uniforms` reads them, as a std140 block.
+pub fn pack_std140(uniforms: &[UniformDesc], data: &[u8]) -> Result<Vec<u8>, VulkanError> {
+    let packed_size: usize = uniforms
+        .iter()
+        .map(|uniform| uniform.uniform_type.size() * uniform.array_count)
+        .sum();
+    if data.len() < packed_size {
+        return Err(VulkanError::MappingFailed(format!(
+            "uniform data of {} bytes is shorter than the {} bytes of its layout",
+            data.len(),
+            packed_size
+        )));
+    }
+    let (offsets, size) = std140_offsets(uniforms);
+    let mut block = vec![0; size];
+    let mut source = 0;
+    for (uniform, offset) in uniforms.iter().zip(offsets) {
+        let columns = uniform_columns(uniform.uniform_type);
+        let column_size = uniform.uniform_type.size() / columns;
+        let strided = columns > 1 || uniform.array_count > 1;
+        for column in 0..columns * uniform.array_count {
+            let target = if strided { offset + column * 16 } else { offset };
+            block[target..target + column_size]
+                .copy_from_slice(&data[source..source + column_size]);
+            source += column_size;
+        }
+    }
+    Ok(block)
+}
+
+/// Check a vertex/fragment shader pair against the pipeline description
+/// before building the pipeline. Every vertex input location must be fed by
+/// a `VertexAttribute` of matching numeric type, uniform blocks must live
+/// at binding 0 of set 0 or `GLOBAL_UNIFORM_SET` and sampler `N` at set 1
+/// binding `N`, named after `meta.texture_slots[N]`. All mismatches are
+/// listed in the error.
+pub fn validate_shader_interface(
+    vertex_spirv: &[u32],
+    fragment_spirv: &[u32],
+    attributes: &[VertexAttribute],
+    meta: &ShaderMeta,
+) -> Result<(), VulkanError> {
+    let vertex = SpirvInterface::reflect(vertex_spirv)?;
+    let fragment = SpirvInterface::reflect(fragment_spirv)?;
+    let mut problems = vec![];
+
+    let mut provided = HashMap::new();
+    let mut location = 0;
+    for attribute in attributes {
+        let count = if attribute.format == VertexFormat::Mat4 { 4 } else { 1 };
+        for _ in 0..count {
+            provided.insert(location, attribute);
+            location += 1;
+        }
+    }
+    for input in &vertex.inputs {
+        match provided.get(&input.location) {
+            None => problems.push(format!(
+                "vertex input `{}` at location {} has no VertexAttribute",
+                input.name, input.location
+            )),
+            Some(attribute) => {
+                let integer = matches!(
+                    attribute.format,
+                    VertexFormat::Int1 | VertexFormat::Int2 | VertexFormat::Int3 | VertexFormat::Int4
+                );
+                if input.integer != integer {
+                    problems.push(format!(
+                        "vertex input `{}` at location {} is {} but attribute `{}` is {:?}",
+                        input.name,
+                        input.location,
+                        if input.integer { "an integer" } else { "a float" },
+                        attribute.name,
+                        attribute.format
+                    ));
+                }
+            }
+        }
+    }
+
+    for resource in vertex.resources.iter().chain(&fragment.resources) {
+        match resource.kind {
+            ResourceKind::UniformBlock
+                if resource.binding != 0 || ![0, GLOBAL_UNIFORM_SET].contains(&resource.set) =>
+            {
+                problems.push(format!(
+                    "uniform block `{}` is at set {} binding {}, expected binding 0 of set 0 \
+                     or of set {} for global uniforms",
+                    resource.name, resource.set, resource.binding, GLOBAL_UNIFORM_SET
+                ))
+            }
+            ResourceKind::Sampler if resource.set != 1 => problems.push(format!(
+                "sampler `{}` is in set {}, expected set 1",
+                resource.name, resource.set
+            )),
+            ResourceKind::Sampler => match meta.texture_slots.get(resource.binding as usize) {
+                None => problems.push(format!(
+                    "sampler `{}` at binding {} has no texture slot in ShaderMeta",
+                    resource.name, resource.binding
+                )),
+                Some(slot) if !resource.name.is_empty() && *slot != resource.name => {
+                    problems.push(format!(
+                        "sampler at binding {} is `{}` in the shader but `{}` in ShaderMeta",
+                        resource.binding, resource.name, slot
+                    ))
+                }
+                Some(_) => {}
+            },
+            _ => {}
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(VulkanError::ShaderCompilation(problems.join("; ")))
+    }
+}
+
+/// Warn when a `PrimitiveType::Points` pipeline uses a vertex shader that
+/// never writes `gl_PointSize`. Vulkan leaves the size of such points
+/// undefined, most drivers draw nothing or single pixels.
+pub fn check_point_size(
+    vertex_spirv: &[u32],
+    primitive_type: PrimitiveType,
+) -> Result<(), VulkanError> {
+    if primitive_type == PrimitiveType::Points
+        && !SpirvInterface::reflect(vertex_spirv)?.writes_point_size
+    {
+        log_04::warn!("Point list pipeline with a vertex shader that does not write gl_PointSize");
+    }
+    Ok(())
+}
+
+/// Input assembly topology for `primitive_type`
+pub(crate) fn primitive_topology(primitive_type: PrimitiveType) -> vk::PrimitiveTopology {
+    match primitive_type {
+        PrimitiveType::Triangles => vk::PrimitiveTopology::TRIANGLE_LIST,
+        PrimitiveType::Lines => vk::PrimitiveTopology::LINE_LIST,
+        PrimitiveType::Points => vk::PrimitiveTopology::POINT_LIST,
+        PrimitiveType::Patches => vk::PrimitiveTopology::PATCH_LIST,
+    }
+}
+
+/// State a graphics pipeline with `flags` leaves dynamic: `apply_viewport`
+/// and `apply_scissor_rect` always, `set_stencil_reference` and
+/// `set_line_width` when enabled.
+pub(crate) fn graphics_dynamic_states(flags: DynamicStateFlags) -> Vec<vk::DynamicState> {
+    let mut states = vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    if flags.stencil_reference {
+        states.push(vk::DynamicState::STENCIL_REFERENCE);
+    }
+    if flags.line_width {
+        states.push(vk::DynamicState::LINE_WIDTH);
+    }
+    states
+}
+
+/// Depth and stencil state of a graphics pipeline. Like on GL the depth test
+/// only runs when `depth_write` is set. The stencil reference values of
+/// `stencil_test` are ignored when they are dynamic state.
+pub(crate) fn depth_stencil_state(
+    params: &PipelineParams,
+) -> vk::PipelineDepthStencilStateCreateInfo {
+    let mut state = vk::PipelineDepthStencilStateCreateInfo::builder()
+        .depth_test_enable(params.depth_write)
+        .depth_write_enable(params.depth_write)
+        .depth_compare_op(vk_comparison(params.depth_test));
+    if let Some(stencil) = params.stencil_test {
+        state = state
+            .stencil_test_enable(true)
+            .front(vk_stencil_face(&stencil.front))
+            .back(vk_stencil_face(&stencil.back));
+    }
+    state.build()
+}
+
+/// Rasterization state of a graphics pipeline: culling, winding and the
+/// `depth_write_offset` depth bias.
+pub(crate) fn rasterization_state(
+    params: &PipelineParams,
+) -> vk::PipelineRasterizationStateCreateInfo {
+    let mut state = vk::PipelineRasterizationStateCreateInfo::builder()
+        .polygon_mode(vk::PolygonMode::FILL)
+        .line_width(1.0)
+        .cull_mode(match params.cull_face {
+            CullFace::Nothing => vk::CullModeFlags::NONE,
+            CullFace::Front => vk::CullModeFlags::FRONT,
+            CullFace::Back => vk::CullModeFlags::BACK,
+        })
+        .front_face(match params.front_face_order {
+            FrontFaceOrder::Clockwise => vk::FrontFace::CLOCKWISE,
+            FrontFaceOrder::CounterClockwise => vk::FrontFace::COUNTER_CLOCKWISE,
+        });
+    if let Some((constant, slope)) = params.depth_write_offset {
+        state = state
+            .depth_bias_enable(true)
+            .depth_bias_constant_factor(constant)
+            .depth_bias_slope_factor(slope);
+    }
+    state.build()
+}
+
+/// Layout of `count` combined image samplers at bindings 0 and on, see
+/// `VulkanContext::apply_textures`
+fn create_texture_set_layout(
+    device: &Device,
+    count: u32,
+) -> Result<vk::DescriptorSetLayout, VulkanError> {
+    let bindings: Vec<vk::DescriptorSetLayoutBinding> = (0..count)
+        .map(|binding| {
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(binding)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+                .build()
+        })
+        .collect();
+    let layout_info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&bindings);
+    unsafe { device.create_descriptor_set_layout(&layout_info, None) }
+        .map_err(|e| VulkanError::ShaderCompilation(e.to_string()))
+}
+
+/// Blend state of a color attachment: `color_blend` for RGB, `alpha_blend`
+/// or else `color_blend` for alpha and the `color_write` mask, as on GL
+fn blend_attachment_state(params: &PipelineParams) -> vk::PipelineColorBlendAttachmentState {
+    let (r, g, b, a) = params.color_write;
+    let mut color_write_mask = vk::ColorComponentFlags::empty();
+    for (enabled, component) in [
+        (r, vk::ColorComponentFlags::R),
+        (g, vk::ColorComponentFlags::G),
+        (b, vk::ColorComponentFlags::B),
+        (a, vk::ColorComponentFlags::A),
+    ] {
+        if enabled {
+            color_write_mask |= component;
+        }
+    }
+    let mut state = vk::PipelineColorBlendAttachmentState {
+        color_write_mask,
+        ..Default::default()
+    };
+    if let Some(color) = params.color_blend {
+        let alpha = params.alpha_blend.unwrap_or(color);
+        state.blend_enable = vk::TRUE;
+        state.color_blend_op = vk_blend_op(color.equation);
+        state.src_color_blend_factor = vk_blend_factor(color.sfactor);
+        state.dst_color_blend_factor = vk_blend_factor(color.dfactor);
+        state.alpha_blend_op = vk_blend_op(alpha.equation);
+        state.src_alpha_blend_factor = vk_blend_factor(alpha.sfactor);
+        state.dst_alpha_blend_factor = vk_blend_factor(alpha.dfactor);
+    }
+    state
+}
+
+fn vk_blend_op(equation: Equation) -> vk::BlendOp {
+    match equation {
+        Equation::Add => vk::BlendOp::ADD,
+        Equation::Subtract => vk::BlendOp::SUBTRACT,
+        Equation::ReverseSubtract => vk::BlendOp::REVERSE_SUBTRACT,
+    }
+}
+
+fn vk_blend_factor(factor: BlendFactor) -> vk::BlendFactor {
+    match factor {
+        BlendFactor::Zero => vk::BlendFactor::ZERO,
+        BlendFactor::One => vk::BlendFactor::ONE,
+        BlendFactor::Value(BlendValue::SourceColor) => vk::BlendFactor::SRC_COLOR,
+        BlendFactor::Value(BlendValue::SourceAlpha) => vk::BlendFactor::SRC_ALPHA,
+        BlendFactor::Value(BlendValue::DestinationColor) => vk::BlendFactor::DST_COLOR,
+        BlendFactor::Value(BlendValue::DestinationAlpha) => vk::BlendFactor::DST_ALPHA,
+        BlendFactor::OneMinusValue(BlendValue::SourceColor) => {
+            vk::BlendFactor::ONE_MINUS_SRC_COLOR
+        }
+        BlendFactor::OneMinusValue(BlendValue::SourceAlpha) => {
+            vk::BlendFactor::ONE_MINUS_SRC_ALPHA
+        }
+        BlendFactor::OneMinusValue(BlendValue::DestinationColor) => {
+            vk::BlendFactor::ONE_MINUS_DST_COLOR
+        }
+        BlendFactor::OneMinusValue(BlendValue::DestinationAlpha) => {
+            vk::BlendFactor::ONE_MINUS_DST_ALPHA
+        }
+        BlendFactor::SourceAlphaSaturate => vk::BlendFactor::SRC_ALPHA_SATURATE,
+    }
+}
+
+/// Build a graphics pipeline from `recipe`, rasterizing with `polygon_mode`.
+/// `graphics_dynamic_states` lists the state left dynamic. `clip_control`
+/// makes clip space depth go from -1 to 1, see `DepthRange::NegOneToOne`.
+fn create_graphics_pipeline(
+    device: &Device,
+    layout: vk::PipelineLayout,
+    recipe: &GraphicsPipelineRecipe,
+    polygon_mode: vk::PolygonMode,
+    clip_control: bool,
+) -> Result<vk::Pipeline, VulkanError> {
+    let tessellated = recipe.tessellation_modules.is_some();
+    if tessellated != (recipe.topology == vk::PrimitiveTopology::PATCH_LIST) {
+        return Err(VulkanError::ShaderCompilation(
+            "tessellation shaders and PrimitiveType::Patches have to be used together".to_string(),
+        ));
+    }
+    let entry = CStr::from_bytes_with_nul(b"main\0").unwrap();
+    let stages = shader_stages(recipe, entry);
+    let vertex_input = vk::PipelineVertexInputStateCreateInfo::builder()
+        .vertex_binding_descriptions(&recipe.bindings)
+        .vertex_attribute_descriptions(&recipe.attributes);
+    let input_assembly =
+        vk::PipelineInputAssemblyStateCreateInfo::builder().topology(recipe.topology);
+    let mut depth_clip =
+        vk::PipelineViewportDepthClipControlCreateInfoEXT::builder().negative_one_to_one(true);
+    let mut viewport = vk::PipelineViewportStateCreateInfo::builder()
+        .viewport_count(1)
+        .scissor_count(1);
+    if clip_control {
+        viewport = viewport.push_next(&mut depth_clip);
+    }
+    let rasterization = vk::PipelineRasterizationStateCreateInfo {
+        polygon_mode,
+        ..rasterization_state(&recipe.params)
+    };
+    let multisample =
+        vk::PipelineMultisampleStateCreateInfo::builder().rasterization_samples(recipe.samples);
+    let depth_stencil = depth_stencil_state(&recipe.params);
+    let blend =
+        vk::PipelineColorBlendStateCreateInfo::builder().attachments(&recipe.blend_attachments);
+    let dynamic_states = graphics_dynamic_states(recipe.params.dynamic_states);
+    let dynamic = vk::PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
+    let tessellation = vk::PipelineTessellationStateCreateInfo::builder()
+        .patch_control_points(recipe.patch_control_points);
+
+    let mut create_info = vk::GraphicsPipelineCreateInfo::builder()
+        .stages(&stages)
+        .vertex_input_state(&vertex_input)
+        .input_assembly_state(&input_assembly)
+        .viewport_state(&viewport)
+        .rasterization_state(&rasterization)
+        .multisample_state(&multisample)
+        .depth_stencil_state(&depth_stencil)
+        .color_blend_state(&blend)
+        .dynamic_state(&dynamic)
+        .layout(layout)
+        .render_pass(recipe.render_pass)
+        .subpass(recipe.subpass);
+    if tessellated {
+        create_info = create_info.tessellation_state(&tessellation);
     }
-    
-    pub fn begin_render_pass(&mut self, _clear_color: (f32, f32, f32, f32)) -> Result<(), VulkanError> {
-        println!("Beginning render pass (placeholder)");
-        Ok(())
+    let create_info = create_info.build();
+    let pipelines = unsafe {
+        device.create_graphics_pipelines(vk::PipelineCache::null(), &[create_info], None)
+    };
+    pipelines
+        .map(|pipelines| pipelines[0])
+        .map_err(|(_, result)| VulkanError::Api(result))
+}
+
+/// One stage per shader module of `recipe`, in pipeline order, all entering
+/// at `entry`
+pub(crate) fn shader_stages(
+    recipe: &GraphicsPipelineRecipe,
+    entry: &CStr,
+) -> Vec<vk::PipelineShaderStageCreateInfo> {
+    let (control, evaluation) = recipe.tessellation_modules.unzip();
+    let modules = [
+        (vk::ShaderStageFlags::VERTEX, Some(recipe.vertex_module)),
+        (vk::ShaderStageFlags::TESSELLATION_CONTROL, control),
+        (vk::ShaderStageFlags::TESSELLATION_EVALUATION, evaluation),
+        (vk::ShaderStageFlags::GEOMETRY, recipe.geometry_module),
+        (vk::ShaderStageFlags::FRAGMENT, Some(recipe.fragment_module)),
+    ];
+    modules
+        .iter()
+        .filter_map(|&(stage, module)| Some((stage, module?)))
+        .map(|(stage, module)| {
+            vk::PipelineShaderStageCreateInfo::builder()
+                .stage(stage)
+                .module(module)
+                .name(entry)
+                .build()
+        })
+        .collect()
+}
+
+/// Vertex input bindings and attributes for a graphics pipeline, laid out
+/// the same way `Pipeline::new` does for GL: attributes take consecutive
+/// locations (four for `Mat4`) and a zero stride packs them tightly.
+pub(crate) fn vertex_input_descriptions(
+    buffer_layout: &[BufferLayout],
+    attributes: &[VertexAttribute],
+) -> (
+    Vec<vk::VertexInputBindingDescription>,
+    Vec<vk::VertexInputAttributeDescription>,
+) {
+    let mut strides = vec![0u32; buffer_layout.len()];
+    let mut offsets = vec![0u32; buffer_layout.len()];
+    let mut descriptions = vec![];
+    let mut location = 0;
+    for attribute in attributes {
+        let (format, count) = match attribute.format {
+            VertexFormat::Mat4 => (VertexFormat::Float4, 4),
+            format => (format, 1),
+        };
+        for _ in 0..count {
+            descriptions.push(vk::VertexInputAttributeDescription {
+                location,
+                binding: attribute.buffer_index as u32,
+                format: vk_vertex_format(format),
+                offset: offsets[attribute.buffer_index],
+            });
+            offsets[attribute.buffer_index] += format.byte_len() as u32;
+            location += 1;
+        }
+        strides[attribute.buffer_index] += attribute.format.byte_len() as u32;
     }
-    
-    pub fn end_render_pass(&mut self) -> Result<(), VulkanError> {
-        println!("Ending render pass (placeholder)");
-        Ok(())
+
+    let bindings = buffer_layout
+        .iter()
+        .enumerate()
+        .map(|(binding, layout)| vk::VertexInputBindingDescription {
+            binding: binding as u32,
+            stride: if layout.stride == 0 {
+                strides[binding]
+            } else {
+                layout.stride as u32
+            },
+            input_rate: match layout.step_func {
+                VertexStep::PerVertex => vk::VertexInputRate::VERTEX,
+                VertexStep::PerInstance => vk::VertexInputRate::INSTANCE,
+            },
+        })
+        .collect();
+    (bindings, descriptions)
+}
+
+/// Byte and Short formats are converted to float like on GL. There are no
+/// 32-bit scaled formats, so Int formats are integer inputs (`uint`/`uvec`).
+fn vk_vertex_format(format: VertexFormat) -> vk::Format {
+    match format {
+        VertexFormat::Float1 => vk::Format::R32_SFLOAT,
+        VertexFormat::Float2 => vk::Format::R32G32_SFLOAT,
+        VertexFormat::Float3 => vk::Format::R32G32B32_SFLOAT,
+        VertexFormat::Float4 | VertexFormat::Mat4 => vk::Format::R32G32B32A32_SFLOAT,
+        VertexFormat::Byte1 => vk::Format::R8_USCALED,
+        VertexFormat::Byte2 => vk::Format::R8G8_USCALED,
+        VertexFormat::Byte3 => vk::Format::R8G8B8_USCALED,
+        VertexFormat::Byte4 => vk::Format::R8G8B8A8_USCALED,
+        VertexFormat::Short1 => vk::Format::R16_USCALED,
+        VertexFormat::Short2 => vk::Format::R16G16_USCALED,
+        VertexFormat::Short3 => vk::Format::R16G16B16_USCALED,
+        VertexFormat::Short4 => vk::Format::R16G16B16A16_USCALED,
+        VertexFormat::Int1 => vk::Format::R32_UINT,
+        VertexFormat::Int2 => vk::Format::R32G32_UINT,
+        VertexFormat::Int3 => vk::Format::R32G32B32_UINT,
+        VertexFormat::Int4 => vk::Format::R32G32B32A32_UINT,
+        VertexFormat::Byte4Norm => vk::Format::R8G8B8A8_UNORM,
+        VertexFormat::SByte4Norm => vk::Format::R8G8B8A8_SNORM,
+        VertexFormat::Short2Norm => vk::Format::R16G16_SNORM,
+        VertexFormat::Short4Norm => vk::Format::R16G16B16A16_SNORM,
     }
-    
-    pub fn get_memory_budget(&self) -> (u64, u64, u64, u64) {
-        (0, 0, 0, 0) // total_size, allocated_size, available_memory, peak_memory_usage
+}
+
+/// Interface variables of a shader module, see `validate_shader_interface`
+#[derive(Default)]
+struct SpirvInterface {
+    inputs: Vec<SpirvInput>,
+    resources: Vec<SpirvResource>,
+    /// Some instruction stores to the `PointSize` built-in
+    writes_point_size: bool,
+}
+
+struct SpirvInput {
+    name: String,
+    location: u32,
+    /// Integer rather than float components
+    integer: bool,
+}
+
+struct SpirvResource {
+    name: String,
+    kind: ResourceKind,
+    set: u32,
+    binding: u32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ResourceKind {
+    UniformBlock,
+    Sampler,
+    Other,
+}
+
+impl SpirvInterface {
+    fn reflect(code: &[u32]) -> Result<SpirvInterface, VulkanError> {
+        const OP_NAME: u32 = 5;
+        const OP_TYPE_INT: u32 = 21;
+        const OP_TYPE_VECTOR: u32 = 23;
+        const OP_TYPE_MATRIX: u32 = 24;
+        const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+        const OP_TYPE_ARRAY: u32 = 28;
+        const OP_TYPE_POINTER: u32 = 32;
+        const OP_CONSTANT: u32 = 43;
+        const OP_VARIABLE: u32 = 59;
+        const OP_STORE: u32 = 62;
+        const OP_ACCESS_CHAIN: u32 = 65;
+        const OP_IN_BOUNDS_ACCESS_CHAIN: u32 = 66;
+        const OP_DECORATE: u32 = 71;
+        const OP_MEMBER_DECORATE: u32 = 72;
+        const DECORATION_BUILTIN: u32 = 11;
+        const DECORATION_LOCATION: u32 = 30;
+        const BUILTIN_POINT_SIZE: u32 = 1;
+        const DECORATION_BINDING: u32 = 33;
+        const DECORATION_DESCRIPTOR_SET: u32 = 34;
+        const STORAGE_INPUT: u32 = 1;
+        const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+        const STORAGE_UNIFORM: u32 = 2;
+
+        let invalid = || VulkanError::ShaderCompilation("malformed SPIR-V module".to_string());
+        if code.len() < 5 || code[0] != 0x0723_0203 {
+            return Err(invalid());
+        }
+
+        let mut names = HashMap::new();
+        let mut locations = HashMap::new();
+        let mut bindings = HashMap::new();
+        let mut sets = HashMap::new();
+        // Element type of vectors, matrices, arrays and pointers, by result id
+        let mut inner_types = HashMap::new();
+        let mut int_types = vec![];
+        let mut sampled_images = vec![];
+        let mut variables = vec![];
+        let mut constants = HashMap::new();
+        // `gl_PointSize` either as a plain variable or as a member of `gl_PerVertex`
+        let mut point_size_pointers = vec![];
+        let mut point_size_members = vec![];
+        let mut access_chains = vec![];
+        let mut stores = vec![];
+
+        let mut words = &code[5..];
+        while !words.is_empty() {
+            let word_count = (words[0] >> 16) as usize;
+            if word_count == 0 || word_count > words.len() {
+                return Err(invalid());
+            }
+            let op = &words[1..word_count];
+            match (words[0] & 0xffff, op) {
+                (OP_NAME, [target, name @ ..]) => {
+                    names.insert(*target, spirv_string(name));
+                }
+                (OP_TYPE_INT, [result, ..]) => int_types.push(*result),
+                (OP_TYPE_VECTOR, [result, inner, ..])
+                | (OP_TYPE_MATRIX, [result, inner, ..])
+                | (OP_TYPE_ARRAY, [result, inner, ..])
+                | (OP_TYPE_POINTER, [result, _, inner]) => {
+                    inner_types.insert(*result, *inner);
+                }
+                (OP_TYPE_SAMPLED_IMAGE, [result, ..]) => sampled_images.push(*result),
+                (OP_VARIABLE, [pointer_type, result, storage, ..]) => {
+                    variables.push((*pointer_type, *result, *storage))
+                }
+                (OP_DECORATE, [target, DECORATION_LOCATION, value]) => {
+                    locations.insert(*target, *value);
+                }
+                (OP_DECORATE, [target, DECORATION_BINDING, value]) => {
+                    bindings.insert(*target, *value);
+                }
+                (OP_DECORATE, [target, DECORATION_DESCRIPTOR_SET, value]) => {
+                    sets.insert(*target, *value);
+                }
+                (OP_DECORATE, [target, DECORATION_BUILTIN, BUILTIN_POINT_SIZE]) => {
+                    point_size_pointers.push(*target);
+                }
+                (OP_MEMBER_DECORATE, [block, member, DECORATION_BUILTIN, BUILTIN_POINT_SIZE]) => {
+                    point_size_members.push((*block, *member));
+                }
+                (OP_CONSTANT, [_, result, value, ..]) => {
+                    constants.insert(*result, *value);
+                }
+                (OP_ACCESS_CHAIN, [_, result, base, index, ..])
+                | (OP_IN_BOUNDS_ACCESS_CHAIN, [_, result, base, index, ..]) => {
+                    access_chains.push((*result, *base, *index));
+                }
+                (OP_STORE, [pointer, ..]) => stores.push(*pointer),
+                _ => {}
+            }
+            words = &words[word_count..];
+        }
+
+        // Strip pointers, arrays, matrices and vectors down to the scalar or image type
+        let base_type = |mut ty: u32| {
+            while let Some(&inner) = inner_types.get(&ty) {
+                ty = inner;
+            }
+            ty
+        };
+
+        // Pointers to the point size member of a `gl_PerVertex` block variable
+        for (result, base, index) in access_chains {
+            let block = variables
+                .iter()
+                .find(|(_, id, _)| *id == base)
+                .and_then(|(pointer_type, _, _)| inner_types.get(pointer_type));
+            let member = constants.get(&index);
+            if let (Some(block), Some(member)) = (block, member) {
+                if point_size_members.contains(&(*block, *member)) {
+                    point_size_pointers.push(result);
+                }
+            }
+        }
+
+        let mut interface = SpirvInterface {
+            writes_point_size: stores.iter().any(|pointer| point_size_pointers.contains(pointer)),
+            ..Default::default()
+        };
+        for (pointer_type, id, storage) in variables {
+            let name = names.get(&id).cloned().unwrap_or_default();
+            match storage {
+                // Built-ins such as gl_VertexIndex carry no location
+                STORAGE_INPUT => {
+                    if let Some(&location) = locations.get(&id) {
+                        interface.inputs.push(SpirvInput {
+                            name,
+                            location,
+                            integer: int_types.contains(&base_type(pointer_type)),
+                        });
+                    }
+                }
+                STORAGE_UNIFORM | STORAGE_UNIFORM_CONSTANT => {
+                    let kind = if storage == STORAGE_UNIFORM {
+                        ResourceKind::UniformBlock
+                    } else if sampled_images.contains(&base_type(pointer_type)) {
+                        ResourceKind::Sampler
+                    } else {
+                        ResourceKind::Other
+                    };
+                    interface.resources.push(SpirvResource {
+                        name,
+                        kind,
+                        set: sets.get(&id).copied().unwrap_or(0),
+                        binding: bindings.get(&id).copied().unwrap_or(0),
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(interface)
     }
-    
-    pub fn initialize(&mut self, _display: &dyn crate::native::NativeDisplay) -> Result<(), VulkanError> {
-        self.init_vulkan()?;
-        Ok(())
+}
+
+/// Decode a nul terminated SPIR-V literal string
+fn spirv_string(words: &[u32]) -> String {
+    let bytes: Vec<u8> = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .take_while(|&byte| byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn vk_stencil_face(face: &StencilFaceState) -> vk::StencilOpState {
+    vk::StencilOpState {
+        fail_op: vk_stencil_op(face.fail_op),
+        pass_op: vk_stencil_op(face.pass_op),
+        depth_fail_op: vk_stencil_op(face.depth_fail_op),
+        compare_op: vk_compare_func(face.test_func),
+        compare_mask: face.test_mask,
+        write_mask: face.write_mask,
+        reference: face.test_ref as u32,
     }
-    
-    pub fn present(&mut self) -> Result<(), VulkanError> {
-        println!("Present (placeholder)");
-        Ok(())
+}
+
+fn vk_stencil_op(op: StencilOp) -> vk::StencilOp {
+    match op {
+        StencilOp::Keep => vk::StencilOp::KEEP,
+        StencilOp::Zero => vk::StencilOp::ZERO,
+        StencilOp::Replace => vk::StencilOp::REPLACE,
+        StencilOp::IncrementClamp => vk::StencilOp::INCREMENT_AND_CLAMP,
+        StencilOp::DecrementClamp => vk::StencilOp::DECREMENT_AND_CLAMP,
+        StencilOp::Invert => vk::StencilOp::INVERT,
+        StencilOp::IncrementWrap => vk::StencilOp::INCREMENT_AND_WRAP,
+        StencilOp::DecrementWrap => vk::StencilOp::DECREMENT_AND_WRAP,
     }
-    
-    pub fn update_buffer(&mut self, _buffer_id: usize, _data: &[u8]) -> Result<(), VulkanError> {
-        println!("Update buffer {} (placeholder)", _buffer_id);
-        Ok(())
+}
+
+fn vk_compare_func(func: CompareFunc) -> vk::CompareOp {
+    match func {
+        CompareFunc::Always => vk::CompareOp::ALWAYS,
+        CompareFunc::Never => vk::CompareOp::NEVER,
+        CompareFunc::Less => vk::CompareOp::LESS,
+        CompareFunc::Equal => vk::CompareOp::EQUAL,
+        CompareFunc::LessOrEqual => vk::CompareOp::LESS_OR_EQUAL,
+        CompareFunc::Greater => vk::CompareOp::GREATER,
+        CompareFunc::NotEqual => vk::CompareOp::NOT_EQUAL,
+        CompareFunc::GreaterOrEqual => vk::CompareOp::GREATER_OR_EQUAL,
     }
-    
-    pub fn cleanup(&mut self) {
-        println!("Cleanup (placeholder)");
+}
+
+fn vk_comparison(comparison: Comparison) -> vk::CompareOp {
+    match comparison {
+        Comparison::Never => vk::CompareOp::NEVER,
+        Comparison::Less => vk::CompareOp::LESS,
+        Comparison::LessOrEqual => vk::CompareOp::LESS_OR_EQUAL,
+        Comparison::Greater => vk::CompareOp::GREATER,
+        Comparison::GreaterOrEqual => vk::CompareOp::GREATER_OR_EQUAL,
+        Comparison::Equal => vk::CompareOp::EQUAL,
+        Comparison::NotEqual => vk::CompareOp::NOT_EQUAL,
+        Comparison::Always => vk::CompareOp::ALWAYS,
     }
-    
-    pub fn is_available() -> bool {
-        println!("Vulkan check (placeholder) - returning true");
-        true
+}
+
+/// Decode `driverVersion`, which vendors pack differently
+fn driver_version_string(vendor_id: u32, version: u32) -> String {
+    const NVIDIA: u32 = 0x10DE;
+    const INTEL: u32 = 0x8086;
+    match vendor_id {
+        NVIDIA => format!(
+            "{}.{}.{}.{}",
+            version >> 22,
+            (version >> 14) & 0xff,
+            (version >> 6) & 0xff,
+            version & 0x3f
+        ),
+        // Mesa on other platforms uses the regular Vulkan packing
+        INTEL if cfg!(windows) => format!("{}.{}", version >> 14, version & 0x3fff),
+        _ => format!(
+            "{}.{}.{}",
+            vk::api_version_major(version),
+            vk::api_version_minor(version),
+            vk::api_version_patch(version)
+        ),
+    }
+}
+
+/// Map a failed wait or submit, singling out device loss so callers can recover.
+fn sync_error(result: vk::Result) -> VulkanError {
+    match result {
+        vk::Result::ERROR_DEVICE_LOST => VulkanError::DeviceLost,
+        _ => VulkanError::SynchronizationFailed(result.to_string()),
+    }
+}
+
+/// 8 bit indices need `VK_EXT_index_type_uint8`, which is not enabled.
+fn vk_index_type(index_type: IndexType) -> Result<vk::IndexType, VulkanError> {
+    match index_type {
+        IndexType::Short => Ok(vk::IndexType::UINT16),
+        IndexType::Int => Ok(vk::IndexType::UINT32),
+        IndexType::Byte => Err(VulkanError::BufferCreationFailed(
+            "u8 indices are not supported on Vulkan, use u16 or u32".to_string(),
+        )),
+    }
+}
+
+/// Vulkan flag for an MSAA sample count of 1, 2, 4 or 8
+fn sample_count_flags(samples: u32) -> Option<vk::SampleCountFlags> {
+    match samples {
+        1 => Some(vk::SampleCountFlags::TYPE_1),
+        2 => Some(vk::SampleCountFlags::TYPE_2),
+        4 => Some(vk::SampleCountFlags::TYPE_4),
+        8 => Some(vk::SampleCountFlags::TYPE_8),
+        _ => None,
+    }
+}
+
+/// Layout attachment `attachment` is read in as an input attachment
+fn input_attachment_layout(attachment: u32, depth_index: u32) -> vk::ImageLayout {
+    if attachment == depth_index {
+        vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+    } else {
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+    }
+}
+
+/// Lock `mutex`, carrying on with the data of a thread that panicked while
+/// holding it. Nothing guarded here is left half updated by a panic.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn aspect_mask(format: vk::Format) -> vk::ImageAspectFlags {
+    match format {
+        vk::Format::D16_UNORM | vk::Format::D32_SFLOAT | vk::Format::X8_D24_UNORM_PACK32 => {
+            vk::ImageAspectFlags::DEPTH
+        }
+        vk::Format::D16_UNORM_S8_UINT
+        | vk::Format::D24_UNORM_S8_UINT
+        | vk::Format::D32_SFLOAT_S8_UINT => {
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        }
+        _ => vk::ImageAspectFlags::COLOR,
+    }
+}
+
+fn vk_filter(filter: FilterMode) -> vk::Filter {
+    match filter {
+        FilterMode::Linear => vk::Filter::LINEAR,
+        FilterMode::Nearest => vk::Filter::NEAREST,
+    }
+}
+
+fn vk_address_mode(wrap: TextureWrap) -> vk::SamplerAddressMode {
+    match wrap {
+        TextureWrap::Repeat => vk::SamplerAddressMode::REPEAT,
+        TextureWrap::Mirror => vk::SamplerAddressMode::MIRRORED_REPEAT,
+        TextureWrap::Clamp => vk::SamplerAddressMode::CLAMP_TO_EDGE,
+    }
+}
+
+/// Texel block of the formats textures are uploaded in, as (width, height,
+/// bytes). Uncompressed formats have 1x1 blocks.
+fn vk_texel_block(format: vk::Format) -> Option<(u32, u32, u32)> {
+    match format {
+        vk::Format::R8_UNORM => Some((1, 1, 1)),
+        vk::Format::R8G8_UNORM | vk::Format::D16_UNORM => Some((1, 1, 2)),
+        vk::Format::R8G8B8_UNORM => Some((1, 1, 3)),
+        vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_UNORM => Some((1, 1, 4)),
+        vk::Format::BC1_RGBA_SRGB_BLOCK
+        | vk::Format::BC1_RGBA_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8_SRGB_BLOCK
+        | vk::Format::ETC2_R8G8B8_UNORM_BLOCK => Some((4, 4, 8)),
+        vk::Format::BC3_SRGB_BLOCK
+        | vk::Format::BC3_UNORM_BLOCK
+        | vk::Format::BC7_SRGB_BLOCK
+        | vk::Format::BC7_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK
+        | vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK
+        | vk::Format::ASTC_4X4_SRGB_BLOCK
+        | vk::Format::ASTC_4X4_UNORM_BLOCK => Some((4, 4, 16)),
+        _ => None,
     }
 }
 
+/// UNORM counterpart of an sRGB format, other formats are returned as they are
+fn vk_unorm_format(format: vk::Format) -> vk::Format {
+    match format {
+        vk::Format::R8G8B8A8_SRGB => vk::Format::R8G8B8A8_UNORM,
+        vk::Format::B8G8R8A8_SRGB => vk::Format::B8G8R8A8_UNORM,
+        vk::Format::BC1_RGBA_SRGB_BLOCK => vk::Format::BC1_RGBA_UNORM_BLOCK,
+        vk::Format::BC3_SRGB_BLOCK => vk::Format::BC3_UNORM_BLOCK,
+        vk::Format::BC7_SRGB_BLOCK => vk::Format::BC7_UNORM_BLOCK,
+        vk::Format::ETC2_R8G8B8_SRGB_BLOCK => vk::Format::ETC2_R8G8B8_UNORM_BLOCK,
+        vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK => vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK,
+        vk::Format::ASTC_4X4_SRGB_BLOCK => vk::Format::ASTC_4X4_UNORM_BLOCK,
+        format => format,
+    }
+}
+
+/// Value to clear an attachment with so it shows the sRGB `color`.
+///
+/// In the linear workflow attachments hold linear values (sRGB formats
+/// encode on write), so the color channels are decoded first. Alpha is
+/// always linear.
+pub fn clear_color_value(mode: ColorSpaceMode, color: [f32; 4]) -> [f32; 4] {
+    match mode {
+        ColorSpaceMode::Srgb => color,
+        ColorSpaceMode::Linear => {
+            let [r, g, b, a] = color;
+            [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+        }
+    }
+}
+
+/// sRGB transfer function decode of one channel
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Maps a miniquad texture format onto the matching Vulkan format.
+/// Vulkan format of `format` in the given color space mode
+fn mode_texture_format(mode: ColorSpaceMode, format: TextureFormat) -> vk::Format {
+    match mode {
+        ColorSpaceMode::Linear => vk_texture_format(format),
+        ColorSpaceMode::Srgb => vk_unorm_format(vk_texture_format(format)),
+    }
+}
+
+/// Whether `format` can be sampled with optimal tiling on `physical_device`
+fn sampled_format_supported(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+    format: vk::Format,
+) -> bool {
+    let properties =
+        unsafe { instance.get_physical_device_format_properties(physical_device, format) };
+    properties
+        .optimal_tiling_features
+        .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE)
+}
+
+fn vk_texture_format(format: TextureFormat) -> vk::Format {
+    match format {
+        TextureFormat::RGB8 => vk::Format::R8G8B8_UNORM,
+        TextureFormat::RGBA8 => vk::Format::R8G8B8A8_SRGB,
+        TextureFormat::Depth => vk::Format::D16_UNORM,
+        TextureFormat::Alpha => vk::Format::R8_UNORM,
+        TextureFormat::LuminanceAlpha => vk::Format::R8G8_UNORM,
+        TextureFormat::Bc1 => vk::Format::BC1_RGBA_SRGB_BLOCK,
+        TextureFormat::Bc3 => vk::Format::BC3_SRGB_BLOCK,
+        TextureFormat::Bc7 => vk::Format::BC7_SRGB_BLOCK,
+        TextureFormat::Etc2Rgb8 => vk::Format::ETC2_R8G8B8_SRGB_BLOCK,
+        TextureFormat::Etc2Rgba8 => vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK,
+        TextureFormat::Astc4x4 => vk::Format::ASTC_4X4_SRGB_BLOCK,
+    }
+}
+
+/// Record an image layout transition barrier covering `range`.
+unsafe fn transition_image_layout(
+    device: &Device,
+    cmd: vk::CommandBuffer,
+    image: vk::Image,
+    range: vk::ImageSubresourceRange,
+    old_layout: vk::ImageLayout,
+    new_layout: vk::ImageLayout,
+) {
+    let (src_access, src_stage) = match old_layout {
+        vk::ImageLayout::UNDEFINED => (vk::AccessFlags::empty(), vk::PipelineStageFlags::TOP_OF_PIPE),
+        vk::ImageLayout::PRESENT_SRC_KHR => {
+            (vk::AccessFlags::MEMORY_READ, vk::PipelineStageFlags::TRANSFER)
+        }
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL => {
+            (vk::AccessFlags::TRANSFER_READ, vk::PipelineStageFlags::TRANSFER)
+        }
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL => {
+            (vk::AccessFlags::TRANSFER_WRITE, vk::PipelineStageFlags::TRANSFER)
+        }
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => {
+            (vk::AccessFlags::SHADER_READ, vk::PipelineStageFlags::FRAGMENT_SHADER)
+        }
+        _ => (
+            vk::AccessFlags::MEMORY_WRITE,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+        ),
+    };
+    let (dst_access, dst_stage) = match new_layout {
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL => {
+            (vk::AccessFlags::TRANSFER_READ, vk::PipelineStageFlags::TRANSFER)
+        }
+        vk::ImageLayout::PRESENT_SRC_KHR => {
+            (vk::AccessFlags::MEMORY_READ, vk::PipelineStageFlags::BOTTOM_OF_PIPE)
+        }
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL => {
+            (vk::AccessFlags::TRANSFER_WRITE, vk::PipelineStageFlags::TRANSFER)
+        }
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => {
+            (vk::AccessFlags::SHADER_READ, vk::PipelineStageFlags::FRAGMENT_SHADER)
+        }
+        _ => (
+            vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+        ),
+    };
+
+    let barrier = vk::ImageMemoryBarrier::builder()
+        .old_layout(old_layout)
+        .new_layout(new_layout)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(range)
+        .src_access_mask(src_access)
+        .dst_access_mask(dst_access)
+        .build();
+    device.cmd_pipeline_barrier(
+        cmd,
+        src_stage,
+        dst_stage,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        &[barrier],
+    );
+}
+
 impl Default for VulkanContext {
     fn default() -> Self {
         Self::new()
@@ -348,9 +10595,581 @@ impl Default for VulkanContext {
 /// Placeholder Vulkan resource types
 #[derive(Debug)]
 pub struct VulkanBuffer {
+    pub buffer: vk::Buffer,
     pub allocation: gpu_allocator_022::vulkan::Allocation,
     pub size: vk::DeviceSize,
     pub usage: vk::BufferUsageFlags,
+    pub location: MemoryLocation,
+    /// Element type for index buffers, `None` for everything else
+    pub index_type: Option<IndexType>,
+}
+
+/// Returned by `VulkanContext::begin_frame` instead of a frame slot while the
+/// window is minimized
+pub const FRAME_SKIPPED: usize = usize::MAX;
+
+/// How long to sleep between checks of a minimized window's size
+const ZERO_EXTENT_POLL: Duration = Duration::from_millis(16);
+
+fn is_zero_area(extent: vk::Extent2D) -> bool {
+    extent.width == 0 || extent.height == 0
+}
+
+/// Occlusion query ids available per frame, see `VulkanContext::begin_occlusion_query`
+pub const OCCLUSION_QUERY_COUNT: u32 = 256;
+
+/// Set of the samplers bound by `VulkanContext::apply_textures`, texture `N`
+/// at binding `N`
+pub const TEXTURE_SET: u32 = 1;
+
+/// Set of the uniforms shared by all pipelines, see
+/// `VulkanContext::set_global_uniforms`. It comes after the sets shaders
+/// already use: 0 for `apply_uniforms`, 1 for samplers and 2 for bindless.
+pub const GLOBAL_UNIFORM_SET: u32 = 3;
+
+/// Size of each per-frame uniform ring buffer
+const UNIFORM_RING_SIZE: vk::DeviceSize = 4 * 1024 * 1024;
+
+/// Per-frame buffer `apply_uniforms` sub-allocates uniform blocks from
+struct UniformRing {
+    buffer: vk::Buffer,
+    allocation: Allocation,
+    set: vk::DescriptorSet,
+    /// Second set over the same buffer, bound as `GLOBAL_UNIFORM_SET`
+    global_set: vk::DescriptorSet,
+    /// Where `set_global_uniforms` put this frame's block
+    global_offset: Option<u32>,
+    /// First free byte, reset when the frame slot is reused
+    offset: vk::DeviceSize,
+}
+
+/// Descriptor array of `create_bindless_texture_set`
+struct BindlessSet {
+    layout: vk::DescriptorSetLayout,
+    pool: vk::DescriptorPool,
+    set: vk::DescriptorSet,
+    capacity: u32,
+    sampler_id: usize,
+    /// Registered textures by array index
+    textures: Vec<usize>,
+}
+
+/// Chain of descriptor pools, growing by one pool whenever the newest is full
+#[derive(Default)]
+struct DescriptorAllocator {
+    pools: Vec<vk::DescriptorPool>,
+    set_count: usize,
+}
+
+impl DescriptorAllocator {
+    fn allocate(
+        &mut self,
+        device: &Device,
+        layout: vk::DescriptorSetLayout,
+        sizes: &DescriptorPoolSizes,
+    ) -> Result<vk::DescriptorSet, VulkanError> {
+        let layouts = [layout];
+        let allocate_from = |pool| {
+            let alloc_info = vk::DescriptorSetAllocateInfo::builder()
+                .descriptor_pool(pool)
+                .set_layouts(&layouts);
+            unsafe { device.allocate_descriptor_sets(&alloc_info) }.map(|sets| sets[0])
+        };
+
+        if let Some(&pool) = self.pools.last() {
+            match allocate_from(pool) {
+                Ok(set) => {
+                    self.set_count += 1;
+                    return Ok(set);
+                }
+                Err(vk::Result::ERROR_OUT_OF_POOL_MEMORY)
+                | Err(vk::Result::ERROR_FRAGMENTED_POOL) => {}
+                Err(e) => return Err(VulkanError::Api(e)),
+            }
+        }
+
+        let pool_sizes: Vec<vk::DescriptorPoolSize> = [
+            (vk::DescriptorType::UNIFORM_BUFFER, sizes.uniform_buffers),
+            (vk::DescriptorType::COMBINED_IMAGE_SAMPLER, sizes.combined_image_samplers),
+            (vk::DescriptorType::STORAGE_BUFFER, sizes.storage_buffers),
+            (vk::DescriptorType::STORAGE_IMAGE, sizes.storage_images),
+            (vk::DescriptorType::INPUT_ATTACHMENT, sizes.input_attachments),
+        ]
+        .iter()
+        .filter(|&&(_, count)| count > 0)
+        .map(|&(ty, descriptor_count)| vk::DescriptorPoolSize { ty, descriptor_count })
+        .collect();
+        let pool_info = vk::DescriptorPoolCreateInfo::builder()
+            .max_sets(sizes.max_sets.max(1))
+            .pool_sizes(&pool_sizes);
+        let pool = unsafe { device.create_descriptor_pool(&pool_info, None) }
+            .map_err(VulkanError::Api)?;
+        self.pools.push(pool);
+
+        let set = allocate_from(pool).map_err(VulkanError::Api)?;
+        self.set_count += 1;
+        Ok(set)
+    }
+}
+
+/// Commands of `VulkanContext::batched_commands` and the staging buffers
+/// they read from
+struct CommandBatch {
+    cmd: vk::CommandBuffer,
+    staging: Vec<(vk::Buffer, Allocation)>,
+}
+
+/// A texture upload that has been submitted but not yet released
+struct PendingUpload {
+    fence: vk::Fence,
+    semaphore: vk::Semaphore,
+    transfer_cmd: vk::CommandBuffer,
+    graphics_cmd: vk::CommandBuffer,
+    staging: vk::Buffer,
+    staging_allocation: Allocation,
+}
+
+/// A buffer copy into host visible memory that has been submitted but not yet collected
+struct PendingReadback {
+    fence: vk::Fence,
+    cmd: vk::CommandBuffer,
+    staging: vk::Buffer,
+    staging_allocation: Allocation,
+    size: usize,
+}
+
+/// Cloneable handle creating buffers and textures from any thread, see
+/// `VulkanContext::resource_uploader`.
+///
+/// Uploads run on the dedicated transfer queue when the device has one and
+/// on the graphics queue otherwise. Every call returns as soon as the copy is
+/// submitted; the render thread turns the result into an id with
+/// `VulkanContext::adopt_upload`.
+#[derive(Clone)]
+pub struct ResourceUploader {
+    inner: Arc<UploaderInner>,
+}
+
+struct UploaderInner {
+    instance: Instance,
+    physical_device: vk::PhysicalDevice,
+    device: Device,
+    allocator: Arc<Mutex<Option<Allocator>>>,
+    queue_lock: Arc<Mutex<()>>,
+    queue: vk::Queue,
+    /// Families the uploaded resources are used on, the upload queue's first
+    families: Vec<u32>,
+    color_space_mode: ColorSpaceMode,
+    /// Pool of the upload queue's family, held for the whole of an upload.
+    /// `None` once the context destroyed its device.
+    command_pool: Mutex<Option<vk::CommandPool>>,
+}
+
+/// A buffer or texture whose upload a `ResourceUploader` has submitted.
+///
+/// Has to be handed to `VulkanContext::adopt_upload`, dropping it leaks the
+/// resource.
+pub struct UploadedResource {
+    resource: UploadedKind,
+    fence: vk::Fence,
+    cmd: vk::CommandBuffer,
+    staging: vk::Buffer,
+    staging_allocation: Allocation,
+    uploader: ResourceUploader,
+}
+
+enum UploadedKind {
+    Buffer(VulkanBuffer),
+    Texture {
+        image: vk::Image,
+        view: vk::ImageView,
+        allocation: Allocation,
+        width: u32,
+        height: u32,
+        format: vk::Format,
+    },
+}
+
+impl UploadedResource {
+    /// Whether the copy has finished, so `adopt_upload` won't block
+    pub fn is_ready(&self) -> Result<bool, VulkanError> {
+        let inner = &self.uploader.inner;
+        let _pool = inner.lock_pool()?;
+        unsafe { inner.device.get_fence_status(self.fence) }.map_err(sync_error)
+    }
+
+    /// Whether `adopt_upload` returns a texture id rather than a buffer id
+    pub fn is_texture(&self) -> bool {
+        matches!(self.resource, UploadedKind::Texture { .. })
+    }
+}
+
+impl ResourceUploader {
+    /// Create a device local buffer filled with `data`, the counterpart of
+    /// `VulkanContext::create_immutable_buffer`.
+    pub fn upload_buffer<T>(
+        &self,
+        buffer_type: BufferType,
+        data: &[T],
+    ) -> Result<UploadedResource, VulkanError> {
+        let (usage, index_type) = match buffer_type {
+            BufferType::VertexBuffer => (vk::BufferUsageFlags::VERTEX_BUFFER, None),
+            BufferType::IndexBuffer => (
+                vk::BufferUsageFlags::INDEX_BUFFER,
+                Some(IndexType::for_type::<T>()),
+            ),
+            BufferType::UniformBuffer => (vk::BufferUsageFlags::UNIFORM_BUFFER, None),
+            BufferType::StorageBuffer => (vk::BufferUsageFlags::STORAGE_BUFFER, None),
+            BufferType::Staging => {
+                return Err(VulkanError::BufferCreationFailed(
+                    "staging buffers are host visible, create them on the context".to_string(),
+                ))
+            }
+        };
+        let size = std::mem::size_of_val(data);
+        let bytes = unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, size) };
+
+        let inner = &self.inner;
+        let device = &inner.device;
+        let (_pool, pool) = inner.lock_pool()?;
+        let (staging, staging_allocation) = inner.create_staging_buffer(bytes)?;
+
+        let usage = usage | vk::BufferUsageFlags::TRANSFER_DST;
+        let buffer_info = vk::BufferCreateInfo::builder()
+            .size(size as vk::DeviceSize)
+            .usage(usage)
+            .sharing_mode(inner.sharing_mode())
+            .queue_family_indices(&inner.families);
+        let buffer = match unsafe { device.create_buffer(&buffer_info, None) } {
+            Ok(buffer) => buffer,
+            Err(e) => {
+                inner.free_buffer(staging, staging_allocation);
+                return Err(VulkanError::BufferCreationFailed(e.to_string()));
+            }
+        };
+        let requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+        let allocation = match inner.allocate("buffer", requirements, true) {
+            Ok(allocation) => allocation,
+            Err(e) => {
+                unsafe { device.destroy_buffer(buffer, None) };
+                inner.free_buffer(staging, staging_allocation);
+                return Err(e);
+            }
+        };
+        let bound = unsafe {
+            device.bind_buffer_memory(buffer, allocation.memory(), allocation.offset())
+        };
+        if let Err(e) = bound {
+            inner.free_buffer(buffer, allocation);
+            inner.free_buffer(staging, staging_allocation);
+            return Err(VulkanError::BufferCreationFailed(e.to_string()));
+        }
+
+        let upload = UploadedResource {
+            resource: UploadedKind::Buffer(VulkanBuffer {
+                buffer,
+                allocation,
+                size: size as vk::DeviceSize,
+                usage,
+                location: MemoryLocation::GpuOnly,
+                index_type,
+            }),
+            fence: vk::Fence::null(),
+            cmd: vk::CommandBuffer::null(),
+            staging,
+            staging_allocation,
+            uploader: self.clone(),
+        };
+        let region = vk::BufferCopy {
+            src_offset: 0,
+            dst_offset: 0,
+            size: size as vk::DeviceSize,
+        };
+        inner.submit(pool, upload, |cmd| unsafe {
+            device.cmd_copy_buffer(cmd, staging, buffer, &[region]);
+        })
+    }
+
+    /// Create a 2D texture holding `data`, left ready to be sampled. The
+    /// format has to pass `VulkanContext::supports_texture_format`.
+    pub fn upload_texture(
+        &self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        data: &[u8],
+    ) -> Result<UploadedResource, VulkanError> {
+        let size = format.size(width, height) as usize;
+        if data.len() != size {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "expected {} bytes for {}x{}, got {}",
+                size,
+                width,
+                height,
+                data.len()
+            )));
+        }
+        let inner = &self.inner;
+        let device = &inner.device;
+        let vk_format = mode_texture_format(inner.color_space_mode, format);
+
+        let (_pool, pool) = inner.lock_pool()?;
+        if !sampled_format_supported(&inner.instance, inner.physical_device, vk_format) {
+            return Err(VulkanError::TextureCreationFailed(format!(
+                "texture format {:?} is not supported by this device",
+                format
+            )));
+        }
+        let (staging, staging_allocation) = inner.create_staging_buffer(data)?;
+
+        let image_info = vk::ImageCreateInfo::builder()
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(vk_format)
+            .extent(vk::Extent3D { width, height, depth: 1 })
+            .mip_levels(1)
+            .array_layers(1)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST)
+            .sharing_mode(inner.sharing_mode())
+            .queue_family_indices(&inner.families)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .build();
+        let image = match unsafe { device.create_image(&image_info, None) } {
+            Ok(image) => image,
+            Err(e) => {
+                inner.free_buffer(staging, staging_allocation);
+                return Err(VulkanError::TextureCreationFailed(e.to_string()));
+            }
+        };
+        let requirements = unsafe { device.get_image_memory_requirements(image) };
+        let allocation = match inner.allocate("texture", requirements, false) {
+            Ok(allocation) => allocation,
+            Err(e) => {
+                unsafe { device.destroy_image(image, None) };
+                inner.free_buffer(staging, staging_allocation);
+                return Err(e);
+            }
+        };
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let view_info = vk::ImageViewCreateInfo::builder()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(vk_format)
+            .subresource_range(range);
+        let view = unsafe {
+            device
+                .bind_image_memory(image, allocation.memory(), allocation.offset())
+                .and_then(|_| device.create_image_view(&view_info, None))
+        };
+        let view = match view {
+            Ok(view) => view,
+            Err(e) => {
+                unsafe { device.destroy_image(image, None) };
+                if let Some(allocator) = lock(&inner.allocator).as_mut() {
+                    let _ = allocator.free(allocation);
+                }
+                inner.free_buffer(staging, staging_allocation);
+                return Err(VulkanError::TextureCreationFailed(e.to_string()));
+            }
+        };
+
+        let upload = UploadedResource {
+            resource: UploadedKind::Texture {
+                image,
+                view,
+                allocation,
+                width,
+                height,
+                format: vk_format,
+            },
+            fence: vk::Fence::null(),
+            cmd: vk::CommandBuffer::null(),
+            staging,
+            staging_allocation,
+            uploader: self.clone(),
+        };
+        let regions = layer_copy_regions(&image_info, size);
+        inner.submit(pool, upload, |cmd| unsafe {
+            transition_image_layout(
+                device,
+                cmd,
+                image,
+                range,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            );
+            device.cmd_copy_buffer_to_image(
+                cmd,
+                staging,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &regions,
+            );
+            // The transfer queue knows no shader stages, the fence wait in
+            // `adopt_upload` orders the copy before any sampling
+            let barrier = vk::ImageMemoryBarrier::builder()
+                .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(image)
+                .subresource_range(range)
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(vk::AccessFlags::empty())
+                .build();
+            device.cmd_pipeline_barrier(
+                cmd,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+        })
+    }
+}
+
+impl UploaderInner {
+    /// Lock the command pool, failing once the device is gone
+    fn lock_pool(
+        &self,
+    ) -> Result<(MutexGuard<'_, Option<vk::CommandPool>>, vk::CommandPool), VulkanError> {
+        let guard = lock(&self.command_pool);
+        let pool = (*guard).ok_or(VulkanError::InvalidHandle)?;
+        Ok((guard, pool))
+    }
+
+    /// Concurrent sharing when uploads run on their own queue family, which
+    /// saves the ownership transfer `create_texture_async` has to do
+    fn sharing_mode(&self) -> vk::SharingMode {
+        if self.families.len() > 1 {
+            vk::SharingMode::CONCURRENT
+        } else {
+            vk::SharingMode::EXCLUSIVE
+        }
+    }
+
+    fn allocate(
+        &self,
+        name: &str,
+        requirements: vk::MemoryRequirements,
+        linear: bool,
+    ) -> Result<Allocation, VulkanError> {
+        let mut allocator = lock(&self.allocator);
+        let allocator = allocator.as_mut().ok_or(VulkanError::InvalidHandle)?;
+        Ok(allocator.allocate(&AllocationCreateDesc {
+            name,
+            requirements,
+            location: MemoryLocation::GpuOnly,
+            linear,
+            allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+        })?)
+    }
+
+    fn create_staging_buffer(&self, data: &[u8]) -> Result<(vk::Buffer, Allocation), VulkanError> {
+        let mut allocator = lock(&self.allocator);
+        let allocator = allocator.as_mut().ok_or(VulkanError::InvalidHandle)?;
+        create_staging_buffer(&self.device, allocator, data)
+    }
+
+    /// Record the copy with `f` and submit it, destroying everything in
+    /// `upload` when that fails. The caller holds the pool lock.
+    fn submit<F>(
+        &self,
+        pool: vk::CommandPool,
+        mut upload: UploadedResource,
+        f: F,
+    ) -> Result<UploadedResource, VulkanError>
+    where
+        F: FnOnce(vk::CommandBuffer),
+    {
+        let device = &self.device;
+        let submitted = unsafe {
+            device
+                .create_fence(&vk::FenceCreateInfo::default(), None)
+                .map_err(sync_error)
+                .and_then(|fence| {
+                    upload.fence = fence;
+                    allocate_command_buffer(device, pool)
+                })
+                .and_then(|cmd| {
+                    upload.cmd = cmd;
+                    record_commands(device, cmd, f)
+                })
+                .and_then(|_| {
+                    let cmds = [upload.cmd];
+                    let submit = vk::SubmitInfo::builder().command_buffers(&cmds);
+                    let _queue = lock(&self.queue_lock);
+                    device
+                        .queue_submit(self.queue, &[submit.build()], upload.fence)
+                        .map_err(sync_error)
+                })
+        };
+        match submitted {
+            Ok(()) => Ok(upload),
+            Err(e) => {
+                let resource = self.release(pool, upload);
+                self.destroy(resource);
+                Err(e)
+            }
+        }
+    }
+
+    /// Destroy the fence, command buffer and staging buffer of an upload
+    /// that has finished or failed, handing back what it created. The
+    /// caller holds the pool lock.
+    fn release(&self, pool: vk::CommandPool, upload: UploadedResource) -> UploadedKind {
+        let device = &self.device;
+        unsafe {
+            device.destroy_fence(upload.fence, None);
+            if upload.cmd != vk::CommandBuffer::null() {
+                device.free_command_buffers(pool, &[upload.cmd]);
+            }
+        }
+        self.free_buffer(upload.staging, upload.staging_allocation);
+        upload.resource
+    }
+
+    /// Destroy a buffer that never became part of an upload, or its staging buffer
+    fn free_buffer(&self, buffer: vk::Buffer, allocation: Allocation) {
+        unsafe { self.device.destroy_buffer(buffer, None) };
+        if let Some(allocator) = lock(&self.allocator).as_mut() {
+            let _ = allocator.free(allocation);
+        }
+    }
+
+    fn destroy(&self, resource: UploadedKind) {
+        let device = &self.device;
+        let allocation = match resource {
+            UploadedKind::Buffer(buffer) => {
+                unsafe { device.destroy_buffer(buffer.buffer, None) };
+                buffer.allocation
+            }
+            UploadedKind::Texture {
+                image,
+                view,
+                allocation,
+                ..
+            } => {
+                unsafe {
+                    device.destroy_image_view(view, None);
+                    device.destroy_image(image, None);
+                }
+                allocation
+            }
+        };
+        if let Some(allocator) = lock(&self.allocator).as_mut() {
+            let _ = allocator.free(allocation);
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -360,18 +11179,188 @@ pub struct VulkanTexture {
     pub allocation: gpu_allocator_022::vulkan::Allocation,
     pub width: u32,
     pub height: u32,
+    pub depth: u32,
+    pub layers: u32,
     pub format: vk::Format,
+    pub usage: vk::ImageUsageFlags,
+    /// Memory imported by `import_external_image` or lazily allocated for a
+    /// transient attachment, owned by the texture instead of the allocator
+    pub imported_memory: Option<vk::DeviceMemory>,
+    /// Attachment only texture whose contents don't outlive a render pass
+    pub transient: bool,
+    /// Page bookkeeping of textures made by `create_sparse_texture`
+    pub sparse: Option<SparseResidency>,
+    /// Layout the image is left in by the last command recorded on it.
+    /// Commands transition from and update it, so a texture only changes
+    /// layout when a use needs a different one.
+    pub current_layout: vk::ImageLayout,
+}
+
+/// Memory backing the resident parts of a sparse texture
+#[derive(Debug)]
+pub struct SparseResidency {
+    /// Texels covered by one page
+    pub page_size: vk::Extent3D,
+    /// Bound pages by page coordinate
+    pub pages: HashMap<[u32; 2], Allocation>,
+    /// Opaque binding for the mip tail when the driver packs one
+    pub mip_tail: Option<Allocation>,
+    /// Size, alignment and memory types of a single page
+    pub page_requirements: vk::MemoryRequirements,
+}
+
+impl SparseResidency {
+    fn into_allocations(self) -> impl Iterator<Item = Allocation> {
+        self.pages.into_values().chain(self.mip_tail)
+    }
+}
+
+/// Offscreen target made of render textures, see `VulkanContext::create_render_target`
+#[derive(Debug)]
+pub struct VulkanRenderTarget {
+    pub color_textures: Vec<usize>,
+    pub depth_texture: Option<usize>,
+    pub color_formats: Vec<vk::Format>,
+    pub depth_format: Option<vk::Format>,
+    pub framebuffer: vk::Framebuffer,
+    pub extent: vk::Extent2D,
+    /// Render passes compatible with `framebuffer`, keyed by the load op of every attachment
+    pub render_passes: HashMap<Vec<vk::AttachmentLoadOp>, vk::RenderPass>,
+    /// Layers every draw is broadcast to, 1 without multiview
+    pub view_count: u32,
+    /// Single layer views owned by targets from `create_layer_render_target`
+    pub layer_views: Vec<vk::ImageView>,
+    /// Samples of the color attachments drawn to
+    pub samples: vk::SampleCountFlags,
+    /// Multisampled color textures of `create_msaa_render_target` that
+    /// passes draw into, resolved into `color_textures` at the end of each
+    pub msaa_textures: Vec<usize>,
+    /// The depth or multisampled attachments are transient and not kept
+    /// between passes
+    pub transient: bool,
+    /// Subpasses of targets from `create_subpass_render_target`, empty for
+    /// a single subpass drawing to every attachment
+    pub subpasses: Vec<Subpass>,
+}
+
+impl VulkanRenderTarget {
+    fn pass_layout(&self) -> TargetPassLayout<'_> {
+        TargetPassLayout {
+            color_formats: &self.color_formats,
+            depth_format: self.depth_format,
+            view_count: self.view_count,
+            samples: self.samples,
+            transient: self.transient,
+            subpasses: &self.subpasses,
+        }
+    }
+}
+
+/// One subpass of a render target from `create_subpass_render_target`.
+/// Attachments are numbered in order of the target's color textures,
+/// followed by its depth texture.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Subpass {
+    /// Attachments written as fragment outputs 0, 1, ...
+    pub colors: Vec<u32>,
+    /// Attachments written by earlier subpasses and read here with
+    /// `subpassLoad`, as `input_attachment_index` 0, 1, ...
+    pub inputs: Vec<u32>,
+    /// Whether the depth attachment is tested and written
+    pub depth: bool,
+}
+
+impl Subpass {
+    fn uses(&self, attachment: u32, depth_index: u32) -> bool {
+        self.colors.contains(&attachment)
+            || self.inputs.contains(&attachment)
+            || self.depth && attachment == depth_index
+    }
+
+    /// Color attachment count and depth format while recording this subpass
+    fn attachments(&self, depth_format: Option<vk::Format>) -> (u32, Option<vk::Format>) {
+        (self.colors.len() as u32, depth_format.filter(|_| self.depth))
+    }
+}
+
+/// Attachments and subpasses a render pass of an offscreen target is made for
+#[derive(Clone, Copy)]
+struct TargetPassLayout<'a> {
+    color_formats: &'a [vk::Format],
+    depth_format: Option<vk::Format>,
+    view_count: u32,
+    samples: vk::SampleCountFlags,
+    transient: bool,
+    /// Empty for a single subpass drawing to every attachment
+    subpasses: &'a [Subpass],
+}
+
+/// Image, view, allocator memory and lazily allocated memory of an
+/// attachment from `create_attachment_image`
+type AttachmentImage = (vk::Image, vk::ImageView, Allocation, Option<vk::DeviceMemory>);
+
+/// Which layers of its attachments a render target draws into
+#[derive(Clone, Copy)]
+enum RenderTargetLayers {
+    /// The first N layers at once through multiview
+    All(u32),
+    /// Just this layer
+    Single(u32),
 }
 
 #[derive(Debug)]
 pub struct VulkanShader {
     pub vertex_module: vk::ShaderModule,
     pub fragment_module: vk::ShaderModule,
+    /// Set for shaders from `create_shader_with_geometry`
+    pub geometry_module: Option<vk::ShaderModule>,
+    /// Control and evaluation modules of shaders from `create_shader_tessellated`
+    pub tessellation_modules: Option<(vk::ShaderModule, vk::ShaderModule)>,
     pub compute_module: Option<vk::ShaderModule>,
+    /// Samplers taken at `TEXTURE_SET`, one per `ShaderMeta::texture_slots`
+    pub texture_count: u32,
 }
 
 #[derive(Debug)]
 pub struct VulkanPipeline {
     pub pipeline: vk::Pipeline,
     pub layout: vk::PipelineLayout,
+    /// Workgroup size of a compute pipeline, see `spirv_local_size`.
+    /// `None` for graphics pipelines.
+    pub local_size: Option<[u32; 3]>,
+    /// Everything needed to build variants of a graphics pipeline
+    pub recipe: Option<GraphicsPipelineRecipe>,
+    /// `PolygonMode::LINE` variant for the wireframe overlay, created lazily
+    pub wireframe: Option<vk::Pipeline>,
+    /// Whether `layout` has `uniform_set_layout` as `GLOBAL_UNIFORM_SET`, to
+    /// have `apply_pipeline` bind the `set_global_uniforms` block
+    pub global_uniforms: bool,
+    /// Layout of the `TEXTURE_SET` samplers and their number, `None` for
+    /// pipelines without textures
+    pub texture_set_layout: Option<(vk::DescriptorSetLayout, u32)>,
+}
+
+/// Fixed function state a graphics pipeline was created with, kept around so
+/// variants differing in a single state (e.g. the polygon mode) can be built
+/// later on without going back to the `Pipeline` description.
+#[derive(Debug, Clone)]
+pub struct GraphicsPipelineRecipe {
+    pub vertex_module: vk::ShaderModule,
+    /// Control and evaluation modules, see `VulkanShader::tessellation_modules`
+    pub tessellation_modules: Option<(vk::ShaderModule, vk::ShaderModule)>,
+    pub geometry_module: Option<vk::ShaderModule>,
+    pub fragment_module: vk::ShaderModule,
+    pub bindings: Vec<vk::VertexInputBindingDescription>,
+    pub attributes: Vec<vk::VertexInputAttributeDescription>,
+    /// Has to be `PATCH_LIST` exactly when there are tessellation modules
+    pub topology: vk::PrimitiveTopology,
+    /// Vertices per patch for tessellation, ignored otherwise
+    pub patch_control_points: u32,
+    pub params: PipelineParams,
+    /// One entry per color attachment of `render_pass`
+    pub blend_attachments: Vec<vk::PipelineColorBlendAttachmentState>,
+    pub render_pass: vk::RenderPass,
+    /// Subpass of `render_pass` the pipeline is used in
+    pub subpass: u32,
+    pub samples: vk::SampleCountFlags,
 }
\ No newline at end of file
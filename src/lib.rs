@@ -25,7 +25,10 @@ pub use native::{gl, NativeDisplay};
 pub use graphics::GraphicsContext as Context;
 
 #[cfg(feature = "vulkan")]
-pub use graphics::backend::{GraphicsContextWrapper, RenderingBackendContext};
+pub use graphics::backend::{
+    BackendError, BufferHandle, GlError, GraphicsContextWrapper, RenderingBackendContext,
+    TextureHandle,
+};
 
 pub mod date {
     #[cfg(not(target_arch = "wasm32"))]
@@ -54,6 +57,14 @@ impl Context {
         self
     }
 
+    /// The Vulkan backend when it renders the window, see
+    /// `Platform::rendering_backend`. Draw through it from
+    /// `EventHandler::draw`, the frame is begun before and presented after.
+    #[cfg(feature = "vulkan")]
+    pub fn vulkan(&mut self) -> Option<&mut VulkanContext> {
+        self.vulkan.as_deref_mut()
+    }
+
     pub fn display(&self) -> &dyn NativeDisplay {
         unsafe { &*self.display.unwrap() }
     }
@@ -109,6 +120,35 @@ impl Context {
         self.display_mut().cancel_quit();
     }
 
+    /// Resource counts, memory use and frame timing of the Vulkan backend,
+    /// see `VulkanStats`.
+    ///
+    /// When the window renders through GL there are no Vulkan resources to
+    /// count and the stats are all zero, see `VulkanStats::default`.
+    ///
+    /// ```no_run
+    /// # #[cfg(feature = "vulkan")]
+    /// fn stats_window(ctx: &mut miniquad::Context) {
+    ///     if let Ok(stats) = ctx.get_vulkan_stats() {
+    ///         println!(
+    ///             "{} buffers, {} bytes, {:.3} ms, MSAA {} ({:?})",
+    ///             stats.buffer_count,
+    ///             stats.allocated_memory,
+    ///             stats.frame_time * 1000.0,
+    ///             stats.msaa_enabled,
+    ///             stats.msaa_samples,
+    ///         );
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "vulkan")]
+    pub fn get_vulkan_stats(&self) -> std::result::Result<VulkanStats, BackendError> {
+        Ok(self
+            .vulkan
+            .as_ref()
+            .map_or_else(VulkanStats::default, |vulkan| vulkan.get_performance_stats()))
+    }
+
     /// Capture mouse cursor to the current window
     /// On WASM this will automatically hide cursor
     /// On desktop this will bound cursor to windows border
@@ -169,6 +209,113 @@ impl Context {
     }
 }
 
+// Not every platform's main loop goes through these yet
+#[cfg_attr(
+    any(target_os = "macos", target_os = "ios", target_arch = "wasm32"),
+    allow(dead_code)
+)]
+impl Context {
+    /// Set up the Vulkan backend on the window when
+    /// `Platform::rendering_backend` asks for it. Stays on GL with a warning
+    /// where Vulkan can't present to the window or fails to initialize.
+    pub(crate) fn init_backend(&mut self, conf: &conf::Conf, display: &mut dyn NativeDisplay) {
+        #[cfg(feature = "vulkan")]
+        {
+            if conf.platform.rendering_backend != conf::RenderingBackend::Vulkan {
+                return;
+            }
+            if display.raw_window_handles().is_none() {
+                log_04::warn!("Vulkan can't present to windows here yet, using OpenGL instead");
+                return;
+            }
+            let mut vulkan = match VulkanContext::try_new() {
+                Ok(vulkan) => Box::new(vulkan),
+                Err(e) => {
+                    log_04::warn!("{}, using OpenGL instead", e);
+                    return;
+                }
+            };
+            vulkan.set_display(conf.clone());
+            match vulkan.initialize(display) {
+                Ok(()) => self.vulkan = Some(vulkan),
+                Err(e) => {
+                    vulkan.cleanup();
+                    log_04::warn!("Vulkan failed to initialize, using OpenGL instead: {}", e);
+                }
+            }
+        }
+        #[cfg(not(feature = "vulkan"))]
+        let _ = (conf, display);
+    }
+
+    /// Whether frames are presented by the Vulkan backend, and the GL
+    /// buffers must not be swapped
+    pub(crate) fn presents_with_vulkan(&self) -> bool {
+        #[cfg(feature = "vulkan")]
+        return self.vulkan.is_some();
+        #[cfg(not(feature = "vulkan"))]
+        false
+    }
+
+    /// Run `update` and `draw` for one frame.
+    ///
+    /// On Vulkan `draw` is wrapped in `begin_frame`, `end_frame` and
+    /// `present`, and skipped while the window is minimized or has no
+    /// surface. A lost device is reinitialized and then reported to
+    /// `EventHandler::on_device_lost`.
+    pub(crate) fn frame(
+        &mut self,
+        display: &mut dyn NativeDisplay,
+        handler: &mut dyn EventHandler,
+    ) {
+        handler.update(self.with_display(display));
+        #[cfg(feature = "vulkan")]
+        {
+            if self.vulkan.is_some() {
+                match self.vulkan_frame(display, handler) {
+                    Ok(()) => {}
+                    Err(VulkanError::DeviceLost) => self.recover_device(display, handler),
+                    Err(e) => log_04::error!("Vulkan frame failed: {}", e),
+                }
+                return;
+            }
+        }
+        handler.draw(self.with_display(display));
+    }
+
+    #[cfg(feature = "vulkan")]
+    fn vulkan_frame(
+        &mut self,
+        display: &mut dyn NativeDisplay,
+        handler: &mut dyn EventHandler,
+    ) -> std::result::Result<(), VulkanError> {
+        let vulkan = self.vulkan.as_mut().ok_or(VulkanError::InvalidHandle)?;
+        if vulkan.begin_frame()? == FRAME_SKIPPED || vulkan.is_surface_lost() {
+            return Ok(());
+        }
+        handler.draw(self.with_display(display));
+        let vulkan = self.vulkan.as_mut().ok_or(VulkanError::InvalidHandle)?;
+        vulkan.end_frame()?;
+        vulkan.present()
+    }
+
+    #[cfg(feature = "vulkan")]
+    fn recover_device(&mut self, display: &mut dyn NativeDisplay, handler: &mut dyn EventHandler) {
+        log_04::warn!("Vulkan device lost, reinitializing");
+        let reinitialized = match self.vulkan.as_mut() {
+            Some(vulkan) => vulkan.reinitialize(display),
+            None => return,
+        };
+        match reinitialized {
+            Ok(()) => handler.on_device_lost(self.with_display(display)),
+            Err(e) => {
+                log_04::error!("Vulkan failed to reinitialize after losing the device: {}", e);
+                self.with_display(display).order_quit();
+            }
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Hash, Eq)]
 pub enum CursorIcon {
     Default,
@@ -185,21 +332,34 @@ pub enum CursorIcon {
     NWSEResize,
 }
 
-/// Start miniquad with specified rendering backend.
+/// Start miniquad with the rendering backend picked by `Conf::resolve_backend`.
+///
+/// A requested backend that can't be used, e.g. Vulkan in a build without
+/// the `vulkan` feature or on a machine without a Vulkan driver, falls back
+/// to OpenGL before any window is opened. Panics when
+/// `Platform::strict_rendering_backend` rules out the fallback, see
+/// `try_start_with_backend` for the fallible version.
 pub fn start_with_backend<F>(conf: conf::Conf, f: F)
 where
     F: 'static + FnOnce(&mut Context) -> Box<dyn EventHandler>,
 {
-    // Check if the requested backend is available
-    #[cfg(feature = "vulkan")]
-    if conf.platform.rendering_backend == conf::RenderingBackend::Vulkan 
-        && !graphics::backend::RenderingBackendContext::is_available(conf::RenderingBackend::Vulkan) {
-        panic!("Vulkan backend requested but not available");
-    }
-    
-    // For now, delegate to the original start function
-    // TODO: Implement backend-specific initialization
+    try_start_with_backend(conf, f).unwrap_or_else(|error| panic!("{}", error))
+}
+
+/// `start_with_backend`, returning `BackendError::Unavailable` instead of
+/// panicking when `Platform::strict_rendering_backend` rules out the
+/// fallback. The window is then opened by `start`, with the resolved backend
+/// in `conf.platform.rendering_backend`.
+pub fn try_start_with_backend<F>(
+    mut conf: conf::Conf,
+    f: F,
+) -> std::result::Result<(), graphics::backend::BackendError>
+where
+    F: 'static + FnOnce(&mut Context) -> Box<dyn EventHandler>,
+{
+    conf.platform.rendering_backend = conf.resolve_backend()?;
     start(conf, f);
+    Ok(())
 }
 
 /// Start miniquad.
@@ -52,9 +52,19 @@ pub trait NativeDisplay: std::any::Any {
 
     fn set_pause_resume_listener(&mut self, _listener: fn(bool)) {}
 
+    /// Handles of the native display and window for creating a Vulkan
+    /// surface on the window. `None` where Vulkan can't present yet.
+    #[cfg(feature = "vulkan")]
+    fn raw_window_handles(&self) -> Option<(RawDisplayHandle, RawWindowHandle)> {
+        None
+    }
+
     fn as_any(&mut self) -> &mut dyn std::any::Any;
 }
 
+#[cfg(feature = "vulkan")]
+use raw_window_handle_05::{RawDisplayHandle, RawWindowHandle};
+
 pub mod module;
 
 #[cfg(target_env = "ohos")]
@@ -7,6 +7,11 @@ use crate::{
 
 use std::{cell::RefCell, sync::mpsc, thread};
 
+#[cfg(feature = "vulkan")]
+use raw_window_handle_05::{
+    AndroidDisplayHandle, AndroidNdkWindowHandle, RawDisplayHandle, RawWindowHandle,
+};
+
 pub use crate::gl::{self, *};
 
 mod keycodes;
@@ -90,6 +95,7 @@ struct AndroidDisplay {
     screen_width: f32,
     screen_height: f32,
     fullscreen: bool,
+    window: *mut ndk_sys::ANativeWindow,
 }
 
 impl NativeDisplay for AndroidDisplay {
@@ -161,6 +167,18 @@ impl NativeDisplay for AndroidDisplay {
             ndk_utils::call_void_method!(env, ACTIVITY, "showKeyboard", "(Z)V", show as i32);
         }
     }
+    #[cfg(feature = "vulkan")]
+    fn raw_window_handles(&self) -> Option<(RawDisplayHandle, RawWindowHandle)> {
+        if self.window.is_null() {
+            return None;
+        }
+        let mut window = AndroidNdkWindowHandle::empty();
+        window.a_native_window = self.window as *mut _;
+        Some((
+            RawDisplayHandle::Android(AndroidDisplayHandle::empty()),
+            RawWindowHandle::AndroidNdk(window),
+        ))
+    }
     fn as_any(&mut self) -> &mut dyn std::any::Any {
         self
     }
@@ -232,18 +250,27 @@ impl MainThreadState {
     }
 
     unsafe fn update_surface(&mut self, window: *mut ndk_sys::ANativeWindow) {
+        let new_window = window != self.window;
         if !self.window.is_null() {
             ndk_sys::ANativeWindow_release(self.window);
         }
         self.window = window;
+        self.display.window = window;
+        if self.context.presents_with_vulkan() {
+            self.update_vulkan_surface(new_window);
+            return;
+        }
         if self.surface.is_null() == false {
             self.destroy_surface();
         }
+        self.create_surface();
+    }
 
+    unsafe fn create_surface(&mut self) {
         self.surface = (self.libegl.eglCreateWindowSurface.unwrap())(
             self.egl_display,
             self.egl_config,
-            window as _,
+            self.window as _,
             std::ptr::null_mut(),
         );
 
@@ -259,13 +286,36 @@ impl MainThreadState {
         assert!(res != 0);
     }
 
+    /// Follow a new or returning window with the Vulkan swapchain, which
+    /// stays as it is when only the size changed
+    fn update_vulkan_surface(&mut self, new_window: bool) {
+        #[cfg(feature = "vulkan")]
+        if let Some(vulkan) = self.context.vulkan() {
+            if new_window {
+                vulkan.on_surface_lost();
+            }
+            if let Err(e) = vulkan.on_surface_recreated(&self.display) {
+                log_04::error!("Vulkan failed to recreate the window surface: {}", e);
+            }
+        }
+        #[cfg(not(feature = "vulkan"))]
+        let _ = new_window;
+    }
+
     fn process_message(&mut self, msg: Message) {
         match msg {
             Message::SurfaceCreated { window } => unsafe {
                 self.update_surface(window);
             },
             Message::SurfaceDestroyed => unsafe {
-                self.destroy_surface();
+                if self.context.presents_with_vulkan() {
+                    #[cfg(feature = "vulkan")]
+                    if let Some(vulkan) = self.context.vulkan() {
+                        vulkan.on_surface_lost();
+                    }
+                } else {
+                    self.destroy_surface();
+                }
             },
             Message::SurfaceChanged {
                 window,
@@ -346,16 +396,21 @@ impl MainThreadState {
     }
 
     fn frame(&mut self) {
-        self.event_handler
-            .update(self.context.with_display(&mut self.display));
+        // Vulkan skips drawing by itself while the window is gone
+        if self.context.presents_with_vulkan() {
+            self.context.frame(&mut self.display, &mut *self.event_handler);
+            return;
+        }
 
-        if self.surface.is_null() == false {
+        if self.surface.is_null() {
             self.event_handler
-                .draw(self.context.with_display(&mut self.display));
+                .update(self.context.with_display(&mut self.display));
+            return;
+        }
+        self.context.frame(&mut self.display, &mut *self.event_handler);
 
-            unsafe {
-                (self.libegl.eglSwapBuffers.unwrap())(self.egl_display, self.surface);
-            }
+        unsafe {
+            (self.libegl.eglSwapBuffers.unwrap())(self.egl_display, self.surface);
         }
     }
 }
@@ -458,12 +513,20 @@ where
             libegl.eglGetProcAddress.expect("non-null function pointer")(name.as_ptr() as _)
         });
 
-        let surface = (libegl.eglCreateWindowSurface.unwrap())(
-            egl_display,
-            egl_config,
-            window as _,
-            std::ptr::null_mut(),
-        );
+        // A Vulkan swapchain needs the window to itself, GL then goes without
+        // a surface unless Vulkan fails to initialize
+        let wants_vulkan = cfg!(feature = "vulkan")
+            && conf.platform.rendering_backend == crate::conf::RenderingBackend::Vulkan;
+        let surface = if wants_vulkan {
+            std::ptr::null_mut()
+        } else {
+            (libegl.eglCreateWindowSurface.unwrap())(
+                egl_display,
+                egl_config,
+                window as _,
+                std::ptr::null_mut(),
+            )
+        };
 
         if (libegl.eglMakeCurrent.unwrap())(egl_display, surface, surface, egl_context) == 0 {
             panic!();
@@ -475,7 +538,9 @@ where
             screen_width,
             screen_height,
             fullscreen: conf.fullscreen,
+            window,
         };
+        context.init_backend(&conf, &mut display);
         let event_handler = f.0(context.with_display(&mut display));
         let mut s = MainThreadState {
             libegl,
@@ -489,6 +554,9 @@ where
             event_handler,
             quit: false,
         };
+        if wants_vulkan && !s.context.presents_with_vulkan() {
+            s.create_surface();
+        }
 
         while !s.quit {
             // process all the messages from the main thread
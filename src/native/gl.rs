@@ -78,6 +78,8 @@ pub const GL_COLOR_ATTACHMENT1: u32 = 0x8CE1;
 pub const GL_RGBA4: u32 = 0x8056;
 pub const GL_RGB8: u32 = 0x8051;
 pub const GL_ARRAY_BUFFER: u32 = 0x8892;
+pub const GL_UNIFORM_BUFFER: u32 = 0x8A11;
+pub const GL_SHADER_STORAGE_BUFFER: u32 = 0x90D2;
 pub const GL_STENCIL: u32 = 0x1802;
 pub const GL_TEXTURE_2D: u32 = 0x0DE1;
 pub const GL_DEPTH: u32 = 0x1801;
@@ -142,6 +144,7 @@ pub const GL_EQUAL: u32 = 0x0202;
 pub const GL_FRAMEBUFFER: u32 = 0x8D40;
 pub const GL_RGB5: u32 = 0x8050;
 pub const GL_LINES: u32 = 0x0001;
+pub const GL_PATCHES: u32 = 0x000E;
 pub const GL_DEPTH_BUFFER_BIT: u32 = 0x00000100;
 pub const GL_SRC_ALPHA: u32 = 0x0302;
 pub const GL_INCR_WRAP: u32 = 0x8507;
@@ -248,6 +251,12 @@ pub const GL_QUERY_RESULT: u32 = 34918;
 pub const GL_QUERY_RESULT_AVAILABLE: u32 = 34919;
 pub const GL_VENDOR: u32 = 0x1F00;
 pub const GL_VERSION: u32 = 0x1F02;
+pub const GL_COMPRESSED_RGBA_S3TC_DXT1_EXT: u32 = 0x83F1;
+pub const GL_COMPRESSED_RGBA_S3TC_DXT5_EXT: u32 = 0x83F3;
+pub const GL_COMPRESSED_RGBA_BPTC_UNORM: u32 = 0x8E8C;
+pub const GL_COMPRESSED_RGB8_ETC2: u32 = 0x9274;
+pub const GL_COMPRESSED_RGBA8_ETC2_EAC: u32 = 0x9278;
+pub const GL_COMPRESSED_RGBA_ASTC_4x4_KHR: u32 = 0x93B0;
 
 pub const WGL_NUMBER_PIXEL_FORMATS_ARB: u32 = 0x2000;
 pub const WGL_SUPPORT_OPENGL_ARB: u32 = 0x2010;
@@ -349,6 +358,10 @@ gl_loader!(
     fn glUniform2iv(location: GLint, count: GLsizei, value: *const GLint) -> (),
     fn glUniform3iv(location: GLint, count: GLsizei, value: *const GLint) -> (),
     fn glUniform4iv(location: GLint, count: GLsizei, value: *const GLint) -> (),
+    fn glUniform1uiv(location: GLint, count: GLsizei, value: *const GLuint) -> (),
+    fn glUniform2uiv(location: GLint, count: GLsizei, value: *const GLuint) -> (),
+    fn glUniform3uiv(location: GLint, count: GLsizei, value: *const GLuint) -> (),
+    fn glUniform4uiv(location: GLint, count: GLsizei, value: *const GLuint) -> (),
     fn glUniform1i(location: GLint, v0: GLint) -> (),
     fn glUniform2i(location: GLint, v0: GLint, v1: GLint) -> (),
     fn glUniform3i(location: GLint, v0: GLint, v1: GLint, v2: GLint) -> (),
@@ -536,6 +549,7 @@ gl_loader!(
     fn glDisable(cap: GLenum) -> (),
     fn glColorMask(red: GLboolean, green: GLboolean, blue: GLboolean, alpha: GLboolean) -> (),
     fn glBindBuffer(target: GLenum, buffer: GLuint) -> (),
+    fn glBindBufferBase(target: GLenum, index: GLuint, buffer: GLuint) -> (),
     fn glBindVertexArray(array: GLuint) -> (),
     fn glDeleteVertexArrays(n: GLsizei, arrays: *const GLuint) -> (),
     fn glDepthMask(flag: GLboolean) -> (),
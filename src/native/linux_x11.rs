@@ -18,6 +18,9 @@ use crate::{
 
 use libx11::*;
 
+#[cfg(feature = "vulkan")]
+use raw_window_handle_05::{RawDisplayHandle, RawWindowHandle, XlibDisplayHandle, XlibWindowHandle};
+
 use std::collections::HashMap;
 
 pub struct Dummy;
@@ -154,6 +157,16 @@ impl crate::native::NativeDisplay for X11Display {
         };
     }
 
+    #[cfg(feature = "vulkan")]
+    fn raw_window_handles(&self) -> Option<(RawDisplayHandle, RawWindowHandle)> {
+        let mut display = XlibDisplayHandle::empty();
+        display.display = self.display as *mut _;
+        display.screen = self.screen;
+        let mut window = XlibWindowHandle::empty();
+        window.window = self.window;
+        Some((RawDisplayHandle::Xlib(display), RawWindowHandle::Xlib(window)))
+    }
+
     fn as_any(&mut self) -> &mut dyn std::any::Any {
         self
     }
@@ -722,6 +735,7 @@ where
     display.data.screen_height = h;
 
     let mut context = GraphicsContext::new(gl::is_gl2());
+    context.init_backend(conf, &mut display);
 
     let mut data = (f.take().unwrap())(context.with_display(&mut display));
 
@@ -738,10 +752,11 @@ where
             }
         }
 
-        data.update(context.with_display(&mut display));
-        data.draw(context.with_display(&mut display));
+        context.frame(&mut display, &mut *data);
 
-        glx.swap_buffers(&mut display, glx_window);
+        if !context.presents_with_vulkan() {
+            glx.swap_buffers(&mut display, glx_window);
+        }
 
         (display.libx11.XFlush)(display.display);
         //display.process_requests(window, &mut data);
@@ -810,6 +825,7 @@ where
     (display.libx11.XFlush)(display.display);
 
     let mut context = GraphicsContext::new(gl::is_gl2());
+    context.init_backend(conf, &mut display);
 
     let (w, h) = display.query_window_size(window);
     display.data.screen_width = w;
@@ -826,10 +842,11 @@ where
             display.process_event(&mut context, &mut *data, &mut event);
         }
 
-        data.update(context.with_display(&mut display));
-        data.draw(context.with_display(&mut display));
+        context.frame(&mut display, &mut *data);
 
-        (egl_lib.eglSwapBuffers.unwrap())(egl_display, egl_surface);
+        if !context.presents_with_vulkan() {
+            (egl_lib.eglSwapBuffers.unwrap())(egl_display, egl_surface);
+        }
         (display.libx11.XFlush)(display.display);
 
         //display.process_requests(window, &mut data);
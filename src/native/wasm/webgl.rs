@@ -143,6 +143,7 @@ pub const GL_EQUAL: u32 = 0x0202;
 pub const GL_FRAMEBUFFER: u32 = 0x8D40;
 pub const GL_RGB5: u32 = 0x8050;
 pub const GL_LINES: u32 = 0x0001;
+pub const GL_PATCHES: u32 = 0x000E;
 pub const GL_DEPTH_BUFFER_BIT: u32 = 0x00000100;
 pub const GL_SRC_ALPHA: u32 = 0x0302;
 pub const GL_INCR_WRAP: u32 = 0x8507;
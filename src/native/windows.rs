@@ -921,6 +921,7 @@ where
         super::gl::load_gl_funcs(|proc| display.get_proc_address(proc));
 
         let mut context = GraphicsContext::new(crate::gl::is_gl2());
+        context.init_backend(conf, &mut display);
 
         let event_handler = f(context.with_display(&mut display));
 
@@ -945,10 +946,10 @@ where
                     DispatchMessageW(&mut msg as *mut _ as _);
                 }
             }
-            p.event_handler
-                .update(p.context.with_display(&mut p.display));
-            p.event_handler.draw(p.context.with_display(&mut p.display));
-            SwapBuffers(p.display.dc);
+            p.context.frame(&mut p.display, &mut *p.event_handler);
+            if !p.context.presents_with_vulkan() {
+                SwapBuffers(p.display.dc);
+            }
 
             if p.display.update_dimensions(wnd) {
                 let width = p.display.display_data.screen_width as _;